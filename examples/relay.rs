@@ -0,0 +1,37 @@
+use clap::Parser;
+use std::io;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about=None)]
+/// Normalizes a zstd stream from stdin, writing it to stdout.
+struct Args {
+    /// Compression level used for transcoded frames.
+    #[arg(short, long, default_value = "3")]
+    level: i32,
+
+    /// Maximum window log of relayed frames.
+    #[arg(short, long, default_value = "23")]
+    window_log_max: u32,
+
+    /// Require a checksum in every frame.
+    #[arg(short, long)]
+    checksum: bool,
+
+    /// Compress input that is not zstd, instead of failing.
+    #[arg(short = 'u', long)]
+    compress_unknown: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let policy = zstd::proxy::Policy::new(args.level)
+        .window_log_max(args.window_log_max)
+        .checksum(args.checksum)
+        .compress_unknown(args.compress_unknown);
+
+    let report =
+        zstd::proxy::relay(io::stdin().lock(), io::stdout().lock(), policy)
+            .unwrap();
+    eprintln!("{:?}", report);
+}
@@ -0,0 +1,64 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about=None)]
+/// Compares the regular bulk functions with the small payload functions.
+struct Args {
+    /// File to cut into small payloads.
+    file: PathBuf,
+
+    /// Size of each payload.
+    #[arg(short, long, default_value = "1024")]
+    size: usize,
+
+    /// Compression level to use.
+    #[arg(short, long, default_value = "3")]
+    level: i32,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let content = std::fs::read(&args.file).unwrap();
+    let payloads: Vec<&[u8]> = content.chunks(args.size).collect();
+
+    // Print tsv headers
+    println!("Method\tCompressed size\tCompression time\tDecompression time");
+
+    let settings = zstd::small::Settings::new(args.level);
+    let compact = settings.compact();
+
+    let bulk = |data: &[u8]| zstd::bulk::compress(data, args.level);
+    let small = |data: &[u8]| settings.compress(data);
+    let compact_small = |data: &[u8]| compact.compress(data);
+
+    let bulk_d = |data: &[u8]| zstd::bulk::decompress(data, args.size);
+    let small_d = |data: &[u8]| settings.decompress(data, args.size);
+    let compact_d = |data: &[u8]| compact.decompress(data, args.size);
+
+    run("bulk", &payloads, bulk, bulk_d);
+    run("small", &payloads, small, small_d);
+    run("small (compact)", &payloads, compact_small, compact_d);
+}
+
+fn run<F, G>(name: &str, payloads: &[&[u8]], compress: F, decompress: G)
+where
+    F: Fn(&[u8]) -> std::io::Result<Vec<u8>>,
+    G: Fn(&[u8]) -> std::io::Result<Vec<u8>>,
+{
+    let start = Instant::now();
+    let compressed: Vec<Vec<u8>> = payloads
+        .iter()
+        .map(|data| compress(data).unwrap())
+        .collect();
+    let mid = Instant::now();
+    for (payload, data) in payloads.iter().zip(&compressed) {
+        assert_eq!(&decompress(data).unwrap()[..], *payload);
+    }
+    let end = Instant::now();
+
+    let size: usize = compressed.iter().map(Vec::len).sum();
+    println!("{}\t{}\t{:?}\t{:?}", name, size, mid - start, end - mid);
+}
@@ -0,0 +1,60 @@
+use clap::Parser;
+use humansize::{format_size, DECIMAL};
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about=None)]
+/// Compares read encoders with and without a stable input buffer.
+///
+/// The input file is loaded in memory first, so only the compression
+/// itself is measured.
+struct Args {
+    /// File to compress (for example a large tar archive).
+    file: PathBuf,
+
+    /// Compression level to use.
+    #[arg(short, long)]
+    level: i32,
+
+    /// Number of times each encoder is run.
+    #[arg(short, long)]
+    rounds: u32,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let data = std::fs::read(&args.file).unwrap();
+
+    println!("Encoder\tCompressed size\tCompression speed");
+
+    for stable in [false, true] {
+        let start = Instant::now();
+        let mut compressed_size = 0;
+        for _ in 0..args.rounds {
+            compressed_size = if stable {
+                let mut encoder =
+                    zstd::stream::read::Encoder::with_stable_input(
+                        &data, args.level,
+                    )
+                    .unwrap();
+                io::copy(&mut encoder, &mut io::sink()).unwrap()
+            } else {
+                let mut encoder =
+                    zstd::stream::read::Encoder::new(&data[..], args.level)
+                        .unwrap();
+                io::copy(&mut encoder, &mut io::sink()).unwrap()
+            };
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let speed =
+            (data.len() as f64 * args.rounds as f64 / elapsed) as usize;
+        let speed = format_size(speed, DECIMAL);
+
+        let name = if stable { "stable input" } else { "buffered" };
+        println!("{}\t{}\t{}/s", name, compressed_size, speed);
+    }
+}
@@ -12,21 +12,22 @@ fn main() {
             .unwrap();
         }
         Some(ref option) if option == "-d" => decompress(),
-        Some(ref option) => {
-            if option.starts_with('-') {
-                let level = match i32::from_str(&option[1..]) {
+        Some(ref option) => match option.strip_prefix('-') {
+            Some(level) => {
+                let level = match i32::from_str(level) {
                     Ok(level) => level,
                     Err(e) => panic!("Error parsing compression level: {}", e),
                 };
                 compress(level);
-            } else {
+            }
+            None => {
                 writeln!(
                     &mut io::stderr(),
                     "Invalid option. Usage: `stream [-d|-1..-22]`"
                 )
                 .unwrap();
             }
-        }
+        },
     }
 }
 
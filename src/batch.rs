@@ -0,0 +1,213 @@
+//! Compress or decompress many independent payloads in parallel.
+//!
+//! Each payload is compressed into its own frame, and each frame
+//! decompressed into its own payload, using [rayon]'s global thread pool.
+//! Every thread keeps its own context for the whole batch, and the
+//! dictionary, if any, is only prepared once and shared between threads.
+//!
+//! ```rust
+//! let options = zstd::Options::new().level(1);
+//! let payloads = vec![b"first".to_vec(), b"second".to_vec()];
+//!
+//! let frames = zstd::batch::compress_par(&payloads, &options).unwrap();
+//! let decompressed = zstd::batch::decompress_par(&frames, &options).unwrap();
+//! assert_eq!(decompressed, payloads);
+//! ```
+//!
+//! [rayon]: https://docs.rs/rayon
+use std::io;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use zstd_safe::{DCtx, InBuffer, OutBuffer};
+
+use crate::bulk::Compressor;
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::map_error_code;
+use crate::Options;
+
+/// Compresses each item into its own frame, in parallel.
+///
+/// The frames are returned in the same order as `items`. If any item fails,
+/// one of the errors is returned.
+pub fn compress_par<T>(
+    items: &[T],
+    options: &Options,
+) -> io::Result<Vec<Vec<u8>>>
+where
+    T: AsRef<[u8]> + Sync,
+{
+    options.check()?;
+    let dictionary = if options.dictionary.is_empty() {
+        None
+    } else {
+        Some(EncoderDictionary::copy(&options.dictionary, options.level))
+    };
+
+    items
+        .par_iter()
+        .map_init(
+            || compressor(dictionary.as_ref(), options).map_err(Arc::new),
+            |compressor, item| match compressor {
+                Ok(compressor) => compressor.compress(item.as_ref()),
+                Err(error) => Err(share_error(error)),
+            },
+        )
+        .collect()
+}
+
+/// Decompresses each item into its own payload, in parallel.
+///
+/// `options` should be the ones used for compression: they give the
+/// dictionary, and the decoder parameters to match. Each item can actually
+/// contain several frames, which are then decompressed into a single
+/// payload.
+///
+/// The payloads are returned in the same order as `items`. If any item
+/// fails, one of the errors is returned.
+pub fn decompress_par<T>(
+    items: &[T],
+    options: &Options,
+) -> io::Result<Vec<Vec<u8>>>
+where
+    T: AsRef<[u8]> + Sync,
+{
+    let dictionary = if options.dictionary.is_empty() {
+        None
+    } else {
+        Some(DecoderDictionary::copy(&options.dictionary))
+    };
+
+    items
+        .par_iter()
+        .map_init(
+            || {
+                decompression_context(dictionary.as_ref(), options)
+                    .map_err(Arc::new)
+            },
+            |context, item| match context {
+                Ok(context) => decompress(context, item.as_ref()),
+                Err(error) => Err(share_error(error)),
+            },
+        )
+        .collect()
+}
+
+fn compressor<'a>(
+    dictionary: Option<&'a EncoderDictionary<'static>>,
+    options: &Options,
+) -> io::Result<Compressor<'a>> {
+    let dictionary = match dictionary {
+        Some(dictionary) => dictionary,
+        None => return Compressor::with_options(options),
+    };
+
+    let mut compressor = Compressor::with_prepared_dictionary(dictionary)?;
+    for parameter in options.parameters() {
+        compressor.set_parameter(parameter)?;
+    }
+    Ok(compressor)
+}
+
+fn decompression_context<'a>(
+    dictionary: Option<&'a DecoderDictionary<'static>>,
+    options: &Options,
+) -> io::Result<DCtx<'a>> {
+    let mut context = DCtx::create();
+    if let Some(dictionary) = dictionary {
+        context
+            .ref_ddict(dictionary.as_ddict())
+            .map_err(map_error_code)?;
+    }
+    for parameter in options.decoder_parameters() {
+        context.set_parameter(parameter).map_err(map_error_code)?;
+    }
+    Ok(context)
+}
+
+fn decompress(context: &mut DCtx<'_>, frames: &[u8]) -> io::Result<Vec<u8>> {
+    // Start afresh, in case the previous item was invalid.
+    context
+        .reset(zstd_safe::ResetDirective::SessionOnly)
+        .map_err(map_error_code)?;
+
+    let capacity = zstd_safe::get_frame_content_size(frames)
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    let mut output = Vec::with_capacity(capacity.min(1 << 20) as usize);
+    if frames.is_empty() {
+        return Ok(output);
+    }
+
+    let mut input = InBuffer::around(frames);
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(DCtx::out_size());
+        }
+        let pos = output.len();
+        let hint = context
+            .decompress_stream(
+                &mut OutBuffer::around_pos(&mut output, pos),
+                &mut input,
+            )
+            .map_err(map_error_code)?;
+
+        if input.pos() == frames.len() {
+            if hint == 0 {
+                return Ok(output);
+            }
+            // zstd only stops early when the output is full.
+            if output.len() < output.capacity() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "incomplete frame",
+                ));
+            }
+        }
+    }
+}
+
+/// Errors are not `Clone`, but every thread needs its own: they wrap the
+/// original error, which is shared.
+fn share_error(error: &Arc<io::Error>) -> io::Error {
+    io::Error::new(error.kind(), Arc::clone(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+
+    use super::{compress_par, decompress_par};
+    use crate::Options;
+
+    #[test]
+    fn test_batch() {
+        let text = include_bytes!("../assets/example.txt");
+        let payloads: Vec<&[u8]> = text.chunks(100).collect();
+
+        for options in [
+            Options::new().level(3),
+            Options::new().level(3).dictionary(&text[..200]),
+        ] {
+            let frames = compress_par(&payloads, &options).unwrap();
+            assert_eq!(frames.len(), payloads.len());
+            assert_eq!(decompress_par(&frames, &options).unwrap(), payloads);
+
+            let mut truncated = frames.clone();
+            truncated[1].pop();
+            assert!(decompress_par(&truncated, &options).is_err());
+        }
+
+        assert!(compress_par(&payloads, &Options::new().level(1000)).is_err());
+
+        // Errors from setting up a context keep the original error.
+        let error = compress_par(&payloads, &Options::new().window_log(100))
+            .unwrap_err();
+        let error = error.get_ref().unwrap();
+        let error = error.downcast_ref::<Arc<io::Error>>().unwrap();
+        assert!(error.get_ref().unwrap().is::<zstd_safe::Error>());
+        assert_eq!(decompress_par(&[b""], &Options::new()).unwrap(), [b""]);
+    }
+}
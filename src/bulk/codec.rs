@@ -0,0 +1,264 @@
+use super::{Compressor, Decompressor};
+
+use std::io;
+
+/// Default maximum size of a decompressed message (4MiB).
+///
+/// This matches the default message size limit of most gRPC implementations.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 << 20;
+
+/// Message-level compression codec, for RPC frameworks.
+///
+/// This bundles a [`Compressor`] and a [`Decompressor`], re-used between
+/// messages, with a limit on the decompressed size of each message.
+///
+/// Both directions append to a caller-provided buffer, so the same output
+/// buffer can be re-used for many messages. This matches the interface
+/// expected by codecs like `tonic`'s compression encodings.
+pub struct MessageCodec<'a> {
+    compressor: Compressor<'a>,
+    decompressor: Decompressor<'a>,
+    max_message_size: usize,
+}
+
+impl MessageCodec<'static> {
+    /// Creates a new codec using the given compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> io::Result<Self> {
+        Self::with_dictionary(level, &[])
+    }
+
+    /// Creates a new codec, using the given dictionary in both directions.
+    ///
+    /// The other end of the connection must use the same dictionary.
+    pub fn with_dictionary(level: i32, dictionary: &[u8]) -> io::Result<Self> {
+        Ok(MessageCodec {
+            compressor: Compressor::with_dictionary(level, dictionary)?,
+            decompressor: Decompressor::with_dictionary(dictionary)?,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        })
+    }
+}
+
+impl<'a> MessageCodec<'a> {
+    /// Creates a new codec using existing prepared dictionaries.
+    ///
+    /// The compression level will be the one specified when creating the
+    /// encoder dictionary.
+    pub fn with_prepared_dictionaries<'b>(
+        encoder_dictionary: &'a crate::dict::EncoderDictionary<'b>,
+        decoder_dictionary: &'a crate::dict::DecoderDictionary<'b>,
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        Ok(MessageCodec {
+            compressor: Compressor::with_prepared_dictionary(
+                encoder_dictionary,
+            )?,
+            decompressor: Decompressor::with_prepared_dictionary(
+                decoder_dictionary,
+            )?,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        })
+    }
+
+    /// Sets the maximum decompressed size of a message.
+    ///
+    /// Messages decompressing to more than this will be refused.
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Returns the maximum decompressed size of a message.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Compresses a message, appending the result to `output`.
+    ///
+    /// Returns the number of bytes appended.
+    pub fn compress(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        output.reserve(zstd_safe::compress_bound(message.len()));
+
+        let start = output.len();
+        let mut cursor = io::Cursor::new(output);
+        cursor.set_position(start as u64);
+        self.compressor.compress_to_buffer(message, &mut cursor)
+    }
+
+    /// Decompresses a message, appending the result to `output`.
+    ///
+    /// Returns the number of bytes appended.
+    ///
+    /// The message must be a single frame: anything after it is refused.
+    ///
+    /// If the message does not include its decompressed size, `output`
+    /// grows as the message is decompressed, up to the maximum message size.
+    pub fn decompress(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        let capacity = match zstd_safe::get_frame_content_size(message) {
+            Ok(Some(size)) if size > self.max_message_size as u64 => {
+                return Err(too_large());
+            }
+            Ok(Some(size)) => Some(size as usize),
+            Ok(None) => None,
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid message header",
+                ));
+            }
+        };
+
+        // The size check above only covers the first frame.
+        let frame_size = zstd_safe::find_frame_compressed_size(message)
+            .map_err(crate::map_error_code)?;
+        if frame_size < message.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trailing data after the message",
+            ));
+        }
+
+        let capacity = match capacity {
+            Some(capacity) => capacity,
+            None => return self.decompress_growing(message, output),
+        };
+        output.reserve(capacity);
+
+        let start = output.len();
+        let mut cursor = io::Cursor::new(output);
+        cursor.set_position(start as u64);
+
+        // Only expose `capacity` bytes to zstd, so that the limit is
+        // enforced even if `output` already had some spare room.
+        let mut limited = Limited {
+            inner: cursor,
+            limit: capacity,
+        };
+        self.decompressor
+            .decompress_to_buffer(message, &mut limited)
+    }
+
+    /// Decompresses a message of unknown size, appending to `output`.
+    ///
+    /// The room given to zstd doubles until the message fits, up to the
+    /// maximum message size. Nothing is appended if this fails.
+    fn decompress_growing(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        let start = output.len();
+        let result = self.decompress_growing_from(message, output, start);
+        if result.is_err() {
+            output.truncate(start);
+        }
+        result
+    }
+
+    fn decompress_growing_from(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+        start: usize,
+    ) -> io::Result<usize> {
+        let limit = start.saturating_add(self.max_message_size);
+
+        self.decompressor.reset_session()?;
+        let mut input = zstd_safe::InBuffer::around(message);
+        loop {
+            if output.len() == output.capacity() {
+                let written = output.len() - start;
+                let additional = written
+                    .max(zstd_safe::DCtx::out_size())
+                    .min(limit - output.len());
+                output.reserve(additional);
+            }
+
+            let (read, written) = (input.pos(), output.len());
+            let mut limited = Limited {
+                inner: &mut *output,
+                limit,
+            };
+            let mut buffer =
+                zstd_safe::OutBuffer::around_pos(&mut limited, written);
+            let hint = self
+                .decompressor
+                .decompress_stream(&mut buffer, &mut input)?;
+
+            if hint == 0 {
+                return Ok(output.len() - start);
+            }
+            if input.pos() == read && output.len() == written {
+                // No room left, or the message is incomplete.
+                return Err(if output.len() == limit {
+                    too_large()
+                } else {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "incomplete frame",
+                    )
+                });
+            }
+        }
+    }
+
+    /// Gives mutable access to the compressor.
+    pub fn compressor_mut(&mut self) -> &mut Compressor<'a> {
+        &mut self.compressor
+    }
+
+    /// Gives mutable access to the decompressor.
+    pub fn decompressor_mut(&mut self) -> &mut Decompressor<'a> {
+        &mut self.decompressor
+    }
+}
+
+/// Restricts the capacity exposed by a `WriteBuf`.
+struct Limited<C> {
+    inner: C,
+    limit: usize,
+}
+
+unsafe impl<C: zstd_safe::WriteBuf> zstd_safe::WriteBuf for Limited<C> {
+    fn as_slice(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
+
+    fn capacity(&self) -> usize {
+        self.inner.capacity().min(self.limit)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.inner.as_mut_ptr()
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        self.inner.filled_until(n)
+    }
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "message exceeds the maximum size",
+    )
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(MessageCodec::new(0));
+}
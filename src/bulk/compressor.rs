@@ -1,5 +1,7 @@
 use crate::map_error_code;
+use crate::Options;
 
+use std::fmt;
 use std::io;
 use zstd_safe;
 
@@ -15,6 +17,8 @@ use zstd_safe;
 #[derive(Default)]
 pub struct Compressor<'a> {
     context: zstd_safe::CCtx<'a>,
+    // Compression level last set, if known.
+    level: Option<i32>,
 }
 
 impl Compressor<'static> {
@@ -34,6 +38,18 @@ impl Compressor<'static> {
 
         Ok(compressor)
     }
+
+    /// Creates a new zstd compressor, configured with the given options.
+    pub fn with_options(options: &Options) -> io::Result<Self> {
+        options.check()?;
+        let mut compressor =
+            Self::with_dictionary(options.level, &options.dictionary)?;
+        for parameter in options.parameters() {
+            compressor.set_parameter(parameter)?;
+        }
+
+        Ok(compressor)
+    }
 }
 
 impl<'a> Compressor<'a> {
@@ -80,6 +96,7 @@ impl<'a> Compressor<'a> {
         self.context
             .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
             .map_err(map_error_code)?;
+        self.level = Some(level);
 
         self.context
             .load_dictionary(dictionary)
@@ -104,6 +121,7 @@ impl<'a> Compressor<'a> {
         self.context
             .ref_cdict(dictionary.as_cdict())
             .map_err(map_error_code)?;
+        self.level = None;
 
         Ok(())
     }
@@ -124,6 +142,33 @@ impl<'a> Compressor<'a> {
             .map_err(map_error_code)
     }
 
+    /// Compresses a block of data to a fixed-size destination.
+    ///
+    /// Unlike [`Compressor::compress_to_buffer`], this checks up-front that
+    /// `destination` can hold the worst case given by
+    /// [`zstd_safe::compress_bound`], so compression never fails halfway for
+    /// lack of space. Otherwise, nothing is compressed, and the error wraps a
+    /// [`WontFit`] giving the required size.
+    ///
+    /// This suits fixed-size slots, where data that doesn't fit is stored
+    /// uncompressed instead.
+    ///
+    /// Returns the number of bytes written.
+    pub fn compress_exact(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+    ) -> io::Result<usize> {
+        let needed = zstd_safe::compress_bound(source.len());
+        if destination.len() < needed {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                WontFit { needed },
+            ));
+        }
+        self.compress_to_buffer(source, destination)
+    }
+
     /// Compresses a block of data and returns the compressed result.
     ///
     /// A level of `0` uses zstd's default (currently `3`).
@@ -151,12 +196,43 @@ impl<'a> Compressor<'a> {
         self.context
             .set_parameter(parameter)
             .map_err(map_error_code)?;
+        if let zstd_safe::CParameter::CompressionLevel(level) = parameter {
+            self.level = Some(level);
+        }
         Ok(())
     }
 
     crate::encoder_parameters!();
 }
 
+/// Error returned by [`Compressor::compress_exact`] when the destination is
+/// too small.
+///
+/// This is wrapped in an [`io::Error`] of kind
+/// [`io::ErrorKind::InvalidInput`], and can be recovered with
+/// [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WontFit {
+    /// Size the destination needs to have.
+    pub needed: usize,
+}
+
+impl fmt::Display for WontFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "destination needs at least {} bytes", self.needed)
+    }
+}
+
+impl std::error::Error for WontFit {}
+
+impl fmt::Debug for Compressor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compressor")
+            .field("level", &self.level)
+            .finish_non_exhaustive()
+    }
+}
+
 fn _assert_traits() {
     fn _assert_send<T: Send>(_: T) {}
 
@@ -2,6 +2,7 @@ use crate::map_error_code;
 
 #[cfg(feature = "experimental")]
 use std::convert::TryInto;
+use std::fmt;
 use std::io;
 use zstd_safe;
 
@@ -93,6 +94,29 @@ impl<'a> Decompressor<'a> {
             .map_err(map_error_code)
     }
 
+    /// Runs a single step of streaming decompression.
+    ///
+    /// The session needs to be reset first (see
+    /// [`Decompressor::reset_session`]). Returns zstd's hint, which is `0`
+    /// once a frame is completely decoded.
+    pub(super) fn decompress_stream<C: zstd_safe::WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut zstd_safe::OutBuffer<'_, C>,
+        input: &mut zstd_safe::InBuffer<'_>,
+    ) -> io::Result<usize> {
+        self.context
+            .decompress_stream(output, input)
+            .map_err(map_error_code)
+    }
+
+    /// Resets the current frame, keeping the parameters and dictionary.
+    pub(super) fn reset_session(&mut self) -> io::Result<()> {
+        self.context
+            .reset(zstd_safe::ResetDirective::SessionOnly)
+            .map_err(map_error_code)?;
+        Ok(())
+    }
+
     /// Decompress a block of data, and return the result in a `Vec<u8>`.
     ///
     /// The decompressed data should be at most `capacity` bytes,
@@ -109,6 +133,45 @@ impl<'a> Decompressor<'a> {
         Ok(buffer)
     }
 
+    /// Decompress a block of data, using the content size it declares.
+    ///
+    /// This allocates exactly the size stored in the frame header, and
+    /// returns an error if the size is unknown or larger than `max_size`.
+    ///
+    /// `data` must contain a single frame.
+    pub fn decompress_with_capacity_hint(
+        &mut self,
+        data: &[u8],
+        max_size: usize,
+    ) -> io::Result<Vec<u8>> {
+        let size = match zstd_safe::get_frame_content_size(data) {
+            Ok(Some(size)) => size,
+            Ok(None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "frame content size is unknown",
+                ))
+            }
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid frame header",
+                ))
+            }
+        };
+
+        if size > max_size as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame content size exceeds the maximum",
+            ));
+        }
+
+        let mut buffer = Vec::with_capacity(size as usize);
+        self.decompress_to_buffer(data, &mut buffer)?;
+        Ok(buffer)
+    }
+
     /// Sets a decompression parameter for this decompressor.
     pub fn set_parameter(
         &mut self,
@@ -144,6 +207,12 @@ impl<'a> Decompressor<'a> {
     }
 }
 
+impl fmt::Debug for Decompressor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decompressor").finish_non_exhaustive()
+    }
+}
+
 fn _assert_traits() {
     fn _assert_send<T: Send>(_: T) {}
 
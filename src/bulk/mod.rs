@@ -3,18 +3,25 @@
 //! These methods process all the input data at once.
 //! It is therefore best used with relatively small blocks
 //! (like small network packets).
+//!
+//! The free functions re-use a thread-local context between calls.
 
+mod codec;
 mod compressor;
 mod decompressor;
 
 #[cfg(test)]
 mod tests;
 
-pub use self::compressor::Compressor;
+pub use self::codec::{MessageCodec, DEFAULT_MAX_MESSAGE_SIZE};
+pub use self::compressor::{Compressor, WontFit};
 pub use self::decompressor::Decompressor;
 
 use std::io;
 
+use crate::context::{with_cctx, with_dctx};
+use crate::map_error_code;
+
 /// Compresses a single block of data to the given destination buffer.
 ///
 /// Returns the number of bytes written, or an error if something happened
@@ -26,14 +33,28 @@ pub fn compress_to_buffer(
     destination: &mut [u8],
     level: i32,
 ) -> io::Result<usize> {
-    Compressor::new(level)?.compress_to_buffer(source, destination)
+    with_cctx(|context| {
+        context
+            .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+        context
+            .compress2(destination, source)
+            .map_err(map_error_code)
+    })
 }
 
 /// Compresses a block of data and returns the compressed result.
 ///
 /// A level of `0` uses zstd's default (currently `3`).
 pub fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
-    Compressor::new(level)?.compress(data)
+    let mut buffer = Vec::with_capacity(zstd_safe::compress_bound(data.len()));
+    with_cctx(|context| {
+        context
+            .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+        context.compress2(&mut buffer, data).map_err(map_error_code)
+    })?;
+    Ok(buffer)
 }
 
 /// Deompress a single block of data to the given destination buffer.
@@ -44,7 +65,11 @@ pub fn decompress_to_buffer(
     source: &[u8],
     destination: &mut [u8],
 ) -> io::Result<usize> {
-    Decompressor::new()?.decompress_to_buffer(source, destination)
+    with_dctx(|context| {
+        context
+            .decompress(destination, source)
+            .map_err(map_error_code)
+    })
 }
 
 /// Decompresses a block of data and returns the decompressed result.
@@ -52,5 +77,53 @@ pub fn decompress_to_buffer(
 /// The decompressed data should be at most `capacity` bytes,
 /// or an error will be returned.
 pub fn decompress(data: &[u8], capacity: usize) -> io::Result<Vec<u8>> {
-    Decompressor::new()?.decompress(data, capacity)
+    let capacity = Decompressor::upper_bound(data)
+        .unwrap_or(capacity)
+        .min(capacity);
+    let mut buffer = Vec::with_capacity(capacity);
+    with_dctx(|context| {
+        context
+            .decompress(&mut buffer, data)
+            .map_err(map_error_code)
+    })?;
+    Ok(buffer)
+}
+
+/// Maximum number of bytes looked at by [`estimate_compressibility`].
+pub const ESTIMATE_SAMPLE_SIZE: usize = 16 * 1024;
+
+/// Quickly estimates how well the given data would compress.
+///
+/// This compresses at most [`ESTIMATE_SAMPLE_SIZE`] bytes from the start of
+/// `sample` with a fast level, and returns the compression ratio: the
+/// compressed size divided by the original size.
+///
+/// A value close to (or above) `1.0` means the data is unlikely to be worth
+/// compressing. An empty sample returns `1.0`.
+pub fn estimate_compressibility(sample: &[u8]) -> f32 {
+    let mut buffer = Vec::new();
+    Compressor::new(1)
+        .and_then(|mut probe| sample_ratio(&mut probe, sample, &mut buffer))
+        .unwrap_or(1.0)
+}
+
+/// Compresses the start of `sample` with `probe`, and returns the ratio.
+///
+/// `buffer` is used as scratch space, to allow re-using allocations.
+pub(crate) fn sample_ratio(
+    probe: &mut Compressor<'_>,
+    sample: &[u8],
+    buffer: &mut Vec<u8>,
+) -> io::Result<f32> {
+    let sample = &sample[..sample.len().min(ESTIMATE_SAMPLE_SIZE)];
+    if sample.is_empty() {
+        return Ok(1.0);
+    }
+
+    buffer.clear();
+    buffer.reserve(zstd_safe::compress_bound(sample.len()));
+    let compressed = probe.compress_to_buffer(sample, buffer)?;
+    buffer.clear();
+
+    Ok(compressed as f32 / sample.len() as f32)
 }
@@ -40,3 +40,151 @@ fn has_content_size() {
         Some(TEXT.len() as u64)
     );
 }
+
+#[test]
+fn test_message_codec() {
+    let mut codec = super::MessageCodec::new(1).unwrap();
+
+    // Both directions append to the given buffer.
+    let mut compressed = b"header".to_vec();
+    let written = codec.compress(TEXT.as_bytes(), &mut compressed).unwrap();
+    assert_eq!(compressed.len(), 6 + written);
+    assert_eq!(&compressed[..6], b"header");
+
+    let mut decompressed = Vec::new();
+    for _ in 0..2 {
+        codec
+            .decompress(&compressed[6..], &mut decompressed)
+            .unwrap();
+    }
+    assert_eq!(decompressed, [TEXT.as_bytes(), TEXT.as_bytes()].concat());
+
+    // A message is a single frame, even if the next one would fit.
+    let mut two_frames = compressed[6..].to_vec();
+    two_frames.extend(compress(b"", 1).unwrap());
+    assert!(codec.decompress(&two_frames, &mut decompressed).is_err());
+    assert_eq!(decompressed.len(), 2 * TEXT.len());
+
+    // Messages larger than the limit are refused.
+    codec.set_max_message_size(TEXT.len() - 1);
+    assert!(codec
+        .decompress(&compressed[6..], &mut decompressed)
+        .is_err());
+}
+
+#[test]
+fn test_message_codec_unknown_size() {
+    let mut codec = super::MessageCodec::new(1).unwrap();
+    // Large enough to need growing the output a few times.
+    let text = TEXT.repeat(1000);
+
+    // Streaming compression does not record the size.
+    let mut encoder = crate::stream::Encoder::new(Vec::new(), 1).unwrap();
+    std::io::Write::write_all(&mut encoder, text.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // The output grows with the message, instead of reserving the limit.
+    let mut decompressed = b"header".to_vec();
+    let written = codec.decompress(&compressed, &mut decompressed).unwrap();
+    assert_eq!(written, text.len());
+    assert_eq!(&decompressed[6..], text.as_bytes());
+    assert!(decompressed.capacity() < super::DEFAULT_MAX_MESSAGE_SIZE);
+
+    // Failures leave the output as it was.
+    let truncated = &compressed[..compressed.len() - 1];
+    assert!(codec.decompress(truncated, &mut decompressed).is_err());
+    assert_eq!(decompressed.len(), 6 + text.len());
+
+    codec.set_max_message_size(text.len() - 1);
+    assert!(codec.decompress(&compressed, &mut decompressed).is_err());
+    assert_eq!(decompressed.len(), 6 + text.len());
+
+    codec.set_max_message_size(text.len());
+    codec.decompress(&compressed, &mut decompressed).unwrap();
+    assert_eq!(decompressed.len(), 6 + 2 * text.len());
+}
+
+#[test]
+fn test_estimate_compressibility() {
+    let text = TEXT.repeat(10);
+    let compressed = compress(text.as_bytes(), 19).unwrap();
+
+    assert!(super::estimate_compressibility(text.as_bytes()) < 0.5);
+    assert!(super::estimate_compressibility(&compressed) > 0.9);
+    assert_eq!(super::estimate_compressibility(&[]), 1.0);
+}
+
+#[test]
+fn test_decompress_with_capacity_hint() {
+    let mut decompressor = super::Decompressor::new().unwrap();
+
+    let compressed = compress(TEXT.as_bytes(), 1).unwrap();
+    let decompressed = decompressor
+        .decompress_with_capacity_hint(&compressed, TEXT.len())
+        .unwrap();
+    assert_eq!(decompressed, TEXT.as_bytes());
+    assert_eq!(decompressed.capacity(), TEXT.len());
+
+    // Too large for the given maximum.
+    assert!(decompressor
+        .decompress_with_capacity_hint(&compressed, TEXT.len() - 1)
+        .is_err());
+
+    // Streaming compression does not know the size in advance.
+    let mut encoder = crate::stream::Encoder::new(Vec::new(), 1).unwrap();
+    std::io::Write::write_all(&mut encoder, TEXT.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(decompressor
+        .decompress_with_capacity_hint(&compressed, usize::MAX)
+        .is_err());
+}
+
+#[cfg(feature = "bumpalo")]
+#[test]
+fn test_arena_buffers() {
+    let arena = bumpalo::Bump::new();
+    let mut compressor = super::Compressor::new(1).unwrap();
+    let mut decompressor = super::Decompressor::new().unwrap();
+
+    let mut compressed = bumpalo::collections::Vec::with_capacity_in(
+        zstd_safe::compress_bound(TEXT.len()),
+        &arena,
+    );
+    compressor
+        .compress_to_buffer(TEXT.as_bytes(), &mut compressed)
+        .unwrap();
+
+    let mut decompressed =
+        bumpalo::collections::Vec::with_capacity_in(TEXT.len(), &arena);
+    decompressor
+        .decompress_to_buffer(&compressed, &mut decompressed)
+        .unwrap();
+    assert_eq!(&decompressed[..], TEXT.as_bytes());
+}
+
+#[test]
+fn test_compress_exact() {
+    let mut compressor = super::Compressor::new(1).unwrap();
+    let needed = zstd_safe::compress_bound(TEXT.len());
+
+    // Even if the compressed data would fit, nothing is attempted.
+    let mut slot = vec![0u8; needed - 1];
+    let err = compressor
+        .compress_exact(TEXT.as_bytes(), &mut slot)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let wont_fit = err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<super::WontFit>())
+        .unwrap();
+    assert_eq!(*wont_fit, super::WontFit { needed });
+
+    let mut slot = vec![0u8; needed];
+    let written = compressor
+        .compress_exact(TEXT.as_bytes(), &mut slot)
+        .unwrap();
+    assert_eq!(
+        decompress(&slot[..written], TEXT.len()).unwrap(),
+        TEXT.as_bytes()
+    );
+}
@@ -0,0 +1,249 @@
+//! Check whether frames can be decoded under given constraints.
+//!
+//! Services accepting compressed uploads may only be able to decode some of
+//! them: decoders often limit the window size (and so the memory used),
+//! only know some dictionaries, or don't support legacy formats.
+//!
+//! [`check`] only looks at the header of a frame, so problematic input can be
+//! rejected (or transcoded, see [`crate::proxy`]) before reading all of it.
+use std::fmt;
+use std::io;
+
+use crate::frame::{self, FrameHeader};
+
+/// Magic number of the v0.1 legacy format.
+const LEGACY_V01_MAGIC: u32 = 0x1EB5_2FFD;
+
+/// Magic numbers of the v0.2 to v0.7 legacy formats.
+const LEGACY_MAGIC_START: u32 = 0xFD2F_B522;
+const LEGACY_MAGIC_END: u32 = 0xFD2F_B527;
+
+/// Constraints of a decoder.
+#[derive(Clone, Debug)]
+pub struct DecoderLimits {
+    window_log_max: u32,
+    dictionaries: Vec<u32>,
+    legacy: bool,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        DecoderLimits::new()
+    }
+}
+
+impl DecoderLimits {
+    /// Creates the limits of a default decoder from this crate.
+    ///
+    /// This accepts windows up to 128MB, no dictionary, and legacy formats
+    /// if the `legacy` feature is enabled.
+    pub fn new() -> Self {
+        DecoderLimits {
+            window_log_max: 27,
+            dictionaries: Vec::new(),
+            legacy: cfg!(feature = "legacy"),
+        }
+    }
+
+    /// Sets the maximum window log the decoder accepts.
+    ///
+    /// This is the same value as given to `window_log_max` on decoders.
+    #[must_use]
+    pub fn window_log_max(mut self, window_log_max: u32) -> Self {
+        self.window_log_max = window_log_max;
+        self
+    }
+
+    /// Adds the ID of a dictionary available to the decoder.
+    #[must_use]
+    pub fn dictionary(mut self, dict_id: u32) -> Self {
+        self.dictionaries.push(dict_id);
+        self
+    }
+
+    /// Sets whether the decoder supports legacy formats (before v0.8).
+    #[must_use]
+    pub fn legacy(mut self, legacy: bool) -> Self {
+        self.legacy = legacy;
+        self
+    }
+}
+
+/// Reason why a frame cannot be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Incompatibility {
+    /// The input doesn't start with a zstd frame.
+    NotZstd,
+
+    /// The input is too short to contain a complete frame header.
+    Truncated,
+
+    /// The frame header is invalid.
+    Corrupted,
+
+    /// The frame requires a larger window than allowed.
+    WindowTooLarge {
+        /// Window size required by the frame.
+        window_size: u64,
+
+        /// Largest window size allowed.
+        window_size_max: u64,
+    },
+
+    /// The frame requires a dictionary which is not available.
+    MissingDictionary(u32),
+
+    /// The frame uses a legacy format, and legacy support is disabled.
+    Legacy,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::NotZstd => write!(f, "input is not zstd"),
+            Incompatibility::Truncated => {
+                write!(f, "frame header is truncated")
+            }
+            Incompatibility::Corrupted => write!(f, "frame header is invalid"),
+            Incompatibility::WindowTooLarge {
+                window_size,
+                window_size_max,
+            } => write!(
+                f,
+                "frame requires a window of {} bytes (limit is {})",
+                window_size, window_size_max
+            ),
+            Incompatibility::MissingDictionary(dict_id) => {
+                write!(f, "frame requires missing dictionary {}", dict_id)
+            }
+            Incompatibility::Legacy => {
+                write!(f, "frame uses an unsupported legacy format")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Incompatibility {}
+
+/// Checks whether the frame at the start of `frame_prefix` can be decoded.
+///
+/// `frame_prefix` needs to contain the frame header: the first
+/// [`FrameHeader::MAX_SIZE`] bytes of the frame are always enough.
+///
+/// Skippable frames are always compatible. Legacy frames are only checked
+/// against `limits.legacy`.
+///
+/// Note that frames may use a dictionary without recording its ID.
+pub fn check(
+    frame_prefix: &[u8],
+    limits: &DecoderLimits,
+) -> Result<(), Incompatibility> {
+    if frame_prefix.len() < 4 {
+        return Err(Incompatibility::Truncated);
+    }
+    if frame::is_skippable(frame_prefix) {
+        return Ok(());
+    }
+
+    let mut magic = [0; 4];
+    magic.copy_from_slice(&frame_prefix[..4]);
+    let magic = u32::from_le_bytes(magic);
+    if magic == LEGACY_V01_MAGIC
+        || (LEGACY_MAGIC_START..=LEGACY_MAGIC_END).contains(&magic)
+    {
+        return if limits.legacy {
+            Ok(())
+        } else {
+            Err(Incompatibility::Legacy)
+        };
+    }
+    if magic != frame::MAGIC_NUMBER {
+        return Err(Incompatibility::NotZstd);
+    }
+
+    let (header, _) = FrameHeader::parse(frame_prefix).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            Incompatibility::Truncated
+        } else {
+            Incompatibility::Corrupted
+        }
+    })?;
+
+    let window_size_max = 1u64 << limits.window_log_max.min(63);
+    if let Some(window_size) = header.effective_window_size() {
+        if window_size > window_size_max {
+            return Err(Incompatibility::WindowTooLarge {
+                window_size,
+                window_size_max,
+            });
+        }
+    }
+
+    match header.dict_id {
+        Some(dict_id) if !limits.dictionaries.contains(&dict_id) => {
+            Err(Incompatibility::MissingDictionary(dict_id))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, DecoderLimits, Incompatibility};
+    use crate::frame::{self, FrameHeader};
+
+    #[test]
+    fn test_check() {
+        let limits = DecoderLimits::new().window_log_max(20).dictionary(42);
+
+        let compressed = crate::encode_all(&b"compatible"[..], 1).unwrap();
+        assert_eq!(check(&compressed, &limits), Ok(()));
+        assert_eq!(
+            check(&compressed[..3], &limits),
+            Err(Incompatibility::Truncated)
+        );
+        assert_eq!(check(b"not zstd", &limits), Err(Incompatibility::NotZstd));
+
+        let mut skippable = Vec::new();
+        frame::write_skippable_frame(&mut skippable, 0, b"data").unwrap();
+        assert_eq!(check(&skippable, &limits), Ok(()));
+
+        let mut header = Vec::new();
+        FrameHeader {
+            window_size: Some(1 << 21),
+            ..FrameHeader::default()
+        }
+        .write(&mut header)
+        .unwrap();
+        assert_eq!(
+            check(&header, &limits),
+            Err(Incompatibility::WindowTooLarge {
+                window_size: 1 << 21,
+                window_size_max: 1 << 20,
+            })
+        );
+
+        for (dict_id, expected) in [
+            (42, Ok(())),
+            (7, Err(Incompatibility::MissingDictionary(7))),
+        ] {
+            let mut header = Vec::new();
+            FrameHeader {
+                window_size: Some(1 << 10),
+                dict_id: Some(dict_id),
+                ..FrameHeader::default()
+            }
+            .write(&mut header)
+            .unwrap();
+            assert_eq!(check(&header, &limits), expected);
+        }
+
+        let legacy = 0xFD2F_B527u32.to_le_bytes();
+        assert_eq!(
+            check(&legacy, &limits.clone().legacy(false)),
+            Err(Incompatibility::Legacy)
+        );
+        assert_eq!(check(&legacy, &limits.legacy(true)), Ok(()));
+    }
+}
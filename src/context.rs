@@ -0,0 +1,142 @@
+//! Thread-local contexts, re-used between calls to the convenience functions.
+use std::cell::RefCell;
+use std::io;
+
+use zstd_safe::{CCtx, DCtx, ResetDirective};
+
+use crate::map_error_code;
+
+thread_local! {
+    static CCTX: RefCell<CCtx<'static>> = RefCell::new(CCtx::create());
+    static DCTX: RefCell<DCtx<'static>> = RefCell::new(DCtx::create());
+}
+
+/// Runs `f` with this thread's compression context.
+///
+/// The context is reset before and after `f`: parameters, dictionaries
+/// and data are forgotten, even if `f` failed or panicked.
+///
+/// If the context is not available (for example if `f` itself calls this
+/// function), a new context is used instead.
+pub(crate) fn with_cctx<T>(
+    f: impl FnOnce(&mut CCtx<'static>) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut f = Some(f);
+    let result = CCTX.try_with(|context| {
+        let mut context = context.try_borrow_mut().ok()?;
+        // A previous call may have panicked before resetting the context:
+        // reset it first, so nothing leaks from that call.
+        if let Err(code) = context.reset(ResetDirective::SessionAndParameters)
+        {
+            return Some(Err(map_error_code(code)));
+        }
+        let result = (f.take().unwrap())(&mut context);
+        Some(
+            context
+                .reset(ResetDirective::SessionAndParameters)
+                .map_err(map_error_code)
+                .and(result),
+        )
+    });
+
+    match result {
+        Ok(Some(result)) => result,
+        _ => (f.take().unwrap())(&mut CCtx::create()),
+    }
+}
+
+/// Runs `f` with this thread's decompression context.
+///
+/// The context is reset before and after `f`: parameters, dictionaries
+/// and data are forgotten, even if `f` failed or panicked.
+///
+/// If the context is not available (for example if `f` itself calls this
+/// function), a new context is used instead.
+pub(crate) fn with_dctx<T>(
+    f: impl FnOnce(&mut DCtx<'static>) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut f = Some(f);
+    let result = DCTX.try_with(|context| {
+        let mut context = context.try_borrow_mut().ok()?;
+        // A previous call may have panicked before resetting the context:
+        // reset it first, so nothing leaks from that call.
+        if let Err(code) = context.reset(ResetDirective::SessionAndParameters)
+        {
+            return Some(Err(map_error_code(code)));
+        }
+        let result = (f.take().unwrap())(&mut context);
+        Some(
+            context
+                .reset(ResetDirective::SessionAndParameters)
+                .map_err(map_error_code)
+                .and(result),
+        )
+    });
+
+    match result {
+        Ok(Some(result)) => result,
+        _ => (f.take().unwrap())(&mut DCtx::create()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+    use std::panic;
+
+    /// Reader giving some data, then panicking.
+    struct Panicking(usize);
+
+    impl Read for Panicking {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                panic!("reader failed");
+            }
+            self.0 -= 1;
+            let len = buf.len().min(4096);
+            buf[..len].fill(b'B');
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_panic_cctx() {
+        let result = panic::catch_unwind(|| {
+            let _ = crate::encode_all(Panicking(4), 3);
+        });
+        assert!(result.is_err());
+
+        let compressed = crate::encode_all(&b"hello world"[..], 3).unwrap();
+        assert_eq!(
+            crate::decode_all(&compressed[..]).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn test_panic_dctx() {
+        let text = vec![b'A'; 1 << 20];
+        let compressed = crate::encode_all(&text[..], 3).unwrap();
+
+        // Panics after the first bytes of the frame.
+        struct Partial<'a>(&'a [u8], bool);
+        impl Read for Partial<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.1 {
+                    panic!("reader failed");
+                }
+                self.1 = true;
+                let len = buf.len().min(self.0.len() / 2);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                Ok(len)
+            }
+        }
+        let result = panic::catch_unwind(|| {
+            let _ = crate::stream::decode_all(Partial(&compressed, false));
+        });
+        assert!(result.is_err());
+
+        let other = crate::encode_all(&b"hello world"[..], 3).unwrap();
+        assert_eq!(crate::decode_all(&other[..]).unwrap(), b"hello world");
+    }
+}
@@ -0,0 +1,87 @@
+//! CRC-32C (Castagnoli) implementation, for content checksum trailers.
+
+/// Reversed Castagnoli polynomial.
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Streaming CRC-32C hasher.
+#[derive(Clone, Debug)]
+pub(crate) struct Crc32c {
+    state: u32,
+}
+
+impl Crc32c {
+    /// Creates a new hasher.
+    pub(crate) fn new() -> Self {
+        Crc32c { state: !0 }
+    }
+
+    /// Adds data to the checksum.
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        let mut crc = self.state;
+        for &byte in data {
+            crc =
+                TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        self.state = crc;
+    }
+
+    /// Returns the checksum of all the data given so far.
+    pub(crate) fn digest(&self) -> u32 {
+        !self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Crc32c;
+
+    fn crc32c(data: &[u8]) -> u32 {
+        let mut hasher = Crc32c::new();
+        hasher.update(data);
+        hasher.digest()
+    }
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(&[0; 32]), 0x8A91_36AA);
+        assert_eq!(crc32c(&[0xFF; 32]), 0x62A8_AB43);
+    }
+
+    #[test]
+    fn test_streaming() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let expected = crc32c(&data);
+
+        for &step in &[1, 3, 31, 100] {
+            let mut hasher = Crc32c::new();
+            for chunk in data.chunks(step) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.digest(), expected);
+        }
+    }
+}
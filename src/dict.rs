@@ -11,20 +11,35 @@
 //!
 //! To use, see [`Encoder::with_dictionary`] or [`Decoder::with_dictionary`].
 //!
+//! Prepared dictionaries can be shared through the global [`cache`], and
+//! selected by ID when decoding with a [`DictRegistry`].
+//!
 //! [`Encoder::with_dictionary`]: ../struct.Encoder.html#method.with_dictionary
 //! [`Decoder::with_dictionary`]: ../struct.Decoder.html#method.with_dictionary
 
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
 #[cfg(feature = "zdict_builder")]
-use std::io::{self, Read};
+use std::io::Read;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::Arc;
 
 pub use zstd_safe::{CDict, DDict};
 
+pub mod cache;
+
 /// Prepared dictionary for compression
 ///
 /// A dictionary can include its own copy of the data (if it is `'static`), or it can merely point
 /// to a separate buffer (if it has another lifetime).
 pub struct EncoderDictionary<'a> {
     cdict: CDict<'a>,
+    // Mapping referenced by `cdict`, if any. Fields are dropped in order, so
+    // it outlives `cdict`.
+    #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+    _mapping: Option<crate::fs::Mmap>,
 }
 
 impl EncoderDictionary<'static> {
@@ -34,8 +49,56 @@ impl EncoderDictionary<'static> {
     pub fn copy(dictionary: &[u8], level: i32) -> Self {
         Self {
             cdict: zstd_safe::create_cdict(dictionary, level),
+            #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+            _mapping: None,
         }
     }
+
+    /// Reads a dictionary from a file, and prepares it for compression.
+    ///
+    /// This is convenient for dictionaries shipped as separate files, like
+    /// the ones written by `zstd --train`. The data is copied, so the file
+    /// can change afterwards.
+    pub fn from_file<P: AsRef<Path>>(path: P, level: i32) -> io::Result<Self> {
+        let dictionary = std::fs::read(path)?;
+        Ok(Self::copy(&dictionary, level))
+    }
+
+    /// Maps a dictionary file in memory, and prepares it for compression
+    /// without copying it.
+    ///
+    /// The file stays mapped as long as the dictionary is alive. This saves
+    /// memory for large dictionaries, compared to
+    /// [`EncoderDictionary::from_file`].
+    ///
+    /// Only available on unix, with the `mmap` and `experimental` features.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified while the dictionary is alive.
+    /// Truncating it may crash the process (with a `SIGBUS`).
+    #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+    #[cfg_attr(
+        feature = "doc-cfg",
+        doc(cfg(all(unix, feature = "mmap", feature = "experimental")))
+    )]
+    pub unsafe fn map_file<P: AsRef<Path>>(
+        path: P,
+        level: i32,
+    ) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mapping = crate::fs::Mmap::map(&file, file.metadata()?.len())?;
+        // The mapping doesn't move when `mapping` does, and is kept alive
+        // with the dictionary.
+        let data: &'static [u8] = std::slice::from_raw_parts(
+            mapping.as_slice().as_ptr(),
+            mapping.as_slice().len(),
+        );
+        Ok(Self {
+            cdict: CDict::create_by_reference(data, level),
+            _mapping: Some(mapping),
+        })
+    }
 }
 
 impl<'a> EncoderDictionary<'a> {
@@ -49,6 +112,8 @@ impl<'a> EncoderDictionary<'a> {
     pub fn new(dictionary: &'a [u8], level: i32) -> Self {
         Self {
             cdict: zstd_safe::CDict::create_by_reference(dictionary, level),
+            #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+            _mapping: None,
         }
     }
 
@@ -56,11 +121,31 @@ impl<'a> EncoderDictionary<'a> {
     pub fn as_cdict(&self) -> &CDict<'a> {
         &self.cdict
     }
+
+    /// Returns the ID of this dictionary.
+    ///
+    /// Returns `None` for raw content dictionaries, which have no ID.
+    pub fn dict_id(&self) -> Option<NonZeroU32> {
+        self.cdict.get_dict_id()
+    }
+}
+
+impl fmt::Debug for EncoderDictionary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncoderDictionary")
+            .field("dict_id", &self.cdict.get_dict_id())
+            .field("size", &self.cdict.sizeof())
+            .finish()
+    }
 }
 
 /// Prepared dictionary for decompression
 pub struct DecoderDictionary<'a> {
     ddict: DDict<'a>,
+    // Mapping referenced by `ddict`, if any. Fields are dropped in order, so
+    // it outlives `ddict`.
+    #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+    _mapping: Option<crate::fs::Mmap>,
 }
 
 impl DecoderDictionary<'static> {
@@ -70,8 +155,50 @@ impl DecoderDictionary<'static> {
     pub fn copy(dictionary: &[u8]) -> Self {
         Self {
             ddict: zstd_safe::DDict::create(dictionary),
+            #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+            _mapping: None,
         }
     }
+
+    /// Reads a dictionary from a file, and prepares it for decompression.
+    ///
+    /// The data is copied, so the file can change afterwards. Use
+    /// [`DecoderDictionary::dict_id`] to find which frames it applies to.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let dictionary = std::fs::read(path)?;
+        Ok(Self::copy(&dictionary))
+    }
+
+    /// Maps a dictionary file in memory, and prepares it for decompression
+    /// without copying it.
+    ///
+    /// The file stays mapped as long as the dictionary is alive.
+    ///
+    /// Only available on unix, with the `mmap` and `experimental` features.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified while the dictionary is alive.
+    /// Truncating it may crash the process (with a `SIGBUS`).
+    #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+    #[cfg_attr(
+        feature = "doc-cfg",
+        doc(cfg(all(unix, feature = "mmap", feature = "experimental")))
+    )]
+    pub unsafe fn map_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mapping = crate::fs::Mmap::map(&file, file.metadata()?.len())?;
+        // The mapping doesn't move when `mapping` does, and is kept alive
+        // with the dictionary.
+        let data: &'static [u8] = std::slice::from_raw_parts(
+            mapping.as_slice().as_ptr(),
+            mapping.as_slice().len(),
+        );
+        Ok(Self {
+            ddict: DDict::create_by_reference(data),
+            _mapping: Some(mapping),
+        })
+    }
 }
 
 impl<'a> DecoderDictionary<'a> {
@@ -83,6 +210,8 @@ impl<'a> DecoderDictionary<'a> {
     pub fn new(dict: &'a [u8]) -> Self {
         Self {
             ddict: zstd_safe::DDict::create_by_reference(dict),
+            #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+            _mapping: None,
         }
     }
 
@@ -90,6 +219,101 @@ impl<'a> DecoderDictionary<'a> {
     pub fn as_ddict(&self) -> &DDict<'a> {
         &self.ddict
     }
+
+    /// Returns the ID of this dictionary.
+    ///
+    /// Returns `None` for raw content dictionaries, which have no ID.
+    pub fn dict_id(&self) -> Option<NonZeroU32> {
+        self.ddict.get_dict_id()
+    }
+}
+
+impl fmt::Debug for DecoderDictionary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecoderDictionary")
+            .field("dict_id", &self.ddict.get_dict_id())
+            .field("size", &self.ddict.sizeof())
+            .finish()
+    }
+}
+
+/// Prepared dictionaries for decompression, by dictionary ID.
+///
+/// Decoders created with a registry (see
+/// [`Decoder::with_dictionaries`](crate::stream::read::Decoder::with_dictionaries))
+/// pick the dictionary declared by each frame header, so streams mixing
+/// several dictionaries (for example after rotating them) can be decoded
+/// without looking at the frames first.
+///
+/// Cloning a registry is cheap: dictionaries are shared.
+#[derive(Clone, Default)]
+pub struct DictRegistry {
+    dictionaries: HashMap<u32, Arc<DecoderDictionary<'static>>>,
+}
+
+impl DictRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a dictionary, under its own ID.
+    ///
+    /// Returns the dictionary previously registered with this ID, if any.
+    /// Fails for raw content dictionaries, which have no ID: use
+    /// [`DictRegistry::insert_with_id`] for these.
+    pub fn insert(
+        &mut self,
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> io::Result<Option<Arc<DecoderDictionary<'static>>>> {
+        let dict_id = dictionary.dict_id().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "dictionary has no ID")
+        })?;
+        Ok(self.insert_with_id(dict_id.get(), dictionary))
+    }
+
+    /// Adds a dictionary, under the given ID.
+    ///
+    /// Returns the dictionary previously registered with this ID, if any.
+    pub fn insert_with_id(
+        &mut self,
+        dict_id: u32,
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> Option<Arc<DecoderDictionary<'static>>> {
+        self.dictionaries.insert(dict_id, dictionary)
+    }
+
+    /// Returns the dictionary registered with this ID, if any.
+    pub fn get(
+        &self,
+        dict_id: u32,
+    ) -> Option<&Arc<DecoderDictionary<'static>>> {
+        self.dictionaries.get(&dict_id)
+    }
+
+    /// Removes the dictionary registered with this ID, and returns it.
+    pub fn remove(
+        &mut self,
+        dict_id: u32,
+    ) -> Option<Arc<DecoderDictionary<'static>>> {
+        self.dictionaries.remove(&dict_id)
+    }
+
+    /// Returns the number of dictionaries.
+    pub fn len(&self) -> usize {
+        self.dictionaries.len()
+    }
+
+    /// Returns `true` if there is no dictionary.
+    pub fn is_empty(&self) -> bool {
+        self.dictionaries.is_empty()
+    }
+}
+
+impl fmt::Debug for DictRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.dictionaries.keys()).finish()
+    }
 }
 
 /// Train a dictionary from a big continuous chunk of data, with all samples
@@ -100,7 +324,7 @@ impl<'a> DecoderDictionary<'a> {
 ///
 /// * `sample_data` is the concatenation of all sample data.
 /// * `sample_sizes` is the size of each sample in `sample_data`.
-///     The sum of all `sample_sizes` should equal the length of `sample_data`.
+///   The sum of all `sample_sizes` should equal the length of `sample_data`.
 /// * `max_size` is the maximum size of the dictionary to generate.
 ///
 /// The result is the dictionary data. You can, for example, feed it to [`CDict::create`].
@@ -222,7 +446,7 @@ where
 /// Train a dict from a list of files.
 ///
 /// * `filenames` is an iterator of files to load. Each file will be treated as an individual
-///     sample.
+///   sample.
 /// * `max_size` is the maximum size of the dictionary to generate.
 ///
 /// The result is the dictionary data. You can, for example, feed it to [`CDict::create`].
@@ -293,4 +517,62 @@ mod tests {
             assert_eq!(&content, &result);
         }
     }
+
+    #[test]
+    fn test_from_file() {
+        use super::{DecoderDictionary, EncoderDictionary};
+
+        let paths: Vec<_> = walkdir::WalkDir::new("src")
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .filter(|path| path.to_str().unwrap().ends_with(".rs"))
+            .collect();
+        let dict = super::from_files(&paths, 4000).unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("zstd_test_dict_from_file_{}", std::process::id()));
+        fs::write(&path, &dict).unwrap();
+
+        let text = include_bytes!("../assets/example.txt");
+        let check =
+            |encoder_dict: &EncoderDictionary<'_>,
+             decoder_dict: &DecoderDictionary<'_>| {
+                assert!(encoder_dict.dict_id().is_some());
+                assert_eq!(encoder_dict.dict_id(), decoder_dict.dict_id());
+
+                let mut encoder =
+                    crate::stream::write::Encoder::with_prepared_dictionary(
+                        Vec::new(),
+                        encoder_dict,
+                    )
+                    .unwrap();
+                io::Write::write_all(&mut encoder, text).unwrap();
+                let compressed = encoder.finish().unwrap();
+
+                let mut decoder =
+                    crate::stream::read::Decoder::with_prepared_dictionary(
+                        &compressed[..],
+                        decoder_dict,
+                    )
+                    .unwrap();
+                let mut result = Vec::new();
+                decoder.read_to_end(&mut result).unwrap();
+                assert_eq!(&result[..], &text[..]);
+            };
+
+        check(
+            &EncoderDictionary::from_file(&path, 3).unwrap(),
+            &DecoderDictionary::from_file(&path).unwrap(),
+        );
+
+        #[cfg(all(unix, feature = "mmap", feature = "experimental"))]
+        unsafe {
+            check(
+                &EncoderDictionary::map_file(&path, 3).unwrap(),
+                &DecoderDictionary::map_file(&path).unwrap(),
+            );
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert!(DecoderDictionary::from_file(&path).is_err());
+    }
 }
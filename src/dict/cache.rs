@@ -0,0 +1,318 @@
+//! Global cache of prepared dictionaries.
+//!
+//! Preparing a dictionary (digesting it into a `CDict` or `DDict`) is
+//! expensive. Processes that keep creating encoders or decoders from the
+//! same raw dictionary can use this cache to only prepare it once.
+//!
+//! Dictionaries are looked up by a hash of their content (and, for encoder
+//! dictionaries, by the compression level), and the content is compared on
+//! hits, so colliding dictionaries never share an entry. The cache is bounded
+//! both in number of entries and in memory: when full, the least recently
+//! used dictionaries are dropped first.
+//!
+//! Using the cache is entirely optional: the regular constructors, like
+//! [`DecoderDictionary::copy`], never use it.
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{DecoderDictionary, EncoderDictionary};
+use crate::xxhash::XxHash64;
+
+/// Default maximum number of dictionaries in the cache.
+pub const DEFAULT_MAX_ENTRIES: usize = 64;
+
+/// Default maximum memory used by the dictionaries in the cache.
+pub const DEFAULT_MAX_BYTES: usize = 64 << 20;
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    entries: Vec::new(),
+    max_entries: DEFAULT_MAX_ENTRIES,
+    max_bytes: DEFAULT_MAX_BYTES,
+    clock: 0,
+});
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Encoder(i32),
+    Decoder,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Key {
+    hash: u64,
+    len: usize,
+    role: Role,
+}
+
+impl Key {
+    fn new(dictionary: &[u8], role: Role) -> Self {
+        let mut hasher = XxHash64::new(0);
+        hasher.update(dictionary);
+        Key {
+            hash: hasher.digest(),
+            len: dictionary.len(),
+            role,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Prepared {
+    Encoder(Arc<EncoderDictionary<'static>>),
+    Decoder(Arc<DecoderDictionary<'static>>),
+}
+
+struct Entry {
+    key: Key,
+    // Raw dictionary, to tell apart dictionaries with the same hash.
+    content: Box<[u8]>,
+    dictionary: Prepared,
+    // Memory used by `dictionary` and `content`.
+    size: usize,
+    last_used: u64,
+}
+
+struct Cache {
+    entries: Vec<Entry>,
+    max_entries: usize,
+    max_bytes: usize,
+    // Increases with each access, to find the least recently used entry.
+    clock: u64,
+}
+
+impl Cache {
+    fn get(&mut self, key: Key, content: &[u8]) -> Option<Prepared> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.key == key && *entry.content == *content)?;
+        entry.last_used = clock;
+        Some(entry.dictionary.clone())
+    }
+
+    fn insert(
+        &mut self,
+        key: Key,
+        content: &[u8],
+        dictionary: Prepared,
+        size: usize,
+    ) {
+        let size = size + content.len();
+        if size > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        self.entries.push(Entry {
+            key,
+            content: content.into(),
+            dictionary,
+            size,
+            last_used: self.clock,
+        });
+        self.shrink();
+    }
+
+    fn size(&self) -> usize {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Drops the least recently used entries until within bounds.
+    fn shrink(&mut self) {
+        while self.entries.len() > self.max_entries
+            || self.size() > self.max_bytes
+        {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(i, _)| i);
+            match oldest {
+                Some(i) => self.entries.swap_remove(i),
+                None => break,
+            };
+        }
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Cache> {
+    // The cache is always left in a consistent state.
+    CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the prepared dictionary for `content`, creating it if needed.
+fn get_or_insert<F>(key: Key, content: &[u8], create: F) -> Prepared
+where
+    F: FnOnce() -> (Prepared, usize),
+{
+    if let Some(dictionary) = lock().get(key, content) {
+        return dictionary;
+    }
+
+    // Don't block other threads while preparing the dictionary.
+    let (dictionary, size) = create();
+
+    let mut cache = lock();
+    // Another thread may have been faster.
+    if let Some(dictionary) = cache.get(key, content) {
+        return dictionary;
+    }
+    cache.insert(key, content, dictionary.clone(), size);
+    dictionary
+}
+
+/// Returns a prepared dictionary for compression, using the cache.
+///
+/// This is equivalent to [`EncoderDictionary::copy`], but re-uses a
+/// previously prepared dictionary when possible.
+pub fn encoder_dictionary(
+    dictionary: &[u8],
+    level: i32,
+) -> Arc<EncoderDictionary<'static>> {
+    let key = Key::new(dictionary, Role::Encoder(level));
+    let prepared = get_or_insert(key, dictionary, || {
+        let prepared = EncoderDictionary::copy(dictionary, level);
+        let size = prepared.as_cdict().sizeof();
+        (Prepared::Encoder(Arc::new(prepared)), size)
+    });
+
+    match prepared {
+        Prepared::Encoder(prepared) => prepared,
+        Prepared::Decoder(_) => unreachable!("keys include the role"),
+    }
+}
+
+/// Returns a prepared dictionary for decompression, using the cache.
+///
+/// This is equivalent to [`DecoderDictionary::copy`], but re-uses a
+/// previously prepared dictionary when possible.
+pub fn decoder_dictionary(
+    dictionary: &[u8],
+) -> Arc<DecoderDictionary<'static>> {
+    let key = Key::new(dictionary, Role::Decoder);
+    let prepared = get_or_insert(key, dictionary, || {
+        let prepared = DecoderDictionary::copy(dictionary);
+        let size = prepared.as_ddict().sizeof();
+        (Prepared::Decoder(Arc::new(prepared)), size)
+    });
+
+    match prepared {
+        Prepared::Decoder(prepared) => prepared,
+        Prepared::Encoder(_) => unreachable!("keys include the role"),
+    }
+}
+
+/// Sets the bounds of the cache.
+///
+/// * `max_entries` is the maximum number of prepared dictionaries.
+/// * `max_bytes` is the maximum memory used by prepared dictionaries.
+///   Dictionaries larger than this are never cached.
+///
+/// Entries are dropped if needed to respect the new bounds.
+pub fn set_limits(max_entries: usize, max_bytes: usize) {
+    let mut cache = lock();
+    cache.max_entries = max_entries;
+    cache.max_bytes = max_bytes;
+    cache.shrink();
+}
+
+/// Removes all dictionaries from the cache.
+///
+/// Dictionaries still in use elsewhere are kept alive until dropped.
+pub fn clear() {
+    lock().entries.clear();
+}
+
+/// Returns the number of dictionaries currently in the cache.
+pub fn len() -> usize {
+    lock().entries.len()
+}
+
+/// Returns the memory currently used by dictionaries in the cache.
+///
+/// This includes a copy of each raw dictionary, used to check hits.
+pub fn size() -> usize {
+    lock().size()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    const TEXT: &[u8] = include_bytes!("../../assets/example.txt");
+
+    // The cache is global, so everything is tested in a single test.
+    #[test]
+    fn test_cache() {
+        let first = super::decoder_dictionary(&TEXT[..512]);
+        let second = super::decoder_dictionary(&TEXT[..512]);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Different roles and levels are different entries.
+        let encoder = super::encoder_dictionary(&TEXT[..512], 3);
+        assert!(Arc::ptr_eq(
+            &encoder,
+            &super::encoder_dictionary(&TEXT[..512], 3)
+        ));
+        assert!(!Arc::ptr_eq(
+            &encoder,
+            &super::encoder_dictionary(&TEXT[..512], 5)
+        ));
+        assert_eq!(super::len(), 3);
+
+        // Cached dictionaries work like regular ones.
+        let compressed =
+            crate::bulk::Compressor::with_prepared_dictionary(&encoder)
+                .unwrap()
+                .compress(TEXT)
+                .unwrap();
+        let decompressed =
+            crate::bulk::Decompressor::with_prepared_dictionary(&first)
+                .unwrap()
+                .decompress(&compressed, TEXT.len())
+                .unwrap();
+        assert_eq!(decompressed, TEXT);
+
+        // Least recently used entries are dropped first.
+        super::set_limits(2, super::DEFAULT_MAX_BYTES);
+        assert_eq!(super::len(), 2);
+        let third = super::decoder_dictionary(&TEXT[..512]);
+        assert!(!Arc::ptr_eq(&first, &third));
+
+        super::set_limits(super::DEFAULT_MAX_ENTRIES, 0);
+        assert_eq!(super::len(), 0);
+        super::decoder_dictionary(&TEXT[..512]);
+        assert_eq!(super::len(), 0);
+
+        super::set_limits(
+            super::DEFAULT_MAX_ENTRIES,
+            super::DEFAULT_MAX_BYTES,
+        );
+        super::decoder_dictionary(&TEXT[..512]);
+        assert!(super::size() > 0);
+        super::clear();
+        assert_eq!(super::len(), 0);
+    }
+
+    #[test]
+    fn test_hash_collision() {
+        let mut cache = super::Cache {
+            entries: Vec::new(),
+            max_entries: super::DEFAULT_MAX_ENTRIES,
+            max_bytes: super::DEFAULT_MAX_BYTES,
+            clock: 0,
+        };
+        let key = super::Key::new(&TEXT[..512], super::Role::Decoder);
+        let prepared = super::Prepared::Decoder(Arc::new(
+            crate::dict::DecoderDictionary::copy(&TEXT[..512]),
+        ));
+        cache.insert(key, &TEXT[..512], prepared, 0);
+        assert!(cache.get(key, &TEXT[..512]).is_some());
+        assert_eq!(cache.size(), 512);
+
+        // Same key, different content.
+        assert!(cache.get(key, &TEXT[1..513]).is_none());
+    }
+}
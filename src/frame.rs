@@ -0,0 +1,846 @@
+//! Build and parse zstd frame headers.
+//!
+//! This is useful to assemble frames manually (for example from blocks
+//! compressed with `CCtx::compress_block`), or to inspect frames without
+//! decompressing them.
+//!
+//! A zstd frame is made of:
+//!
+//! * A [`FrameHeader`].
+//! * One or more blocks, each starting with a header (see
+//!   [`write_block_header`] and [`parse_block_header`]).
+//! * An optional [`Checksum`] of the decompressed content.
+//!
+//! Skippable frames, holding arbitrary user data, can be written with
+//! [`write_skippable_frame`].
+//!
+//! See the [format specification] for details.
+//!
+//! [format specification]: https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md
+use std::convert::TryFrom;
+use std::io::{self, BufRead};
+
+use crate::xxhash::XxHash64;
+
+/// Magic number starting every zstd frame.
+pub const MAGIC_NUMBER: u32 = 0xFD2F_B528;
+
+/// Magic number of the first skippable frame variant.
+///
+/// Skippable frames use any magic number from this one to
+/// `SKIPPABLE_MAGIC_START + 15`. This crate uses the following variants:
+///
+/// * [`DICTIONARY_MAGIC_VARIANT`] (`0xB`) for packed dictionaries.
+/// * [`CONTENT_CHECKSUM_MAGIC_VARIANT`] (`0xC`) for content checksums.
+/// * [`DICT_ID_MAGIC_VARIANT`] (`0xD`) for dictionary ID guards.
+/// * [`SEEK_TABLE_MAGIC_VARIANT`] (`0xE`) for seek tables, as in zstd's
+///   seekable format.
+pub const SKIPPABLE_MAGIC_START: u32 = 0x184D_2A50;
+
+/// Magic variant of the skippable frames recording dictionary IDs.
+///
+/// These frames hold the ID of the dictionary used by the next frame, as 4
+/// little-endian bytes. See
+/// [`Encoder::set_dict_id_guard`](crate::stream::raw::Encoder::set_dict_id_guard).
+pub const DICT_ID_MAGIC_VARIANT: u8 = 0xD;
+
+/// Magic variant of the skippable frames holding content checksums.
+///
+/// These frames follow the frame they check, and hold the
+/// [`ContentChecksum::id`](crate::stream::ContentChecksum::id) of the
+/// checksum, followed by the checksum itself. See
+/// [`Encoder::set_content_checksum`](crate::stream::raw::Encoder::set_content_checksum).
+pub const CONTENT_CHECKSUM_MAGIC_VARIANT: u8 = 0xC;
+
+/// Magic variant of the skippable frames holding a whole dictionary.
+///
+/// These frames hold the dictionary used by the frames after them. See
+/// [`pack`](crate::pack).
+pub const DICTIONARY_MAGIC_VARIANT: u8 = 0xB;
+
+/// Magic variant of the skippable frame holding a seek table.
+///
+/// See [`seekable`](crate::stream::seekable).
+pub const SEEK_TABLE_MAGIC_VARIANT: u8 = 0xE;
+
+/// Maximum size of the content of a single block.
+pub const BLOCK_SIZE_MAX: u32 = 128 * 1024;
+
+/// Smallest accepted target size for compressed blocks.
+///
+/// See [`Encoder::set_target_block_size`](crate::stream::write::Encoder::set_target_block_size).
+pub const TARGET_BLOCK_SIZE_MIN: u32 = 1340;
+
+/// Largest accepted target size for compressed blocks.
+pub const TARGET_BLOCK_SIZE_MAX: u32 = BLOCK_SIZE_MAX;
+
+const MIN_WINDOW_LOG: u32 = 10;
+const MAX_WINDOW_LOG: u32 = 41;
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "frame header is truncated")
+}
+
+fn truncated_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "frame is truncated")
+}
+
+/// Reads a little-endian integer of `bytes.len()` bytes.
+fn read_le(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u64::from(byte))
+}
+
+/// Header of a zstd frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Decompressed size of the frame, if known.
+    pub content_size: Option<u64>,
+
+    /// Window size required to decompress the frame.
+    ///
+    /// If `None`, this is a single-segment frame: the window is the entire
+    /// content, and `content_size` must be known.
+    pub window_size: Option<u64>,
+
+    /// ID of the dictionary required to decompress the frame, if any.
+    pub dict_id: Option<u32>,
+
+    /// Whether the frame ends with a checksum of its content.
+    pub checksum: bool,
+}
+
+impl FrameHeader {
+    /// Maximum size of a frame header, including the magic number.
+    pub const MAX_SIZE: usize = 18;
+
+    /// Parses a frame header from the start of `input`.
+    ///
+    /// Returns the header and its size in bytes (including the magic
+    /// number). An error of kind `UnexpectedEof` means `input` is too short
+    /// to contain the entire header.
+    pub fn parse(input: &[u8]) -> io::Result<(Self, usize)> {
+        if input.len() < 5 {
+            return Err(unexpected_eof());
+        }
+        if read_le(&input[..4]) as u32 != MAGIC_NUMBER {
+            return Err(invalid_data("unknown frame magic number"));
+        }
+
+        let descriptor = input[4];
+        let fcs_flag = descriptor >> 6;
+        let single_segment = descriptor & 0b0010_0000 != 0;
+        let checksum = descriptor & 0b0000_0100 != 0;
+        let dict_id_flag = descriptor & 0b0000_0011;
+
+        if descriptor & 0b0000_1000 != 0 {
+            return Err(invalid_data("reserved frame header bit is set"));
+        }
+
+        let window_descriptor_size = if single_segment { 0 } else { 1 };
+        let dict_id_size = [0, 1, 2, 4][dict_id_flag as usize];
+        let fcs_size = match fcs_flag {
+            0 if single_segment => 1,
+            0 => 0,
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+
+        let size = 5 + window_descriptor_size + dict_id_size + fcs_size;
+        if input.len() < size {
+            return Err(unexpected_eof());
+        }
+
+        let mut pos = 5;
+        let window_size = if single_segment {
+            None
+        } else {
+            let exponent = u32::from(input[pos] >> 3);
+            let mantissa = u64::from(input[pos] & 0b111);
+            let base = 1u64 << (MIN_WINDOW_LOG + exponent);
+            pos += 1;
+            Some(base + (base / 8) * mantissa)
+        };
+
+        let dict_id = read_le(&input[pos..pos + dict_id_size]) as u32;
+        pos += dict_id_size;
+
+        let content_size = match fcs_size {
+            0 => None,
+            2 => Some(read_le(&input[pos..pos + 2]) + 256),
+            n => Some(read_le(&input[pos..pos + n])),
+        };
+
+        let header = FrameHeader {
+            content_size,
+            window_size,
+            dict_id: Some(dict_id).filter(|&id| id != 0),
+            checksum,
+        };
+        Ok((header, size))
+    }
+
+    /// Returns the window size needed to decompress this frame.
+    ///
+    /// For single-segment frames, this is the content size.
+    pub fn effective_window_size(&self) -> Option<u64> {
+        self.window_size.or(self.content_size)
+    }
+
+    /// Appends this header to `output`, including the magic number.
+    ///
+    /// The window size is rounded up to the next size zstd can represent.
+    ///
+    /// Returns the number of bytes written, or an error if this header
+    /// cannot be represented (for example a single-segment frame without
+    /// content size).
+    pub fn write(&self, output: &mut Vec<u8>) -> io::Result<usize> {
+        let single_segment = self.window_size.is_none();
+        if single_segment && self.content_size.is_none() {
+            return Err(invalid_input(
+                "single-segment frames require a content size",
+            ));
+        }
+
+        let window_descriptor =
+            self.window_size.map(window_descriptor).transpose()?;
+
+        let dict_id = self.dict_id.unwrap_or(0);
+        let (dict_id_flag, dict_id_size) = match dict_id {
+            0 => (0, 0),
+            1..=0xFF => (1, 1),
+            0x100..=0xFFFF => (2, 2),
+            _ => (3, 4),
+        };
+
+        let (fcs_flag, content_size, fcs_size) = match self.content_size {
+            None => (0, 0, 0),
+            Some(size @ 0..=0xFF) if single_segment => (0, size, 1),
+            Some(size @ 0x100..=0x100FF) => (1, size - 256, 2),
+            Some(size @ 0..=0xFFFF_FFFF) => (2, size, 4),
+            Some(size) => (3, size, 8),
+        };
+
+        let start = output.len();
+        output.extend_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        output.push(
+            fcs_flag << 6
+                | u8::from(single_segment) << 5
+                | u8::from(self.checksum) << 2
+                | dict_id_flag,
+        );
+        output.extend(window_descriptor);
+        output.extend_from_slice(&dict_id.to_le_bytes()[..dict_id_size]);
+        output.extend_from_slice(&content_size.to_le_bytes()[..fcs_size]);
+
+        Ok(output.len() - start)
+    }
+}
+
+/// Returns the smallest window descriptor covering `window_size`.
+fn window_descriptor(window_size: u64) -> io::Result<u8> {
+    for exponent in 0..=(MAX_WINDOW_LOG - MIN_WINDOW_LOG) {
+        let base = 1u64 << (MIN_WINDOW_LOG + exponent);
+        for mantissa in 0..8 {
+            if base + (base / 8) * mantissa >= window_size {
+                return Ok((exponent as u8) << 3 | mantissa as u8);
+            }
+        }
+    }
+    Err(invalid_input("window size is too large"))
+}
+
+/// Type of a block inside a zstd frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockType {
+    /// Uncompressed data, stored as-is.
+    Raw,
+
+    /// A single byte, repeated `size` times.
+    Rle,
+
+    /// Compressed data.
+    Compressed,
+}
+
+/// Appends a block header to `output`.
+///
+/// `size` is the size of the block content following the header, except for
+/// RLE blocks, where it is the number of repetitions of the single byte
+/// following the header.
+///
+/// `last` must be `true` for the last block of the frame.
+pub fn write_block_header(
+    output: &mut Vec<u8>,
+    block_type: BlockType,
+    size: u32,
+    last: bool,
+) -> io::Result<()> {
+    if size > BLOCK_SIZE_MAX {
+        return Err(invalid_input("block is too large"));
+    }
+
+    let block_type = match block_type {
+        BlockType::Raw => 0,
+        BlockType::Rle => 1,
+        BlockType::Compressed => 2,
+    };
+    let header = size << 3 | block_type << 1 | u32::from(last);
+    output.extend_from_slice(&header.to_le_bytes()[..3]);
+    Ok(())
+}
+
+/// Parses a block header from the start of `input`.
+///
+/// Returns the block type, its size (as given to [`write_block_header`]),
+/// and whether it is the last block of the frame.
+pub fn parse_block_header(input: &[u8]) -> io::Result<(BlockType, u32, bool)> {
+    if input.len() < 3 {
+        return Err(unexpected_eof());
+    }
+
+    let header = read_le(&input[..3]) as u32;
+    let block_type = match (header >> 1) & 0b11 {
+        0 => BlockType::Raw,
+        1 => BlockType::Rle,
+        2 => BlockType::Compressed,
+        _ => return Err(invalid_data("reserved block type")),
+    };
+    let size = header >> 3;
+    if size > BLOCK_SIZE_MAX {
+        return Err(invalid_data("block is too large"));
+    }
+    Ok((block_type, size, header & 1 != 0))
+}
+
+/// Appends a complete frame to `output`, storing `data` without compression.
+///
+/// The frame includes the content size, so it can be decompressed in a
+/// single step.
+pub fn write_raw_frame(data: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let header = FrameHeader {
+        content_size: Some(data.len() as u64),
+        ..FrameHeader::default()
+    };
+    header.write(output)?;
+
+    let mut blocks = data.chunks(BLOCK_SIZE_MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        // An empty frame still needs a (last) block.
+        write_block_header(output, BlockType::Raw, 0, true)?;
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        write_block_header(output, BlockType::Raw, block.len() as u32, last)?;
+        output.extend_from_slice(block);
+    }
+    Ok(())
+}
+
+/// Checksum of the content of a frame.
+///
+/// This is written after the last block of frames with `checksum` set in
+/// their header.
+#[derive(Clone, Debug)]
+pub struct Checksum {
+    hasher: XxHash64,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum::new()
+    }
+}
+
+impl Checksum {
+    /// Size of the checksum, in bytes.
+    pub const SIZE: usize = 4;
+
+    /// Creates a new checksum, for an empty content.
+    pub fn new() -> Self {
+        Checksum {
+            hasher: XxHash64::new(0),
+        }
+    }
+
+    /// Adds some (decompressed) content to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Returns the checksum of all the content given so far.
+    pub fn value(&self) -> u32 {
+        self.hasher.digest() as u32
+    }
+
+    /// Appends the checksum to `output`.
+    pub fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.value().to_le_bytes());
+    }
+}
+
+/// Header of a skippable frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkippableHeader {
+    /// Which of the 16 skippable magic numbers is used (`0..=15`).
+    pub magic_variant: u8,
+
+    /// Size of the user data following the header.
+    pub size: u32,
+}
+
+impl SkippableHeader {
+    /// Size of a skippable frame header.
+    pub const SIZE: usize = 8;
+
+    /// Parses a skippable frame header from the start of `input`.
+    ///
+    /// Returns `Ok(None)` if `input` starts with a different magic number,
+    /// and an error of kind `UnexpectedEof` if `input` is too short.
+    pub fn parse(input: &[u8]) -> io::Result<Option<Self>> {
+        if input.len() < 4 {
+            return Err(unexpected_eof());
+        }
+        if !is_skippable(input) {
+            return Ok(None);
+        }
+        if input.len() < Self::SIZE {
+            return Err(unexpected_eof());
+        }
+
+        Ok(Some(SkippableHeader {
+            magic_variant: input[0] & 0x0F,
+            size: read_le(&input[4..8]) as u32,
+        }))
+    }
+
+    /// Appends this header to `output`.
+    pub fn write(&self, output: &mut Vec<u8>) -> io::Result<()> {
+        if self.magic_variant > 15 {
+            return Err(invalid_input("magic variant must be in 0..=15"));
+        }
+
+        let magic = SKIPPABLE_MAGIC_START + u32::from(self.magic_variant);
+        output.extend_from_slice(&magic.to_le_bytes());
+        output.extend_from_slice(&self.size.to_le_bytes());
+        Ok(())
+    }
+}
+
+/// Returns `true` if `input` starts with a skippable frame magic number.
+pub fn is_skippable(input: &[u8]) -> bool {
+    input.len() >= 4
+        && read_le(&input[..4]) as u32 & 0xFFFF_FFF0 == SKIPPABLE_MAGIC_START
+}
+
+/// Appends a skippable frame containing `data` to `output`.
+///
+/// Decoders ignore these frames, so they can hold arbitrary metadata.
+/// `magic_variant` must be in `0..=15`.
+pub fn write_skippable_frame(
+    output: &mut Vec<u8>,
+    magic_variant: u8,
+    data: &[u8],
+) -> io::Result<()> {
+    let size = u32::try_from(data.len())
+        .map_err(|_| invalid_input("skippable frame is too large"))?;
+    SkippableHeader {
+        magic_variant,
+        size,
+    }
+    .write(output)?;
+    output.extend_from_slice(data);
+    Ok(())
+}
+
+/// Reads the header of the frame at the start of `input`, using zstd's own
+/// parser.
+///
+/// Returns the header and its size in bytes, like [`FrameHeader::parse`].
+/// This gives the sizes needed to allocate buffers, or to reject a frame,
+/// before decompressing anything. `input` only needs to hold the header:
+/// [`FrameHeader::MAX_SIZE`] bytes are always enough.
+///
+/// An error of kind `UnexpectedEof` means `input` is too short to contain
+/// the entire header. Skippable frames are not supported.
+///
+/// Only available with the `experimental` feature.
+///
+/// ```rust
+/// let compressed = zstd::bulk::compress(b"data", 3)?;
+/// let (header, _) = zstd::frame_info(&compressed)?;
+/// assert_eq!(header.content_size, Some(4));
+/// assert_eq!(header.dict_id, None);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+pub fn frame_info(input: &[u8]) -> io::Result<(FrameHeader, usize)> {
+    let header = zstd_safe::get_frame_header(input)
+        .map_err(crate::map_error_code)?
+        .ok_or_else(unexpected_eof)?;
+    if header.frameType == zstd_safe::FrameType::ZSTD_skippableFrame {
+        return Err(invalid_data("skippable frames are not supported"));
+    }
+
+    // zstd reports the content size as the window of single-segment frames.
+    let single_segment = input[4] & 0b0010_0000 != 0;
+    let header_size = header.headerSize as usize;
+    let header = FrameHeader {
+        content_size: Some(header.frameContentSize)
+            .filter(|&size| size != zstd_safe::CONTENTSIZE_UNKNOWN),
+        window_size: Some(header.windowSize).filter(|_| !single_segment),
+        dict_id: Some(header.dictID).filter(|&id| id != 0),
+        checksum: header.checksumFlag != 0,
+    };
+    Ok((header, header_size))
+}
+
+/// Position and size of a frame in a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameBoundary {
+    /// Offset of the first byte of the frame in the stream.
+    pub offset: u64,
+
+    /// Size of the entire frame, including headers and checksum.
+    pub compressed_size: u64,
+
+    /// Header of the frame, or `None` for skippable frames.
+    pub header: Option<FrameHeader>,
+}
+
+/// Reads a complete frame from `reader` and discards it.
+///
+/// Only the frame and block headers are parsed, so this doesn't need to
+/// decompress anything, and works on non-seekable inputs.
+///
+/// Returns the header of the frame (`None` for skippable frames) and its
+/// total size in bytes, or `Ok(None)` if `reader` is at EOF. Legacy frames
+/// are not supported.
+pub fn skip_frame<R: BufRead + ?Sized>(
+    reader: &mut R,
+) -> io::Result<Option<(Option<FrameHeader>, u64)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut header = [0u8; FrameHeader::MAX_SIZE];
+    read_exact(reader, &mut header[..4])?;
+    if is_skippable(&header) {
+        read_exact(reader, &mut header[4..SkippableHeader::SIZE])?;
+        let size = SkippableHeader::parse(&header)?.map_or(0, |h| h.size);
+        discard(reader, u64::from(size))?;
+        return Ok(Some((
+            None,
+            SkippableHeader::SIZE as u64 + u64::from(size),
+        )));
+    }
+
+    // Header fields have variable sizes, so read it one byte at a time.
+    let mut len = 4;
+    let header = loop {
+        match FrameHeader::parse(&header[..len]) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                read_exact(reader, &mut header[len..len + 1])?;
+                len += 1;
+            }
+            result => break result?.0,
+        }
+    };
+
+    let mut compressed_size = len as u64;
+    loop {
+        let mut block_header = [0u8; 3];
+        read_exact(reader, &mut block_header)?;
+        let (block_type, size, last) = parse_block_header(&block_header)?;
+        let size = match block_type {
+            BlockType::Rle => 1,
+            _ => u64::from(size),
+        };
+        discard(reader, size)?;
+        compressed_size += 3 + size;
+
+        if last {
+            break;
+        }
+    }
+
+    if header.checksum {
+        discard(reader, Checksum::SIZE as u64)?;
+        compressed_size += Checksum::SIZE as u64;
+    }
+
+    Ok(Some((Some(header), compressed_size)))
+}
+
+/// Like `Read::read_exact`, with a frame-specific error.
+fn read_exact<R: BufRead + ?Sized>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> io::Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated_frame(),
+        _ => e,
+    })
+}
+
+/// Consumes `len` bytes from `reader`.
+fn discard<R: BufRead + ?Sized>(
+    reader: &mut R,
+    mut len: u64,
+) -> io::Result<()> {
+    while len > 0 {
+        let available = reader.fill_buf()?.len();
+        if available == 0 {
+            return Err(truncated_frame());
+        }
+        let n =
+            usize::try_from(len).map_or(available, |len| len.min(available));
+        reader.consume(n);
+        len -= n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = include_bytes!("../assets/example.txt");
+
+    #[test]
+    fn test_header_round_trip() {
+        let headers = [
+            FrameHeader {
+                content_size: Some(0),
+                ..FrameHeader::default()
+            },
+            FrameHeader {
+                content_size: Some(300),
+                window_size: Some(1 << 20),
+                dict_id: Some(0x1234),
+                checksum: true,
+            },
+            FrameHeader {
+                content_size: Some(1 << 40),
+                window_size: Some(1 << 27),
+                dict_id: Some(7),
+                checksum: false,
+            },
+            FrameHeader {
+                content_size: None,
+                window_size: Some(3 << 19),
+                dict_id: Some(0xDEAD_BEEF),
+                checksum: true,
+            },
+        ];
+
+        for header in &headers {
+            let mut output = Vec::new();
+            let size = header.write(&mut output).unwrap();
+            assert_eq!(size, output.len());
+            assert!(size <= FrameHeader::MAX_SIZE);
+            assert_eq!(FrameHeader::parse(&output).unwrap(), (*header, size));
+
+            let kind =
+                FrameHeader::parse(&output[..size - 1]).unwrap_err().kind();
+            assert_eq!(kind, io::ErrorKind::UnexpectedEof);
+        }
+
+        // Window sizes are rounded up.
+        let header = FrameHeader {
+            window_size: Some(1000),
+            ..FrameHeader::default()
+        };
+        let mut output = Vec::new();
+        header.write(&mut output).unwrap();
+        let (parsed, _) = FrameHeader::parse(&output).unwrap();
+        assert_eq!(parsed.window_size, Some(1024));
+
+        // Single-segment frames need a content size.
+        assert!(FrameHeader::default().write(&mut output).is_err());
+    }
+
+    #[test]
+    fn test_parse_zstd_header() {
+        let compressed = crate::bulk::compress(TEXT, 1).unwrap();
+        let (header, _) = FrameHeader::parse(&compressed).unwrap();
+        assert_eq!(header.content_size, Some(TEXT.len() as u64));
+        assert_eq!(header.dict_id, None);
+        assert!(!header.checksum);
+    }
+
+    #[test]
+    fn test_manual_frame() {
+        let mut frame = Vec::new();
+        let header_size = FrameHeader {
+            content_size: Some(TEXT.len() as u64 + 10),
+            window_size: Some(1 << 16),
+            dict_id: None,
+            checksum: true,
+        }
+        .write(&mut frame)
+        .unwrap();
+
+        write_block_header(
+            &mut frame,
+            BlockType::Raw,
+            TEXT.len() as u32,
+            false,
+        )
+        .unwrap();
+        frame.extend_from_slice(TEXT);
+        write_block_header(&mut frame, BlockType::Rle, 10, true).unwrap();
+        frame.push(b'!');
+
+        let first_block = parse_block_header(&frame[header_size..]).unwrap();
+        assert_eq!(first_block, (BlockType::Raw, TEXT.len() as u32, false));
+        let second_block =
+            parse_block_header(&frame[header_size + 3 + TEXT.len()..]);
+        assert_eq!(second_block.unwrap(), (BlockType::Rle, 10, true));
+
+        let mut checksum = Checksum::new();
+        checksum.update(TEXT);
+        checksum.update(b"!!!!!!!!!!");
+        checksum.write(&mut frame);
+
+        let mut expected = TEXT.to_vec();
+        expected.extend_from_slice(b"!!!!!!!!!!");
+        assert_eq!(crate::decode_all(&frame[..]).unwrap(), expected);
+
+        // A wrong checksum is detected.
+        let last = frame.len() - 1;
+        frame[last] ^= 1;
+        assert!(crate::decode_all(&frame[..]).is_err());
+    }
+
+    #[test]
+    fn test_checksum_matches_zstd() {
+        let mut encoder = crate::stream::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(true).unwrap();
+        io::Write::write_all(&mut encoder, TEXT).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut checksum = Checksum::new();
+        checksum.update(TEXT);
+        let trailer = &compressed[compressed.len() - Checksum::SIZE..];
+        assert_eq!(trailer, checksum.value().to_le_bytes());
+    }
+
+    #[test]
+    fn test_raw_frame() {
+        for &size in &[0, 1, 255, 256, 65791, 65792, 300_000] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let mut frame = Vec::new();
+            write_raw_frame(&data, &mut frame).unwrap();
+
+            assert_eq!(
+                zstd_safe::get_frame_content_size(&frame).unwrap(),
+                Some(size as u64)
+            );
+            assert_eq!(crate::decode_all(&frame[..]).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_raw_and_skippable_frames() {
+        let mut output = Vec::new();
+        write_skippable_frame(&mut output, 3, b"metadata").unwrap();
+        write_raw_frame(TEXT, &mut output).unwrap();
+
+        assert!(is_skippable(&output));
+        let header = SkippableHeader::parse(&output).unwrap().unwrap();
+        assert_eq!(
+            header,
+            SkippableHeader {
+                magic_variant: 3,
+                size: 8
+            }
+        );
+        let frame = &output[SkippableHeader::SIZE + 8..];
+        assert_eq!(SkippableHeader::parse(frame).unwrap(), None);
+
+        assert_eq!(crate::decode_all(&output[..]).unwrap(), TEXT);
+        assert!(write_skippable_frame(&mut output, 16, b"").is_err());
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_frame_info() {
+        // Same as our own parser.
+        let compressed = crate::bulk::compress(TEXT, 3).unwrap();
+        let (header, header_size) = frame_info(&compressed).unwrap();
+        assert_eq!(header.content_size, Some(TEXT.len() as u64));
+        assert_eq!(header.window_size, None);
+        assert_eq!(
+            (header, header_size),
+            FrameHeader::parse(&compressed).unwrap()
+        );
+
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 3).unwrap();
+        encoder.include_checksum(true).unwrap();
+        std::io::Write::write_all(&mut encoder, TEXT).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let (header, header_size) =
+            frame_info(&compressed[..FrameHeader::MAX_SIZE]).unwrap();
+        assert_eq!(header.content_size, None);
+        assert!(header.window_size.is_some());
+        assert!(header.checksum);
+        assert_eq!(
+            (header, header_size),
+            FrameHeader::parse(&compressed).unwrap()
+        );
+
+        let err = frame_info(&compressed[..header_size - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(frame_info(b"not a frame").is_err());
+
+        let mut skippable = Vec::new();
+        write_skippable_frame(&mut skippable, 0, b"metadata").unwrap();
+        assert!(frame_info(&skippable).is_err());
+    }
+
+    #[test]
+    fn test_skip_frame() {
+        let mut output = Vec::new();
+        write_skippable_frame(&mut output, 0, b"metadata").unwrap();
+        let skippable_size = output.len() as u64;
+        output.extend(crate::encode_all(TEXT, 1).unwrap());
+        let compressed_size = output.len() as u64 - skippable_size;
+        write_raw_frame(b"raw", &mut output).unwrap();
+
+        // Read one byte at a time, to check headers split across buffers.
+        let mut reader = io::BufReader::with_capacity(1, &output[..]);
+        assert_eq!(
+            skip_frame(&mut reader).unwrap(),
+            Some((None, skippable_size))
+        );
+
+        let (header, size) = skip_frame(&mut reader).unwrap().unwrap();
+        assert!(header.is_some());
+        assert_eq!(size, compressed_size);
+
+        let (header, _) = skip_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(header.unwrap().content_size, Some(3));
+        assert_eq!(skip_frame(&mut reader).unwrap(), None);
+
+        let truncated = &output[..output.len() - 1];
+        let mut reader = truncated;
+        skip_frame(&mut reader).unwrap();
+        skip_frame(&mut reader).unwrap();
+        let err = skip_frame(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,188 @@
+//! Compress files directly from the filesystem.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::stream::write::Encoder;
+
+/// Size of the slices given to the encoder at once.
+#[cfg(unix)]
+const SLICE_SIZE: usize = 1 << 20;
+
+/// Compresses the file at `src_path` into `writer`, as a single frame.
+///
+/// On unix, the file is mapped in memory and compressed from the mapping,
+/// without any `read` call. This is what the `zstd` CLI does for large
+/// files. On other platforms, the file is read normally.
+///
+/// The size of the file is pledged, so it is written in the frame header.
+/// Other kinds of files (like pipes or devices) can't be mapped: they are
+/// read normally, and the frame doesn't include their size.
+///
+/// Returns the writer once the frame is complete.
+///
+/// # Safety
+///
+/// The file must not be modified while it is being compressed. Changes to
+/// the file may corrupt the output, and truncating it may crash the process
+/// (with a `SIGBUS`).
+pub unsafe fn compress_mmap<P, W>(
+    src_path: P,
+    writer: W,
+    level: i32,
+) -> io::Result<W>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let mut file = File::open(src_path)?;
+    let metadata = file.metadata()?;
+
+    let mut encoder = Encoder::new(writer, level)?;
+    if !metadata.is_file() {
+        io::copy(&mut file, &mut encoder)?;
+        return encoder.finish();
+    }
+
+    let len = metadata.len();
+    encoder.set_pledged_src_size(Some(len))?;
+
+    #[cfg(unix)]
+    {
+        let map = Mmap::map(&file, len)?;
+        for slice in map.as_slice().chunks(SLICE_SIZE) {
+            encoder.write_all(slice)?;
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        use std::io::Read;
+
+        io::copy(&mut (&file).take(len), &mut encoder)?;
+    }
+
+    encoder.finish()
+}
+
+/// A read-only mapping of a file.
+#[cfg(unix)]
+pub(crate) struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mmap {
+    /// Maps the first `len` bytes of `file`.
+    ///
+    /// The file must not be modified while the mapping is alive.
+    pub(crate) unsafe fn map(file: &File, len: u64) -> io::Result<Self> {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(len).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "file is too large")
+        })?;
+
+        // Empty mappings are not allowed.
+        if len == 0 {
+            return Ok(Mmap {
+                ptr: std::ptr::null_mut(),
+                len,
+            });
+        }
+
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        // This is only a hint, failure doesn't matter.
+        libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+
+        Ok(Mmap { ptr, len })
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+// The mapping is read-only, so it can be shared between threads.
+#[cfg(unix)]
+unsafe impl Send for Mmap {}
+#[cfg(unix)]
+unsafe impl Sync for Mmap {}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compress_mmap;
+
+    #[test]
+    fn test_compress_mmap() {
+        let text = include_bytes!("../assets/example.txt");
+
+        let compressed =
+            unsafe { compress_mmap("assets/example.txt", Vec::new(), 1) }
+                .unwrap();
+        let content_size = zstd_safe::get_frame_content_size(&compressed);
+        assert_eq!(content_size.unwrap(), Some(text.len() as u64));
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+        let path = std::env::temp_dir()
+            .join(format!("zstd_test_compress_mmap_{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let compressed =
+            unsafe { compress_mmap(&path, Vec::new(), 1) }.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let content_size = zstd_safe::get_frame_content_size(&compressed);
+        assert_eq!(content_size.unwrap(), Some(0));
+        assert!(crate::decode_all(&compressed[..]).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compress_fifo() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let text = include_bytes!("../assets/example.txt");
+        let path = std::env::temp_dir()
+            .join(format!("zstd_test_compress_fifo_{}", std::process::id()));
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        // Pipes can't be mapped, so they are streamed.
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || std::fs::write(path, text))
+        };
+        let compressed =
+            unsafe { compress_mmap(&path, Vec::new(), 1) }.unwrap();
+        writer.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+    }
+}
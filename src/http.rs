@@ -0,0 +1,207 @@
+//! Helpers for the `zstd` HTTP content-coding.
+//!
+//! [RFC 8878] registers `zstd` as a `Content-Encoding`. This module provides
+//! the pieces needed to implement it in a server or client middleware
+//! (for example a `tower::Layer`), without depending on any HTTP stack:
+//!
+//! * [`accepts_zstd`] checks an `Accept-Encoding` header value.
+//! * [`ContentEncoding`] holds the configuration, and creates chunk
+//!   encoders (for response bodies) and decoders (for request bodies).
+//!
+//! The chunk codecs from [`stream::chunk`] work on streaming bodies: each
+//! body frame can be fed as it arrives.
+//!
+//! [RFC 8878]: https://datatracker.ietf.org/doc/html/rfc8878
+//! [`stream::chunk`]: crate::stream::chunk
+use std::io;
+
+use crate::stream::chunk;
+
+/// Name of the content-coding, as used in `Content-Encoding` headers.
+pub const CONTENT_CODING: &str = "zstd";
+
+/// Maximum window log decoders are required to support.
+///
+/// [RFC 8878] asks HTTP encoders not to use windows larger than 8MB, so
+/// decoders can reject anything larger.
+///
+/// [RFC 8878]: https://datatracker.ietf.org/doc/html/rfc8878#section-3.1.1.1.2
+pub const WINDOW_LOG_MAX: u32 = 23;
+
+/// Returns `true` if the given `Accept-Encoding` header value allows `zstd`.
+///
+/// An explicit `zstd` entry takes precedence over a `*` wildcard. Entries
+/// with a quality value of zero are refused.
+pub fn accepts_zstd(accept_encoding: &str) -> bool {
+    let mut wildcard = false;
+
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+
+        let accepted = parts
+            .rev()
+            .find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                if key.trim().eq_ignore_ascii_case("q") {
+                    value.trim().parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .map_or(true, |quality| quality > 0.0);
+
+        if coding.eq_ignore_ascii_case(CONTENT_CODING) {
+            return accepted;
+        } else if coding == "*" {
+            wildcard = accepted;
+        }
+    }
+
+    wildcard
+}
+
+/// Configuration for the `zstd` content-coding.
+#[derive(Clone, Debug, Default)]
+pub struct ContentEncoding {
+    level: i32,
+    window_log_max: Option<u32>,
+    flush_chunks: bool,
+}
+
+impl ContentEncoding {
+    /// Creates a new configuration using the given compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> Self {
+        ContentEncoding {
+            level,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the compression level used for encoding.
+    #[must_use]
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the maximum window log.
+    ///
+    /// Encoders will use this window log, and decoders will refuse frames
+    /// requiring a larger one.
+    ///
+    /// By default, decoders accept up to [`WINDOW_LOG_MAX`], and encoders
+    /// only restrict the window for levels above 19 (the only ones using a
+    /// larger window).
+    #[must_use]
+    pub fn window_log_max(mut self, window_log_max: u32) -> Self {
+        self.window_log_max = Some(window_log_max);
+        self
+    }
+
+    /// Sets whether each body chunk should be flushed after encoding.
+    ///
+    /// This lets clients decode each chunk as soon as it arrives (useful
+    /// for server-sent events or long polling), at the cost of a slightly
+    /// reduced compression ratio. Defaults to `false`.
+    #[must_use]
+    pub fn flush_chunks(mut self, flush_chunks: bool) -> Self {
+        self.flush_chunks = flush_chunks;
+        self
+    }
+
+    /// Creates an encoder for a body, usually a response.
+    pub fn encoder(&self) -> io::Result<BodyEncoder> {
+        let mut encoder = chunk::Encoder::new(self.level)?;
+
+        match self.window_log_max {
+            Some(window_log) => encoder.window_log(window_log)?,
+            // Levels up to 19 never use more than an 8MB window.
+            None if self.level > 19 => encoder.window_log(WINDOW_LOG_MAX)?,
+            None => (),
+        }
+
+        Ok(BodyEncoder {
+            encoder,
+            flush_chunks: self.flush_chunks,
+        })
+    }
+
+    /// Creates a decoder for a body, usually a request.
+    pub fn decoder(&self) -> io::Result<chunk::Decoder<'static>> {
+        let mut decoder = chunk::Decoder::new()?;
+        decoder
+            .window_log_max(self.window_log_max.unwrap_or(WINDOW_LOG_MAX))?;
+        Ok(decoder)
+    }
+}
+
+/// Encodes an HTTP body, one frame of data at a time.
+///
+/// Created by [`ContentEncoding::encoder`].
+pub struct BodyEncoder {
+    encoder: chunk::Encoder<'static>,
+    flush_chunks: bool,
+}
+
+impl BodyEncoder {
+    /// Encodes the given body chunk, appending the result to `output`.
+    pub fn encode(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        self.encoder.encode(chunk, output)?;
+        if self.flush_chunks {
+            self.encoder.flush(output)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the body, appending the remaining data to `output`.
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        self.encoder.finish(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts_zstd, ContentEncoding};
+
+    #[test]
+    fn test_accepts_zstd() {
+        assert!(accepts_zstd("gzip, zstd"));
+        assert!(accepts_zstd("gzip;q=0.5, ZSTD;q=0.8"));
+        assert!(accepts_zstd("*"));
+        assert!(!accepts_zstd("gzip, br"));
+        assert!(!accepts_zstd("zstd;q=0, *"));
+        assert!(!accepts_zstd("*;q=0"));
+        assert!(!accepts_zstd(""));
+    }
+
+    #[test]
+    fn test_body_cycle() {
+        let config = ContentEncoding::new(22).flush_chunks(true);
+
+        let mut encoder = config.encoder().unwrap();
+        let mut decoder = config.decoder().unwrap();
+
+        let mut body = Vec::new();
+        let mut decoded = Vec::new();
+        for chunk in [&b"hello "[..], b"world"] {
+            let start = body.len();
+            encoder.encode(chunk, &mut body).unwrap();
+            decoder.decode(&body[start..], &mut decoded).unwrap();
+        }
+        assert_eq!(decoded, b"hello world");
+
+        let start = body.len();
+        encoder.finish(&mut body).unwrap();
+        decoder.decode(&body[start..], &mut decoded).unwrap();
+        decoder.finish().unwrap();
+
+        assert_eq!(decoded, b"hello world");
+    }
+}
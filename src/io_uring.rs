@@ -0,0 +1,749 @@
+//! Compressed file I/O on io_uring, with registered buffers.
+//!
+//! [`FixedWriter`] compresses straight into buffers registered with the
+//! ring, and writes them with `WriteFixed` operations. [`FixedReader`] reads
+//! compressed data into registered buffers with `ReadFixed` operations, and
+//! decompresses it from there. Neither copies compressed data around.
+//!
+//! Both leave the ring to the application: entries to submit are taken
+//! with `pop_submission`, and completions are given back with `complete`.
+//! This lets one ring drive many files, along with unrelated operations.
+//! When a ring is only used for one file, `drive` does all of this.
+//!
+//! Entries use the index of their buffer as user data, which must be kept
+//! to give completions back.
+//!
+//! Buffers are rotated between operations, so zstd's stable buffer
+//! parameters (which require the same buffer on every call) don't apply:
+//! the registered buffers are used as zstd's input and output directly.
+//!
+//! ```no_run
+//! use zstd::io_uring::{FixedBuffers, FixedWriter};
+//!
+//! let mut ring = io_uring::IoUring::new(32)?;
+//! let file = std::fs::File::create("data.zst")?;
+//!
+//! let buffers = FixedBuffers::new(8, 1 << 16);
+//! // The writer owns the buffers, and keeps them alive until the end.
+//! unsafe { buffers.register(&ring.submitter())? };
+//!
+//! let mut writer = FixedWriter::new(&file, buffers, 3)?;
+//! let mut data = &b"some data"[..];
+//! while !data.is_empty() {
+//!     let written = writer.write(data)?;
+//!     data = &data[written..];
+//!     writer.drive(&mut ring)?;
+//! }
+//! while !writer.finish()? {
+//!     writer.drive(&mut ring)?;
+//! }
+//! while !writer.is_done() {
+//!     writer.drive(&mut ring)?;
+//! }
+//! // Nothing uses the buffers anymore: they can be dropped.
+//! ring.submitter().unregister_buffers()?;
+//! drop(writer);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use ::io_uring::{cqueue, opcode, squeue, types, IoUring, Submitter};
+use zstd_safe::{InBuffer, OutBuffer};
+
+use crate::stream::raw::{self, Operation};
+
+/// Buffers to register with an io_uring instance.
+///
+/// Each buffer is identified by its index, used as `buf_index` in fixed
+/// reads and writes.
+pub struct FixedBuffers {
+    buffers: Vec<Box<[u8]>>,
+    free: Vec<u16>,
+}
+
+impl FixedBuffers {
+    /// Allocates `count` buffers of `size` bytes each.
+    ///
+    /// # Panics
+    ///
+    /// If `count` or `size` is 0, or if `size` doesn't fit in a `u32`.
+    pub fn new(count: u16, size: usize) -> Self {
+        assert!(count > 0, "at least one buffer is needed");
+        assert!(size > 0, "buffers can't be empty");
+        assert!(u32::try_from(size).is_ok(), "buffers are too large");
+        FixedBuffers {
+            buffers: (0..count)
+                .map(|_| vec![0; size].into_boxed_slice())
+                .collect(),
+            // Pop from the end, to use the first buffers first.
+            free: (0..count).rev().collect(),
+        }
+    }
+
+    /// Returns the number of buffers.
+    pub fn count(&self) -> u16 {
+        self.buffers.len() as u16
+    }
+
+    /// Returns the size of each buffer.
+    pub fn buffer_size(&self) -> usize {
+        self.buffers[0].len()
+    }
+
+    /// Registers these buffers with the ring.
+    ///
+    /// Only one set of buffers can be registered with a ring at a time.
+    ///
+    /// # Safety
+    ///
+    /// The buffers must stay alive until they are unregistered, or until
+    /// the ring is dropped. Moving `FixedBuffers` (or the reader or writer
+    /// owning it) is fine, but dropping it is not.
+    pub unsafe fn register(
+        &self,
+        submitter: &Submitter<'_>,
+    ) -> io::Result<()> {
+        let iovecs: Vec<libc::iovec> = self
+            .buffers
+            .iter()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+        submitter.register_buffers(&iovecs)
+    }
+
+    fn take(&mut self) -> Option<u16> {
+        self.free.pop()
+    }
+
+    fn release(&mut self, index: u16) {
+        self.free.push(index);
+    }
+}
+
+impl std::fmt::Debug for FixedBuffers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedBuffers")
+            .field("count", &self.count())
+            .field("buffer_size", &self.buffer_size())
+            .field("free", &self.free.len())
+            .finish()
+    }
+}
+
+/// Returns the error for a failed operation, or the number of bytes done.
+fn completion_result(entry: &cqueue::Entry) -> io::Result<usize> {
+    let result = entry.result();
+    if result < 0 {
+        Err(io::Error::from_raw_os_error(-result))
+    } else {
+        Ok(result as usize)
+    }
+}
+
+/// Returns the buffer index an entry was submitted for.
+fn buffer_index(entry: &cqueue::Entry, count: u16) -> io::Result<u16> {
+    match u16::try_from(entry.user_data()) {
+        Ok(index) if index < count => Ok(index),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "completion doesn't belong to this file",
+        )),
+    }
+}
+
+/// Pushes every available entry, then waits for and handles completions.
+fn drive_ring<F, C>(
+    ring: &mut IoUring,
+    mut pop: F,
+    mut complete: C,
+    in_flight: impl Fn() -> bool,
+) -> io::Result<()>
+where
+    F: FnMut() -> Option<squeue::Entry>,
+    C: FnMut(&cqueue::Entry) -> io::Result<()>,
+{
+    loop {
+        let mut queue = ring.submission();
+        if queue.is_full() {
+            break;
+        }
+        match pop() {
+            // The buffers are registered and owned by the caller of `pop`.
+            Some(entry) => unsafe {
+                queue.push(&entry).expect("the queue isn't full")
+            },
+            None => break,
+        }
+    }
+
+    let want = usize::from(in_flight());
+    ring.submit_and_wait(want)?;
+    let completions: Vec<cqueue::Entry> = ring.completion().collect();
+    for entry in &completions {
+        complete(entry)?;
+    }
+    Ok(())
+}
+
+/// A write of (part of) a buffer, at a given file offset.
+#[derive(Debug)]
+struct PendingWrite {
+    index: u16,
+    start: usize,
+    end: usize,
+    offset: u64,
+}
+
+/// Compresses data into registered buffers, written with `WriteFixed`.
+///
+/// The compressed data is written as a single frame, starting at the
+/// given offset of the file (0 by default).
+pub struct FixedWriter<'a> {
+    encoder: raw::Encoder<'a>,
+    buffers: FixedBuffers,
+    fd: types::Fd,
+    offset: u64,
+    // Buffer being filled, and the size of its content.
+    current: Option<(u16, usize)>,
+    queued: VecDeque<PendingWrite>,
+    // Writes submitted, indexed by buffer.
+    in_flight: Vec<Option<PendingWrite>>,
+    finished: bool,
+}
+
+impl FixedWriter<'static> {
+    /// Creates a new writer, compressing at the given level.
+    ///
+    /// The file is only used through its file descriptor: it must stay open
+    /// while writing.
+    pub fn new<F: AsRawFd>(
+        file: &F,
+        buffers: FixedBuffers,
+        level: i32,
+    ) -> io::Result<Self> {
+        let encoder = raw::Encoder::new(level)?;
+        Ok(Self::with_encoder(file, buffers, encoder))
+    }
+}
+
+impl<'a> FixedWriter<'a> {
+    /// Creates a new writer, around an already configured encoder.
+    pub fn with_encoder<F: AsRawFd>(
+        file: &F,
+        buffers: FixedBuffers,
+        encoder: raw::Encoder<'a>,
+    ) -> Self {
+        let count = usize::from(buffers.count());
+        FixedWriter {
+            encoder,
+            buffers,
+            fd: types::Fd(file.as_raw_fd()),
+            offset: 0,
+            current: None,
+            queued: VecDeque::new(),
+            in_flight: (0..count).map(|_| None).collect(),
+            finished: false,
+        }
+    }
+
+    /// Sets the file offset to start writing at.
+    ///
+    /// This must be called before any data is written.
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// Compresses some data.
+    ///
+    /// Returns the number of bytes consumed, which is `0` when every buffer
+    /// is waiting to be written: submit and complete writes, then retry.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the frame is already finished",
+            ));
+        }
+        let mut input = InBuffer::around(data);
+        while input.pos() < data.len() {
+            let (index, len) = match self.current_buffer() {
+                Some(current) => current,
+                None => break,
+            };
+            let buffer = &mut self.buffers.buffers[usize::from(index)];
+            let mut output = OutBuffer::around_pos(&mut buffer[..], len);
+            self.encoder.run(&mut input, &mut output)?;
+            let len = output.pos();
+            self.fill_current(index, len);
+        }
+        Ok(input.pos())
+    }
+
+    /// Ends the frame, and queues the remaining data.
+    ///
+    /// Returns `false` when every buffer is waiting to be written: submit
+    /// and complete writes, then call this again until it returns `true`.
+    /// No more data can be written afterwards.
+    pub fn finish(&mut self) -> io::Result<bool> {
+        while !self.finished {
+            let (index, len) = match self.current_buffer() {
+                Some(current) => current,
+                None => return Ok(false),
+            };
+            let buffer = &mut self.buffers.buffers[usize::from(index)];
+            let mut output = OutBuffer::around_pos(&mut buffer[..], len);
+            let remaining = self.encoder.finish(&mut output, true)?;
+            let len = output.pos();
+            self.fill_current(index, len);
+            self.finished = remaining == 0;
+        }
+        if let Some((index, len)) = self.current.take() {
+            self.queue(index, len);
+        }
+        Ok(true)
+    }
+
+    /// Returns the next entry to submit, if any.
+    pub fn pop_submission(&mut self) -> Option<squeue::Entry> {
+        let write = self.queued.pop_front()?;
+        let buffer = &self.buffers.buffers[usize::from(write.index)];
+        let entry = opcode::WriteFixed::new(
+            self.fd,
+            buffer[write.start..].as_ptr(),
+            (write.end - write.start) as u32,
+            write.index,
+        )
+        .offset(write.offset)
+        .build()
+        .user_data(u64::from(write.index));
+        let index = usize::from(write.index);
+        self.in_flight[index] = Some(write);
+        Some(entry)
+    }
+
+    /// Handles the completion of an entry from `pop_submission`.
+    ///
+    /// Partial writes are queued again for the rest of the buffer. Failed
+    /// writes are queued again as well, so they can be retried after the
+    /// error is handled.
+    pub fn complete(&mut self, entry: &cqueue::Entry) -> io::Result<()> {
+        let index = buffer_index(entry, self.buffers.count())?;
+        let mut write =
+            self.in_flight[usize::from(index)].take().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "completion for a buffer which isn't being written",
+                )
+            })?;
+        let written = match completion_result(entry) {
+            Ok(0) => Err(io::ErrorKind::WriteZero.into()),
+            result => result,
+        };
+        let written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                self.queued.push_front(write);
+                return Err(e);
+            }
+        };
+        write.start += written;
+        write.offset += written as u64;
+        if write.start < write.end {
+            self.queued.push_front(write);
+        } else {
+            self.buffers.release(index);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` once the frame is finished and entirely written.
+    pub fn is_done(&self) -> bool {
+        self.finished && self.queued.is_empty() && !self.has_in_flight()
+    }
+
+    /// Submits queued writes to `ring`, and handles their completions.
+    ///
+    /// This waits for at least one completion if writes are in flight. The
+    /// ring must not be used for anything else.
+    pub fn drive(&mut self, ring: &mut IoUring) -> io::Result<()> {
+        let this = std::cell::RefCell::new(self);
+        drive_ring(
+            ring,
+            || this.borrow_mut().pop_submission(),
+            |entry| this.borrow_mut().complete(entry),
+            || this.borrow().has_in_flight(),
+        )
+    }
+
+    /// Returns the buffers, once done.
+    pub fn into_buffers(self) -> FixedBuffers {
+        self.buffers
+    }
+
+    /// Returns the encoder.
+    pub fn encoder(&self) -> &raw::Encoder<'a> {
+        &self.encoder
+    }
+
+    fn has_in_flight(&self) -> bool {
+        self.in_flight.iter().any(Option::is_some)
+    }
+
+    /// Returns the buffer to compress into, taking a free one if needed.
+    fn current_buffer(&mut self) -> Option<(u16, usize)> {
+        if self.current.is_none() {
+            self.current = self.buffers.take().map(|index| (index, 0));
+        }
+        self.current
+    }
+
+    /// Records the new size of the current buffer, queuing it once full.
+    fn fill_current(&mut self, index: u16, len: usize) {
+        if len == self.buffers.buffer_size() {
+            self.current = None;
+            self.queue(index, len);
+        } else {
+            self.current = Some((index, len));
+        }
+    }
+
+    fn queue(&mut self, index: u16, len: usize) {
+        if len == 0 {
+            self.buffers.release(index);
+            return;
+        }
+        self.queued.push_back(PendingWrite {
+            index,
+            start: 0,
+            end: len,
+            offset: self.offset,
+        });
+        self.offset += len as u64;
+    }
+}
+
+impl std::fmt::Debug for FixedWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedWriter")
+            .field("buffers", &self.buffers)
+            .field("offset", &self.offset)
+            .field("queued", &self.queued.len())
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+/// State of a buffer used by a `FixedReader`.
+#[derive(Debug)]
+enum ReadState {
+    /// Being read, from `offset` on, with `filled` bytes read so far.
+    Reading { offset: u64, filled: usize },
+    /// Read, and being decompressed from `pos`.
+    Ready { pos: usize, filled: usize },
+}
+
+/// Reads compressed data into registered buffers with `ReadFixed`, and
+/// decompresses it.
+///
+/// Buffers are read ahead, from the given offset of the file (0 by
+/// default) up to its end. Every frame found is decompressed.
+pub struct FixedReader<'a> {
+    decoder: raw::Decoder<'a>,
+    buffers: FixedBuffers,
+    fd: types::Fd,
+    offset: u64,
+    // Buffers in file order, with their state.
+    order: VecDeque<(u16, ReadState)>,
+    // Buffers to read again, after a partial read.
+    retries: VecDeque<u16>,
+    submitted: Vec<bool>,
+    eof: bool,
+    frame_ended: bool,
+}
+
+impl FixedReader<'static> {
+    /// Creates a new reader.
+    ///
+    /// The file is only used through its file descriptor: it must stay open
+    /// while reading.
+    pub fn new<F: AsRawFd>(
+        file: &F,
+        buffers: FixedBuffers,
+    ) -> io::Result<Self> {
+        let decoder = raw::Decoder::new()?;
+        Ok(Self::with_decoder(file, buffers, decoder))
+    }
+}
+
+impl<'a> FixedReader<'a> {
+    /// Creates a new reader, around an already configured decoder.
+    pub fn with_decoder<F: AsRawFd>(
+        file: &F,
+        buffers: FixedBuffers,
+        decoder: raw::Decoder<'a>,
+    ) -> Self {
+        let count = usize::from(buffers.count());
+        FixedReader {
+            decoder,
+            buffers,
+            fd: types::Fd(file.as_raw_fd()),
+            offset: 0,
+            order: VecDeque::new(),
+            retries: VecDeque::new(),
+            submitted: vec![false; count],
+            eof: false,
+            frame_ended: true,
+        }
+    }
+
+    /// Sets the file offset to start reading at.
+    ///
+    /// This must be called before any entry is submitted.
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// Returns the next entry to submit, if any.
+    pub fn pop_submission(&mut self) -> Option<squeue::Entry> {
+        let index = match self.retries.pop_front() {
+            Some(index) => index,
+            None if self.eof => return None,
+            None => {
+                let index = self.buffers.take()?;
+                self.order.push_back((
+                    index,
+                    ReadState::Reading {
+                        offset: self.offset,
+                        filled: 0,
+                    },
+                ));
+                self.offset += self.buffers.buffer_size() as u64;
+                index
+            }
+        };
+        let (offset, filled) = match self.state(index) {
+            Some(ReadState::Reading { offset, filled }) => (*offset, *filled),
+            _ => unreachable!("only buffers being read are submitted"),
+        };
+        let buffer = &mut self.buffers.buffers[usize::from(index)];
+        let entry = opcode::ReadFixed::new(
+            self.fd,
+            buffer[filled..].as_mut_ptr(),
+            (buffer.len() - filled) as u32,
+            index,
+        )
+        .offset(offset + filled as u64)
+        .build()
+        .user_data(u64::from(index));
+        self.submitted[usize::from(index)] = true;
+        Some(entry)
+    }
+
+    /// Handles the completion of an entry from `pop_submission`.
+    ///
+    /// Partial reads are queued again for the rest of the buffer. Failed
+    /// reads are queued again as well, so they can be retried after the
+    /// error is handled.
+    pub fn complete(&mut self, entry: &cqueue::Entry) -> io::Result<()> {
+        let index = buffer_index(entry, self.buffers.count())?;
+        if !std::mem::replace(&mut self.submitted[usize::from(index)], false) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "completion for a buffer which isn't being read",
+            ));
+        }
+        let read = match completion_result(entry) {
+            Ok(read) => read,
+            Err(e) => {
+                self.retries.push_front(index);
+                return Err(e);
+            }
+        };
+        let size = self.buffers.buffer_size();
+        let state = self.state(index).expect("submitted buffers are known");
+        let retry = match *state {
+            ReadState::Reading { offset, filled } => {
+                let filled = filled + read;
+                if read > 0 && filled < size {
+                    *state = ReadState::Reading { offset, filled };
+                    true
+                } else {
+                    *state = ReadState::Ready { pos: 0, filled };
+                    false
+                }
+            }
+            ReadState::Ready { .. } => unreachable!("buffer was submitted"),
+        };
+        if retry {
+            self.retries.push_back(index);
+        }
+        if read == 0 {
+            // The end of the file: nothing more to read ahead.
+            self.eof = true;
+        }
+        Ok(())
+    }
+
+    /// Decompresses data into `buf`.
+    ///
+    /// Returns the number of bytes decompressed, `0` at the end of the
+    /// file. Fails with `WouldBlock` when more data must be read first:
+    /// submit and complete reads, then retry.
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut output = OutBuffer::around(buf);
+        while output.pos() == 0 && output.capacity() > 0 {
+            let (index, pos, filled) = match self.order.front() {
+                Some(&(index, ReadState::Ready { pos, filled })) => {
+                    (index, pos, filled)
+                }
+                Some(_) => return Err(io::ErrorKind::WouldBlock.into()),
+                None if self.eof && !self.has_in_flight() => {
+                    if !self.frame_ended {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "incomplete frame",
+                        ));
+                    }
+                    return Ok(0);
+                }
+                None => return Err(io::ErrorKind::WouldBlock.into()),
+            };
+
+            let buffer = &self.buffers.buffers[usize::from(index)];
+            let mut input = InBuffer::around(&buffer[..filled]);
+            input.set_pos(pos);
+            let hint = self.decoder.run(&mut input, &mut output)?;
+            if input.pos() > pos || output.pos() > 0 {
+                self.frame_ended = self.decoder.ends_frame(hint);
+            }
+
+            if input.pos() == filled {
+                self.order.pop_front();
+                self.buffers.release(index);
+            } else {
+                self.order[0].1 = ReadState::Ready {
+                    pos: input.pos(),
+                    filled,
+                };
+            }
+        }
+        Ok(output.pos())
+    }
+
+    /// Submits reads to `ring`, and handles their completions.
+    ///
+    /// This waits for at least one completion if reads are in flight. The
+    /// ring must not be used for anything else.
+    pub fn drive(&mut self, ring: &mut IoUring) -> io::Result<()> {
+        let this = std::cell::RefCell::new(self);
+        drive_ring(
+            ring,
+            || this.borrow_mut().pop_submission(),
+            |entry| this.borrow_mut().complete(entry),
+            || this.borrow().has_in_flight(),
+        )
+    }
+
+    /// Returns the buffers.
+    ///
+    /// Reads still in flight must be completed first.
+    pub fn into_buffers(self) -> FixedBuffers {
+        self.buffers
+    }
+
+    /// Returns the decoder.
+    pub fn decoder(&self) -> &raw::Decoder<'a> {
+        &self.decoder
+    }
+
+    fn has_in_flight(&self) -> bool {
+        self.submitted.iter().any(|&submitted| submitted)
+    }
+
+    fn state(&mut self, index: u16) -> Option<&mut ReadState> {
+        self.order
+            .iter_mut()
+            .find(|(i, _)| *i == index)
+            .map(|(_, state)| state)
+    }
+}
+
+impl std::fmt::Debug for FixedReader<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedReader")
+            .field("buffers", &self.buffers)
+            .field("offset", &self.offset)
+            .field("order", &self.order)
+            .field("eof", &self.eof)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixedBuffers, FixedReader, FixedWriter};
+    use std::io;
+
+    #[test]
+    fn test_round_trip() {
+        // io_uring may be disabled, for example in containers.
+        let mut ring = match ::io_uring::IoUring::new(8) {
+            Ok(ring) => ring,
+            Err(_) => return,
+        };
+
+        let text = include_bytes!("../assets/example.txt");
+        let path = std::env::temp_dir()
+            .join(format!("zstd_test_io_uring_{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+
+        // Small buffers, to go through many of them.
+        let buffers = FixedBuffers::new(3, 64);
+        unsafe { buffers.register(&ring.submitter()).unwrap() };
+        let mut writer = FixedWriter::new(&file, buffers, 3).unwrap();
+        let mut data = &text[..];
+        while !data.is_empty() {
+            let written = writer.write(data).unwrap();
+            data = &data[written..];
+            writer.drive(&mut ring).unwrap();
+        }
+        while !writer.finish().unwrap() {
+            writer.drive(&mut ring).unwrap();
+        }
+        while !writer.is_done() {
+            writer.drive(&mut ring).unwrap();
+        }
+        ring.submitter().unregister_buffers().unwrap();
+        drop(writer);
+        drop(file);
+
+        let compressed = std::fs::read(&path).unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), &text[..]);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let buffers = FixedBuffers::new(4, 100);
+        unsafe { buffers.register(&ring.submitter()).unwrap() };
+        let mut reader = FixedReader::new(&file, buffers).unwrap();
+        let mut decompressed = Vec::new();
+        let mut chunk = [0; 256];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => decompressed.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    reader.drive(&mut ring).unwrap()
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(decompressed, &text[..]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -24,30 +24,74 @@
 // Re-export the zstd-safe crate.
 pub use zstd_safe;
 
+#[cfg(feature = "rayon")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "rayon")))]
+pub mod batch;
 pub mod bulk;
+pub mod compat;
+mod context;
+mod crc32c;
 pub mod dict;
+pub mod frame;
+#[cfg(feature = "mmap")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "mmap")))]
+pub mod fs;
+pub mod http;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[cfg_attr(
+    feature = "doc-cfg",
+    doc(cfg(all(target_os = "linux", feature = "io-uring")))
+)]
+pub mod io_uring;
+mod options;
+pub mod pack;
+pub mod prelude;
+pub mod preset;
+pub mod proxy;
+pub mod small;
 
 #[macro_use]
 pub mod stream;
+mod xxhash;
 
 use std::io;
 
 /// Default compression level.
 pub use zstd_safe::CLEVEL_DEFAULT as DEFAULT_COMPRESSION_LEVEL;
 
+#[doc(no_inline)]
+pub use zstd_safe::{
+    max_c_level, min_c_level, parse_strategy, strategy_name,
+    ParseStrategyError, Strategy,
+};
+
 /// The accepted range of compression levels.
 pub fn compression_level_range(
 ) -> std::ops::RangeInclusive<zstd_safe::CompressionLevel> {
     zstd_safe::min_c_level()..=zstd_safe::max_c_level()
 }
 
+#[cfg(feature = "xxhash")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "xxhash")))]
+pub use crate::xxhash::xxhash64;
+
+pub use crate::options::{Options, WindowTooSmall};
+
+#[doc(no_inline)]
+pub use crate::bulk::estimate_compressibility;
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+#[doc(no_inline)]
+pub use crate::frame::frame_info;
 #[doc(no_inline)]
 pub use crate::stream::{decode_all, encode_all, Decoder, Encoder};
 
 /// Returns the error message as io::Error based on error_code.
+///
+/// The error wraps a [`zstd_safe::Error`], to classify it without looking at
+/// the message.
 fn map_error_code(code: usize) -> io::Error {
-    let msg = zstd_safe::get_error_name(code);
-    io::Error::new(io::ErrorKind::Other, msg.to_string())
+    io::Error::new(io::ErrorKind::Other, zstd_safe::Error::from_code(code))
 }
 
 // Some helper functions to write full-cycle tests.
@@ -0,0 +1,539 @@
+//! A complete compression configuration.
+use std::io;
+
+use zstd_safe::{CParameter, DParameter};
+
+use crate::map_error_code;
+
+/// Compression options, accepted by every compression entry point.
+///
+/// This gathers the level, the dictionary and the usual parameters in one
+/// value, which can be checked once with [`Options::validate`] and then
+/// given to [`encode_all_with`], [`copy_encode_with`],
+/// [`Encoder::with_options`] or [`Compressor::with_options`] alike.
+///
+/// ```rust
+/// let options = zstd::Options::new().level(19).checksum(true);
+/// options.validate().unwrap();
+///
+/// let compressed = zstd::stream::encode_all_with(&b"data"[..], &options).unwrap();
+/// # assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), b"data");
+/// ```
+///
+/// [`encode_all_with`]: crate::stream::encode_all_with
+/// [`copy_encode_with`]: crate::stream::copy_encode_with
+/// [`Encoder::with_options`]: crate::stream::write::Encoder::with_options
+/// [`Compressor::with_options`]: crate::bulk::Compressor::with_options
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub(crate) level: i32,
+    pub(crate) dictionary: Vec<u8>,
+    checksum: bool,
+    window_log: Option<u32>,
+    long_distance_matching: bool,
+    pub(crate) pledged_size: Option<u64>,
+    #[cfg(feature = "zstdmt")]
+    workers: u32,
+    #[cfg(feature = "experimental")]
+    magicless: bool,
+    #[cfg(feature = "experimental")]
+    dict_attach_pref: Option<zstd_safe::DictAttachPref>,
+    #[cfg(feature = "experimental")]
+    dictionary_window: bool,
+    // Whether all the compression parameters are set explicitly.
+    pinned: bool,
+}
+
+impl Options {
+    /// Returns the default options.
+    ///
+    /// This uses zstd's default level, no dictionary, no checksum, and lets
+    /// zstd pick the window from the level.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    #[must_use]
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the dictionary to compress with.
+    ///
+    /// The dictionary is copied. Decompression will need the same
+    /// dictionary.
+    #[must_use]
+    pub fn dictionary(mut self, dictionary: &[u8]) -> Self {
+        self.dictionary = dictionary.to_vec();
+        self
+    }
+
+    /// Applies a preset: its level, window, long-distance matching,
+    /// checksum and threads.
+    ///
+    /// This replaces these settings, and keeps the others (like the
+    /// dictionary). They can still be changed afterwards.
+    #[must_use]
+    pub fn preset(mut self, preset: &crate::preset::Preset) -> Self {
+        self.level = preset.level;
+        self.window_log = preset.window_log;
+        self.long_distance_matching = preset.long_distance_matching;
+        self.checksum = preset.checksum;
+        #[cfg(feature = "zstdmt")]
+        {
+            self.workers = if preset.multithread {
+                crate::stream::auto_workers()
+            } else {
+                0
+            };
+        }
+        self.pinned = preset.pinned;
+        self
+    }
+
+    /// Sets whether to include a checksum at the end of each frame.
+    #[must_use]
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the maximum back-reference distance, as a power of 2.
+    ///
+    /// Decompression will need at least the same window.
+    ///
+    /// By default, zstd picks the window from the level. Setting a window
+    /// too small for the dictionary is an error (see [`WindowTooSmall`]),
+    /// since most of the dictionary would be ignored.
+    #[must_use]
+    pub fn window_log(mut self, window_log: u32) -> Self {
+        self.window_log = Some(window_log);
+        self
+    }
+
+    /// Sets whether to enable long-distance matching.
+    #[must_use]
+    pub fn long_distance_matching(mut self, enabled: bool) -> Self {
+        self.long_distance_matching = enabled;
+        self
+    }
+
+    /// Sets the size of the data to compress, if known in advance.
+    ///
+    /// This is written in the frame header, and lets zstd adapt its
+    /// parameters to the size. Compressing a different amount of data then
+    /// fails. It only applies to the first frame of streaming compression:
+    /// bulk compression already knows the size of its input, and ignores
+    /// it.
+    #[must_use]
+    pub fn pledged_size(mut self, size: u64) -> Self {
+        self.pledged_size = Some(size);
+        self
+    }
+
+    /// Sets the number of worker threads.
+    ///
+    /// `0` (the default) disables multithreading.
+    ///
+    /// Note: This is only available if the `zstdmt` cargo feature is activated.
+    #[cfg(feature = "zstdmt")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zstdmt")))]
+    #[must_use]
+    pub fn workers(mut self, workers: u32) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets whether to omit the magic bytes at the beginning of each frame.
+    ///
+    /// Decompression will need to use the same setting.
+    ///
+    /// Only available with the `experimental` feature.
+    #[cfg(feature = "experimental")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+    #[must_use]
+    pub fn magicless(mut self, magicless: bool) -> Self {
+        self.magicless = magicless;
+        self
+    }
+
+    /// Sets how prepared dictionaries are used by the context.
+    ///
+    /// Attaching the dictionary is cheap for small inputs, while copying
+    /// or reloading it is slower to start but faster on large inputs. By
+    /// default, zstd decides based on the input size.
+    ///
+    /// Only available with the `experimental` feature.
+    #[cfg(feature = "experimental")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+    #[must_use]
+    pub fn dict_attach_pref(
+        mut self,
+        pref: zstd_safe::DictAttachPref,
+    ) -> Self {
+        self.dict_attach_pref = Some(pref);
+        self
+    }
+
+    /// Sets whether to raise the window to reference all of the dictionary.
+    ///
+    /// zstd picks the window from the level, and doesn't grow it for the
+    /// dictionary: data can then only reference the end of a larger
+    /// dictionary. When enabled, the window is raised if the dictionary is
+    /// larger than zstd's choice. A window set with [`Options::window_log`]
+    /// is kept as is.
+    ///
+    /// Decompression needs at least the same window: past a window log of
+    /// 27 (dictionaries larger than 128 MiB), decoders need a matching
+    /// `window_log_max`, like [`Decoder::window_log_max`].
+    ///
+    /// Only available with the `experimental` feature.
+    ///
+    /// [`Decoder::window_log_max`]: crate::stream::read::Decoder::window_log_max
+    #[cfg(feature = "experimental")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+    #[must_use]
+    pub fn dictionary_window(mut self, enabled: bool) -> Self {
+        self.dictionary_window = enabled;
+        self
+    }
+
+    /// Checks that zstd accepts these options.
+    ///
+    /// Every entry point checks the options anyway, so this is only useful
+    /// to report errors early, for example when parsing a configuration.
+    pub fn validate(&self) -> io::Result<()> {
+        self.check()?;
+
+        let mut context = zstd_safe::CCtx::create();
+        for parameter in self.parameters() {
+            context.set_parameter(parameter).map_err(map_error_code)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the options zstd would silently accept: the compression
+    /// level, which it would clamp, and the window, which could be too
+    /// small for the dictionary.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if !crate::compression_level_range().contains(&self.level) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "compression level out of range",
+            ));
+        }
+
+        match (self.window_log, dictionary_window_log(&self.dictionary)) {
+            (Some(window_log), Some(required)) if window_log < required => {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    WindowTooSmall {
+                        required,
+                        window_log,
+                    },
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the window log to set, if zstd shouldn't pick it.
+    fn effective_window_log(&self) -> Option<u32> {
+        #[cfg(feature = "experimental")]
+        if self.window_log.is_none() && self.dictionary_window {
+            return window_log_for_dictionary(self.level, &self.dictionary);
+        }
+        self.window_log
+    }
+
+    /// Applies these options to an existing compression context.
+    pub(crate) fn configure(
+        &self,
+        context: &mut zstd_safe::CCtx<'_>,
+    ) -> io::Result<()> {
+        self.check()?;
+        context
+            .set_parameter(CParameter::CompressionLevel(self.level))
+            .map_err(map_error_code)?;
+        context
+            .load_dictionary(&self.dictionary)
+            .map_err(map_error_code)?;
+        for parameter in self.parameters() {
+            context.set_parameter(parameter).map_err(map_error_code)?;
+        }
+        context
+            .set_pledged_src_size(self.pledged_size)
+            .map_err(map_error_code)?;
+        Ok(())
+    }
+
+    /// Returns the parameters to apply to an encoder, besides the level
+    /// and the dictionary.
+    pub(crate) fn parameters(&self) -> Vec<CParameter> {
+        #[allow(unused_mut)]
+        let mut parameters = self.preset_parameters();
+
+        #[cfg(feature = "experimental")]
+        parameters.push(CParameter::Format(if self.magicless {
+            zstd_safe::FrameFormat::Magicless
+        } else {
+            zstd_safe::FrameFormat::One
+        }));
+
+        #[cfg(feature = "experimental")]
+        parameters.push(CParameter::ForceAttachDict(
+            self.dict_attach_pref
+                .unwrap_or(zstd_safe::DictAttachPref::DefaultAttach),
+        ));
+
+        parameters
+    }
+
+    /// Returns the parameters covered by presets (see [`Options::preset`]),
+    /// besides the level.
+    pub(crate) fn preset_parameters(&self) -> Vec<CParameter> {
+        let mut parameters = vec![
+            CParameter::ChecksumFlag(self.checksum),
+            CParameter::EnableLongDistanceMatching(
+                self.long_distance_matching,
+            ),
+        ];
+
+        // `0` lets zstd pick the window from the level.
+        parameters.push(CParameter::WindowLog(
+            self.effective_window_log().unwrap_or(0),
+        ));
+        if self.pinned {
+            parameters.extend(crate::preset::pinned_parameters());
+        }
+
+        #[cfg(feature = "zstdmt")]
+        parameters.push(CParameter::NbWorkers(self.workers));
+
+        parameters
+    }
+
+    /// Returns the parameters a decoder needs to read frames compressed
+    /// with these options, besides the dictionary.
+    pub(crate) fn decoder_parameters(&self) -> Vec<DParameter> {
+        let mut parameters = Vec::new();
+
+        // Windows larger than the default limit need to be allowed.
+        if let Some(window_log) = self.effective_window_log() {
+            parameters.push(DParameter::WindowLogMax(window_log.max(27)));
+        }
+
+        #[cfg(feature = "experimental")]
+        parameters.push(DParameter::Format(if self.magicless {
+            zstd_safe::FrameFormat::Magicless
+        } else {
+            zstd_safe::FrameFormat::One
+        }));
+
+        parameters
+    }
+}
+
+/// zstd's smallest window log.
+const WINDOW_LOG_MIN: u32 = 10;
+
+/// Returns the window log needed to reference all of `dictionary`, if any.
+fn dictionary_window_log(dictionary: &[u8]) -> Option<u32> {
+    if dictionary.is_empty() {
+        return None;
+    }
+    let len = dictionary.len() as u64;
+    let window_log = 64 - (len - 1).leading_zeros();
+    Some(window_log.max(WINDOW_LOG_MIN))
+}
+
+/// Returns the window log covering all of `dictionary`, if zstd picks a
+/// smaller one for `level`.
+#[cfg(feature = "experimental")]
+fn window_log_for_dictionary(level: i32, dictionary: &[u8]) -> Option<u32> {
+    let required = dictionary_window_log(dictionary)?;
+
+    // zstd doesn't grow the window for dictionaries, so its choice only
+    // depends on the level when the input size is unknown.
+    // Safety: Just FFI
+    let parameters =
+        unsafe { zstd_safe::zstd_sys::ZSTD_getCParams(level, 0, 0) };
+    Some(required).filter(|&required| required > parameters.windowLog)
+}
+
+/// Error returned when the window is too small for the dictionary.
+///
+/// Data can only reference the last `1 << window_log` bytes of the
+/// dictionary, so most of a larger dictionary would be silently ignored.
+/// This is returned inside an `io::Error` of kind `InvalidInput`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WindowTooSmall {
+    /// The smallest window log covering the whole dictionary.
+    pub required: u32,
+    /// The window log which was set.
+    pub window_log: u32,
+}
+
+impl std::fmt::Display for WindowTooSmall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "window log {} is too small for the dictionary, which needs {}",
+            self.window_log, self.required
+        )
+    }
+}
+
+impl std::error::Error for WindowTooSmall {}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    #[test]
+    fn test_options() {
+        let text = include_bytes!("../assets/example.txt");
+        let dictionary = &text[..500];
+        let options = Options::new()
+            .level(5)
+            .dictionary(dictionary)
+            .checksum(true)
+            .window_log(20);
+        options.validate().unwrap();
+
+        let decode = |compressed: &[u8]| {
+            let (header, _) =
+                crate::frame::FrameHeader::parse(compressed).unwrap();
+            assert!(header.checksum);
+
+            let mut decoder = crate::stream::read::Decoder::with_dictionary(
+                compressed, dictionary,
+            )
+            .unwrap();
+            let mut decompressed = Vec::new();
+            std::io::Read::read_to_end(&mut decoder, &mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, text);
+        };
+
+        decode(&crate::stream::encode_all_with(&text[..], &options).unwrap());
+
+        let mut compressed = Vec::new();
+        crate::stream::copy_encode_with(&text[..], &mut compressed, &options)
+            .unwrap();
+        decode(&compressed);
+
+        let mut encoder =
+            crate::stream::write::Encoder::with_options(Vec::new(), &options)
+                .unwrap();
+        std::io::Write::write_all(&mut encoder, text).unwrap();
+        decode(&encoder.finish().unwrap());
+
+        let mut encoder =
+            crate::stream::read::Encoder::with_options(&text[..], &options)
+                .unwrap();
+        let mut compressed = Vec::new();
+        std::io::Read::read_to_end(&mut encoder, &mut compressed).unwrap();
+        decode(&compressed);
+
+        let mut compressor =
+            crate::bulk::Compressor::with_options(&options).unwrap();
+        decode(&compressor.compress(text).unwrap());
+    }
+
+    #[test]
+    fn test_pledged_size() {
+        let text = include_bytes!("../assets/example.txt");
+        let options = Options::new().pledged_size(text.len() as u64);
+
+        let check = |compressed: &[u8]| {
+            let (header, _) =
+                crate::frame::FrameHeader::parse(compressed).unwrap();
+            assert_eq!(header.content_size, Some(text.len() as u64));
+            assert_eq!(crate::decode_all(compressed).unwrap(), text);
+        };
+
+        check(&crate::stream::encode_all_with(&text[..], &options).unwrap());
+
+        let mut encoder =
+            crate::stream::write::Encoder::with_options(Vec::new(), &options)
+                .unwrap();
+        std::io::Write::write_all(&mut encoder, text).unwrap();
+        check(&encoder.finish().unwrap());
+
+        // The data must match the pledged size.
+        let options = Options::new().pledged_size(text.len() as u64 + 1);
+        assert!(crate::stream::encode_all_with(&text[..], &options).is_err());
+    }
+
+    #[test]
+    fn test_dictionary_window() {
+        use super::WindowTooSmall;
+
+        let text = include_bytes!("../assets/example.txt");
+        // A raw content dictionary of 4MB, larger than the default window.
+        let dictionary: Vec<u8> =
+            text.iter().copied().cycle().take(4 << 20).collect();
+
+        let err = Options::new()
+            .dictionary(&dictionary)
+            .window_log(20)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<WindowTooSmall>())
+            .unwrap();
+        assert_eq!(
+            *err,
+            WindowTooSmall {
+                required: 22,
+                window_log: 20
+            }
+        );
+        let options = Options::new().dictionary(&dictionary).window_log(22);
+        options.validate().unwrap();
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_dictionary_window_opt_in() {
+        let text = include_bytes!("../assets/example.txt");
+        let dictionary: Vec<u8> =
+            text.iter().copied().cycle().take(4 << 20).collect();
+        let window_size = |options: &Options| {
+            let compressed =
+                crate::stream::encode_all_with(&text[..], options).unwrap();
+            let (header, _) =
+                crate::frame::FrameHeader::parse(&compressed).unwrap();
+            header.window_size.unwrap()
+        };
+
+        // By default, zstd keeps the window of the level.
+        let options = Options::new().level(3).dictionary(&dictionary);
+        assert_eq!(window_size(&options), 1 << 21);
+
+        let options = options.dictionary_window(true);
+        options.validate().unwrap();
+        assert_eq!(window_size(&options), 4 << 20);
+
+        // The window is not lowered for smaller dictionaries.
+        let options = Options::new()
+            .level(19)
+            .dictionary(&dictionary)
+            .dictionary_window(true);
+        assert_eq!(window_size(&options), 8 << 20);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Options::new().level(1000).validate().is_err());
+        assert!(Options::new().window_log(100).validate().is_err());
+    }
+}
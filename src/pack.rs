@@ -0,0 +1,276 @@
+//! Pack many small entries in one buffer, along with their dictionary.
+//!
+//! Small entries (like a folder of small JSON files) compress poorly on
+//! their own, but well with a dictionary trained on them. [`pack`] trains
+//! such a dictionary (or uses the one from the options), stores it once at
+//! the start of the output, and compresses each entry as its own frame
+//! using it. [`unpack`] reads everything back:
+//!
+//! ```rust
+//! let entries: Vec<String> = (0..100)
+//!     .map(|i| format!(r#"{{"id": {}, "name": "entry {}"}}"#, i, i))
+//!     .collect();
+//!
+//! let packed = zstd::pack::pack(&entries, &zstd::Options::new())?;
+//! let unpacked = zstd::pack::unpack(&packed)?;
+//! assert_eq!(unpacked.len(), entries.len());
+//! assert_eq!(unpacked[42], entries[42].as_bytes());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! The packed data is a sequence of frames:
+//!
+//! * A skippable frame using [`DICTIONARY_MAGIC_VARIANT`], holding the
+//!   dictionary. It is omitted when no dictionary is used.
+//! * One zstd frame per entry, in order, compressed with the dictionary.
+//!
+//! Any zstd decoder given the dictionary can decode the whole stream, as
+//! the concatenation of the entries.
+//!
+//! [`DICTIONARY_MAGIC_VARIANT`]: crate::frame::DICTIONARY_MAGIC_VARIANT
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+use crate::bulk::Compressor;
+use crate::frame::{self, SkippableHeader, DICTIONARY_MAGIC_VARIANT};
+use crate::stream::raw::DecodeLimitExceeded;
+use crate::stream::read::Decoder;
+use crate::stream::OutputLimitExceeded;
+use crate::Options;
+
+/// Largest dictionary trained by [`pack`], like the `zstd` CLI default.
+#[cfg(feature = "zdict_builder")]
+const DICTIONARY_SIZE_MAX: usize = 110 * 1024;
+
+/// Compresses `entries` into a single buffer, with a shared dictionary.
+///
+/// If `options` has a dictionary, it is used as is. Otherwise, with the
+/// `zdict_builder` feature, a dictionary is trained on the entries; if
+/// training fails (for example with too few entries), the entries are
+/// compressed without dictionary.
+///
+/// Frames always use the standard format, with magic numbers.
+pub fn pack<I>(entries: I, options: &Options) -> io::Result<Vec<u8>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let entries: Vec<I::Item> = entries.into_iter().collect();
+
+    let mut options = options.clone();
+    if options.dictionary.is_empty() {
+        if let Some(dictionary) = train(&entries) {
+            options = options.dictionary(&dictionary);
+        }
+    }
+
+    let mut compressor = Compressor::with_options(&options)?;
+    #[cfg(feature = "experimental")]
+    compressor.set_parameter(zstd_safe::CParameter::Format(
+        zstd_safe::FrameFormat::One,
+    ))?;
+
+    let mut packed = Vec::new();
+    if !options.dictionary.is_empty() {
+        frame::write_skippable_frame(
+            &mut packed,
+            DICTIONARY_MAGIC_VARIANT,
+            &options.dictionary,
+        )?;
+    }
+    for entry in &entries {
+        packed.extend(compressor.compress(entry.as_ref())?);
+    }
+    Ok(packed)
+}
+
+/// Trains a dictionary on the entries, if possible.
+#[cfg(feature = "zdict_builder")]
+fn train<S: AsRef<[u8]>>(entries: &[S]) -> Option<Vec<u8>> {
+    let total: usize = entries.iter().map(|entry| entry.as_ref().len()).sum();
+    // Dictionaries much larger than a tenth of the samples don't help.
+    let max_size = (total / 10).min(DICTIONARY_SIZE_MAX);
+    crate::dict::from_samples(entries, max_size).ok()
+}
+
+#[cfg(not(feature = "zdict_builder"))]
+fn train<S: AsRef<[u8]>>(_entries: &[S]) -> Option<Vec<u8>> {
+    None
+}
+
+/// Decompresses the entries packed by [`pack`].
+///
+/// The entries are returned in order. This puts no limit on the number or
+/// size of the entries: for untrusted input, use [`unpack_limited`].
+pub fn unpack(packed: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    unpack_limited(packed, u64::MAX, u64::MAX)
+}
+
+/// Decompresses the entries packed by [`pack`], with limits.
+///
+/// Fails with a [`DecodeLimitExceeded`] if `packed` holds more than
+/// `max_entries` entries, or with an [`OutputLimitExceeded`] if the entries
+/// add up to more than `max_bytes`. Both are wrapped in an `io::Error` of
+/// kind `InvalidData`.
+pub fn unpack_limited(
+    packed: &[u8],
+    max_entries: u64,
+    max_bytes: u64,
+) -> io::Result<Vec<Vec<u8>>> {
+    let (dictionary, frames) = match SkippableHeader::parse(packed) {
+        Ok(Some(header))
+            if header.magic_variant == DICTIONARY_MAGIC_VARIANT =>
+        {
+            let start = SkippableHeader::SIZE;
+            let end = usize::try_from(header.size)
+                .ok()
+                .and_then(|size| start.checked_add(size))
+                .filter(|&end| end <= packed.len())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "dictionary frame is truncated",
+                    )
+                })?;
+            (&packed[start..end], &packed[end..])
+        }
+        _ => (&[][..], packed),
+    };
+
+    let mut decoder = Decoder::with_dictionary(frames, dictionary)?;
+    // Only the dictionary is stored in a skippable frame.
+    decoder.ignore_skippable_frames(false);
+
+    let mut entries = Vec::new();
+    let mut remaining = max_bytes;
+    let mut frames = decoder.frames();
+    while let Some(frame) = frames.next_frame()? {
+        if entries.len() as u64 >= max_entries {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                DecodeLimitExceeded::Frames { limit: max_entries },
+            ));
+        }
+        let mut entry = Vec::new();
+        frame
+            .take(remaining.saturating_add(1))
+            .read_to_end(&mut entry)?;
+        remaining =
+            remaining.checked_sub(entry.len() as u64).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    OutputLimitExceeded { limit: max_bytes },
+                )
+            })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack, unpack, unpack_limited};
+    use crate::stream::raw::DecodeLimitExceeded;
+    use crate::stream::OutputLimitExceeded;
+    use crate::Options;
+
+    #[test]
+    fn test_with_dictionary() {
+        let text = include_bytes!("../assets/example.txt");
+        let entries: Vec<&[u8]> = text.chunks(100).collect();
+        let options = Options::new().level(5).dictionary(&text[..500]);
+
+        let packed = pack(&entries, &options).unwrap();
+        assert!(crate::frame::is_skippable(&packed));
+        assert_eq!(unpack(&packed).unwrap(), entries);
+
+        // Entries are concatenated by regular decoders.
+        let mut decompressed = Vec::new();
+        let start = crate::frame::SkippableHeader::SIZE + 500;
+        crate::stream::copy_decode_with_dictionary(
+            &packed[start..],
+            &mut decompressed,
+            &text[..500],
+        )
+        .unwrap();
+        assert_eq!(&decompressed[..], &text[..]);
+    }
+
+    #[test]
+    fn test_without_dictionary() {
+        let entries: [&[u8]; 3] = [b"first", b"", b"last"];
+        // Too few entries to train a dictionary.
+        let packed = pack(&entries[..], &Options::new()).unwrap();
+        assert!(!crate::frame::is_skippable(&packed));
+        assert_eq!(unpack(&packed).unwrap(), entries);
+
+        let packed = pack(Vec::<Vec<u8>>::new(), &Options::new()).unwrap();
+        assert!(unpack(&packed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        let entries: [&[u8]; 3] = [b"first", b"", b"last"];
+        let packed = pack(&entries[..], &Options::new()).unwrap();
+        assert_eq!(unpack_limited(&packed, 3, 9).unwrap(), entries);
+
+        let error = unpack_limited(&packed, 2, 9).unwrap_err();
+        assert_eq!(
+            error.get_ref().unwrap().downcast_ref(),
+            Some(&DecodeLimitExceeded::Frames { limit: 2 })
+        );
+        let error = unpack_limited(&packed, 3, 8).unwrap_err();
+        assert_eq!(
+            error.get_ref().unwrap().downcast_ref(),
+            Some(&OutputLimitExceeded { limit: 8 })
+        );
+
+        // Only the dictionary goes in a skippable frame.
+        let mut packed = pack(&entries[..], &Options::new()).unwrap();
+        crate::frame::write_skippable_frame(&mut packed, 0, b"extra").unwrap();
+        assert!(unpack(&packed).is_err());
+
+        // The dictionary frame can't go past the end of the input.
+        let mut packed = Vec::new();
+        crate::frame::write_skippable_frame(
+            &mut packed,
+            crate::frame::DICTIONARY_MAGIC_VARIANT,
+            b"dictionary",
+        )
+        .unwrap();
+        packed.truncate(packed.len() - 1);
+        assert!(unpack(&packed).is_err());
+    }
+
+    #[cfg(feature = "zdict_builder")]
+    #[test]
+    fn test_trained() {
+        let entries: Vec<String> = (0..1000)
+            .map(|i| {
+                format!(
+                    r#"{{"id": {}, "name": "user {}", "active": {}}}"#,
+                    i,
+                    i * 7 % 13,
+                    i % 3 == 0
+                )
+            })
+            .collect();
+
+        let packed = pack(&entries, &Options::new()).unwrap();
+        assert!(crate::frame::is_skippable(&packed));
+        let unpacked = unpack(&packed).unwrap();
+        assert_eq!(unpacked.len(), entries.len());
+        for (entry, unpacked) in entries.iter().zip(&unpacked) {
+            assert_eq!(entry.as_bytes(), &unpacked[..]);
+        }
+
+        // Smaller than compressing each entry on its own, dictionary
+        // included.
+        let separate: usize = entries
+            .iter()
+            .map(|entry| crate::bulk::compress(entry.as_bytes(), 3).unwrap())
+            .map(|compressed| compressed.len())
+            .sum();
+        assert!(packed.len() < separate);
+    }
+}
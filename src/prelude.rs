@@ -0,0 +1,45 @@
+//! Commonly used types, for glob imports.
+//!
+//! This gathers the types needed to tune compression, including the
+//! parameter types from `zstd_safe`, so they don't have to be imported from
+//! both crates.
+//!
+//! Stream encoders and decoders come in two flavors, which are renamed here:
+//! `ReadEncoder` and `ReadDecoder` wrap a reader, while `WriteEncoder` and
+//! `WriteDecoder` wrap a writer.
+//!
+//! ```rust
+//! use std::io::Write;
+//! use zstd::prelude::*;
+//!
+//! let mut encoder = WriteEncoder::new(Vec::new(), 3).unwrap();
+//! encoder.set_parameter(CParameter::Strategy(Strategy::ZSTD_lazy2)).unwrap();
+//! encoder.write_all(b"data").unwrap();
+//! let compressed = encoder.finish().unwrap();
+//!
+//! let mut decoder = ReadDecoder::new(&compressed[..]).unwrap();
+//! decoder.set_parameter(DParameter::WindowLogMax(27)).unwrap();
+//! # let mut decompressed = Vec::new();
+//! # std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+//! # assert_eq!(decompressed, b"data");
+//! ```
+#[doc(no_inline)]
+pub use crate::bulk::{Compressor, Decompressor};
+#[doc(no_inline)]
+pub use crate::dict::{DecoderDictionary, DictRegistry, EncoderDictionary};
+#[doc(no_inline)]
+pub use crate::preset::Preset;
+#[doc(no_inline)]
+pub use crate::stream::read::{
+    Decoder as ReadDecoder, DecoderBuilder, Encoder as ReadEncoder,
+};
+#[doc(no_inline)]
+pub use crate::stream::write::{
+    Decoder as WriteDecoder, Encoder as WriteEncoder, EncoderBuilder,
+};
+#[doc(no_inline)]
+pub use crate::stream::ChecksumPolicy;
+#[doc(no_inline)]
+pub use crate::Options;
+#[doc(no_inline)]
+pub use zstd_safe::{CParameter, DParameter, Strategy};
@@ -0,0 +1,187 @@
+//! Named bundles of compression parameters.
+//!
+//! The compression level alone doesn't tell the whole story: the window size
+//! decides how much memory decoders need, long-distance matching helps with
+//! large inputs, and checksums protect data at rest. A [`Preset`] picks a
+//! consistent set of these for common use cases.
+//!
+//! Presets can be applied to any encoder with `apply_preset`, or to
+//! [`Options`] with [`Options::preset`], which decides what each preset
+//! sets:
+//!
+//! ```rust
+//! use zstd::preset::Preset;
+//!
+//! let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 0).unwrap();
+//! encoder.apply_preset(&Preset::archival()).unwrap();
+//! ```
+use zstd_safe::CParameter;
+
+use crate::Options;
+
+/// Version of zstd for which [`Preset::stable`] is pinned, as returned by
+/// `zstd_safe::version_number()`.
+#[cfg(feature = "stability")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "stability")))]
+pub const STABLE_ZSTD_VERSION: u32 = 10504;
+
+/// A bundle of compression parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub(crate) level: i32,
+    pub(crate) window_log: Option<u32>,
+    pub(crate) long_distance_matching: bool,
+    pub(crate) checksum: bool,
+    pub(crate) multithread: bool,
+    // Whether all the compression parameters are set explicitly.
+    pub(crate) pinned: bool,
+}
+
+impl Preset {
+    /// Compresses as fast as possible.
+    ///
+    /// Uses level 1, which is still much better than no compression. Good
+    /// for temporary data, or when the CPU is the bottleneck.
+    pub fn fastest() -> Self {
+        Preset {
+            level: 1,
+            window_log: None,
+            long_distance_matching: false,
+            checksum: false,
+            multithread: false,
+            pinned: false,
+        }
+    }
+
+    /// A good default, fast to compress and to decompress.
+    ///
+    /// Uses zstd's default level (currently `3`), and multithreading with
+    /// the `zstdmt` feature (see `multithread_auto`).
+    pub fn balanced() -> Self {
+        Preset {
+            level: zstd_safe::CLEVEL_DEFAULT,
+            multithread: true,
+            ..Preset::fastest()
+        }
+    }
+
+    /// Compresses as small as possible, for data decompressed anywhere.
+    ///
+    /// Uses level 19, which is slow to compress but still fast to
+    /// decompress. The window stays at 8MB, so decoders don't need much
+    /// memory (this is also the limit for HTTP content-coding).
+    pub fn best_ratio() -> Self {
+        Preset {
+            level: 19,
+            ..Preset::balanced()
+        }
+    }
+
+    /// Compresses large data for long-term storage.
+    ///
+    /// Like [`Preset::best_ratio`], but with a 128MB window and
+    /// long-distance matching, to find redundancy far apart in large inputs
+    /// (like backups or archives). Each frame includes a checksum, to detect
+    /// corruption.
+    ///
+    /// Decoders need up to 128MB of memory, which is the default limit
+    /// (see `window_log_max`).
+    pub fn archival() -> Self {
+        Preset {
+            window_log: Some(27),
+            long_distance_matching: true,
+            checksum: true,
+            ..Preset::best_ratio()
+        }
+    }
+
+    /// Compresses with fixed parameters, for reproducible output.
+    ///
+    /// Every compression parameter is set explicitly (equivalent to level
+    /// 3), instead of being derived from the level, so the same input
+    /// should give the same frames. Frames include a checksum, and no
+    /// multithreading is used.
+    ///
+    /// The output is only checked against the bundled zstd library, whose
+    /// version is [`STABLE_ZSTD_VERSION`]: another version of zstd, like a
+    /// system library, may compress differently. Flushing the encoder
+    /// before the end (which ends blocks early) or pledging a source size
+    /// (which is written in the frame header) also changes the output.
+    ///
+    /// Note: This is only available if the `stability` cargo feature is
+    /// activated.
+    #[cfg(feature = "stability")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "stability")))]
+    pub fn stable() -> Self {
+        Preset {
+            level: 3,
+            window_log: Some(21),
+            checksum: true,
+            pinned: true,
+            ..Preset::fastest()
+        }
+    }
+
+    /// Returns the compression level used by this preset.
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Returns the parameters to apply to an encoder, in order.
+    ///
+    /// These are the level and the parameters set by [`Options::preset`].
+    pub fn parameters(&self) -> Vec<CParameter> {
+        let options = Options::new().preset(self);
+        let mut parameters = vec![CParameter::CompressionLevel(self.level)];
+        parameters.extend(options.preset_parameters());
+        parameters
+    }
+}
+
+/// Parameters set by pinned presets, besides the level, the window and the
+/// checksum: zstd's parameters for level 3, with no known source size.
+pub(crate) fn pinned_parameters() -> [CParameter; 8] {
+    [
+        CParameter::ChainLog(16),
+        CParameter::HashLog(17),
+        CParameter::SearchLog(1),
+        CParameter::MinMatch(5),
+        CParameter::TargetLength(0),
+        CParameter::Strategy(zstd_safe::Strategy::ZSTD_dfast),
+        CParameter::ContentSizeFlag(true),
+        CParameter::DictIdFlag(true),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Preset;
+
+    #[test]
+    fn test_presets() {
+        let text = include_bytes!("../assets/example.txt");
+
+        for preset in [
+            Preset::fastest(),
+            Preset::balanced(),
+            Preset::best_ratio(),
+            Preset::archival(),
+        ] {
+            let mut encoder =
+                crate::stream::write::Encoder::new(Vec::new(), 0).unwrap();
+            encoder.apply_preset(&preset).unwrap();
+            std::io::Write::write_all(&mut encoder, text).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let (header, _) =
+                crate::frame::FrameHeader::parse(&compressed).unwrap();
+            assert_eq!(header.checksum, preset == Preset::archival());
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let mut compressor = crate::bulk::Compressor::new(0).unwrap();
+            compressor.apply_preset(&preset).unwrap();
+            let compressed = compressor.compress(text).unwrap();
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Normalize zstd streams coming from different producers.
+//!
+//! [`relay`] reads a stream of frames and writes it to a destination,
+//! following a [`Policy`]:
+//!
+//! * Frames that already follow the policy are copied as-is, without being
+//!   decompressed.
+//! * Other frames are decompressed, and compressed again using the policy.
+//! * Skippable frames are copied as-is.
+//! * Data that is not zstd is either compressed, or rejected.
+//!
+//! The compression level is not recorded in frames, so it cannot be
+//! checked. Frames are judged on the parameters their decoders depend on:
+//! window size and checksum.
+use std::io::{self, BufRead, Read, Write};
+
+use crate::frame::{self, FrameHeader, SkippableHeader};
+use crate::stream::{read, write};
+
+/// Describes which frames are acceptable, and how to compress the others.
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    level: i32,
+    window_log_max: u32,
+    checksum: bool,
+    input_window_log_max: u32,
+    compress_unknown: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::new(0)
+    }
+}
+
+impl Policy {
+    /// Creates a new policy, compressing frames with the given level.
+    ///
+    /// By default, frames with a window up to 8MB (the largest window HTTP
+    /// clients are required to support) are accepted.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> Self {
+        Policy {
+            level,
+            window_log_max: crate::http::WINDOW_LOG_MAX,
+            checksum: false,
+            input_window_log_max: 27,
+            compress_unknown: false,
+        }
+    }
+
+    /// Sets the compression level used for transcoded frames.
+    #[must_use]
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the maximum window log of relayed frames.
+    ///
+    /// Frames requiring a larger window are transcoded. Values of `64` or
+    /// more accept any window.
+    #[must_use]
+    pub fn window_log_max(mut self, window_log_max: u32) -> Self {
+        self.window_log_max = window_log_max;
+        self
+    }
+
+    /// Sets whether relayed frames must include a checksum.
+    ///
+    /// If `true`, frames without checksum are transcoded.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the maximum window log of frames that can be transcoded.
+    ///
+    /// This limits the memory used when decompressing incoming frames.
+    /// Frames requiring a larger window will fail the relay.
+    /// Defaults to `27`, like regular decoders.
+    #[must_use]
+    pub fn input_window_log_max(mut self, input_window_log_max: u32) -> Self {
+        self.input_window_log_max = input_window_log_max;
+        self
+    }
+
+    /// Sets whether input that is not zstd should be compressed.
+    ///
+    /// If `true`, when something else than a frame is found, everything
+    /// from there to the end of the input is compressed into a single
+    /// frame. Otherwise, the relay fails. Defaults to `false`.
+    #[must_use]
+    pub fn compress_unknown(mut self, compress_unknown: bool) -> Self {
+        self.compress_unknown = compress_unknown;
+        self
+    }
+
+    /// Returns `true` if a frame with this header can be relayed as-is.
+    pub fn accepts(&self, header: &FrameHeader) -> bool {
+        let window_size_max =
+            1u64.checked_shl(self.window_log_max).unwrap_or(u64::MAX);
+        let window_ok = header
+            .effective_window_size()
+            .map_or(false, |size| size <= window_size_max);
+
+        window_ok && (header.checksum || !self.checksum)
+    }
+
+    fn encoder<W: Write>(
+        &self,
+        writer: W,
+    ) -> io::Result<write::Encoder<'static, W>> {
+        let mut encoder = write::Encoder::new(writer, self.level)?;
+        // Levels up to 19 never use more than an 8MB window.
+        if self.window_log_max < 23 || self.level > 19 {
+            encoder.window_log(self.window_log_max)?;
+        }
+        encoder.include_checksum(self.checksum)?;
+        Ok(encoder)
+    }
+}
+
+/// Summary of a call to [`relay`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Number of frames copied as-is.
+    pub passed: u64,
+
+    /// Number of frames decompressed and compressed again.
+    pub transcoded: u64,
+
+    /// Number of skippable frames copied.
+    pub skippable: u64,
+
+    /// Number of bytes of non-zstd input that were compressed.
+    pub compressed_unknown: u64,
+}
+
+/// Relays a zstd stream from `reader` to `writer`, following `policy`.
+///
+/// Returns a summary of what was done with each frame.
+///
+/// Frames using a dictionary can be copied, but not transcoded: if such a
+/// frame does not follow the policy, an error is returned.
+pub fn relay<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    policy: Policy,
+) -> io::Result<Report> {
+    let mut input = Input::new(reader);
+    let mut report = Report::default();
+
+    loop {
+        let magic = input.peek(4)?;
+        if magic.is_empty() {
+            break;
+        }
+        let skippable = frame::is_skippable(magic);
+        let zstd = magic == &frame::MAGIC_NUMBER.to_le_bytes()[..];
+
+        if skippable {
+            let header = input.peek(SkippableHeader::SIZE)?;
+            let size = match SkippableHeader::parse(header)? {
+                Some(header) => header.size,
+                None => unreachable!("magic number was checked"),
+            };
+            copy(&mut input, &mut writer, 8 + u64::from(size))?;
+            report.skippable += 1;
+        } else if zstd {
+            let header = input.peek(FrameHeader::MAX_SIZE)?;
+            let (header, header_size) = FrameHeader::parse(header)?;
+            if policy.accepts(&header) {
+                pass_through(&mut input, &mut writer, &header, header_size)?;
+                report.passed += 1;
+            } else {
+                transcode(&mut input, &mut writer, &header, &policy)?;
+                report.transcoded += 1;
+            }
+        } else if policy.compress_unknown {
+            let mut encoder = policy.encoder(&mut writer)?;
+            report.compressed_unknown = io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+            break;
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input is not a zstd stream",
+            ));
+        }
+    }
+
+    writer.flush()?;
+    Ok(report)
+}
+
+/// Copies a frame, one block at a time.
+fn pass_through<R: BufRead, W: Write>(
+    input: &mut Input<R>,
+    writer: &mut W,
+    header: &FrameHeader,
+    header_size: usize,
+) -> io::Result<()> {
+    copy(input, writer, header_size as u64)?;
+
+    loop {
+        let (block_type, size, last) =
+            frame::parse_block_header(input.peek(3)?)?;
+        let content_size = match block_type {
+            frame::BlockType::Rle => 1,
+            _ => size,
+        };
+        copy(input, writer, 3 + u64::from(content_size))?;
+
+        if last {
+            break;
+        }
+    }
+
+    if header.checksum {
+        copy(input, writer, frame::Checksum::SIZE as u64)?;
+    }
+    Ok(())
+}
+
+/// Decompresses a frame, and compresses it again.
+fn transcode<R: BufRead, W: Write>(
+    input: &mut Input<R>,
+    writer: &mut W,
+    header: &FrameHeader,
+    policy: &Policy,
+) -> io::Result<()> {
+    if header.dict_id.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "cannot transcode a frame using a dictionary",
+        ));
+    }
+
+    let mut decoder = read::Decoder::with_buffer(input)?.single_frame();
+    decoder.window_log_max(policy.input_window_log_max)?;
+
+    let mut encoder = policy.encoder(writer)?;
+    encoder.set_pledged_src_size(header.content_size)?;
+    io::copy(&mut decoder, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Copies exactly `len` bytes from `input` to `writer`.
+fn copy<R: BufRead, W: Write>(
+    input: &mut Input<R>,
+    writer: &mut W,
+    len: u64,
+) -> io::Result<()> {
+    if io::copy(&mut input.take(len), writer)? < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "incomplete frame",
+        ));
+    }
+    Ok(())
+}
+
+/// A `BufRead` that can look ahead a few bytes.
+struct Input<R> {
+    reader: R,
+    // Bytes read ahead of `reader`.
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Input<R> {
+    fn new(reader: R) -> Self {
+        Input {
+            reader,
+            buffer: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the next `n` bytes, without consuming them.
+    ///
+    /// Returns less than `n` bytes only at the end of the input.
+    fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.pos == self.buffer.len() {
+            self.buffer.clear();
+            self.pos = 0;
+        }
+
+        while self.buffer.len() - self.pos < n {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+            let missing = n - (self.buffer.len() - self.pos);
+            let len = available.len().min(missing);
+            self.buffer.extend_from_slice(&available[..len]);
+            self.reader.consume(len);
+        }
+
+        let end = self.buffer.len().min(self.pos + n);
+        Ok(&self.buffer[self.pos..end])
+    }
+}
+
+impl<R: BufRead> Read for Input<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Input<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos < self.buffer.len() {
+            Ok(&self.buffer[self.pos..])
+        } else {
+            self.reader.fill_buf()
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.pos < self.buffer.len() {
+            self.pos += amt;
+        } else {
+            self.reader.consume(amt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{relay, Policy, Report};
+    use crate::frame::FrameHeader;
+    use std::io::{self, Write};
+
+    const TEXT: &[u8] = include_bytes!("../assets/example.txt");
+
+    /// Compresses `data` without pledging its size, so the frame header
+    /// shows the level's full window.
+    fn compress(data: &[u8], level: i32) -> Vec<u8> {
+        let mut encoder =
+            crate::stream::Encoder::new(Vec::new(), level).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_relay() {
+        let first = compress(TEXT, 1);
+        let mut input = first.clone();
+        crate::frame::write_skippable_frame(&mut input, 0, b"meta").unwrap();
+        input.extend(compress(TEXT, 22));
+
+        let mut output = Vec::new();
+        let report = relay(&input[..], &mut output, Policy::new(3)).unwrap();
+        assert_eq!(
+            report,
+            Report {
+                passed: 1,
+                transcoded: 1,
+                skippable: 1,
+                compressed_unknown: 0,
+            }
+        );
+        assert!(output.starts_with(&first));
+        assert_eq!(crate::decode_all(&output[..]).unwrap(), TEXT.repeat(2));
+
+        // The output now follows the policy.
+        let mut again = Vec::new();
+        let report = relay(&output[..], &mut again, Policy::new(3)).unwrap();
+        assert_eq!(report.passed, 2);
+        assert_eq!(again, output);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let input = crate::encode_all(TEXT, 1).unwrap();
+        let policy = Policy::new(1).checksum(true);
+
+        let mut output = Vec::new();
+        let report = relay(&input[..], &mut output, policy).unwrap();
+        assert_eq!(report.transcoded, 1);
+        assert_eq!(output.len(), input.len() + 4);
+        assert_eq!(crate::decode_all(&output[..]).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn test_unknown() {
+        let mut input = crate::encode_all(TEXT, 1).unwrap();
+        input.extend_from_slice(TEXT);
+
+        let mut output = Vec::new();
+        let err = relay(&input[..], &mut output, Policy::new(1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let policy = Policy::new(1).compress_unknown(true);
+        let mut output = Vec::new();
+        let report = relay(&input[..], &mut output, policy).unwrap();
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.compressed_unknown, TEXT.len() as u64);
+        assert_eq!(crate::decode_all(&output[..]).unwrap(), TEXT.repeat(2));
+    }
+
+    #[test]
+    fn test_window_log_max() {
+        let input = crate::encode_all(TEXT, 1).unwrap();
+        let (header, _) = FrameHeader::parse(&input).unwrap();
+        for window_log_max in [9, 31, 64, u32::MAX] {
+            let policy = Policy::new(1).window_log_max(window_log_max);
+            assert_eq!(policy.accepts(&header), window_log_max > 9);
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let input = crate::encode_all(TEXT, 1).unwrap();
+        let truncated = &input[..input.len() - 1];
+
+        let err = relay(truncated, io::sink(), Policy::new(1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,312 @@
+//! Compress and decompress small payloads.
+//!
+//! For payloads of a few KB (values in a key-value store, network messages,
+//! ...), the cost of creating a context and the size of the frame header
+//! become significant. The functions in this module:
+//!
+//! * Re-use a thread-local context between calls.
+//! * Can use prepared dictionaries, without copying them.
+//! * Can use a more compact framing, without content size or dictionary ID
+//!   (and, with the `experimental` feature, without magic number).
+//!
+//! The same settings must be used for compression and decompression.
+use std::io;
+
+use zstd_safe::{CCtx, CParameter, DCtx, WriteBuf};
+
+use crate::context::{with_cctx, with_dctx};
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::map_error_code;
+
+/// Compresses a small payload using a thread-local context.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    Settings::new(level).compress(data)
+}
+
+/// Decompresses a small payload using a thread-local context.
+///
+/// The decompressed data should be at most `capacity` bytes,
+/// or an error will be returned.
+pub fn decompress(data: &[u8], capacity: usize) -> io::Result<Vec<u8>> {
+    Settings::new(0).decompress(data, capacity)
+}
+
+/// Settings for compressing and decompressing small payloads.
+#[derive(Clone, Copy)]
+pub struct Settings<'a> {
+    level: i32,
+    content_size: bool,
+    dict_id: bool,
+    #[cfg(feature = "experimental")]
+    magicless: bool,
+    encoder_dictionary: Option<&'a EncoderDictionary<'static>>,
+    decoder_dictionary: Option<&'a DecoderDictionary<'static>>,
+}
+
+impl Settings<'static> {
+    /// Creates new settings using the given compression level.
+    ///
+    /// This uses the standard zstd framing, and no dictionary.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> Self {
+        Settings {
+            level,
+            content_size: true,
+            dict_id: true,
+            #[cfg(feature = "experimental")]
+            magicless: false,
+            encoder_dictionary: None,
+            decoder_dictionary: None,
+        }
+    }
+}
+
+impl<'a> Settings<'a> {
+    /// Uses a more compact framing.
+    ///
+    /// This drops the content size and the dictionary ID, saving a few
+    /// bytes per payload. Frames can still be read by any zstd decoder. To
+    /// also drop the magic number, see `magicless`.
+    #[must_use]
+    pub fn compact(mut self) -> Self {
+        self.content_size = false;
+        self.dict_id = false;
+        self
+    }
+
+    /// Sets the compression level.
+    ///
+    /// This is ignored when using an encoder dictionary, which carries its
+    /// own level.
+    #[must_use]
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets whether the decompressed size is stored in each frame.
+    ///
+    /// Without it, decompression relies entirely on the given capacity.
+    #[must_use]
+    pub fn content_size(mut self, content_size: bool) -> Self {
+        self.content_size = content_size;
+        self
+    }
+
+    /// Sets whether the dictionary ID is stored in each frame.
+    #[must_use]
+    pub fn dict_id(mut self, dict_id: bool) -> Self {
+        self.dict_id = dict_id;
+        self
+    }
+
+    /// Sets whether the magic number is omitted from each frame.
+    ///
+    /// This saves 4 more bytes per payload, but frames can then only be
+    /// decompressed with the same setting.
+    #[cfg(feature = "experimental")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+    #[must_use]
+    pub fn magicless(mut self, magicless: bool) -> Self {
+        self.magicless = magicless;
+        self
+    }
+
+    /// Uses the given dictionaries.
+    ///
+    /// Both should come from the same raw dictionary. The compression level
+    /// will be the one used to prepare the encoder dictionary.
+    #[must_use]
+    pub fn dictionaries<'b>(
+        self,
+        encoder_dictionary: &'b EncoderDictionary<'static>,
+        decoder_dictionary: &'b DecoderDictionary<'static>,
+    ) -> Settings<'b> {
+        Settings {
+            level: self.level,
+            content_size: self.content_size,
+            dict_id: self.dict_id,
+            #[cfg(feature = "experimental")]
+            magicless: self.magicless,
+            encoder_dictionary: Some(encoder_dictionary),
+            decoder_dictionary: Some(decoder_dictionary),
+        }
+    }
+
+    /// Compresses a payload and returns the compressed result.
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut buffer =
+            Vec::with_capacity(zstd_safe::compress_bound(data.len()));
+        self.compress_to_buffer(data, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Compresses a payload to the given destination buffer.
+    ///
+    /// Returns the number of bytes written.
+    pub fn compress_to_buffer<C: WriteBuf + ?Sized>(
+        &self,
+        data: &[u8],
+        destination: &mut C,
+    ) -> io::Result<usize> {
+        with_cctx(|context| self.compress_with(context, data, destination))
+    }
+
+    fn compress_with<C: WriteBuf + ?Sized>(
+        &self,
+        context: &mut CCtx<'static>,
+        data: &[u8],
+        destination: &mut C,
+    ) -> io::Result<usize> {
+        match self.encoder_dictionary {
+            Some(dictionary) => context.ref_cdict(dictionary.as_cdict()),
+            None => {
+                context.set_parameter(CParameter::CompressionLevel(self.level))
+            }
+        }
+        .map_err(map_error_code)?;
+
+        context
+            .set_parameter(CParameter::ContentSizeFlag(self.content_size))
+            .map_err(map_error_code)?;
+        context
+            .set_parameter(CParameter::DictIdFlag(self.dict_id))
+            .map_err(map_error_code)?;
+        #[cfg(feature = "experimental")]
+        context
+            .set_parameter(CParameter::Format(self.format()))
+            .map_err(map_error_code)?;
+
+        context.compress2(destination, data).map_err(map_error_code)
+    }
+
+    /// Decompresses a payload and returns the decompressed result.
+    ///
+    /// The decompressed data should be at most `capacity` bytes,
+    /// or an error will be returned.
+    pub fn decompress(
+        &self,
+        data: &[u8],
+        capacity: usize,
+    ) -> io::Result<Vec<u8>> {
+        let capacity = match zstd_safe::get_frame_content_size(data) {
+            Ok(Some(size)) if size < capacity as u64 => size as usize,
+            _ => capacity,
+        };
+        let mut buffer = Vec::with_capacity(capacity);
+        self.decompress_to_buffer(data, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Decompresses a payload to the given destination buffer.
+    ///
+    /// Returns the number of bytes written.
+    pub fn decompress_to_buffer<C: WriteBuf + ?Sized>(
+        &self,
+        data: &[u8],
+        destination: &mut C,
+    ) -> io::Result<usize> {
+        with_dctx(|context| self.decompress_with(context, data, destination))
+    }
+
+    fn decompress_with<C: WriteBuf + ?Sized>(
+        &self,
+        context: &mut DCtx<'static>,
+        data: &[u8],
+        destination: &mut C,
+    ) -> io::Result<usize> {
+        if let Some(dictionary) = self.decoder_dictionary {
+            context
+                .ref_ddict(dictionary.as_ddict())
+                .map_err(map_error_code)?;
+        }
+
+        #[cfg(feature = "experimental")]
+        context
+            .set_parameter(zstd_safe::DParameter::Format(self.format()))
+            .map_err(map_error_code)?;
+
+        context
+            .decompress(destination, data)
+            .map_err(map_error_code)
+    }
+
+    #[cfg(feature = "experimental")]
+    fn format(&self) -> zstd_safe::FrameFormat {
+        if self.magicless {
+            zstd_safe::FrameFormat::Magicless
+        } else {
+            zstd_safe::FrameFormat::One
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress, Settings};
+    use crate::dict::{DecoderDictionary, EncoderDictionary};
+
+    const TEXT: &[u8] = include_bytes!("../assets/example.txt");
+
+    #[test]
+    fn test_cycle() {
+        let compressed = compress(TEXT, 1).unwrap();
+        assert_eq!(decompress(&compressed, TEXT.len()).unwrap(), TEXT);
+
+        // Compatible with the regular functions.
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn test_compact() {
+        let settings = Settings::new(1).compact();
+
+        let compact = settings.compress(TEXT).unwrap();
+        let regular = compress(TEXT, 1).unwrap();
+        assert!(compact.len() < regular.len());
+
+        assert!(settings.decompress(&compact, TEXT.len() - 1).is_err());
+        assert_eq!(settings.decompress(&compact, TEXT.len()).unwrap(), TEXT);
+
+        // Settings do not leak to the next call.
+        assert_eq!(crate::decode_all(&regular[..]).unwrap(), TEXT);
+        assert_eq!(compress(TEXT, 1).unwrap(), regular);
+
+        // Compact frames are still standard frames.
+        assert_eq!(&compact[..4], &crate::frame::MAGIC_NUMBER.to_le_bytes());
+        assert_eq!(crate::decode_all(&compact[..]).unwrap(), TEXT);
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_magicless() {
+        let settings = Settings::new(1).compact().magicless(true);
+
+        let magicless = settings.compress(TEXT).unwrap();
+        let compact = Settings::new(1).compact().compress(TEXT).unwrap();
+        assert_eq!(magicless.len() + 4, compact.len());
+        assert_eq!(settings.decompress(&magicless, TEXT.len()).unwrap(), TEXT);
+        assert!(crate::decode_all(&magicless[..]).is_err());
+    }
+
+    #[test]
+    fn test_dictionaries() {
+        let encoder_dictionary = EncoderDictionary::copy(&TEXT[..512], 3);
+        let decoder_dictionary = DecoderDictionary::copy(&TEXT[..512]);
+        let settings = Settings::new(0)
+            .dictionaries(&encoder_dictionary, &decoder_dictionary);
+
+        let compressed = settings.compress(TEXT).unwrap();
+        assert!(compressed.len() < compress(TEXT, 3).unwrap().len());
+        assert_eq!(
+            settings.decompress(&compressed, TEXT.len()).unwrap(),
+            TEXT
+        );
+
+        // The dictionary is not kept around.
+        assert!(decompress(&compressed, TEXT.len()).is_err());
+    }
+}
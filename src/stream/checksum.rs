@@ -0,0 +1,96 @@
+use crate::crc32c::Crc32c;
+
+/// How frame checksums are written and checked.
+///
+/// The same policy can be given to encoders (`set_checksum_policy`) and
+/// decoders, so both sides of a pipeline agree on it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumPolicy {
+    /// Encoders don't write checksums (the default).
+    ///
+    /// Decoders still verify checksums when frames have them.
+    #[default]
+    None,
+
+    /// Encoders write a checksum at the end of each frame.
+    ///
+    /// Decoders verify checksums when frames have them.
+    Frame,
+
+    /// Like `Frame`, but decoders also require every frame to have a
+    /// checksum, and fail on frames without one.
+    ///
+    /// Skippable frames are not affected.
+    FramePlusVerifyOnDecode,
+}
+
+impl ChecksumPolicy {
+    /// Returns `true` if encoders should write checksums.
+    pub fn writes_checksum(self) -> bool {
+        self != ChecksumPolicy::None
+    }
+
+    /// Returns `true` if decoders should fail on frames without checksum.
+    pub fn requires_checksum(self) -> bool {
+        self == ChecksumPolicy::FramePlusVerifyOnDecode
+    }
+}
+
+/// Checksum of the uncompressed content of frames, written in a trailer.
+///
+/// Encoders configured with one (`set_content_checksum`) follow each frame
+/// with a skippable frame holding the checksum of its content (see
+/// [`CONTENT_CHECKSUM_MAGIC_VARIANT`]). Standard zstd tools ignore these
+/// trailers, and decoders configured with the same checksum verify them.
+///
+/// This complements zstd's own checksums, for systems which require a
+/// specific algorithm.
+///
+/// [`CONTENT_CHECKSUM_MAGIC_VARIANT`]: crate::frame::CONTENT_CHECKSUM_MAGIC_VARIANT
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContentChecksum {
+    /// CRC-32C (Castagnoli), written as 4 little-endian bytes.
+    Crc32c,
+}
+
+impl ContentChecksum {
+    /// Returns the identifier of this checksum, written in trailers.
+    pub fn id(self) -> u8 {
+        match self {
+            ContentChecksum::Crc32c => 1,
+        }
+    }
+
+    pub(crate) fn hasher(self) -> ContentHasher {
+        match self {
+            ContentChecksum::Crc32c => ContentHasher::Crc32c(Crc32c::new()),
+        }
+    }
+}
+
+/// Running checksum of the content of a frame.
+#[derive(Clone, Debug)]
+pub(crate) enum ContentHasher {
+    Crc32c(Crc32c),
+}
+
+impl ContentHasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::Crc32c(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the content of the trailer frame: the checksum ID, followed
+    /// by the checksum itself.
+    pub(crate) fn trailer(&self) -> Vec<u8> {
+        match self {
+            ContentHasher::Crc32c(hasher) => {
+                let mut trailer = vec![ContentChecksum::Crc32c.id()];
+                trailer.extend_from_slice(&hasher.digest().to_le_bytes());
+                trailer
+            }
+        }
+    }
+}
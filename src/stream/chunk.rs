@@ -0,0 +1,325 @@
+//! Push-based compression and decompression of discrete chunks.
+//!
+//! Unlike the [`read`] and [`write`] wrappers, these types do not wrap any
+//! IO object: each call takes an input chunk and appends the result to a
+//! caller-provided `Vec<u8>`.
+//!
+//! This fits frameworks where data comes as a sequence of buffers, like
+//! streaming HTTP bodies.
+//!
+//! [`read`]: crate::stream::read
+//! [`write`]: crate::stream::write
+use std::io;
+
+#[cfg(feature = "futures-stream")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "futures-stream")))]
+pub mod futures;
+
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+
+/// Makes sure `output` has some spare capacity for the next operation step.
+fn reserve_output(output: &mut Vec<u8>, additional: usize) {
+    if output.capacity() - output.len() < additional {
+        output.reserve(additional);
+    }
+}
+
+/// Compresses a stream made of discrete chunks.
+///
+/// Call [`Encoder::finish`] after the last chunk to complete the frame.
+#[derive(Debug)]
+pub struct Encoder<'a> {
+    operation: raw::Encoder<'a>,
+    finished: bool,
+}
+
+impl Encoder<'static> {
+    /// Creates a new chunk encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> io::Result<Self> {
+        Self::with_dictionary(level, &[])
+    }
+
+    /// Creates a new chunk encoder, using an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during decompression.
+    pub fn with_dictionary(level: i32, dictionary: &[u8]) -> io::Result<Self> {
+        let operation = raw::Encoder::with_dictionary(level, dictionary)?;
+        Ok(Self::with_encoder(operation))
+    }
+}
+
+impl<'a> Encoder<'a> {
+    /// Creates a new chunk encoder around the given raw encoder.
+    pub fn with_encoder(operation: raw::Encoder<'a>) -> Self {
+        Encoder {
+            operation,
+            finished: false,
+        }
+    }
+
+    /// Creates a new chunk encoder, using an existing `EncoderDictionary`.
+    ///
+    /// The dictionary must be the same as the one used during decompression.
+    pub fn with_prepared_dictionary<'b>(
+        dictionary: &EncoderDictionary<'b>,
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let operation = raw::Encoder::with_prepared_dictionary(dictionary)?;
+        Ok(Self::with_encoder(operation))
+    }
+
+    /// Compresses the given chunk, appending any output to `output`.
+    ///
+    /// zstd buffers data internally, so this may not produce any output
+    /// until enough input was given. Use [`Encoder::flush`] to force it out.
+    pub fn encode(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "encoder is finished",
+            ));
+        }
+
+        let mut input = InBuffer::around(chunk);
+        while input.pos() < chunk.len() {
+            reserve_output(output, zstd_safe::CCtx::out_size());
+            let pos = output.len();
+            let mut dst = OutBuffer::around_pos(output, pos);
+            self.operation.run(&mut input, &mut dst)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes all input given so far, appending the output to `output`.
+    ///
+    /// The result can be decoded by the receiving end right away, at the
+    /// cost of a slightly reduced compression ratio.
+    pub fn flush(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        loop {
+            reserve_output(output, zstd_safe::CCtx::out_size());
+            let pos = output.len();
+            let mut dst = OutBuffer::around_pos(output, pos);
+            if self.operation.flush(&mut dst)? == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Ends the frame, appending the remaining output to `output`.
+    ///
+    /// After this, the encoder cannot be given any more input.
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        while !self.finished {
+            reserve_output(output, zstd_safe::CCtx::out_size());
+            let pos = output.len();
+            let mut dst = OutBuffer::around_pos(output, pos);
+            self.finished = self.operation.finish(&mut dst, true)? == 0;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if [`Encoder::finish`] completed successfully.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Sets the given zstd compression parameter.
+    pub fn set_parameter(
+        &mut self,
+        parameter: zstd_safe::CParameter,
+    ) -> io::Result<()> {
+        self.operation.set_parameter(parameter)
+    }
+
+    /// Sets the expected size of the input.
+    ///
+    /// Giving a `None` size means the size is unknown (this is the default).
+    pub fn set_pledged_src_size(
+        &mut self,
+        size: Option<u64>,
+    ) -> io::Result<()> {
+        self.operation.set_pledged_src_size(size)
+    }
+
+    crate::encoder_parameters!();
+}
+
+/// Decompresses a stream made of discrete chunks.
+///
+/// Concatenated frames are decoded transparently.
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    operation: raw::Decoder<'a>,
+    finished_frame: bool,
+}
+
+impl Decoder<'static> {
+    /// Creates a new chunk decoder.
+    pub fn new() -> io::Result<Self> {
+        Self::with_dictionary(&[])
+    }
+
+    /// Creates a new chunk decoder, using an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_dictionary(dictionary: &[u8]) -> io::Result<Self> {
+        let operation = raw::Decoder::with_dictionary(dictionary)?;
+        Ok(Self::with_decoder(operation))
+    }
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a new chunk decoder around the given raw decoder.
+    pub fn with_decoder(operation: raw::Decoder<'a>) -> Self {
+        Decoder {
+            operation,
+            finished_frame: false,
+        }
+    }
+
+    /// Creates a new chunk decoder, using an existing `DecoderDictionary`.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_prepared_dictionary<'b>(
+        dictionary: &DecoderDictionary<'b>,
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let operation = raw::Decoder::with_prepared_dictionary(dictionary)?;
+        Ok(Self::with_decoder(operation))
+    }
+
+    /// Decompresses the given chunk, appending any output to `output`.
+    pub fn decode(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let mut input = InBuffer::around(chunk);
+        loop {
+            // Support concatenated frames by re-initializing the context.
+            if self.finished_frame && input.pos() < chunk.len() {
+                self.operation.reinit()?;
+                self.finished_frame = false;
+            }
+
+            reserve_output(output, zstd_safe::DCtx::out_size());
+            let pos = output.len();
+            let mut dst = OutBuffer::around_pos(output, pos);
+            let hint = self.operation.run(&mut input, &mut dst)?;
+            let output_full = dst.pos() == dst.capacity();
+
+            if hint == 0 {
+                self.finished_frame = true;
+            }
+
+            // If the output was filled, zstd may still have some data for us.
+            if input.pos() == chunk.len() && !output_full {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Checks that the stream ended cleanly.
+    ///
+    /// Returns an error if the last frame is incomplete.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut output = [0u8; 0];
+        self.operation.finish(
+            &mut OutBuffer::around(&mut output[..]),
+            self.finished_frame,
+        )?;
+        Ok(())
+    }
+
+    /// Sets a decompression parameter on the decompression stream.
+    pub fn set_parameter(
+        &mut self,
+        parameter: zstd_safe::DParameter,
+    ) -> io::Result<()> {
+        self.operation.set_parameter(parameter)
+    }
+
+    crate::decoder_parameters!();
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(Encoder::new(1));
+    _assert_send(Decoder::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Encoder};
+
+    const TEXT: &[u8] = include_bytes!("../../assets/example.txt");
+
+    #[test]
+    fn test_cycle() {
+        let mut encoder = Encoder::new(1).unwrap();
+        let mut compressed = Vec::new();
+        for chunk in TEXT.chunks(1000) {
+            encoder.encode(chunk, &mut compressed).unwrap();
+        }
+        encoder.finish(&mut compressed).unwrap();
+
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), TEXT);
+
+        let mut decoder = Decoder::new().unwrap();
+        let mut decompressed = Vec::new();
+        for chunk in compressed.chunks(7) {
+            decoder.decode(chunk, &mut decompressed).unwrap();
+        }
+        decoder.finish().unwrap();
+
+        assert_eq!(decompressed, TEXT);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut encoder = Encoder::new(1).unwrap();
+        let mut decoder = Decoder::new().unwrap();
+
+        let mut compressed = Vec::new();
+        let mut decompressed = Vec::new();
+
+        encoder.encode(b"hello", &mut compressed).unwrap();
+        encoder.flush(&mut compressed).unwrap();
+
+        // The receiving end can decode everything sent so far.
+        decoder.decode(&compressed, &mut decompressed).unwrap();
+        assert_eq!(decompressed, b"hello");
+
+        // But the frame is not complete yet.
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn test_concatenated_frames() {
+        let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+        compressed.extend(crate::encode_all(&b"bar"[..], 1).unwrap());
+
+        let mut decoder = Decoder::new().unwrap();
+        let mut decompressed = Vec::new();
+        decoder.decode(&compressed, &mut decompressed).unwrap();
+        decoder.finish().unwrap();
+
+        assert_eq!(decompressed, b"foobar");
+    }
+}
@@ -0,0 +1,368 @@
+//! [`Stream`] and [`Sink`] adapters over chunks of bytes.
+//!
+//! Many network stacks expose bodies as streams of byte chunks rather than
+//! as `AsyncRead`. These adapters compress or decompress such streams
+//! directly, using the chunk [`Encoder`] and [`Decoder`]:
+//!
+//! * [`EncoderStream`] and [`DecoderStream`] wrap a
+//!   `Stream<Item = io::Result<B>>` and yield processed [`Bytes`].
+//! * [`EncoderSink`] and [`DecoderSink`] take [`Bytes`], and send processed
+//!   [`Bytes`] to an inner `Sink<Bytes, Error = io::Error>`.
+//!
+//! Input streams can yield any `B: AsRef<[u8]>`, like `Bytes` or `Vec<u8>`.
+//! Empty output chunks are never produced.
+//!
+//! [`Encoder`]: super::Encoder
+//! [`Decoder`]: super::Decoder
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_sink::Sink;
+
+use super::{Decoder, Encoder};
+
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(value) => value,
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+mod private {
+    use std::io;
+
+    /// Common interface of the chunk encoder and decoder.
+    pub trait Codec {
+        fn process(
+            &mut self,
+            chunk: &[u8],
+            output: &mut Vec<u8>,
+        ) -> io::Result<()>;
+        fn flush(&mut self, output: &mut Vec<u8>) -> io::Result<()>;
+        fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()>;
+    }
+}
+use self::private::Codec;
+
+impl Codec for Encoder<'_> {
+    fn process(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        self.encode(chunk, output)
+    }
+
+    fn flush(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        Encoder::flush(self, output)
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        Encoder::finish(self, output)
+    }
+}
+
+impl Codec for Decoder<'_> {
+    fn process(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        self.decode(chunk, output)
+    }
+
+    fn flush(&mut self, _output: &mut Vec<u8>) -> io::Result<()> {
+        // Decoded data is always output right away.
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> io::Result<()> {
+        Decoder::finish(self)
+    }
+}
+
+/// A stream of compressed chunks, from a stream of uncompressed chunks.
+pub type EncoderStream<'a, S> = ChunkStream<S, Encoder<'a>>;
+
+/// A stream of decompressed chunks, from a stream of compressed chunks.
+///
+/// This fails if the input ends in the middle of a frame.
+pub type DecoderStream<'a, S> = ChunkStream<S, Decoder<'a>>;
+
+/// A sink compressing chunks before sending them to an inner sink.
+pub type EncoderSink<'a, K> = ChunkSink<K, Encoder<'a>>;
+
+/// A sink decompressing chunks before sending them to an inner sink.
+///
+/// Closing it fails if the input ended in the middle of a frame.
+pub type DecoderSink<'a, K> = ChunkSink<K, Decoder<'a>>;
+
+/// Implements [`Stream`] by processing the chunks of an inner stream.
+///
+/// See [`EncoderStream`] and [`DecoderStream`].
+pub struct ChunkStream<S, C> {
+    stream: S,
+    codec: C,
+    finished: bool,
+}
+
+impl<S, C> ChunkStream<S, C> {
+    /// Creates a new stream, processing `stream` with `codec`.
+    ///
+    /// `codec` is either a chunk [`Encoder`] or [`Decoder`].
+    pub fn new(stream: S, codec: C) -> Self {
+        ChunkStream {
+            stream,
+            codec,
+            finished: false,
+        }
+    }
+
+    /// Acquires a reference to the inner stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Acquires a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the inner stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, C, B> Stream for ChunkStream<S, C>
+where
+    S: Stream<Item = io::Result<B>> + Unpin,
+    C: Codec + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut output = Vec::new();
+        while !this.finished {
+            let result = match ready!(Pin::new(&mut this.stream).poll_next(cx))
+            {
+                Some(Ok(chunk)) => {
+                    this.codec.process(chunk.as_ref(), &mut output)
+                }
+                Some(Err(e)) => Err(e),
+                None => {
+                    this.finished = true;
+                    this.codec.finish(&mut output)
+                }
+            };
+            if let Err(e) = result {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if !output.is_empty() {
+                return Poll::Ready(Some(Ok(output.into())));
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Implements [`Sink`] by processing chunks before sending them to an inner
+/// sink.
+///
+/// See [`EncoderSink`] and [`DecoderSink`].
+pub struct ChunkSink<K, C> {
+    sink: K,
+    codec: C,
+    // Output not yet accepted by the inner sink.
+    pending: Option<Bytes>,
+}
+
+impl<K, C> ChunkSink<K, C> {
+    /// Creates a new sink, processing chunks with `codec` before sending
+    /// them to `sink`.
+    ///
+    /// `codec` is either a chunk [`Encoder`] or [`Decoder`].
+    pub fn new(sink: K, codec: C) -> Self {
+        ChunkSink {
+            sink,
+            codec,
+            pending: None,
+        }
+    }
+
+    /// Acquires a reference to the inner sink.
+    pub fn get_ref(&self) -> &K {
+        &self.sink
+    }
+
+    /// Acquires a mutable reference to the inner sink.
+    pub fn get_mut(&mut self) -> &mut K {
+        &mut self.sink
+    }
+
+    /// Returns the inner sink.
+    ///
+    /// Some output may be lost if this sink was not flushed or closed first.
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+impl<K, C> ChunkSink<K, C>
+where
+    K: Sink<Bytes, Error = io::Error> + Unpin,
+{
+    /// Sends the pending output to the inner sink.
+    fn poll_send_pending(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pending.is_some() {
+            ready!(Pin::new(&mut self.sink).poll_ready(cx))?;
+            if let Some(chunk) = self.pending.take() {
+                Pin::new(&mut self.sink).start_send(chunk)?;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Queues the output of the codec, if any.
+    fn set_pending(&mut self, output: Vec<u8>) {
+        if !output.is_empty() {
+            self.pending = Some(output.into());
+        }
+    }
+}
+
+impl<K, C> Sink<Bytes> for ChunkSink<K, C>
+where
+    K: Sink<Bytes, Error = io::Error> + Unpin,
+    C: Codec + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().poll_send_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        let this = self.get_mut();
+        let mut output = Vec::new();
+        this.codec.process(&item, &mut output)?;
+        this.set_pending(output);
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_pending(cx))?;
+        let mut output = Vec::new();
+        this.codec.flush(&mut output)?;
+        this.set_pending(output);
+        ready!(this.poll_send_pending(cx))?;
+        Pin::new(&mut this.sink).poll_flush(cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_pending(cx))?;
+        let mut output = Vec::new();
+        this.codec.finish(&mut output)?;
+        this.set_pending(output);
+        ready!(this.poll_send_pending(cx))?;
+        Pin::new(&mut this.sink).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::executor::block_on;
+    use futures::{stream, SinkExt, StreamExt, TryStreamExt};
+
+    use super::{DecoderSink, DecoderStream, EncoderSink, EncoderStream};
+    use crate::stream::chunk::{Decoder, Encoder};
+
+    #[test]
+    fn test_chunk_stream() {
+        let text = include_bytes!("../../../assets/example.txt");
+        let chunks =
+            || stream::iter(text.chunks(100).map(|chunk| Ok(chunk.to_vec())));
+
+        block_on(async {
+            let encoder =
+                EncoderStream::new(chunks(), Encoder::new(1).unwrap());
+            let compressed: Vec<Bytes> = encoder.try_collect().await.unwrap();
+            let compressed = compressed.concat();
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let input = stream::iter(
+                compressed.chunks(7).map(|chunk| Ok(chunk.to_vec())),
+            );
+            let decoder = DecoderStream::new(input, Decoder::new().unwrap());
+            let decompressed: Vec<Bytes> =
+                decoder.try_collect().await.unwrap();
+            assert!(decompressed.iter().all(|chunk| !chunk.is_empty()));
+            assert_eq!(decompressed.concat(), text);
+
+            // Incomplete frames are reported at the end.
+            let truncated = stream::iter(vec![Ok(compressed[..10].to_vec())]);
+            let mut decoder =
+                DecoderStream::new(truncated, Decoder::new().unwrap());
+            assert!(decoder.next().await.unwrap().is_err());
+        });
+    }
+
+    #[test]
+    fn test_chunk_sink() {
+        let text = include_bytes!("../../../assets/example.txt");
+
+        block_on(async {
+            let output = Vec::<Bytes>::new().sink_map_err(|e| match e {});
+            let mut encoder =
+                EncoderSink::new(output, Encoder::new(1).unwrap());
+            for chunk in text.chunks(100) {
+                encoder.send(Bytes::copy_from_slice(chunk)).await.unwrap();
+            }
+            encoder.close().await.unwrap();
+            let compressed =
+                Bytes::from(encoder.into_inner().into_inner().concat());
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let output = Vec::<Bytes>::new().sink_map_err(|e| match e {});
+            let mut decoder =
+                DecoderSink::new(output, Decoder::new().unwrap());
+            for chunk in compressed.chunks(7) {
+                decoder.feed(Bytes::copy_from_slice(chunk)).await.unwrap();
+            }
+            decoder.close().await.unwrap();
+            let decompressed = decoder.into_inner().into_inner().concat();
+            assert_eq!(decompressed, text);
+
+            let output = Vec::<Bytes>::new().sink_map_err(|e| match e {});
+            let mut decoder =
+                DecoderSink::new(output, Decoder::new().unwrap());
+            decoder.send(compressed.slice(..10)).await.unwrap();
+            assert!(decoder.close().await.is_err());
+        });
+    }
+}
@@ -1,47 +1,317 @@
-use std::io;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::time::Instant;
 
-use super::{Decoder, Encoder};
+use super::raw::{self, InBuffer, Operation, OutBuffer};
+use super::{read, write};
+use crate::context::{with_cctx, with_dctx};
+use crate::dict::DecoderDictionary;
+use crate::map_error_code;
+use crate::Options;
 
 /// Decompress from the given source as if using a `Decoder`.
 ///
 /// The input data must be in the zstd frame format.
+///
+/// This re-uses a thread-local context between calls.
 pub fn decode_all<R: io::Read>(source: R) -> io::Result<Vec<u8>> {
     let mut result = Vec::new();
     copy_decode(source, &mut result)?;
     Ok(result)
 }
 
+/// Decompress from the given source into `destination`.
+///
+/// This is like [`decode_all`], but re-uses the allocation of
+/// `destination`, which is cleared first. This saves an allocation per call
+/// when decoding many streams in a row.
+///
+/// This re-uses a thread-local context between calls.
+///
+/// ```rust
+/// let compressed = zstd::encode_all(&b"foo"[..], 1).unwrap();
+///
+/// let mut buffer = Vec::new();
+/// for _ in 0..3 {
+///     zstd::stream::decode_all_into(&compressed[..], &mut buffer).unwrap();
+///     assert_eq!(buffer, b"foo");
+/// }
+/// ```
+pub fn decode_all_into<R: io::Read>(
+    source: R,
+    destination: &mut Vec<u8>,
+) -> io::Result<()> {
+    destination.clear();
+    copy_decode(source, destination)
+}
+
 /// Decompress from the given source as if using a `Decoder`.
 ///
 /// Decompressed data will be appended to `destination`.
-pub fn copy_decode<R, W>(source: R, mut destination: W) -> io::Result<()>
+///
+/// This re-uses a thread-local context between calls.
+pub fn copy_decode<R, W>(source: R, destination: W) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    copy_decode_into(source, destination)?;
+    Ok(())
+}
+
+/// Decompress from the given source, using a dictionary.
+///
+/// The dictionary must be the one used for compression. Decompressed data
+/// will be appended to `destination`.
+///
+/// This re-uses a thread-local context between calls.
+pub fn copy_decode_with_dictionary<R, W>(
+    source: R,
+    mut destination: W,
+    dictionary: &[u8],
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_dctx(|context| {
+        context
+            .load_dictionary(dictionary)
+            .map_err(map_error_code)?;
+        let buffer_size = zstd_safe::DCtx::in_size();
+        let source = io::BufReader::with_capacity(buffer_size, source);
+        let mut decoder = read::Decoder::with_context(source, context);
+        io::copy(&mut decoder, &mut destination)?;
+        Ok(())
+    })
+}
+
+/// Decompress from the given source, using a prepared dictionary.
+///
+/// This is like [`copy_decode_with_dictionary`], but skips loading the
+/// dictionary, which is best when decoding many streams with the same one.
+pub fn copy_decode_with_prepared_dictionary<R, W>(
+    source: R,
+    mut destination: W,
+    dictionary: &DecoderDictionary<'_>,
+) -> io::Result<()>
 where
     R: io::Read,
     W: io::Write,
 {
-    let mut decoder = Decoder::new(source)?;
+    let buffer_size = zstd_safe::DCtx::in_size();
+    let source = io::BufReader::with_capacity(buffer_size, source);
+    let mut decoder =
+        read::Decoder::with_prepared_dictionary(source, dictionary)?;
     io::copy(&mut decoder, &mut destination)?;
     Ok(())
 }
 
+/// Decompress from the given source, failing if the output is larger than
+/// `max_bytes`.
+///
+/// This protects against "decompression bombs": small inputs expanding to
+/// huge outputs. See [`copy_decode_limited`].
+///
+/// This re-uses a thread-local context between calls.
+pub fn decode_all_limited<R: io::Read>(
+    source: R,
+    max_bytes: u64,
+) -> io::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    copy_decode_limited(source, &mut result, max_bytes)?;
+    Ok(result)
+}
+
+/// Decompress from the given source, failing if the output is larger than
+/// `max_bytes`.
+///
+/// Decompressed data will be appended to `destination`. Once `max_bytes`
+/// were written, any more output makes this return an error of kind
+/// [`io::ErrorKind::InvalidData`], wrapping an [`OutputLimitExceeded`].
+/// Nothing past the limit is written to `destination`.
+///
+/// This re-uses a thread-local context between calls.
+///
+/// ```rust
+/// let compressed = zstd::encode_all(&[0u8; 1000][..], 1).unwrap();
+///
+/// let error =
+///     zstd::stream::decode_all_limited(&compressed[..], 100).unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+/// ```
+pub fn copy_decode_limited<R, W>(
+    source: R,
+    mut destination: W,
+    max_bytes: u64,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_dctx(|context| {
+        let buffer_size = zstd_safe::DCtx::in_size();
+        let source = io::BufReader::with_capacity(buffer_size, source);
+        let mut decoder = read::Decoder::with_context(source, context);
+        io::copy(
+            &mut io::Read::take(&mut decoder, max_bytes),
+            &mut destination,
+        )?;
+
+        // Any output left goes over the limit.
+        if io::Read::read(&mut decoder, &mut [0u8])? > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                OutputLimitExceeded { limit: max_bytes },
+            ));
+        }
+        Ok(())
+    })
+}
+
+/// Error returned by [`copy_decode_limited`] when the output is too large.
+///
+/// This is wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`],
+/// and can be recovered with [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputLimitExceeded {
+    /// Maximum size of the output, as given by the caller.
+    pub limit: u64,
+}
+
+impl fmt::Display for OutputLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decompressed data exceeds {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for OutputLimitExceeded {}
+
+/// Decompress from the given source, and return the destination.
+///
+/// This is like [`copy_decode`], but gives back `destination`, along with
+/// some statistics. This is convenient with owned writers, like a `Vec<u8>`.
+pub fn copy_decode_into<R, W>(
+    source: R,
+    mut destination: W,
+) -> io::Result<(W, CopyStats)>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_dctx(|context| {
+        let buffer_size = zstd_safe::DCtx::in_size();
+        let source = io::BufReader::with_capacity(buffer_size, source);
+        let mut decoder = read::Decoder::with_context(source, context);
+        let bytes_written = io::copy(&mut decoder, &mut destination)?;
+        let stats = CopyStats {
+            bytes_read: decoder.total_in(),
+            bytes_written,
+        };
+        Ok((destination, stats))
+    })
+}
+
+/// Decompress from the given source, giving each decoded chunk to `sink`.
+///
+/// Chunks are handed over straight from zstd's output buffer, without any
+/// intermediate copy. They are only valid during the call, and can have any
+/// size up to [`zstd_safe::DCtx::out_size`]. An error from `sink` stops the
+/// decompression and is returned as is.
+///
+/// This re-uses a thread-local context between calls.
+pub fn copy_decode_with_sink<R, F>(source: R, mut sink: F) -> io::Result<()>
+where
+    R: io::Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    with_dctx(|context| {
+        let buffer_size = zstd_safe::DCtx::in_size();
+        let mut source = io::BufReader::with_capacity(buffer_size, source);
+        let mut decoder = raw::Decoder::with_context(context);
+        let mut buffer = vec![0u8; zstd_safe::DCtx::out_size()];
+        let mut finished_frame = false;
+
+        loop {
+            let input = source.fill_buf()?;
+            if input.is_empty() {
+                break;
+            }
+            if finished_frame {
+                decoder.reinit()?;
+                finished_frame = false;
+            }
+
+            let mut src = InBuffer::around(input);
+            loop {
+                let mut dst = OutBuffer::around(&mut buffer[..]);
+                let hint = decoder.run(&mut src, &mut dst)?;
+                let written = dst.pos();
+                if written > 0 {
+                    sink(&buffer[..written])?;
+                }
+
+                if hint == 0 {
+                    finished_frame = true;
+                    break;
+                }
+                // A full output buffer may leave more data inside zstd.
+                if src.pos() == src.src.len() && written < buffer.len() {
+                    break;
+                }
+            }
+            let consumed = src.pos();
+            source.consume(consumed);
+        }
+
+        let mut dst = OutBuffer::around(&mut buffer[..]);
+        decoder.finish(&mut dst, finished_frame)?;
+        Ok(())
+    })
+}
+
 /// Compress all data from the given source as if using an `Encoder`.
 ///
 /// Result will be in the zstd frame format.
 ///
-/// A level of `0` uses zstd's default (currently `3`).
+/// A level of `0` uses zstd's default (currently `3`). For more options,
+/// like a checksum or a pledged size, see [`encode_all_with`].
+///
+/// This re-uses a thread-local context between calls.
 pub fn encode_all<R: io::Read>(source: R, level: i32) -> io::Result<Vec<u8>> {
     let mut result = Vec::<u8>::new();
     copy_encode(source, &mut result, level)?;
     Ok(result)
 }
 
+/// Compress all data from the given source into `destination`.
+///
+/// This is like [`encode_all`], but re-uses the allocation of
+/// `destination`, which is cleared first. This saves an allocation per call
+/// when encoding many streams in a row.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+///
+/// This re-uses a thread-local context between calls.
+pub fn encode_all_into<R: io::Read>(
+    source: R,
+    level: i32,
+    destination: &mut Vec<u8>,
+) -> io::Result<()> {
+    destination.clear();
+    copy_encode(source, destination, level)
+}
+
 /// Compress all data from the given source as if using an `Encoder`.
 ///
 /// Compressed data will be appended to `destination`.
 ///
-/// A level of `0` uses zstd's default (currently `3`).
+/// This re-uses a thread-local context between calls.
+///
+/// A level of `0` uses zstd's default (currently `3`). For more options,
+/// see [`copy_encode_with`].
 pub fn copy_encode<R, W>(
-    mut source: R,
+    source: R,
     destination: W,
     level: i32,
 ) -> io::Result<()>
@@ -49,11 +319,461 @@ where
     R: io::Read,
     W: io::Write,
 {
-    let mut encoder = Encoder::new(destination, level)?;
-    io::copy(&mut source, &mut encoder)?;
-    encoder.finish()?;
+    copy_encode_into(source, destination, level)?;
     Ok(())
 }
 
+/// Compress all data from the given source, using a dictionary.
+///
+/// This is like [`encode_all`], with a dictionary (for example, one made
+/// with [`crate::dict::from_samples`]). Decompression will need the same
+/// dictionary, for example with
+/// [`read::Decoder::with_dictionary`](crate::stream::read::Decoder::with_dictionary).
+///
+/// A level of `0` uses zstd's default (currently `3`).
+///
+/// This re-uses a thread-local context between calls.
+pub fn encode_all_with_dictionary<R: io::Read>(
+    source: R,
+    level: i32,
+    dictionary: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut result = Vec::<u8>::new();
+    copy_encode_with_dictionary(source, &mut result, level, dictionary)?;
+    Ok(result)
+}
+
+/// Compress all data from the given source, using a dictionary.
+///
+/// Compressed data will be appended to `destination`.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+///
+/// Like [`copy_encode`], this re-uses a thread-local context between calls.
+pub fn copy_encode_with_dictionary<R, W>(
+    mut source: R,
+    destination: W,
+    level: i32,
+    dictionary: &[u8],
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_cctx(|context| {
+        context
+            .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+        context
+            .load_dictionary(dictionary)
+            .map_err(map_error_code)?;
+        let mut encoder = write::Encoder::with_encoder(
+            destination,
+            raw::Encoder::with_context(context),
+        );
+        io::copy(&mut source, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    })
+}
+
+/// Compress all data from the given source, configured with `options`.
+///
+/// This is like [`encode_all`], with more control over compression.
+///
+/// This re-uses a thread-local context between calls.
+pub fn encode_all_with<R: io::Read>(
+    source: R,
+    options: &Options,
+) -> io::Result<Vec<u8>> {
+    let mut result = Vec::<u8>::new();
+    copy_encode_with(source, &mut result, options)?;
+    Ok(result)
+}
+
+/// Compress all data from the given source, configured with `options`.
+///
+/// Compressed data will be appended to `destination`.
+///
+/// Like [`copy_encode`], this re-uses a thread-local context between calls.
+pub fn copy_encode_with<R, W>(
+    mut source: R,
+    destination: W,
+    options: &Options,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_cctx(|context| {
+        options.configure(context)?;
+        let mut encoder = write::Encoder::with_encoder(
+            destination,
+            raw::Encoder::with_context(context),
+        );
+        io::copy(&mut source, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    })
+}
+
+/// Compress all data from the given source, configured with `options`,
+/// giving up once `deadline` has passed.
+///
+/// The deadline is checked between chunks of input, so a single chunk may
+/// still run past it. When it passes, this returns an error of kind
+/// [`io::ErrorKind::TimedOut`], wrapping a [`DeadlineExceeded`] with the
+/// number of bytes processed so far. The data written to `destination` is
+/// then an incomplete stream.
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+///
+/// let deadline = Instant::now() + Duration::from_secs(10);
+/// let mut compressed = Vec::new();
+/// let stats = zstd::stream::copy_encode_with_deadline(
+///     &b"some data"[..],
+///     &mut compressed,
+///     &zstd::Options::new(),
+///     deadline,
+/// )
+/// .unwrap();
+/// assert_eq!(stats.bytes_read, 9);
+/// ```
+pub fn copy_encode_with_deadline<R, W>(
+    mut source: R,
+    destination: W,
+    options: &Options,
+    deadline: Instant,
+) -> io::Result<CopyStats>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_cctx(|context| {
+        let destination = Counter {
+            writer: destination,
+            count: 0,
+        };
+        options.configure(context)?;
+        let mut encoder = write::Encoder::with_encoder(
+            destination,
+            raw::Encoder::with_context(context),
+        );
+        let mut buffer = vec![0u8; zstd_safe::CCtx::in_size()];
+        let mut bytes_read = 0;
+
+        loop {
+            if Instant::now() >= deadline {
+                let stats = CopyStats {
+                    bytes_read,
+                    bytes_written: encoder.get_ref().count,
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    DeadlineExceeded { stats },
+                ));
+            }
+
+            let len = match source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            io::Write::write_all(&mut encoder, &buffer[..len])?;
+            bytes_read += len as u64;
+        }
+
+        let destination = encoder.finish()?;
+        Ok(CopyStats {
+            bytes_read,
+            bytes_written: destination.count,
+        })
+    })
+}
+
+/// Error returned by [`copy_encode_with_deadline`] when the deadline passed.
+///
+/// This is wrapped in an [`io::Error`] of kind [`io::ErrorKind::TimedOut`],
+/// and can be recovered with [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineExceeded {
+    /// What was processed before the deadline.
+    pub stats: CopyStats,
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "deadline exceeded after reading {} bytes and writing {} bytes",
+            self.stats.bytes_read, self.stats.bytes_written
+        )
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Compress all data from the given source, and return the destination.
+///
+/// This is like [`copy_encode`], but gives back `destination`, along with
+/// some statistics. This is convenient with owned writers, like a `Vec<u8>`.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn copy_encode_into<R, W>(
+    mut source: R,
+    destination: W,
+    level: i32,
+) -> io::Result<(W, CopyStats)>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    with_cctx(|context| {
+        context
+            .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+        let destination = Counter {
+            writer: destination,
+            count: 0,
+        };
+        let mut encoder = write::Encoder::with_encoder(
+            destination,
+            raw::Encoder::with_context(context),
+        );
+        let bytes_read = io::copy(&mut source, &mut encoder)?;
+        let destination = encoder.finish()?;
+        let stats = CopyStats {
+            bytes_read,
+            bytes_written: destination.count,
+        };
+        Ok((destination.writer, stats))
+    })
+}
+
+/// Statistics returned by [`copy_encode_into`] and [`copy_decode_into`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyStats {
+    /// Number of bytes read from the source.
+    ///
+    /// When decoding, this only includes the bytes actually decompressed.
+    pub bytes_read: u64,
+
+    /// Number of bytes written to the destination.
+    pub bytes_written: u64,
+}
+
+/// Counts the bytes written to a writer.
+struct Counter<W> {
+    writer: W,
+    count: u64,
+}
+
+impl<W: io::Write> io::Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::{
+        copy_decode_into, copy_decode_limited, copy_decode_with_dictionary,
+        copy_decode_with_prepared_dictionary, copy_decode_with_sink,
+        copy_encode_into, copy_encode_with_deadline, decode_all_into,
+        decode_all_limited, encode_all_into, encode_all_with_dictionary,
+        DeadlineExceeded, OutputLimitExceeded,
+    };
+
+    #[test]
+    fn test_copy_into() {
+        let text = include_bytes!("../../assets/example.txt");
+
+        let (compressed, stats) =
+            copy_encode_into(&text[..], Vec::new(), 1).unwrap();
+        assert_eq!(stats.bytes_read, text.len() as u64);
+        assert_eq!(stats.bytes_written, compressed.len() as u64);
+
+        // Data is appended to the given writer.
+        let (decompressed, stats) =
+            copy_decode_into(&compressed[..], b"prefix".to_vec()).unwrap();
+        assert_eq!(decompressed, [&b"prefix"[..], text].concat());
+        assert_eq!(stats.bytes_read, compressed.len() as u64);
+        assert_eq!(stats.bytes_written, text.len() as u64);
+    }
+
+    #[test]
+    fn test_copy_decode_with_sink() {
+        let text = include_bytes!("../../assets/example.txt");
+        let compressed = [
+            crate::encode_all(&text[..], 1).unwrap(),
+            crate::encode_all(&text[..], 1).unwrap(),
+        ]
+        .concat();
+
+        let mut decompressed = Vec::new();
+        copy_decode_with_sink(&compressed[..], |chunk| {
+            assert!(!chunk.is_empty());
+            decompressed.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(decompressed, [&text[..], &text[..]].concat());
+
+        // Errors from the sink stop the decompression.
+        let mut calls = 0;
+        let error = copy_decode_with_sink(&compressed[..], |_| {
+            calls += 1;
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "full"))
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "full");
+        assert_eq!(calls, 1);
+
+        let truncated = &compressed[..compressed.len() - 1];
+        assert!(copy_decode_with_sink(truncated, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_copy_encode_with_deadline() {
+        use std::time::{Duration, Instant};
+
+        let text = include_bytes!("../../assets/example.txt");
+        let options = crate::Options::new().level(1);
+
+        let mut compressed = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let stats = copy_encode_with_deadline(
+            &text[..],
+            &mut compressed,
+            &options,
+            deadline,
+        )
+        .unwrap();
+        assert_eq!(stats.bytes_read, text.len() as u64);
+        assert_eq!(stats.bytes_written, compressed.len() as u64);
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+        // The deadline is checked before the first chunk.
+        let error = copy_encode_with_deadline(
+            &text[..],
+            Vec::new(),
+            &options,
+            Instant::now(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+        let exceeded = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<DeadlineExceeded>())
+            .unwrap();
+        assert_eq!(exceeded.stats.bytes_read, 0);
+    }
+    #[test]
+    fn test_decode_all_limited() {
+        let text = include_bytes!("../../assets/example.txt");
+        let mut compressed = crate::encode_all(&text[..], 1).unwrap();
+        compressed.extend(crate::encode_all(&text[..], 1).unwrap());
+        let len = 2 * text.len() as u64;
+
+        let decoded = decode_all_limited(&compressed[..], len).unwrap();
+        assert_eq!(decoded, [&text[..], text].concat());
+
+        let mut destination = Vec::new();
+        let error =
+            copy_decode_limited(&compressed[..], &mut destination, len - 1)
+                .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        let exceeded = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<OutputLimitExceeded>())
+            .unwrap();
+        assert_eq!(exceeded.limit, len - 1);
+        assert_eq!(destination.len() as u64, len - 1);
+
+        assert!(decode_all_limited(&compressed[..], 0).is_err());
+    }
+
+    #[test]
+    fn test_encode_all_with_dictionary() {
+        let text = include_bytes!("../../assets/example.txt");
+        let dictionary = &text[..500];
+
+        let compressed =
+            encode_all_with_dictionary(&text[..], 1, dictionary).unwrap();
+        assert!(
+            compressed.len() < crate::encode_all(&text[..], 1).unwrap().len()
+        );
+
+        let mut decoder = crate::stream::read::Decoder::with_dictionary(
+            &compressed[..],
+            dictionary,
+        )
+        .unwrap();
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+        assert_eq!(decompressed, &text[..]);
+
+        // The dictionary is not kept for the next call.
+        let plain = crate::encode_all(&text[..], 1).unwrap();
+        assert_eq!(crate::decode_all(&plain[..]).unwrap(), &text[..]);
+    }
+
+    #[test]
+    fn test_copy_decode_with_dictionary() {
+        let text = include_bytes!("../../assets/example.txt");
+        let dictionary = &text[..500];
+        let compressed =
+            encode_all_with_dictionary(&text[..], 1, dictionary).unwrap();
+
+        let mut decompressed = Vec::new();
+        copy_decode_with_dictionary(
+            &compressed[..],
+            &mut decompressed,
+            dictionary,
+        )
+        .unwrap();
+        assert_eq!(decompressed, &text[..]);
+
+        let prepared = crate::dict::DecoderDictionary::copy(dictionary);
+        let mut decompressed = Vec::new();
+        copy_decode_with_prepared_dictionary(
+            &compressed[..],
+            &mut decompressed,
+            &prepared,
+        )
+        .unwrap();
+        assert_eq!(decompressed, &text[..]);
+
+        // The dictionary is not kept for the next call.
+        assert!(crate::decode_all(&compressed[..]).is_err());
+    }
+
+    #[test]
+    fn test_all_into() {
+        let text = include_bytes!("../../assets/example.txt");
+
+        let mut compressed = b"garbage".to_vec();
+        encode_all_into(&text[..], 1, &mut compressed).unwrap();
+        assert_eq!(compressed, crate::encode_all(&text[..], 1).unwrap());
+
+        let mut decompressed = b"garbage".to_vec();
+        decode_all_into(&compressed[..], &mut decompressed).unwrap();
+        assert_eq!(decompressed, &text[..]);
+
+        // The allocation is kept.
+        let capacity = decompressed.capacity();
+        decode_all_into(&compressed[..], &mut decompressed).unwrap();
+        assert_eq!(decompressed, &text[..]);
+        assert_eq!(decompressed.capacity(), capacity);
+    }
+}
@@ -0,0 +1,892 @@
+//! Async compression and decompression, using the `futures-io` traits.
+//!
+//! These adapters do not depend on any runtime: they work with any type
+//! implementing [`AsyncBufRead`] or [`AsyncWrite`] from `futures-io`, as
+//! used by `smol`, `async-std` or the `futures` crate.
+//!
+//! * [`Encoder`] and [`Decoder`] implement [`AsyncWrite`] and [`AsyncRead`],
+//!   like [`write::Encoder`] and [`read::Decoder`].
+//! * [`ReadEncoder`] and [`WriteDecoder`] cover the other two directions,
+//!   like [`read::Encoder`] and [`write::Decoder`].
+//! * [`Decoder`] and [`ReadEncoder`] also implement [`AsyncBufRead`], using
+//!   their own output buffer.
+//!
+//! Writers need to be closed (with `AsyncWriteExt::close`) to complete the
+//! stream. This also closes the inner writer; use [`Writer::finish`] to
+//! keep it open.
+//!
+//! [`copy_encode`] and [`copy_decode`] are async equivalents of the
+//! functions from [`stream`](crate::stream).
+//!
+//! `async-std` uses these traits too, so its files and sockets can be used
+//! directly, without spawning blocking tasks.
+//!
+//! The inner reader or writer needs to be [`Unpin`]; wrap it in
+//! `Box::pin` if it is not.
+//!
+//! [`write::Encoder`]: crate::stream::write::Encoder
+//! [`write::Decoder`]: crate::stream::write::Decoder
+//! [`read::Encoder`]: crate::stream::read::Encoder
+//! [`read::Decoder`]: crate::stream::read::Decoder
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+use crate::Options;
+
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(Ok(value)) => value,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+/// An encoder that compresses data written to it, and forwards it to an
+/// [`AsyncWrite`].
+pub type Encoder<'a, W> = Writer<W, raw::Encoder<'a>>;
+
+/// A decoder that decompresses data written to it, and forwards it to an
+/// [`AsyncWrite`].
+pub type WriteDecoder<'a, W> = Writer<W, raw::Decoder<'a>>;
+
+/// A decoder that reads compressed data from an [`AsyncBufRead`].
+pub type Decoder<'a, R> = Reader<R, raw::Decoder<'a>>;
+
+/// An encoder that reads uncompressed data from an [`AsyncBufRead`].
+pub type ReadEncoder<'a, R> = Reader<R, raw::Encoder<'a>>;
+
+/// Implements [`AsyncRead`] and [`AsyncBufRead`] around an [`Operation`].
+///
+/// This is the async equivalent of [`zio::Reader`](super::zio::Reader).
+pub struct Reader<R, D> {
+    reader: R,
+    operation: D,
+
+    state: State,
+    finished_frame: bool,
+
+    // Output of the operation, for `AsyncBufRead`, and the part of it
+    // already consumed.
+    buffer: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+enum State {
+    // Still actively reading from the inner reader.
+    Reading,
+    // We reached EOF from the inner reader, finishing the operation.
+    PastEof,
+    // The operation is finished.
+    Finished,
+}
+
+impl<R: AsyncBufRead + Unpin> Reader<R, raw::Decoder<'static>> {
+    /// Creates a new decoder.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Reader::with_operation(reader, raw::Decoder::new()?))
+    }
+
+    /// Creates a new decoder, using an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_dictionary(reader: R, dictionary: &[u8]) -> io::Result<Self> {
+        Ok(Reader::with_operation(
+            reader,
+            raw::Decoder::with_dictionary(dictionary)?,
+        ))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Reader<R, raw::Encoder<'static>> {
+    /// Creates a new encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(reader: R, level: i32) -> io::Result<Self> {
+        Ok(Reader::with_operation(reader, raw::Encoder::new(level)?))
+    }
+
+    /// Creates a new encoder, configured with the given options.
+    pub fn with_options(reader: R, options: &Options) -> io::Result<Self> {
+        Ok(Reader::with_operation(
+            reader,
+            raw::Encoder::with_options(options)?,
+        ))
+    }
+}
+
+impl<R, D> Reader<R, D> {
+    /// Creates a new `Reader` around the given operation.
+    pub fn with_operation(reader: R, operation: D) -> Self {
+        Reader {
+            reader,
+            operation,
+            state: State::Reading,
+            finished_frame: false,
+            buffer: Vec::new(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this reader is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Gives a mutable reference to the inner operation.
+    pub fn operation_mut(&mut self) -> &mut D {
+        &mut self.operation
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, D> Reader<R, D>
+where
+    R: AsyncBufRead + Unpin,
+    D: Operation,
+{
+    /// Runs the operation, with `buf` as output.
+    fn poll_operation(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        // Start with an empty input, to flush what zstd may still hold.
+        let mut first = true;
+        loop {
+            match self.state {
+                State::Reading => {
+                    let input = if first {
+                        &[][..]
+                    } else {
+                        ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))
+                    };
+
+                    if !first && input.is_empty() {
+                        self.state = State::PastEof;
+                        continue;
+                    }
+                    first = false;
+
+                    // Empty input doesn't start a new frame.
+                    if self.finished_frame && !input.is_empty() {
+                        self.operation.reinit()?;
+                        self.finished_frame = false;
+                    }
+
+                    let mut src = InBuffer::around(input);
+                    let mut dst = OutBuffer::around(buf);
+                    let hint = self.operation.run(&mut src, &mut dst)?;
+                    if hint == 0 {
+                        self.finished_frame = true;
+                    }
+                    let (bytes_read, bytes_written) = (src.pos(), dst.pos());
+
+                    Pin::new(&mut self.reader).consume(bytes_read);
+
+                    if bytes_written > 0 || buf.is_empty() {
+                        return Poll::Ready(Ok(bytes_written));
+                    }
+                }
+                State::PastEof => {
+                    let mut dst = OutBuffer::around(buf);
+                    let hint = self
+                        .operation
+                        .finish(&mut dst, self.finished_frame)?;
+                    if hint == 0 {
+                        self.state = State::Finished;
+                    } else if dst.pos() == 0 && dst.capacity() > 0 {
+                        // Nothing is ready yet, but this is not EOF.
+                        continue;
+                    }
+                    return Poll::Ready(Ok(dst.pos()));
+                }
+                State::Finished => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+}
+
+impl<R, D> AsyncRead for Reader<R, D>
+where
+    R: AsyncBufRead + Unpin,
+    D: Operation + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Output buffered by `poll_fill_buf` comes first.
+        if this.pos < this.filled {
+            let buffered = &this.buffer[this.pos..this.filled];
+            let len = buffered.len().min(buf.len());
+            buf[..len].copy_from_slice(&buffered[..len]);
+            this.pos += len;
+            return Poll::Ready(Ok(len));
+        }
+
+        this.poll_operation(cx, buf)
+    }
+}
+
+impl<R, D> AsyncBufRead for Reader<R, D>
+where
+    R: AsyncBufRead + Unpin,
+    D: Operation + Unpin,
+{
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        if this.pos == this.filled {
+            if this.buffer.is_empty() {
+                // 32KB buffer, like `Writer`.
+                this.buffer = vec![0; 32 * 1024];
+            }
+            let mut buffer = std::mem::take(&mut this.buffer);
+            let result = this.poll_operation(cx, &mut buffer);
+            this.buffer = buffer;
+            this.filled = ready!(result);
+            this.pos = 0;
+        }
+
+        Poll::Ready(Ok(&this.buffer[this.pos..this.filled]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = (this.pos + amt).min(this.filled);
+    }
+}
+
+/// Implements [`AsyncWrite`] around an [`Operation`].
+///
+/// This is the async equivalent of [`zio::Writer`](super::zio::Writer).
+pub struct Writer<W, D> {
+    writer: W,
+    operation: D,
+
+    // Output of the operation, not yet sent to the writer.
+    buffer: Vec<u8>,
+    offset: usize,
+
+    // The operation is finished, only the buffer is left.
+    finished: bool,
+    // The operation just finished a frame (only when decompressing).
+    finished_frame: bool,
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W, raw::Encoder<'static>> {
+    /// Creates a new encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> io::Result<Self> {
+        Ok(Writer::with_operation(writer, raw::Encoder::new(level)?))
+    }
+
+    /// Creates a new encoder, using an existing dictionary.
+    ///
+    /// The dictionary will be needed for decompression.
+    pub fn with_dictionary(
+        writer: W,
+        level: i32,
+        dictionary: &[u8],
+    ) -> io::Result<Self> {
+        Ok(Writer::with_operation(
+            writer,
+            raw::Encoder::with_dictionary(level, dictionary)?,
+        ))
+    }
+
+    /// Creates a new encoder, configured with the given options.
+    pub fn with_options(writer: W, options: &Options) -> io::Result<Self> {
+        Ok(Writer::with_operation(
+            writer,
+            raw::Encoder::with_options(options)?,
+        ))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W, raw::Decoder<'static>> {
+    /// Creates a new decoder.
+    pub fn new(writer: W) -> io::Result<Self> {
+        Ok(Writer::with_operation(writer, raw::Decoder::new()?))
+    }
+
+    /// Creates a new decoder, using an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_dictionary(writer: W, dictionary: &[u8]) -> io::Result<Self> {
+        Ok(Writer::with_operation(
+            writer,
+            raw::Decoder::with_dictionary(dictionary)?,
+        ))
+    }
+}
+
+impl<W, D> Writer<W, D> {
+    /// Creates a new `Writer` around the given operation.
+    pub fn with_operation(writer: W, operation: D) -> Self {
+        Writer {
+            writer,
+            operation,
+            // 32KB buffer, like `zio::Writer`.
+            buffer: Vec::with_capacity(32 * 1024),
+            offset: 0,
+            finished: false,
+            finished_frame: false,
+        }
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this writer is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Gives a mutable reference to the inner operation.
+    pub fn operation_mut(&mut self) -> &mut D {
+        &mut self.operation
+    }
+
+    /// Returns the inner writer.
+    ///
+    /// The output may be incomplete if this writer was not closed first.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W, D> Writer<W, D>
+where
+    W: AsyncWrite + Unpin,
+    D: Operation,
+{
+    /// Attempts to finish the stream, without closing the inner writer.
+    ///
+    /// Nothing can be written after this.
+    pub fn poll_finish(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            ready!(self.poll_write_buffer(cx));
+            if self.finished {
+                return Poll::Ready(Ok(()));
+            }
+            let finished_frame = self.finished_frame;
+            let hint =
+                self.with_buffer(|dst, op| op.finish(dst, finished_frame))?;
+            self.finished = hint == 0;
+        }
+    }
+
+    /// Finishes the stream, without closing the inner writer.
+    ///
+    /// Nothing can be written after this.
+    pub async fn finish(&mut self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_finish(cx)).await
+    }
+
+    /// Sends the content of `self.buffer` to the writer.
+    ///
+    /// Like `zio::Writer`, this retries writes failing with `Interrupted`.
+    fn poll_write_buffer(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.offset < self.buffer.len() {
+            let pending = &self.buffer[self.offset..];
+            match Pin::new(&mut self.writer).poll_write(cx, pending) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "writer will not accept any more data",
+                    )))
+                }
+                Poll::Ready(Ok(n)) => self.offset += n,
+                Poll::Ready(Err(ref e))
+                    if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Runs `f` with an emptied `self.buffer` as output.
+    fn with_buffer<T>(
+        &mut self,
+        f: impl FnOnce(&mut OutBuffer<'_, Vec<u8>>, &mut D) -> T,
+    ) -> T {
+        self.buffer.clear();
+        self.offset = 0;
+        f(
+            &mut OutBuffer::around(&mut self.buffer),
+            &mut self.operation,
+        )
+    }
+}
+
+impl<W, D> AsyncWrite for Writer<W, D>
+where
+    W: AsyncWrite + Unpin,
+    D: Operation + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::Other,
+                "encoder is finished",
+            )));
+        }
+
+        loop {
+            // Nothing can be consumed until the previous output is sent.
+            ready!(this.poll_write_buffer(cx));
+
+            if this.finished_frame {
+                this.operation.reinit()?;
+                this.finished_frame = false;
+            }
+
+            let mut src = InBuffer::around(buf);
+            let hint = this.with_buffer(|dst, op| op.run(&mut src, dst))?;
+            if hint == 0 {
+                this.finished_frame = true;
+            }
+
+            // Once some input is consumed, report it right away.
+            if src.pos() > 0 || buf.is_empty() {
+                return Poll::Ready(Ok(src.pos()));
+            }
+        }
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut flushed = this.finished;
+        loop {
+            ready!(this.poll_write_buffer(cx));
+            if flushed {
+                break;
+            }
+            let hint = this.with_buffer(|dst, op| op.flush(dst))?;
+            flushed = hint == 0;
+        }
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_finish(cx));
+        Pin::new(&mut this.writer).poll_close(cx)
+    }
+}
+
+/// Compresses all data from the given source, and writes it to the
+/// destination.
+///
+/// Like [`stream::copy_encode`](crate::stream::copy_encode), this does not
+/// close or flush the destination.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub async fn copy_encode<R, W>(
+    source: R,
+    destination: W,
+    level: i32,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut encoder = Encoder::new(destination, level)?;
+    copy(source, &mut encoder).await?;
+    encoder.finish().await
+}
+
+/// Decompresses all data from the given source, and writes it to the
+/// destination.
+///
+/// Like [`stream::copy_decode`](crate::stream::copy_decode), this does not
+/// close or flush the destination.
+pub async fn copy_decode<R, W>(source: R, destination: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut decoder = WriteDecoder::new(destination)?;
+    copy(source, &mut decoder).await?;
+    decoder.finish().await
+}
+
+/// Copies everything from `source` to `destination`.
+async fn copy<R, W>(mut source: R, destination: &mut W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; 32 * 1024];
+    loop {
+        let read =
+            poll_fn(|cx| Pin::new(&mut source).poll_read(cx, &mut buffer));
+        let len = match read.await {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let mut pos = 0;
+        while pos < len {
+            let pending = &buffer[pos..len];
+            match poll_fn(|cx| {
+                Pin::new(&mut *destination).poll_write(cx, pending)
+            })
+            .await
+            {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "writer will not accept any more data",
+                    ))
+                }
+                Ok(n) => pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use futures::io::{
+        AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader,
+    };
+    use futures_io::{AsyncRead, AsyncWrite};
+    use partial_io::PartialOp;
+
+    use super::{
+        copy_decode, copy_encode, Decoder, Encoder, ReadEncoder, WriteDecoder,
+    };
+    use crate::stream::tests::{
+        conformance_text, cycle_ops, multi_frame_input, partial_op_patterns,
+    };
+
+    /// Async version of `partial_io`'s wrappers: operations are limited in
+    /// the same way, and `WouldBlock` becomes `Pending`.
+    struct PartialAsync<T> {
+        inner: T,
+        ops: Box<dyn Iterator<Item = PartialOp> + Send>,
+    }
+
+    impl<T: Unpin> PartialAsync<T> {
+        fn new(inner: T, ops: &[PartialOp]) -> Self {
+            PartialAsync {
+                inner,
+                ops: Box::new(cycle_ops(ops)),
+            }
+        }
+
+        /// Returns how many of `len` bytes the next operation can use.
+        fn next_op(
+            &mut self,
+            cx: &mut Context<'_>,
+            len: usize,
+        ) -> Poll<io::Result<usize>> {
+            match self.ops.next() {
+                Some(PartialOp::Limited(n)) => Poll::Ready(Ok(n.min(len))),
+                Some(PartialOp::Err(io::ErrorKind::WouldBlock)) => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                Some(PartialOp::Err(kind)) => Poll::Ready(Err(kind.into())),
+                _ => Poll::Ready(Ok(len)),
+            }
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for PartialAsync<R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            let len = ready!(this.next_op(cx, buf.len()));
+            Pin::new(&mut this.inner).poll_read(cx, &mut buf[..len])
+        }
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for PartialAsync<W> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            let len = ready!(this.next_op(cx, buf.len()));
+            Pin::new(&mut this.inner).poll_write(cx, &buf[..len])
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_close(cx)
+        }
+    }
+
+    /// Retries `f` until it doesn't fail with `Interrupted`.
+    async fn retry<T>(
+        mut f: impl FnMut(&mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> T {
+        loop {
+            match poll_fn(&mut f).await {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result.unwrap(),
+            }
+        }
+    }
+
+    /// Reads until EOF, `size` bytes at a time.
+    async fn read_all_partial<R: AsyncRead + Unpin>(
+        mut reader: R,
+        size: usize,
+    ) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut buffer = vec![0u8; size];
+        loop {
+            let read = |cx: &mut Context<'_>| {
+                Pin::new(&mut reader).poll_read(cx, &mut buffer)
+            };
+            match retry(read).await {
+                0 => return output,
+                n => output.extend_from_slice(&buffer[..n]),
+            }
+        }
+    }
+
+    /// Writes `data` in chunks of `size` bytes, flushing now and then,
+    /// then closes `writer`.
+    ///
+    /// Closing is not retried: only the inner writer fails, and the
+    /// wrappers retry when it is interrupted.
+    async fn write_all_partial<W: AsyncWrite + Unpin>(
+        writer: &mut W,
+        data: &[u8],
+        size: usize,
+    ) {
+        for (i, mut chunk) in data.chunks(size).enumerate() {
+            while !chunk.is_empty() {
+                let write = |cx: &mut Context<'_>| {
+                    Pin::new(&mut *writer).poll_write(cx, chunk)
+                };
+                chunk = &chunk[retry(write).await..];
+            }
+            if i % 100 == 0 {
+                retry(|cx| Pin::new(&mut *writer).poll_flush(cx)).await;
+            }
+        }
+        writer.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_partial_io() {
+        let text = conformance_text();
+        let (compressed, content) = multi_frame_input();
+
+        for ops in partial_op_patterns() {
+            for &size in &[1, 100, 100_000] {
+                block_on(async {
+                    let writer = PartialAsync::new(Vec::new(), &ops);
+                    let mut encoder = Encoder::new(writer, 1).unwrap();
+                    write_all_partial(&mut encoder, &text, size).await;
+                    let output = encoder.into_inner().inner;
+                    assert_eq!(crate::decode_all(&output[..]).unwrap(), text);
+
+                    let reader = PartialAsync::new(&text[..], &ops);
+                    let reader = BufReader::with_capacity(100, reader);
+                    let encoder = ReadEncoder::new(reader, 1).unwrap();
+                    let output = read_all_partial(encoder, size).await;
+                    assert_eq!(crate::decode_all(&output[..]).unwrap(), text);
+
+                    let reader = PartialAsync::new(&compressed[..], &ops);
+                    let reader = BufReader::with_capacity(100, reader);
+                    let decoder = Decoder::new(reader).unwrap();
+                    let output = read_all_partial(decoder, size).await;
+                    assert_eq!(output, content);
+
+                    let writer = PartialAsync::new(Vec::new(), &ops);
+                    let mut decoder = WriteDecoder::new(writer).unwrap();
+                    write_all_partial(&mut decoder, &compressed, size).await;
+                    assert_eq!(decoder.into_inner().inner, content);
+                });
+            }
+
+            block_on(async {
+                let source = PartialAsync::new(&text[..], &ops);
+                let mut destination = PartialAsync::new(Vec::new(), &ops);
+                copy_encode(source, &mut destination, 1).await.unwrap();
+                let output = destination.inner;
+                assert_eq!(crate::decode_all(&output[..]).unwrap(), text);
+
+                let source = PartialAsync::new(&compressed[..], &ops);
+                let mut destination = PartialAsync::new(Vec::new(), &ops);
+                copy_decode(source, &mut destination).await.unwrap();
+                assert_eq!(destination.inner, content);
+            });
+        }
+    }
+
+    #[test]
+    fn test_futures_cycle() {
+        let text = include_bytes!("../../assets/example.txt");
+
+        block_on(async {
+            let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+            for chunk in text.chunks(100) {
+                encoder.write_all(chunk).await.unwrap();
+            }
+            encoder.close().await.unwrap();
+            let compressed = encoder.into_inner();
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let mut decoder = Decoder::new(&compressed[..]).unwrap();
+            let mut output = Vec::new();
+            decoder.read_to_end(&mut output).await.unwrap();
+            assert_eq!(output, text);
+
+            let mut encoder = ReadEncoder::new(&text[..], 1).unwrap();
+            let mut compressed = Vec::new();
+            encoder.read_to_end(&mut compressed).await.unwrap();
+
+            // Concatenated frames are decompressed in a single stream.
+            let compressed = [&compressed[..], &compressed[..]].concat();
+            let mut decoder = WriteDecoder::new(Vec::new()).unwrap();
+            decoder.write_all(&compressed).await.unwrap();
+            decoder.close().await.unwrap();
+            assert_eq!(decoder.into_inner(), [&text[..], &text[..]].concat());
+
+            // Incomplete frames are reported when closing.
+            let mut decoder = WriteDecoder::new(Vec::new()).unwrap();
+            decoder.write_all(&compressed[..10]).await.unwrap();
+            assert!(decoder.close().await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_futures_buf_read() {
+        let text = include_bytes!("../../assets/example.txt");
+        let compressed = crate::encode_all(&text[..], 1).unwrap();
+
+        block_on(async {
+            let mut decoder = Decoder::new(&compressed[..]).unwrap();
+
+            // Buffered and plain reads can be mixed.
+            let mut output = Vec::new();
+            decoder.read_until(b'\n', &mut output).await.unwrap();
+            assert_eq!(output.last(), Some(&b'\n'));
+
+            let mut chunk = [0u8; 10];
+            decoder.read_exact(&mut chunk).await.unwrap();
+            output.extend_from_slice(&chunk);
+
+            let available = decoder.fill_buf().await.unwrap().len();
+            assert!(available > 0);
+            let data = decoder.fill_buf().await.unwrap()[..3].to_vec();
+            output.extend_from_slice(&data);
+            decoder.consume_unpin(3);
+
+            decoder.read_to_end(&mut output).await.unwrap();
+            assert_eq!(output, text);
+            assert!(decoder.fill_buf().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_futures_copy() {
+        let text = include_bytes!("../../assets/example.txt");
+
+        block_on(async {
+            let mut compressed = Vec::new();
+            copy_encode(&text[..], &mut compressed, 1).await.unwrap();
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let mut decompressed = Vec::new();
+            copy_decode(&compressed[..], &mut decompressed)
+                .await
+                .unwrap();
+            assert_eq!(decompressed, text);
+
+            let truncated = &compressed[..compressed.len() - 1];
+            assert!(copy_decode(truncated, Vec::new()).await.is_err());
+
+            // `finish` leaves the writer open for more data.
+            let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+            encoder.write_all(text).await.unwrap();
+            encoder.finish().await.unwrap();
+            assert!(encoder.write_all(b"more").await.is_err());
+            let mut compressed = encoder.into_inner();
+            compressed.write_all(b"trailer").await.unwrap();
+            assert!(compressed.ends_with(b"trailer"));
+        });
+    }
+}
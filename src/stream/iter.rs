@@ -0,0 +1,200 @@
+//! Compress or decompress iterators of chunks.
+//!
+//! Each chunk is compressed into its own frame, and each frame decompressed
+//! into its own chunk. A single context is used for the entire iterator, so
+//! this fits well in iterator-based pipelines:
+//!
+//! ```rust
+//! use zstd::stream::iter::{compress_chunks, decompress_chunks};
+//!
+//! let options = zstd::Options::new().level(1);
+//! let chunks = vec![b"first".to_vec(), b"second".to_vec()];
+//!
+//! let frames = compress_chunks(chunks.clone(), &options);
+//! let decompressed: Vec<Vec<u8>> = decompress_chunks(frames.map(Result::unwrap), &options)
+//!     .collect::<std::io::Result<_>>()
+//!     .unwrap();
+//! assert_eq!(decompressed, chunks);
+//! ```
+use std::io::{self, Read};
+
+use crate::bulk::Compressor;
+use crate::map_error_code;
+use crate::stream::read;
+use crate::Options;
+
+/// Compresses each chunk from `chunks` into its own frame.
+///
+/// If `options` are invalid, the first item is an error, and the iterator
+/// ends there.
+pub fn compress_chunks<I>(
+    chunks: I,
+    options: &Options,
+) -> CompressChunks<I::IntoIter>
+where
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let (compressor, error) = match Compressor::with_options(options) {
+        Ok(compressor) => (Some(compressor), None),
+        Err(error) => (None, Some(error)),
+    };
+    CompressChunks {
+        chunks: chunks.into_iter(),
+        compressor,
+        error,
+    }
+}
+
+/// Decompresses each frame from `frames` into its own chunk.
+///
+/// `options` should be the ones used for compression: they give the
+/// dictionary, and the decoder parameters to match. Each item can actually
+/// contain several frames, which are then decompressed into a single chunk.
+///
+/// If `options` are invalid, the first item is an error, and the iterator
+/// ends there.
+pub fn decompress_chunks<I>(
+    frames: I,
+    options: &Options,
+) -> DecompressChunks<I::IntoIter>
+where
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let (context, error) = match decompression_context(options) {
+        Ok(context) => (Some(context), None),
+        Err(error) => (None, Some(error)),
+    };
+    DecompressChunks {
+        frames: frames.into_iter(),
+        context,
+        error,
+    }
+}
+
+fn decompression_context(
+    options: &Options,
+) -> io::Result<zstd_safe::DCtx<'static>> {
+    let mut context = zstd_safe::DCtx::create();
+    context
+        .load_dictionary(&options.dictionary)
+        .map_err(map_error_code)?;
+    for parameter in options.decoder_parameters() {
+        context.set_parameter(parameter).map_err(map_error_code)?;
+    }
+    Ok(context)
+}
+
+/// Iterator of compressed frames, returned by [`compress_chunks`].
+pub struct CompressChunks<I> {
+    chunks: I,
+    compressor: Option<Compressor<'static>>,
+    error: Option<io::Error>,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Iterator for CompressChunks<I> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        let compressor = self.compressor.as_mut()?;
+        let chunk = self.chunks.next()?;
+        Some(compressor.compress(&chunk))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match (&self.compressor, &self.error) {
+            (Some(_), _) => self.chunks.size_hint(),
+            (None, Some(_)) => (1, Some(1)),
+            (None, None) => (0, Some(0)),
+        }
+    }
+}
+
+/// Iterator of decompressed chunks, returned by [`decompress_chunks`].
+pub struct DecompressChunks<I> {
+    frames: I,
+    context: Option<zstd_safe::DCtx<'static>>,
+    error: Option<io::Error>,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> DecompressChunks<I> {
+    fn decompress(
+        context: &mut zstd_safe::DCtx<'static>,
+        frame: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        // Start afresh, in case the previous frame was invalid.
+        context
+            .reset(zstd_safe::ResetDirective::SessionOnly)
+            .map_err(map_error_code)?;
+
+        let capacity = zstd_safe::get_frame_content_size(frame)
+            .ok()
+            .flatten()
+            .unwrap_or(0);
+        let mut chunk = Vec::with_capacity(capacity.min(1 << 20) as usize);
+        read::Decoder::with_context(frame, context).read_to_end(&mut chunk)?;
+        Ok(chunk)
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Iterator for DecompressChunks<I> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        let context = self.context.as_mut()?;
+        let frame = self.frames.next()?;
+        Some(Self::decompress(context, &frame))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match (&self.context, &self.error) {
+            (Some(_), _) => self.frames.size_hint(),
+            (None, Some(_)) => (1, Some(1)),
+            (None, None) => (0, Some(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_chunks, decompress_chunks};
+    use crate::Options;
+
+    #[test]
+    fn test_chunks() {
+        let text = include_bytes!("../../assets/example.txt");
+        let chunks: Vec<Vec<u8>> =
+            text.chunks(100).map(|chunk| chunk.to_vec()).collect();
+        let options = Options::new().level(3).dictionary(&text[..200]);
+
+        let frames: Vec<Vec<u8>> = compress_chunks(chunks.clone(), &options)
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+        assert_eq!(frames.len(), chunks.len());
+
+        let mut frames = frames.into_iter();
+        let first = frames.next().unwrap();
+        let mut decompressed = decompress_chunks(
+            frames.chain([b"invalid".to_vec(), first]),
+            &options,
+        );
+        for chunk in &chunks[1..] {
+            assert_eq!(&decompressed.next().unwrap().unwrap(), chunk);
+        }
+        // Errors don't affect the next frames.
+        assert!(decompressed.next().unwrap().is_err());
+        assert_eq!(decompressed.next().unwrap().unwrap(), chunks[0]);
+        assert!(decompressed.next().is_none());
+
+        let mut invalid = compress_chunks(chunks, &Options::new().level(1000));
+        assert!(invalid.next().unwrap().is_err());
+        assert!(invalid.next().is_none());
+    }
+}
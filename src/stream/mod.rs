@@ -5,11 +5,26 @@
 //!
 //! This module provides both `Read` and `Write` interfaces to compressing and
 //! decompressing.
+//!
+//! Encoders and decoders are `Send` and `Sync` (as long as the wrapped reader
+//! or writer is), but each one handles a single stream. To share a
+//! configuration between threads, see [`SyncEncoder`] and [`SyncDecoder`].
 
+mod checksum;
+pub mod chunk;
+pub mod iter;
 pub mod read;
+pub mod record;
+pub mod seekable;
+mod shared;
+mod size_hint;
 pub mod write;
 
 mod functions;
+#[cfg(feature = "futures-io")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "futures-io")))]
+pub mod futures;
+pub mod pool;
 pub mod zio;
 
 #[cfg(test)]
@@ -17,9 +32,65 @@ mod tests;
 
 pub mod raw;
 
-pub use self::functions::{copy_decode, copy_encode, decode_all, encode_all};
-pub use self::read::Decoder;
-pub use self::write::{AutoFinishEncoder, Encoder};
+pub use self::checksum::{ChecksumPolicy, ContentChecksum};
+pub use self::functions::{
+    copy_decode, copy_decode_into, copy_decode_limited,
+    copy_decode_with_dictionary, copy_decode_with_prepared_dictionary,
+    copy_decode_with_sink, copy_encode, copy_encode_into, copy_encode_with,
+    copy_encode_with_deadline, copy_encode_with_dictionary, decode_all,
+    decode_all_into, decode_all_limited, encode_all, encode_all_into,
+    encode_all_with, encode_all_with_dictionary, CopyStats, DeadlineExceeded,
+    OutputLimitExceeded,
+};
+pub use self::pool::BufferPool;
+pub use self::read::{Decoder, DecoderBuilder};
+pub use self::shared::{SyncDecoder, SyncEncoder};
+pub use self::size_hint::SizeHint;
+pub use self::write::{AutoFinishEncoder, Encoder, EncoderBuilder};
+
+/// A decoder reading from any boxed `BufRead`.
+///
+/// This has no type parameter, so it can be stored in a struct without
+/// making it generic over the source. See [`Decoder::new_dyn`].
+pub type DynDecoder = read::Decoder<'static, Box<dyn std::io::BufRead + Send>>;
+
+/// An encoder writing to any boxed `Write`.
+///
+/// This has no type parameter, so it can be stored in a struct without
+/// making it generic over the destination. See [`Encoder::new_dyn`].
+pub type DynEncoder = write::Encoder<'static, Box<dyn std::io::Write + Send>>;
+
+/// Maximum number of workers picked by `multithread_auto`.
+#[cfg(feature = "zstdmt")]
+const AUTO_WORKERS_MAX: u32 = 16;
+
+/// Smallest job size picked by `multithread_auto_for_size`.
+///
+/// zstd doesn't allow jobs smaller than 512KB, and small jobs hurt the
+/// compression ratio.
+#[cfg(feature = "zstdmt")]
+const AUTO_JOB_SIZE_MIN: u64 = 1 << 20;
+
+/// Largest job size picked by `multithread_auto_for_size`.
+#[cfg(feature = "zstdmt")]
+const AUTO_JOB_SIZE_MAX: u64 = 256 << 20;
+
+/// Returns the number of workers to use on this machine.
+#[cfg(feature = "zstdmt")]
+pub(crate) fn auto_workers() -> u32 {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    cores.saturating_sub(1).clamp(1, AUTO_WORKERS_MAX as usize) as u32
+}
+
+/// Returns the number of workers and the job size for `src_size` bytes.
+#[cfg(feature = "zstdmt")]
+pub(crate) fn auto_workers_for_size(src_size: u64) -> (u32, u32) {
+    let jobs = (src_size / AUTO_JOB_SIZE_MIN).max(1);
+    let n_workers = u64::from(auto_workers()).min(jobs);
+    let job_size = ((src_size + n_workers - 1) / n_workers)
+        .clamp(AUTO_JOB_SIZE_MIN, AUTO_JOB_SIZE_MAX);
+    (n_workers as u32, job_size as u32)
+}
 
 #[doc(hidden)]
 #[macro_export]
@@ -72,6 +143,182 @@ macro_rules! decoder_common {
             self.$readwrite.operation_mut().set_parameter(parameter)
         }
 
+        /// Returns the number of frames fully decoded so far.
+        ///
+        /// Skippable frames are included.
+        pub fn frames_decoded(&self) -> u64 {
+            self.$readwrite.operation().frames_decoded()
+        }
+
+        /// Returns the number of compressed bytes consumed so far.
+        pub fn total_in(&self) -> u64 {
+            self.$readwrite.operation().total_in()
+        }
+
+        /// Returns the number of decompressed bytes produced so far.
+        ///
+        /// Some of this data may still be buffered internally.
+        pub fn total_out(&self) -> u64 {
+            self.$readwrite.operation().total_out()
+        }
+
+        /// Returns counters of the work done on the zstd context so far.
+        ///
+        /// See [`ContextMetrics`](crate::stream::raw::ContextMetrics).
+        pub fn metrics(&self) -> $crate::stream::raw::ContextMetrics {
+            self.$readwrite.operation().metrics()
+        }
+
+        /// Sets whether errors should include the position of the failure.
+        ///
+        /// When enabled, decoding errors wrap a
+        /// [`DecodeError`](crate::stream::raw::DecodeError). This is
+        /// disabled by default.
+        pub fn set_error_context(&mut self, error_context: bool) {
+            self.$readwrite
+                .operation_mut()
+                .set_error_context(error_context)
+        }
+
+        /// Sets a callback to run each time a frame is fully decoded.
+        ///
+        /// The callback is given the decompressed offset of the end of the
+        /// frame, which is also the start of the next one.
+        pub fn on_frame_end<F>(&mut self, callback: F)
+        where
+            F: FnMut(u64) + Send + 'a,
+        {
+            self.$readwrite.operation_mut().on_frame_end(callback)
+        }
+
+        /// Sets whether to keep track of the frame ends, for
+        /// `next_frame_end`. Disabled by default.
+        pub fn track_frame_ends(&mut self, track: bool) {
+            self.$readwrite.operation_mut().track_frame_ends(track)
+        }
+
+        /// Takes the oldest frame end not taken yet, if any.
+        ///
+        /// Frame ends are given as decompressed offsets, and only recorded
+        /// once enabled with `track_frame_ends`. This lets a consumer find
+        /// where each frame starts in the output (for example to extract
+        /// entries of an archive written with one frame per entry), while
+        /// frames are still decoded as a single stream. See
+        /// [`raw::Decoder::next_frame_end`](crate::stream::raw::Decoder::next_frame_end).
+        pub fn next_frame_end(&mut self) -> Option<u64> {
+            self.$readwrite.operation_mut().next_frame_end()
+        }
+
+        /// Returns the dictionary ID declared by the current frame, if any.
+        ///
+        /// This is available even if decoding fails because the dictionary
+        /// is missing or wrong. See
+        /// [`raw::Decoder::current_frame_dict_id`](crate::stream::raw::Decoder::current_frame_dict_id).
+        #[doc(alias = "dict_id")]
+        pub fn current_frame_dict_id(&self) -> Option<u32> {
+            self.$readwrite.operation().current_frame_dict_id()
+        }
+
+        /// Returns the decompressed size declared by the current frame, if
+        /// any.
+        ///
+        /// See [`raw::Decoder::content_size`](crate::stream::raw::Decoder::content_size).
+        pub fn content_size(&self) -> Option<u64> {
+            self.$readwrite.operation().content_size()
+        }
+
+        /// Returns the window size required by the current frame, if known.
+        ///
+        /// This is available even if decoding fails because the window is
+        /// too large. See
+        /// [`raw::Decoder::required_window_size`](crate::stream::raw::Decoder::required_window_size).
+        pub fn required_window_size(&self) -> Option<u64> {
+            self.$readwrite.operation().required_window_size()
+        }
+
+        /// Sets how frame checksums are checked.
+        ///
+        /// With [`ChecksumPolicy::FramePlusVerifyOnDecode`], frames without
+        /// checksum make decoding fail. Checksums are always verified when
+        /// present.
+        ///
+        /// [`ChecksumPolicy::FramePlusVerifyOnDecode`]: crate::stream::ChecksumPolicy::FramePlusVerifyOnDecode
+        pub fn set_checksum_policy(
+            &mut self,
+            policy: $crate::stream::ChecksumPolicy,
+        ) {
+            self.$readwrite.operation_mut().set_checksum_policy(policy)
+        }
+
+        /// Sets whether skippable frames are allowed in the input.
+        ///
+        /// When `true` (the default), skippable frames (like seek tables or
+        /// metadata) are consumed without producing any output. When
+        /// `false`, they make decoding fail.
+        pub fn ignore_skippable_frames(&mut self, ignore: bool) {
+            self.$readwrite
+                .operation_mut()
+                .ignore_skippable_frames(ignore)
+        }
+
+        /// Sets the maximum number of frames to decode, `None` (the
+        /// default) for no limit.
+        ///
+        /// See [`raw::Decoder::set_max_frames`](crate::stream::raw::Decoder::set_max_frames).
+        pub fn set_max_frames(&mut self, max: Option<u64>) {
+            self.$readwrite.operation_mut().set_max_frames(max)
+        }
+
+        /// Sets the maximum total size of skippable frames, `None` for no
+        /// limit.
+        ///
+        /// See [`raw::Decoder::set_max_skippable_bytes`](crate::stream::raw::Decoder::set_max_skippable_bytes).
+        pub fn set_max_skippable_bytes(&mut self, max: Option<u64>) {
+            self.$readwrite.operation_mut().set_max_skippable_bytes(max)
+        }
+
+        /// Sets whether to check the content size declared by frames.
+        ///
+        /// See [`raw::Decoder::enforce_content_size`](crate::stream::raw::Decoder::enforce_content_size).
+        pub fn enforce_content_size(&mut self, enforce: bool) {
+            self.$readwrite
+                .operation_mut()
+                .enforce_content_size(enforce)
+        }
+
+        /// Sets a callback to run on each skippable frame.
+        ///
+        /// The callback is given the magic variant (`0..=15`) and the content
+        /// of the frame. This limits skippable frames to 16 MiB by default
+        /// (see [`raw::Decoder::on_skippable_frame`](crate::stream::raw::Decoder::on_skippable_frame)).
+        pub fn on_skippable_frame<F>(&mut self, callback: F)
+        where
+            F: FnMut(u8, &[u8]) + Send + 'a,
+        {
+            self.$readwrite.operation_mut().on_skippable_frame(callback)
+        }
+
+        /// Sets whether to check dictionary IDs recorded before frames.
+        ///
+        /// See
+        /// [`raw::Decoder::set_dict_id_check`](crate::stream::raw::Decoder::set_dict_id_check).
+        pub fn set_dict_id_check(&mut self, check: bool) -> io::Result<()> {
+            self.$readwrite.operation_mut().set_dict_id_check(check)
+        }
+
+        /// Sets a checksum to verify after each frame.
+        ///
+        /// See
+        /// [`raw::Decoder::set_content_checksum`](crate::stream::raw::Decoder::set_content_checksum).
+        pub fn set_content_checksum(
+            &mut self,
+            checksum: Option<$crate::stream::ContentChecksum>,
+        ) {
+            self.$readwrite
+                .operation_mut()
+                .set_content_checksum(checksum)
+        }
+
         $crate::decoder_parameters!();
     };
 }
@@ -81,6 +328,20 @@ macro_rules! decoder_common {
 /// Parameter-setters for the encoder. Relies on a `set_parameter` method.
 macro_rules! encoder_parameters {
     () => {
+        /// Applies all the parameters of the given preset.
+        ///
+        /// Parameters set before are overwritten, but parameters can still
+        /// be changed afterwards.
+        pub fn apply_preset(
+            &mut self,
+            preset: &$crate::preset::Preset,
+        ) -> io::Result<()> {
+            for parameter in preset.parameters() {
+                self.set_parameter(parameter)?;
+            }
+            Ok(())
+        }
+
         /// Controls whether zstd should include a content checksum at the end
         /// of each frame.
         pub fn include_checksum(
@@ -92,6 +353,16 @@ macro_rules! encoder_parameters {
             ))
         }
 
+        /// Sets whether to write checksums, following the given policy.
+        ///
+        /// See [`ChecksumPolicy`](crate::stream::ChecksumPolicy).
+        pub fn set_checksum_policy(
+            &mut self,
+            policy: $crate::stream::ChecksumPolicy,
+        ) -> io::Result<()> {
+            self.include_checksum(policy.writes_checksum())
+        }
+
         /// Enables multithreaded compression
         ///
         /// * If `n_workers == 0` (default), then multithreaded will be
@@ -102,6 +373,9 @@ macro_rules! encoder_parameters {
         /// So even `n_workers = 1` may increase performance by separating
         /// IO and compression.
         ///
+        /// Flushing or finishing the stream waits for the workers, so all the
+        /// data given so far is included in the output.
+        ///
         /// Note: This is only available if the `zstdmt` cargo feature is activated.
         #[cfg(feature = "zstdmt")]
         #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zstdmt")))]
@@ -109,6 +383,42 @@ macro_rules! encoder_parameters {
             self.set_parameter(zstd_safe::CParameter::NbWorkers(n_workers))
         }
 
+        /// Enables multithreaded compression, with as many workers as
+        /// available cores.
+        ///
+        /// One core is left for the thread feeding the encoder, and at most
+        /// 16 workers are used. Returns the number of workers.
+        ///
+        /// Note: This is only available if the `zstdmt` cargo feature is activated.
+        #[cfg(feature = "zstdmt")]
+        #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zstdmt")))]
+        pub fn multithread_auto(&mut self) -> io::Result<u32> {
+            let n_workers = $crate::stream::auto_workers();
+            self.multithread(n_workers)?;
+            Ok(n_workers)
+        }
+
+        /// Enables multithreaded compression, adapted to an input of
+        /// `src_size` bytes.
+        ///
+        /// Like [`multithread_auto`](Self::multithread_auto), but small
+        /// inputs use fewer workers, and the input is split in jobs so that
+        /// all workers get some. Returns the number of workers.
+        ///
+        /// Note: This is only available if the `zstdmt` cargo feature is activated.
+        #[cfg(feature = "zstdmt")]
+        #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zstdmt")))]
+        pub fn multithread_auto_for_size(
+            &mut self,
+            src_size: u64,
+        ) -> io::Result<u32> {
+            let (n_workers, job_size) =
+                $crate::stream::auto_workers_for_size(src_size);
+            self.multithread(n_workers)?;
+            self.set_parameter(zstd_safe::CParameter::JobSize(job_size))?;
+            Ok(n_workers)
+        }
+
         /// Enables or disables storing of the dict id.
         ///
         /// Defaults to true. If false, the behaviour of decoding with a wrong
@@ -145,20 +455,50 @@ macro_rules! encoder_parameters {
             )
         }
 
-        /// Sets the target size for compressed blocks.
+        /// Sets or removes the target size for compressed blocks.
         ///
-        /// A lower block size may result in slightly lower speed (~2%) and compression ratio
-        /// (~0.1%), but may decrease end-to-end latency in low-bandwidth environments (time to
-        /// first decompressed byte).
-        ///
-        /// No value, or a value of zero, results in no contraint for the block sizes.
+        /// `Some(size)` is the same as
+        /// [`set_target_block_size(size)`](Self::set_target_block_size),
+        /// and fails for sizes out of range. No value, or a value of zero,
+        /// results in no contraint for the block sizes.
         pub fn set_target_cblock_size(
             &mut self,
             target_size: Option<u32>,
         ) -> io::Result<()> {
-            self.set_parameter(zstd_safe::CParameter::TargetCBlockSize(
-                target_size.unwrap_or(0),
-            ))
+            match target_size {
+                None | Some(0) => self
+                    .set_parameter(zstd_safe::CParameter::TargetCBlockSize(0)),
+                Some(size) => self.set_target_block_size(size),
+            }
+        }
+
+        /// Sets the target size for compressed blocks, in bytes.
+        ///
+        /// A decoder can only start returning the content of a block once it
+        /// received all of it. Smaller blocks therefore lower the time to the
+        /// first decompressed byte over slow links, which matters for
+        /// streaming or interactive uses. This costs some compression ratio
+        /// and speed, more so for smaller targets.
+        ///
+        /// The size must be between [`TARGET_BLOCK_SIZE_MIN`] and
+        /// [`TARGET_BLOCK_SIZE_MAX`]. Blocks can still end up slightly
+        /// larger, when zstd cannot split them further.
+        ///
+        /// Use [`set_target_cblock_size(None)`](Self::set_target_cblock_size)
+        /// to remove the target.
+        ///
+        /// [`TARGET_BLOCK_SIZE_MIN`]: crate::frame::TARGET_BLOCK_SIZE_MIN
+        /// [`TARGET_BLOCK_SIZE_MAX`]: crate::frame::TARGET_BLOCK_SIZE_MAX
+        pub fn set_target_block_size(&mut self, size: u32) -> io::Result<()> {
+            let range = $crate::frame::TARGET_BLOCK_SIZE_MIN
+                ..=$crate::frame::TARGET_BLOCK_SIZE_MAX;
+            if !range.contains(&size) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "target block size out of range",
+                ));
+            }
+            self.set_parameter(zstd_safe::CParameter::TargetCBlockSize(size))
         }
 
         /// Sets the maximum back-reference distance.
@@ -170,6 +510,37 @@ macro_rules! encoder_parameters {
             self.set_parameter(zstd_safe::CParameter::WindowLog(log_distance))
         }
 
+        #[cfg(feature = "experimental")]
+        #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+        /// Sets whether to use the row-based match finder.
+        ///
+        /// Only available with the `experimental` feature.
+        ///
+        /// This mostly affects the speed of mid levels (roughly 5 to 12).
+        /// By default, zstd decides based on the other parameters.
+        pub fn use_row_match_finder(
+            &mut self,
+            mode: zstd_safe::ParamSwitch,
+        ) -> io::Result<()> {
+            self.set_parameter(zstd_safe::CParameter::UseRowMatchFinder(mode))
+        }
+
+        #[cfg(feature = "experimental")]
+        #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+        /// Sets how a prepared dictionary is used by the context.
+        ///
+        /// Only available with the `experimental` feature.
+        ///
+        /// Attaching the dictionary is cheap for small inputs, while copying
+        /// or reloading it is slower to start but faster on large inputs.
+        /// By default, zstd decides based on the input size.
+        pub fn dict_attach_pref(
+            &mut self,
+            pref: zstd_safe::DictAttachPref,
+        ) -> io::Result<()> {
+            self.set_parameter(zstd_safe::CParameter::ForceAttachDict(pref))
+        }
+
         #[cfg(feature = "experimental")]
         #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
         /// Enables or disable the magic bytes at the beginning of each frame.
@@ -222,6 +593,35 @@ macro_rules! encoder_common {
             self.$readwrite.operation_mut().set_pledged_src_size(size)
         }
 
+        /// Sets whether to record the dictionary ID before each frame.
+        ///
+        /// This makes `include_dictid(false)` safe to use with decoders
+        /// checking the ID. See
+        /// [`raw::Encoder::set_dict_id_guard`](crate::stream::raw::Encoder::set_dict_id_guard).
+        pub fn set_dict_id_guard(&mut self, guard: bool) -> io::Result<()> {
+            self.$readwrite.operation_mut().set_dict_id_guard(guard)
+        }
+
+        /// Sets a checksum of the content to write after each frame.
+        ///
+        /// See
+        /// [`raw::Encoder::set_content_checksum`](crate::stream::raw::Encoder::set_content_checksum).
+        pub fn set_content_checksum(
+            &mut self,
+            checksum: Option<$crate::stream::ContentChecksum>,
+        ) {
+            self.$readwrite
+                .operation_mut()
+                .set_content_checksum(checksum)
+        }
+
+        /// Returns counters of the work done on the zstd context so far.
+        ///
+        /// See [`ContextMetrics`](crate::stream::raw::ContextMetrics).
+        pub fn metrics(&self) -> $crate::stream::raw::ContextMetrics {
+            self.$readwrite.operation().metrics()
+        }
+
         $crate::encoder_parameters!();
     };
 }
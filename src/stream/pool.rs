@@ -0,0 +1,223 @@
+//! Re-use buffers across streams.
+//!
+//! Each stream allocates a buffer of a few hundred KB (for the input of
+//! readers, or the output of writers). Servers creating many short-lived
+//! streams can share a [`BufferPool`] to avoid these repeated allocations.
+use std::io::{self, BufRead, Read};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A pool of buffers shared by streams.
+///
+/// Cloning the pool is cheap, and gives another handle to the same buffers.
+///
+/// Buffers are taken from the pool when a stream is created, and given back
+/// when it is dropped.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    buffers: Vec<Vec<u8>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates a new pool, keeping up to `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        BufferPool {
+            inner: Arc::new(Mutex::new(Inner {
+                buffers: Vec::new(),
+                max_buffers,
+            })),
+        }
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn available(&self) -> usize {
+        self.lock().buffers.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // Buffers are only pushed or popped, so they are always consistent.
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes an empty buffer with at least the given capacity.
+    pub(crate) fn get(&self, capacity: usize) -> PooledBuffer {
+        let mut inner = self.lock();
+        let buffer = inner
+            .buffers
+            .iter()
+            .position(|buffer| buffer.capacity() >= capacity)
+            .map(|i| inner.buffers.swap_remove(i));
+        drop(inner);
+
+        PooledBuffer {
+            buffer: buffer.unwrap_or_else(|| Vec::with_capacity(capacity)),
+            pool: Some(self.clone()),
+        }
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        let mut inner = self.lock();
+        if inner.buffers.len() < inner.max_buffers {
+            buffer.clear();
+            inner.buffers.push(buffer);
+        }
+    }
+}
+
+/// A buffer which goes back to its pool (if any) when dropped.
+pub(crate) struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Option<BufferPool>,
+}
+
+impl From<Vec<u8>> for PooledBuffer {
+    fn from(buffer: Vec<u8>) -> Self {
+        PooledBuffer { buffer, pool: None }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+/// A buffered reader using a buffer from a [`BufferPool`].
+///
+/// This is like `std::io::BufReader`, and is used by streams created with
+/// a pool.
+pub struct PooledBufReader<R> {
+    reader: R,
+    buffer: PooledBuffer,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R> PooledBufReader<R> {
+    /// Creates a new reader, taking a buffer of `capacity` bytes from `pool`.
+    pub fn new(reader: R, capacity: usize, pool: &BufferPool) -> Self {
+        PooledBufReader {
+            reader,
+            buffer: pool.get(capacity),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    ///
+    /// Buffered data is lost, and the buffer goes back to the pool.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for PooledBufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Skip the buffer for large reads.
+        if self.pos == self.filled && buf.len() >= self.buffer.capacity() {
+            return self.reader.read(buf);
+        }
+
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for PooledBufReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            // Only the first fill has to initialize the buffer.
+            let capacity = self.buffer.capacity();
+            self.buffer.resize(capacity, 0);
+
+            self.pos = 0;
+            self.filled = 0;
+            self.filled = self.reader.read(&mut self.buffer)?;
+        }
+        Ok(&self.buffer[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPool;
+    use crate::stream::{read, write};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_pool() {
+        let input = include_bytes!("../../assets/example.txt");
+        let pool = BufferPool::new(2);
+
+        for _ in 0..3 {
+            let mut encoder =
+                write::Encoder::new_with_pool(Vec::new(), 1, &pool).unwrap();
+            encoder.write_all(input).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let mut decoder =
+                read::Decoder::new_with_pool(&compressed[..], &pool).unwrap();
+            let mut output = Vec::new();
+            decoder.read_to_end(&mut output).unwrap();
+            assert_eq!(&output[..], &input[..]);
+            assert_eq!(pool.available(), 1);
+
+            drop(decoder);
+            assert_eq!(pool.available(), 2);
+        }
+
+        let mut encoder =
+            read::Encoder::new_with_pool(&input[..], 1, &pool).unwrap();
+        let mut compressed = Vec::new();
+        encoder.read_to_end(&mut compressed).unwrap();
+
+        let mut decoder =
+            write::Decoder::new_with_pool(Vec::new(), &pool).unwrap();
+        decoder.write_all(&compressed).unwrap();
+        decoder.flush().unwrap();
+        assert_eq!(&decoder.into_inner()[..], &input[..]);
+        assert_eq!(pool.available(), 1);
+
+        drop(encoder);
+        assert_eq!(pool.available(), 2);
+    }
+}
@@ -4,12 +4,27 @@
 //! of data using buffers.
 //!
 //! They are mostly thin wrappers around `zstd_safe::{DCtx, CCtx}`.
+//!
+//! Both implement [`Operation`], the backend trait used by the wrappers in
+//! [`zio`](super::zio). Other implementations can be used with the same
+//! wrappers, for example [`NoOp`] or [`Passthrough`] to mock compression in
+//! tests.
+use std::collections::VecDeque;
+use std::fmt;
 use std::io;
+use std::num::NonZeroU32;
+use std::sync::Arc;
 
-pub use zstd_safe::{CParameter, DParameter, InBuffer, OutBuffer, WriteBuf};
+pub use zstd_safe::{
+    CParameter, DParameter, EndDirective, InBuffer, OutBuffer, WriteBuf,
+};
 
-use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::dict::{DecoderDictionary, DictRegistry, EncoderDictionary};
+use crate::frame::{self, FrameHeader, SkippableHeader};
 use crate::map_error_code;
+use crate::stream::checksum::ContentHasher;
+use crate::stream::{ChecksumPolicy, ContentChecksum};
+use crate::Options;
 
 /// Represents an abstract compression/decompression operation.
 ///
@@ -36,18 +51,44 @@ pub trait Operation {
         input: &[u8],
         output: &mut [u8],
     ) -> io::Result<Status> {
+        self.run_on_buffers_detailed(input, output)
+            .map(|detailed| detailed.status)
+    }
+
+    /// Performs a single step of this operation.
+    ///
+    /// Like `Operation::run_on_buffers`, but also reports whether a frame
+    /// just ended and whether the entire input was consumed.
+    fn run_on_buffers_detailed(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<DetailedStatus> {
         let mut input = InBuffer::around(input);
         let mut output = OutBuffer::around(output);
 
         let remaining = self.run(&mut input, &mut output)?;
 
-        Ok(Status {
-            remaining,
-            bytes_read: input.pos(),
-            bytes_written: output.pos(),
+        Ok(DetailedStatus {
+            status: Status {
+                remaining,
+                bytes_read: input.pos(),
+                bytes_written: output.pos(),
+            },
+            frame_ended: self.ends_frame(remaining),
+            input_exhausted: input.pos() == input.src.len(),
         })
     }
 
+    /// Returns `true` if `run` returning `hint` means a frame just ended.
+    ///
+    /// Defaults to `false`, for operations which never end frames while
+    /// running (like encoders, which only end frames in `finish`).
+    fn ends_frame(&self, hint: usize) -> bool {
+        let _ = hint;
+        false
+    }
+
     /// Flushes any internal buffer, if any.
     ///
     /// Returns the number of bytes still in the buffer.
@@ -60,6 +101,23 @@ pub trait Operation {
         Ok(0)
     }
 
+    /// Flushes any internal buffer, and describes the result.
+    ///
+    /// This is a wrapper around `Operation::flush`, reporting how much was
+    /// written along with what is left, so the next output buffer can be
+    /// sized from [`FlushStatus::remaining`].
+    fn flush_status<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+    ) -> io::Result<FlushStatus> {
+        let output_pos = output.pos();
+        let remaining = self.flush(output)?;
+        Ok(FlushStatus {
+            remaining,
+            bytes_written: output.pos() - output_pos,
+        })
+    }
+
     /// Prepares the operation for a new frame.
     ///
     /// This is hopefully cheaper than creating a new operation.
@@ -82,9 +140,72 @@ pub trait Operation {
         let _ = finished_frame;
         Ok(0)
     }
+
+    /// Finishes the operation, and describes the result.
+    ///
+    /// This is a wrapper around `Operation::finish`, like
+    /// `Operation::flush_status`. Keep calling this method until the status
+    /// is done.
+    fn finish_status<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+        finished_frame: bool,
+    ) -> io::Result<FlushStatus> {
+        let output_pos = output.pos();
+        let remaining = self.finish(output, finished_frame)?;
+        Ok(FlushStatus {
+            remaining,
+            bytes_written: output.pos() - output_pos,
+        })
+    }
+
+    /// Finishes the operation, with the end of the input still available.
+    ///
+    /// This is used when the input buffer is stable (see
+    /// [`crate::stream::zio::Reader::set_stable_input`]): all of the input is
+    /// given on each call, with `input.pos()` where the previous call stopped.
+    ///
+    /// The default implementation runs the operation on the remaining input,
+    /// then calls `Operation::finish`.
+    ///
+    /// Keep calling this method until it returns `Ok(0)`.
+    fn finish_with_input<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+        finished_frame: bool,
+    ) -> io::Result<usize> {
+        if input.pos() < input.src.len() {
+            let hint = self.run(input, output)?;
+            return Ok(hint.max(1));
+        }
+        self.finish(output, finished_frame)
+    }
+
+    /// Finishes the operation, writing any footer if necessary.
+    ///
+    /// This is a convenience wrapper around `Operation::finish` if you don't
+    /// want to deal with `OutBuffer`. The operation is completely finished
+    /// once the returned status has `remaining = 0`.
+    fn finish_on_buffers(
+        &mut self,
+        output: &mut [u8],
+        finished_frame: bool,
+    ) -> io::Result<Status> {
+        let mut output = OutBuffer::around(output);
+
+        let remaining = self.finish(&mut output, finished_frame)?;
+
+        Ok(Status {
+            remaining,
+            bytes_read: 0,
+            bytes_written: output.pos(),
+        })
+    }
 }
 
 /// Dummy operation that just copies its input to the output.
+#[derive(Debug)]
 pub struct NoOp;
 
 impl Operation for NoOp {
@@ -93,28 +214,308 @@ impl Operation for NoOp {
         input: &mut InBuffer<'_>,
         output: &mut OutBuffer<'_, C>,
     ) -> io::Result<usize> {
-        // Skip the prelude
-        let src = &input.src[input.pos..];
-        // Safe because `output.pos() <= output.capacity()`.
-        let output_pos = output.pos();
-        let dst = unsafe { output.as_mut_ptr().add(output_pos) };
+        copy_input(input, output);
+        Ok(0)
+    }
+}
+
+/// Copies as much as possible from `input` to `output`.
+///
+/// Returns the number of bytes copied.
+fn copy_input<C: WriteBuf + ?Sized>(
+    input: &mut InBuffer<'_>,
+    output: &mut OutBuffer<'_, C>,
+) -> usize {
+    // Skip the prelude
+    let src = &input.src[input.pos..];
+    // Safe because `output.pos() <= output.capacity()`.
+    let output_pos = output.pos();
+    let dst = unsafe { output.as_mut_ptr().add(output_pos) };
+
+    // Ignore anything past the end
+    let len = usize::min(src.len(), output.capacity() - output_pos);
+    let src = &src[..len];
+
+    // Safe because:
+    // * `len` is less than either of the two lengths
+    // * `src` and `dst` do not overlap because we have `&mut` to each.
+    unsafe { std::ptr::copy_nonoverlapping(src.as_ptr(), dst, len) };
+    input.set_pos(input.pos() + len);
+    unsafe { output.set_pos(output_pos + len) };
 
-        // Ignore anything past the end
-        let len = usize::min(src.len(), output.capacity() - output_pos);
-        let src = &src[..len];
+    len
+}
 
-        // Safe because:
-        // * `len` is less than either of the two lengths
-        // * `src` and `dst` do not overlap because we have `&mut` to each.
-        unsafe { std::ptr::copy_nonoverlapping(src.as_ptr(), dst, len) };
-        input.set_pos(input.pos() + len);
-        unsafe { output.set_pos(output_pos + len) };
+/// Mock encoder that copies its input to the output, counting frames.
+///
+/// This can replace an `Encoder` in tests, for example in a
+/// [`zio::Writer`](super::zio::Writer), to check how the stream is driven
+/// without actually compressing anything.
+///
+/// A frame ends each time the operation is finished.
+#[derive(Debug, Default)]
+pub struct Passthrough {
+    frames: u64,
+    bytes: u64,
+    finished: bool,
+}
 
+impl Passthrough {
+    /// Creates a new passthrough operation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of frames finished so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of bytes copied so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Operation for Passthrough {
+    fn run<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+    ) -> io::Result<usize> {
+        let copied = copy_input(input, output);
+        if copied > 0 {
+            self.finished = false;
+            self.bytes += copied as u64;
+        }
+        // Like encoders, never report the end of a frame while running.
+        Ok(1)
+    }
+
+    fn reinit(&mut self) -> io::Result<()> {
+        self.finished = false;
+        Ok(())
+    }
+
+    fn finish<C: WriteBuf + ?Sized>(
+        &mut self,
+        _output: &mut OutBuffer<'_, C>,
+        _finished_frame: bool,
+    ) -> io::Result<usize> {
+        // Calling `finish` again without new data doesn't start a new frame.
+        if !self.finished {
+            self.finished = true;
+            self.frames += 1;
+        }
         Ok(0)
     }
 }
 
+/// Object-safe version of `WriteBuf`.
+///
+/// This is implemented for every sized `WriteBuf`, and `dyn DynWriteBuf`
+/// itself implements `WriteBuf`, so it can be used to erase the type of an
+/// output buffer.
+///
+/// # Safety
+///
+/// The same as for `WriteBuf`: `as_mut_ptr()` must point to at least
+/// `capacity()` bytes of writable memory, which zstd writes to, and
+/// `as_slice()` must only cover initialized data.
+pub unsafe trait DynWriteBuf {
+    /// Returns the valid data part of this container.
+    fn as_slice(&self) -> &[u8];
+
+    /// Returns the full capacity of this container.
+    fn capacity(&self) -> usize;
+
+    /// Returns a pointer to the start of the data.
+    fn as_mut_ptr(&mut self) -> *mut u8;
+
+    /// Indicates that the first `n` bytes of the container have been written.
+    ///
+    /// # Safety
+    ///
+    /// This should only be called if the `n` first bytes of this buffer have
+    /// actually been initialized.
+    unsafe fn filled_until(&mut self, n: usize);
+}
+
+// Safe because `C` upholds the same contract as a `WriteBuf`.
+unsafe impl<C: WriteBuf> DynWriteBuf for C {
+    fn as_slice(&self) -> &[u8] {
+        WriteBuf::as_slice(self)
+    }
+
+    fn capacity(&self) -> usize {
+        WriteBuf::capacity(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        WriteBuf::as_mut_ptr(self)
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        WriteBuf::filled_until(self, n)
+    }
+}
+
+unsafe impl WriteBuf for dyn DynWriteBuf + '_ {
+    fn as_slice(&self) -> &[u8] {
+        DynWriteBuf::as_slice(self)
+    }
+
+    fn capacity(&self) -> usize {
+        DynWriteBuf::capacity(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        DynWriteBuf::as_mut_ptr(self)
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        DynWriteBuf::filled_until(self, n)
+    }
+}
+
+/// Object-safe version of `Operation`.
+///
+/// This is implemented for every `Operation`, and `Box<dyn DynOperation>`
+/// itself implements `Operation`. This lets the operation be picked at
+/// runtime, for example to get a `zio::Writer<W, Box<dyn DynOperation>>`.
+pub trait DynOperation {
+    /// Performs a single step of this operation.
+    ///
+    /// See `Operation::run`.
+    fn run_dyn(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+    ) -> io::Result<usize>;
+
+    /// Flushes any internal buffer, if any.
+    ///
+    /// See `Operation::flush`.
+    fn flush_dyn(
+        &mut self,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+    ) -> io::Result<usize>;
+
+    /// Prepares the operation for a new frame.
+    ///
+    /// See `Operation::reinit`.
+    fn reinit_dyn(&mut self) -> io::Result<()>;
+
+    /// Returns `true` if `run_dyn` returning `hint` means a frame just ended.
+    ///
+    /// See `Operation::ends_frame`.
+    fn ends_frame_dyn(&self, hint: usize) -> bool;
+
+    /// Finishes the operation, writing any footer if necessary.
+    ///
+    /// See `Operation::finish`.
+    fn finish_dyn(
+        &mut self,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+        finished_frame: bool,
+    ) -> io::Result<usize>;
+}
+
+impl<T: Operation> DynOperation for T {
+    fn run_dyn(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+    ) -> io::Result<usize> {
+        self.run(input, output)
+    }
+
+    fn flush_dyn(
+        &mut self,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+    ) -> io::Result<usize> {
+        self.flush(output)
+    }
+
+    fn reinit_dyn(&mut self) -> io::Result<()> {
+        self.reinit()
+    }
+
+    fn ends_frame_dyn(&self, hint: usize) -> bool {
+        self.ends_frame(hint)
+    }
+
+    fn finish_dyn(
+        &mut self,
+        output: &mut OutBuffer<'_, dyn DynWriteBuf + '_>,
+        finished_frame: bool,
+    ) -> io::Result<usize> {
+        self.finish(output, finished_frame)
+    }
+}
+
+/// Runs `f` on a type-erased view of `output`.
+///
+/// Anything written through the view moves the position of `output`.
+fn with_dyn_output<C, F>(
+    output: &mut OutBuffer<'_, C>,
+    f: F,
+) -> io::Result<usize>
+where
+    C: WriteBuf + ?Sized,
+    F: FnOnce(&mut OutBuffer<'_, dyn DynWriteBuf + '_>) -> io::Result<usize>,
+{
+    let pos = output.pos();
+    let output: &mut dyn DynWriteBuf = output;
+    f(&mut OutBuffer::around_pos(output, pos))
+}
+
+macro_rules! impl_boxed_operation {
+    ($($bounds:tt)*) => {
+        impl<'a> Operation for Box<dyn DynOperation $($bounds)* + 'a> {
+            fn run<C: WriteBuf + ?Sized>(
+                &mut self,
+                input: &mut InBuffer<'_>,
+                output: &mut OutBuffer<'_, C>,
+            ) -> io::Result<usize> {
+                with_dyn_output(output, |output| {
+                    (**self).run_dyn(input, output)
+                })
+            }
+
+            fn flush<C: WriteBuf + ?Sized>(
+                &mut self,
+                output: &mut OutBuffer<'_, C>,
+            ) -> io::Result<usize> {
+                with_dyn_output(output, |output| (**self).flush_dyn(output))
+            }
+
+            fn reinit(&mut self) -> io::Result<()> {
+                (**self).reinit_dyn()
+            }
+
+            fn ends_frame(&self, hint: usize) -> bool {
+                (**self).ends_frame_dyn(hint)
+            }
+
+            fn finish<C: WriteBuf + ?Sized>(
+                &mut self,
+                output: &mut OutBuffer<'_, C>,
+                finished_frame: bool,
+            ) -> io::Result<usize> {
+                with_dyn_output(output, |output| {
+                    (**self).finish_dyn(output, finished_frame)
+                })
+            }
+        }
+    };
+}
+
+impl_boxed_operation!();
+impl_boxed_operation!(+ Send);
+
 /// Describes the result of an operation.
+#[derive(Debug)]
 pub struct Status {
     /// Number of bytes expected for next input.
     ///
@@ -130,11 +531,133 @@ pub struct Status {
     pub bytes_written: usize,
 }
 
+/// Describes the result of an operation, with details about frames.
+///
+/// See [`Operation::run_on_buffers_detailed`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DetailedStatus {
+    /// The status also returned by [`Operation::run_on_buffers`].
+    pub status: Status,
+
+    /// `true` if a frame was just completed.
+    ///
+    /// Unlike `remaining = 0`, this is only set by operations which actually
+    /// produce or consume frames.
+    pub frame_ended: bool,
+
+    /// `true` if the entire input was consumed.
+    pub input_exhausted: bool,
+}
+
+/// Describes the result of flushing or finishing an operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlushStatus {
+    /// Number of bytes still buffered, as reported by zstd.
+    ///
+    /// This is a minimum: an output buffer of this size may not be enough
+    /// to complete the operation, for example if zstd still has input to
+    /// compress. `0` means everything was written.
+    pub remaining: usize,
+
+    /// Number of bytes written to the output.
+    pub bytes_written: usize,
+}
+
+impl FlushStatus {
+    /// Returns `true` if nothing is left to write.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
 /// An in-memory decoder for streams of data.
 pub struct Decoder<'a> {
     context: MaybeOwnedDCtx<'a>,
+    // Dictionary referenced by the context, kept alive here.
+    dictionary: Option<Arc<DecoderDictionary<'static>>>,
+
+    frames_decoded: u64,
+    total_in: u64,
+    total_out: u64,
+    metrics: ContextMetrics,
+    error_context: bool,
+
+    // `true` if the last call to `run` ended a frame, and no input was
+    // consumed since.
+    frame_ended: bool,
+    on_frame_end: Option<Callback<dyn FnMut(u64) + Send + 'a>>,
+    // Ends of frames not taken yet, if tracked.
+    frame_ends: Option<VecDeque<u64>>,
+    // Limits on the input, if any.
+    max_frames: Option<u64>,
+    // Limit on the size of skippable frames, if set explicitly.
+    max_skippable_bytes: Option<Option<u64>>,
+    // Size of the skippable frames read so far, headers included.
+    skippable_bytes: u64,
+    enforce_content_size: bool,
+    // Value of `total_out` when the current frame started.
+    frame_start_out: u64,
+
+    dict_resolver: Option<Callback<DictResolver<'a>>>,
+    // Dictionaries given by the resolver so far.
+    dictionaries: Vec<(u32, Arc<DecoderDictionary<'static>>)>,
+    // ID of the dictionary currently used by the context.
+    dict_id: Option<u32>,
+    // `true` if the next input starts a frame. Only used when reading
+    // headers (see `reads_headers`).
+    in_header: bool,
+    // Frame header being read. Only used when reading headers.
+    header: Vec<u8>,
+
+    // Header of the current frame, if it could be parsed.
+    current_header: Option<FrameHeader>,
+    // Window log limit, as set through `set_parameter`.
+    window_log_max: u32,
+    require_checksum: bool,
+    ignore_skippable: bool,
+    on_skippable: Option<Callback<SkippableCallback<'a>>>,
+    // Skippable frame being read, when handled here instead of by zstd.
+    skippable: Option<SkippableFrame>,
+    // ID of the dictionary given when creating the decoder, `0` if none.
+    given_dict_id: u32,
+    // `true` if that dictionary has no ID (raw content or prefix).
+    raw_dictionary: bool,
+    check_dict_id: bool,
+    // Checksum to verify in content checksum trailers, if any.
+    content_checksum: Option<ContentChecksum>,
+    // Checksum of the content of the current frame.
+    content_hasher: Option<ContentHasher>,
+    // Trailer expected after the last frame.
+    expected_trailer: Option<Vec<u8>>,
+}
+
+/// zstd's default limit for the window log, when decoding.
+const WINDOW_LOG_MAX_DEFAULT: u32 = 27;
+
+/// Default limit for the size of skippable frames given to callbacks.
+const MAX_SKIPPABLE_BYTES_DEFAULT: u64 = 16 << 20;
+
+type SkippableCallback<'a> = dyn FnMut(u8, &[u8]) + Send + 'a;
+
+/// State of a skippable frame being read.
+struct SkippableFrame {
+    magic_variant: u8,
+    remaining: u32,
+    payload: Vec<u8>,
 }
 
+type DictResolver<'a> =
+    dyn FnMut(u32) -> Option<Arc<DecoderDictionary<'static>>> + Send + 'a;
+
+/// Callback given by the user.
+struct Callback<F: ?Sized>(Box<F>);
+
+// Callbacks are only ever used through `&mut self`: sharing a `&Decoder`
+// between threads gives no access to them, so they don't need to be `Sync`.
+unsafe impl<F: ?Sized + Send> Sync for Callback<F> {}
+
 impl Decoder<'static> {
     /// Creates a new decoder.
     pub fn new() -> io::Result<Self> {
@@ -148,17 +671,83 @@ impl Decoder<'static> {
         context
             .load_dictionary(dictionary)
             .map_err(map_error_code)?;
-        Ok(Decoder {
-            context: MaybeOwnedDCtx::Owned(context),
-        })
+        let mut decoder =
+            Decoder::from_context(MaybeOwnedDCtx::Owned(context));
+        decoder.given_dict_id = zstd_safe::get_dict_id_from_dict(dictionary)
+            .map_or(0, NonZeroU32::get);
+        decoder.raw_dictionary =
+            decoder.given_dict_id == 0 && !dictionary.is_empty();
+        decoder.metrics = ContextMetrics::setup(!dictionary.is_empty());
+        Ok(decoder)
+    }
+
+    /// Creates a new decoder, using a shared `DecoderDictionary`.
+    ///
+    /// Unlike [`Decoder::with_prepared_dictionary`], the decoder keeps the
+    /// dictionary alive, so it doesn't borrow anything.
+    pub fn with_shared_dictionary(
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let mut decoder = Decoder::with_prepared_dictionary(&dictionary)?;
+        decoder.dictionary = Some(dictionary);
+        Ok(decoder)
+    }
+
+    /// Creates a new decoder, selecting dictionaries from `registry`.
+    ///
+    /// Each frame is decoded with the dictionary registered under the ID
+    /// declared by its header. Frames requiring a dictionary missing from
+    /// the registry fail to decode, and frames without dictionary ID are
+    /// decoded without dictionary.
+    pub fn with_dictionaries(registry: DictRegistry) -> io::Result<Self> {
+        let mut decoder = Decoder::new()?;
+        decoder.set_dict_resolver(move |dict_id| {
+            registry.get(dict_id).map(Arc::clone)
+        });
+        Ok(decoder)
     }
 }
 
 impl<'a> Decoder<'a> {
     /// Creates a new decoder which employs the provided context for deserialization.
     pub fn with_context(context: &'a mut zstd_safe::DCtx<'static>) -> Self {
-        Self {
-            context: MaybeOwnedDCtx::Borrowed(context),
+        Self::from_context(MaybeOwnedDCtx::Borrowed(context))
+    }
+
+    fn from_context(context: MaybeOwnedDCtx<'a>) -> Self {
+        Decoder {
+            context,
+            dictionary: None,
+            frames_decoded: 0,
+            total_in: 0,
+            total_out: 0,
+            metrics: ContextMetrics::default(),
+            error_context: false,
+            frame_ended: false,
+            on_frame_end: None,
+            frame_ends: None,
+            max_frames: None,
+            max_skippable_bytes: None,
+            skippable_bytes: 0,
+            enforce_content_size: false,
+            frame_start_out: 0,
+            dict_resolver: None,
+            dictionaries: Vec::new(),
+            dict_id: None,
+            in_header: true,
+            header: Vec::new(),
+            current_header: None,
+            window_log_max: WINDOW_LOG_MAX_DEFAULT,
+            require_checksum: false,
+            ignore_skippable: true,
+            on_skippable: None,
+            skippable: None,
+            given_dict_id: 0,
+            raw_dictionary: false,
+            check_dict_id: false,
+            content_checksum: None,
+            content_hasher: None,
+            expected_trailer: None,
         }
     }
 
@@ -173,45 +762,750 @@ impl<'a> Decoder<'a> {
         context
             .ref_ddict(dictionary.as_ddict())
             .map_err(map_error_code)?;
-        Ok(Decoder {
-            context: MaybeOwnedDCtx::Owned(context),
+        let mut decoder =
+            Decoder::from_context(MaybeOwnedDCtx::Owned(context));
+        decoder.given_dict_id = dictionary
+            .as_ddict()
+            .get_dict_id()
+            .map_or(0, NonZeroU32::get);
+        decoder.raw_dictionary = decoder.given_dict_id == 0;
+        decoder.metrics = ContextMetrics::setup(true);
+        Ok(decoder)
+    }
+
+    /// Creates a new decoder, using a ref prefix
+    pub fn with_ref_prefix<'b>(ref_prefix: &'b [u8]) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let mut context = zstd_safe::DCtx::create();
+        context.ref_prefix(ref_prefix).map_err(map_error_code)?;
+        let mut decoder =
+            Decoder::from_context(MaybeOwnedDCtx::Owned(context));
+        decoder.raw_dictionary = !ref_prefix.is_empty();
+        decoder.metrics = ContextMetrics::setup(true);
+        Ok(decoder)
+    }
+
+    /// Sets a decompression parameter for this decoder.
+    pub fn set_parameter(&mut self, parameter: DParameter) -> io::Result<()> {
+        match &mut self.context {
+            MaybeOwnedDCtx::Owned(x) => x.set_parameter(parameter),
+            MaybeOwnedDCtx::Borrowed(x) => x.set_parameter(parameter),
+        }
+        .map_err(map_error_code)?;
+
+        if let DParameter::WindowLogMax(window_log_max) = parameter {
+            // `0` restores the default.
+            self.window_log_max = match window_log_max {
+                0 => WINDOW_LOG_MAX_DEFAULT,
+                window_log_max => window_log_max,
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the number of frames fully decoded so far.
+    ///
+    /// Skippable frames are included.
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded
+    }
+
+    /// Returns the number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Returns the number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Returns counters of the work done on the zstd context so far.
+    pub fn metrics(&self) -> ContextMetrics {
+        self.metrics
+    }
+
+    /// Sets whether errors should include the position of the failure.
+    ///
+    /// When enabled, decoding errors wrap a [`DecodeError`] with the
+    /// compressed and decompressed offsets, and the frame index. Otherwise
+    /// (the default), they only contain the error from zstd.
+    pub fn set_error_context(&mut self, error_context: bool) {
+        self.error_context = error_context;
+    }
+
+    /// Creates an error at the current position.
+    fn error(&self, kind: io::ErrorKind, message: &'static str) -> io::Error {
+        if !self.error_context {
+            return io::Error::new(kind, message);
+        }
+
+        io::Error::new(kind, self.decode_error(message))
+    }
+
+    /// Creates the context of an error at the current position.
+    fn decode_error(&self, message: &'static str) -> DecodeError {
+        DecodeError {
+            message,
+            compressed_offset: self.total_in,
+            decompressed_offset: self.total_out,
+            frame_index: self.frames_decoded,
+            zstd_error: None,
+            window: None,
+        }
+    }
+
+    /// Creates an error from zstd's error code.
+    ///
+    /// Errors about the window size also include the size required by the
+    /// frame, and the limit of this decoder.
+    fn zstd_error(&self, code: usize) -> io::Error {
+        if !self.error_context {
+            return map_error_code(code);
+        }
+
+        let zstd_error = zstd_safe::Error::from_code(code);
+        let mut error = self.decode_error(zstd_safe::get_error_name(code));
+        error.zstd_error = Some(zstd_error);
+        if zstd_error.kind()
+            == zstd_safe::ErrorKind::FrameParameterWindowTooLarge
+        {
+            error.window =
+                Some((self.required_window_size(), self.window_log_max));
+        }
+        io::Error::new(io::ErrorKind::Other, error)
+    }
+
+    /// Sets a callback to run each time a frame is fully decoded.
+    ///
+    /// The callback is given the decompressed offset of the end of the
+    /// frame, which is also the start of the next one.
+    pub fn on_frame_end<F>(&mut self, callback: F)
+    where
+        F: FnMut(u64) + Send + 'a,
+    {
+        self.on_frame_end = Some(Callback(Box::new(callback)));
+    }
+
+    /// Sets whether to keep track of the frame ends, for
+    /// [`Decoder::next_frame_end`]. Disabled by default.
+    ///
+    /// Disabling it drops any frame end not taken yet.
+    pub fn track_frame_ends(&mut self, track: bool) {
+        if !track {
+            self.frame_ends = None;
+        } else if self.frame_ends.is_none() {
+            self.frame_ends = Some(VecDeque::new());
+        }
+    }
+
+    /// Takes the oldest frame end not taken yet, if any.
+    ///
+    /// This is the pull version of [`Decoder::on_frame_end`]: each frame end
+    /// is given as the decompressed offset of the end of the frame, which is
+    /// also the start of the next one. Skippable frames are included, and
+    /// end at the same offset they start.
+    ///
+    /// Frame ends are only recorded once enabled with
+    /// [`Decoder::track_frame_ends`].
+    pub fn next_frame_end(&mut self) -> Option<u64> {
+        self.frame_ends.as_mut()?.pop_front()
+    }
+
+    /// Sets a callback to find dictionaries on demand.
+    ///
+    /// When a frame header declares a dictionary ID the decoder doesn't
+    /// have yet, the callback is given this ID, and can return the
+    /// corresponding dictionary (for example after fetching it from a
+    /// dictionary service). Each ID is only resolved once: dictionaries are
+    /// kept by the decoder for later frames.
+    ///
+    /// If the callback returns `None`, decoding the frame will fail.
+    ///
+    /// Frames without dictionary ID are decoded without dictionary.
+    pub fn set_dict_resolver<F>(&mut self, resolver: F)
+    where
+        F: FnMut(u32) -> Option<Arc<DecoderDictionary<'static>>> + Send + 'a,
+    {
+        self.dict_resolver = Some(Callback(Box::new(resolver)));
+    }
+
+    /// Returns the dictionary ID declared by the current frame, if any.
+    ///
+    /// This is read from the frame header, so it is available even if
+    /// decoding fails because the dictionary is missing or wrong: it can be
+    /// used to build errors like "frame requires dictionary 4660".
+    ///
+    /// After the end of a frame, this is still the ID of this frame, until
+    /// the next one starts. Frames can use a dictionary without declaring
+    /// its ID: like `ZSTD_getDictID_fromFrame`, this can't tell them apart
+    /// from frames without dictionary.
+    #[doc(alias = "dict_id")]
+    pub fn current_frame_dict_id(&self) -> Option<u32> {
+        self.current_header.and_then(|header| header.dict_id)
+    }
+
+    /// Returns the decompressed size declared by the current frame, if any.
+    ///
+    /// This is read from the frame header, before any content is decoded:
+    /// it can be used to pre-size an output buffer or to report progress.
+    /// Frames don't always declare their size, for example when they were
+    /// compressed by a streaming encoder without a pledged size.
+    ///
+    /// Like [`Decoder::current_frame_dict_id`], this stays available after
+    /// the end of a frame, until the next one starts.
+    pub fn content_size(&self) -> Option<u64> {
+        self.current_header.and_then(|header| header.content_size)
+    }
+
+    /// Returns the window size required by the current frame, if known.
+    ///
+    /// This is read from the frame header, so it is available even if
+    /// decoding fails because the window is too large: compare it with
+    /// `1 << window_log_max` to decide whether to raise the limit.
+    ///
+    /// Like [`Decoder::current_frame_dict_id`], this stays available after
+    /// the end of a frame, until the next one starts.
+    pub fn required_window_size(&self) -> Option<u64> {
+        self.current_header.and_then(|header| {
+            // Single-segment frames use their content as window.
+            header.window_size.or(header.content_size)
         })
     }
 
-    /// Creates a new decoder, using a ref prefix
-    pub fn with_ref_prefix<'b>(ref_prefix: &'b [u8]) -> io::Result<Self>
-    where
-        'b: 'a,
-    {
-        let mut context = zstd_safe::DCtx::create();
-        context.ref_prefix(ref_prefix).map_err(map_error_code)?;
-        Ok(Decoder {
-            context: MaybeOwnedDCtx::Owned(context),
-        })
+    /// Sets how frame checksums are checked.
+    ///
+    /// With [`ChecksumPolicy::FramePlusVerifyOnDecode`], frames without
+    /// checksum make decoding fail. Checksums are always verified when
+    /// present.
+    ///
+    /// [`ChecksumPolicy::FramePlusVerifyOnDecode`]: crate::stream::ChecksumPolicy::FramePlusVerifyOnDecode
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.require_checksum = policy.requires_checksum();
+    }
+
+    /// Sets whether skippable frames are allowed in the input.
+    ///
+    /// When `true` (the default), skippable frames (like seek tables or
+    /// metadata) are consumed without producing any output. When `false`,
+    /// they make decoding fail.
+    pub fn ignore_skippable_frames(&mut self, ignore: bool) {
+        self.ignore_skippable = ignore;
+    }
+
+    /// Sets the maximum number of frames to decode, `None` (the default)
+    /// for no limit.
+    ///
+    /// Skippable frames are included. Input starting another frame past
+    /// the limit makes decoding fail with [`DecodeLimitExceeded`]. This
+    /// protects against inputs made of many tiny frames, each of them
+    /// costing a context reset.
+    pub fn set_max_frames(&mut self, max: Option<u64>) {
+        self.max_frames = max;
+    }
+
+    /// Sets the maximum total size of skippable frames, headers included,
+    /// `None` for no limit.
+    ///
+    /// A skippable frame going over the limit makes decoding fail with
+    /// [`DecodeLimitExceeded`], as soon as its header is read.
+    ///
+    /// There is no limit by default, unless a callback is set with
+    /// [`Decoder::on_skippable_frame`]: as the content of skippable frames
+    /// is then kept in memory, the limit defaults to 16 MiB.
+    pub fn set_max_skippable_bytes(&mut self, max: Option<u64>) {
+        self.max_skippable_bytes = Some(max);
+    }
+
+    /// Sets whether to check the content size declared by frames.
+    ///
+    /// When enabled, a frame whose header declares its content size must
+    /// decode to exactly that many bytes, or decoding fails with
+    /// [`ContentSizeMismatch`]. Each frame of a multi-frame stream is
+    /// checked separately. Frames which don't declare their size are
+    /// decoded as usual.
+    ///
+    /// zstd itself rejects most frames with a wrong declared size, but when
+    /// streaming, a large frame ending short of its declared size can go
+    /// unnoticed.
+    pub fn enforce_content_size(&mut self, enforce: bool) {
+        self.enforce_content_size = enforce;
+    }
+
+    /// Sets a callback to run on each skippable frame.
+    ///
+    /// The callback is given the magic variant (`0..=15`) and the content of
+    /// the frame. Skippable frames still don't produce any output.
+    ///
+    /// Unless set otherwise with [`Decoder::set_max_skippable_bytes`],
+    /// skippable frames are then limited to 16 MiB in total.
+    pub fn on_skippable_frame<F>(&mut self, callback: F)
+    where
+        F: FnMut(u8, &[u8]) + Send + 'a,
+    {
+        self.on_skippable = Some(Callback(Box::new(callback)));
+    }
+
+    /// Sets whether to check dictionary IDs recorded before frames.
+    ///
+    /// When enabled, skippable frames written by an encoder with
+    /// [`Encoder::set_dict_id_guard`] are compared to the dictionary given
+    /// when creating this decoder (`0` if none). A different ID makes
+    /// decoding fail, even if the frames themselves don't include the ID.
+    ///
+    /// Frames that are not preceded by such a skippable frame are decoded
+    /// as usual.
+    ///
+    /// Raw content dictionaries and prefixes have no ID: enabling the check
+    /// with one of them fails with an error of kind `InvalidInput`. With
+    /// [`Decoder::with_context`], any dictionary loaded in the context is
+    /// unknown to the decoder, which then expects an ID of `0`.
+    pub fn set_dict_id_check(&mut self, check: bool) -> io::Result<()> {
+        if check && self.raw_dictionary {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "dictionary has no ID to check",
+            ));
+        }
+        self.check_dict_id = check;
+        Ok(())
+    }
+
+    /// Sets a checksum to verify after each frame.
+    ///
+    /// Trailers written by an encoder with
+    /// [`Encoder::set_content_checksum`] are compared to the checksum of
+    /// the content of the frame they follow. A different checksum, a
+    /// trailer using another kind of checksum, or a frame without trailer
+    /// makes decoding fail.
+    pub fn set_content_checksum(&mut self, checksum: Option<ContentChecksum>) {
+        self.content_checksum = checksum;
+        self.content_hasher = checksum.map(ContentChecksum::hasher);
+    }
+
+    /// Adds the output written since `start` to the content checksum.
+    fn hash_output<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+        start: usize,
+    ) {
+        if let Some(hasher) = &mut self.content_hasher {
+            let len = output.pos() - start;
+            // Safe because everything up to `output.pos()` was written.
+            let written = unsafe {
+                std::slice::from_raw_parts(output.as_mut_ptr().add(start), len)
+            };
+            hasher.update(written);
+        }
+    }
+
+    /// Returns `true` if frame headers need to be read before zstd.
+    fn reads_headers(&self) -> bool {
+        self.dict_resolver.is_some()
+            || self.require_checksum
+            || self.handles_skippable()
+    }
+
+    /// Returns `true` if skippable frames are read here instead of by zstd.
+    fn handles_skippable(&self) -> bool {
+        !self.ignore_skippable
+            || self.on_skippable.is_some()
+            || self.skippable_limit().is_some()
+            || self.check_dict_id
+            || self.content_checksum.is_some()
+    }
+
+    /// Returns the limit on the total size of skippable frames, if any.
+    fn skippable_limit(&self) -> Option<u64> {
+        match self.max_skippable_bytes {
+            Some(max) => max,
+            None if self.on_skippable.is_some() => {
+                Some(MAX_SKIPPABLE_BYTES_DEFAULT)
+            }
+            None => None,
+        }
+    }
+
+    /// Returns `true` if the content of `skippable` is needed.
+    fn keeps_payload(&self, skippable: &SkippableFrame) -> bool {
+        self.on_skippable.is_some()
+            || (self.check_dict_id
+                && skippable.magic_variant == frame::DICT_ID_MAGIC_VARIANT)
+            || (self.content_checksum.is_some()
+                && skippable.magic_variant
+                    == frame::CONTENT_CHECKSUM_MAGIC_VARIANT)
+    }
+
+    /// Consumes the content of the current skippable frame.
+    ///
+    /// Returns the number of bytes left in the frame.
+    fn read_skippable(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        mut skippable: SkippableFrame,
+    ) -> io::Result<usize> {
+        let available = &input.src[input.pos()..];
+        let n = available.len().min(skippable.remaining as usize);
+        if self.keeps_payload(&skippable) {
+            skippable.payload.extend_from_slice(&available[..n]);
+        }
+        skippable.remaining -= n as u32;
+        input.set_pos(input.pos() + n);
+        self.total_in += n as u64;
+
+        if skippable.remaining > 0 {
+            let remaining = skippable.remaining as usize;
+            self.skippable = Some(skippable);
+            return Ok(remaining);
+        }
+
+        if self.check_dict_id
+            && skippable.magic_variant == frame::DICT_ID_MAGIC_VARIANT
+            && skippable.payload != self.given_dict_id.to_le_bytes()
+        {
+            return Err(self.error(
+                io::ErrorKind::InvalidData,
+                "frame was compressed with another dictionary",
+            ));
+        }
+
+        if self.content_checksum.is_some()
+            && skippable.magic_variant == frame::CONTENT_CHECKSUM_MAGIC_VARIANT
+            && self.expected_trailer.take().as_ref()
+                != Some(&skippable.payload)
+        {
+            return Err(self.error(
+                io::ErrorKind::InvalidData,
+                "content checksum mismatch",
+            ));
+        }
+
+        if let Some(Callback(callback)) = &mut self.on_skippable {
+            callback(skippable.magic_variant, &skippable.payload);
+        }
+        self.end_frame();
+        Ok(0)
+    }
+
+    /// Compares the size of the frame which just ended to its header.
+    fn check_content_size(&self) -> io::Result<()> {
+        let expected =
+            match self.current_header.and_then(|header| header.content_size) {
+                Some(expected) if self.enforce_content_size => expected,
+                _ => return Ok(()),
+            };
+        let actual = self.total_out - self.frame_start_out;
+        if actual != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ContentSizeMismatch { expected, actual },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Records the end of a frame.
+    fn end_frame(&mut self) {
+        self.frame_ended = true;
+        self.frame_start_out = self.total_out;
+        self.in_header = true;
+        self.frames_decoded += 1;
+        if let Some(Callback(callback)) = &mut self.on_frame_end {
+            callback(self.total_out);
+        }
+        if let Some(frame_ends) = &mut self.frame_ends {
+            frame_ends.push_back(self.total_out);
+        }
+    }
+
+    /// Makes the context use the dictionary `dict_id`.
+    fn use_dictionary(&mut self, dict_id: Option<u32>) -> io::Result<()> {
+        if self.dict_id == dict_id {
+            return Ok(());
+        }
+
+        let dictionary = match dict_id {
+            None => None,
+            Some(dict_id) => {
+                let known = self
+                    .dictionaries
+                    .iter()
+                    .find(|(id, _)| *id == dict_id)
+                    .map(|(_, dictionary)| Arc::clone(dictionary));
+                match (known, &mut self.dict_resolver) {
+                    (Some(dictionary), _) => Some(dictionary),
+                    (None, Some(Callback(resolver))) => {
+                        let dictionary = resolver(dict_id);
+                        if let Some(dictionary) = &dictionary {
+                            self.dictionaries
+                                .push((dict_id, Arc::clone(dictionary)));
+                        }
+                        dictionary
+                    }
+                    (None, None) => None,
+                }
+            }
+        };
+
+        // zstd only allows changing dictionaries on a fresh session. This is
+        // only called between frames, so nothing is lost.
+        self.metrics.resets += 1;
+        self.metrics.dictionary_loads += u64::from(dictionary.is_some());
+        match &mut self.context {
+            MaybeOwnedDCtx::Owned(x) => {
+                x.reset(zstd_safe::ResetDirective::SessionOnly)
+            }
+            MaybeOwnedDCtx::Borrowed(x) => {
+                x.reset(zstd_safe::ResetDirective::SessionOnly)
+            }
+        }
+        .map_err(map_error_code)?;
+
+        // The dictionaries are kept in `self.dictionaries`, which outlives
+        // the context.
+        match (&mut self.context, &dictionary) {
+            (MaybeOwnedDCtx::Owned(x), Some(d)) => x.ref_ddict(d.as_ddict()),
+            (MaybeOwnedDCtx::Borrowed(x), Some(d)) => {
+                x.ref_ddict(d.as_ddict())
+            }
+            (MaybeOwnedDCtx::Owned(x), None) => x.load_dictionary(&[]),
+            (MaybeOwnedDCtx::Borrowed(x), None) => x.load_dictionary(&[]),
+        }
+        .map_err(map_error_code)?;
+
+        self.dict_id = dictionary.and(dict_id);
+        Ok(())
+    }
+
+    /// Reads the frame header before giving it to zstd, to resolve its
+    /// dictionary.
+    ///
+    /// Returns `false` if more input is needed to complete the header.
+    fn read_header<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+    ) -> io::Result<bool> {
+        if !self.in_header
+            || (self.header.is_empty() && input.pos() == input.src.len())
+        {
+            return Ok(true);
+        }
+
+        let handle_skippable = self.handles_skippable();
+
+        // Take one byte at a time, to never go past the header.
+        let header = loop {
+            if handle_skippable && frame::is_skippable(&self.header) {
+                if self.header.len() == SkippableHeader::SIZE {
+                    return self.start_skippable().map(|()| true);
+                }
+                if input.pos() == input.src.len() {
+                    return Ok(false);
+                }
+                self.header.push(input.src[input.pos()]);
+                input.set_pos(input.pos() + 1);
+                self.total_in += 1;
+                continue;
+            }
+
+            match FrameHeader::parse(&self.header) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    if input.pos() == input.src.len() {
+                        return Ok(false);
+                    }
+                    self.header.push(input.src[input.pos()]);
+                    input.set_pos(input.pos() + 1);
+                    self.total_in += 1;
+                    self.frame_ended = false;
+                }
+                // Skippable frames (or invalid input) are left to zstd.
+                result => break result.ok(),
+            }
+        };
+        self.current_header = header.map(|(header, _)| header);
+        self.check_trailer_read()?;
+
+        if let Some((header, _)) = header {
+            if self.require_checksum && !header.checksum {
+                return Err(self.error(
+                    io::ErrorKind::InvalidData,
+                    "frame has no checksum",
+                ));
+            }
+            if self.dict_resolver.is_some() {
+                self.use_dictionary(header.dict_id)?;
+            }
+        }
+
+        // The header alone doesn't produce any output.
+        let header = std::mem::take(&mut self.header);
+        let mut header_input = InBuffer::around(&header);
+        match &mut self.context {
+            MaybeOwnedDCtx::Owned(x) => {
+                x.decompress_stream(output, &mut header_input)
+            }
+            MaybeOwnedDCtx::Borrowed(x) => {
+                x.decompress_stream(output, &mut header_input)
+            }
+        }
+        .map_err(|code| self.zstd_error(code))?;
+        if header_input.pos() < header.len() {
+            return Err(self.error(
+                io::ErrorKind::Other,
+                "frame header was not consumed",
+            ));
+        }
+
+        self.header = header;
+        self.header.clear();
+        self.in_header = false;
+        Ok(true)
+    }
+
+    /// Starts reading the skippable frame whose header is in `self.header`.
+    /// Fails if the previous frame is still waiting for its content
+    /// checksum trailer.
+    fn check_trailer_read(&self) -> io::Result<()> {
+        if self.expected_trailer.is_some() {
+            return Err(self.error(
+                io::ErrorKind::InvalidData,
+                "frame has no content checksum",
+            ));
+        }
+        Ok(())
     }
 
-    /// Sets a decompression parameter for this decoder.
-    pub fn set_parameter(&mut self, parameter: DParameter) -> io::Result<()> {
-        match &mut self.context {
-            MaybeOwnedDCtx::Owned(x) => x.set_parameter(parameter),
-            MaybeOwnedDCtx::Borrowed(x) => x.set_parameter(parameter),
+    fn start_skippable(&mut self) -> io::Result<()> {
+        if !self.ignore_skippable {
+            return Err(self.error(
+                io::ErrorKind::InvalidData,
+                "unexpected skippable frame",
+            ));
         }
-        .map_err(map_error_code)?;
+
+        let header = SkippableHeader::parse(&self.header)?
+            .expect("header starts with a skippable magic number");
+        if header.magic_variant != frame::CONTENT_CHECKSUM_MAGIC_VARIANT {
+            self.check_trailer_read()?;
+        }
+        self.skippable_bytes +=
+            SkippableHeader::SIZE as u64 + u64::from(header.size);
+        if let Some(limit) = self.skippable_limit() {
+            if self.skippable_bytes > limit {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    DecodeLimitExceeded::SkippableBytes { limit },
+                ));
+            }
+        }
+        self.header.clear();
+        self.in_header = false;
+        self.frame_ended = false;
+        self.skippable = Some(SkippableFrame {
+            magic_variant: header.magic_variant,
+            remaining: header.size,
+            payload: Vec::new(),
+        });
         Ok(())
     }
 }
 
+impl fmt::Debug for Decoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("frames_decoded", &self.frames_decoded)
+            .field("total_in", &self.total_in)
+            .field("total_out", &self.total_out)
+            .field("dict_id", &self.given_dict_id)
+            .field("window_log_max", &self.window_log_max)
+            .field("require_checksum", &self.require_checksum)
+            .field("frame_ended", &self.frame_ended)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Operation for Decoder<'_> {
     fn run<C: WriteBuf + ?Sized>(
         &mut self,
         input: &mut InBuffer<'_>,
         output: &mut OutBuffer<'_, C>,
     ) -> io::Result<usize> {
-        match &mut self.context {
+        if let Some(skippable) = self.skippable.take() {
+            return self.read_skippable(input, skippable);
+        }
+
+        if self.in_header && input.pos() < input.src.len() {
+            if let Some(limit) = self.max_frames {
+                if self.frames_decoded >= limit {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        DecodeLimitExceeded::Frames { limit },
+                    ));
+                }
+            }
+        }
+
+        // Peek at the header if it is all there, or read it separately.
+        if self.in_header && self.header.is_empty() && !self.reads_headers() {
+            match FrameHeader::parse(&input.src[input.pos()..]) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+                header => {
+                    self.current_header =
+                        header.ok().map(|(header, _)| header);
+                    self.in_header = false;
+                }
+            }
+        }
+
+        if (self.reads_headers() || self.in_header)
+            && !self.read_header(input, output)?
+        {
+            // Ask for the rest of the header.
+            return Ok(FrameHeader::MAX_SIZE - self.header.len());
+        }
+
+        if let Some(skippable) = self.skippable.take() {
+            return self.read_skippable(input, skippable);
+        }
+
+        let input_pos = input.pos();
+        let output_pos = output.pos();
+
+        let result = match &mut self.context {
             MaybeOwnedDCtx::Owned(x) => x.decompress_stream(output, input),
             MaybeOwnedDCtx::Borrowed(x) => x.decompress_stream(output, input),
+        };
+
+        self.total_in += (input.pos() - input_pos) as u64;
+        self.total_out += (output.pos() - output_pos) as u64;
+        self.hash_output(output, output_pos);
+
+        let hint = result.map_err(|code| self.zstd_error(code))?;
+
+        // Once a frame is over, zstd keeps returning 0 until it gets more
+        // input: only count the frame once.
+        let consumed = input.pos() > input_pos;
+        if hint == 0 && (consumed || !self.frame_ended) {
+            self.check_content_size()?;
+            if let Some(hasher) = &self.content_hasher {
+                self.expected_trailer = Some(hasher.trailer());
+                self.content_hasher =
+                    self.content_checksum.map(ContentChecksum::hasher);
+            }
+            self.end_frame();
+        } else if consumed {
+            self.frame_ended = false;
         }
-        .map_err(map_error_code)
+
+        Ok(hint)
+    }
+
+    fn ends_frame(&self, hint: usize) -> bool {
+        // The decoder returns 0 exactly when a frame was fully decoded.
+        hint == 0
     }
 
     fn flush<C: WriteBuf + ?Sized>(
@@ -232,6 +1526,7 @@ impl Operation for Decoder<'_> {
     }
 
     fn reinit(&mut self) -> io::Result<()> {
+        self.metrics.resets += 1;
         match &mut self.context {
             MaybeOwnedDCtx::Owned(x) => {
                 x.reset(zstd_safe::ResetDirective::SessionOnly)
@@ -241,6 +1536,12 @@ impl Operation for Decoder<'_> {
             }
         }
         .map_err(map_error_code)?;
+        self.in_header = true;
+        self.header.clear();
+        self.skippable = None;
+        self.frame_start_out = self.total_out;
+        self.content_hasher =
+            self.content_checksum.map(ContentChecksum::hasher);
         Ok(())
     }
 
@@ -249,13 +1550,208 @@ impl Operation for Decoder<'_> {
         _output: &mut OutBuffer<'_, C>,
         finished_frame: bool,
     ) -> io::Result<usize> {
-        if finished_frame {
-            Ok(0)
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "incomplete frame",
-            ))
+        if !finished_frame {
+            return Err(
+                self.error(io::ErrorKind::UnexpectedEof, "incomplete frame")
+            );
+        }
+        self.check_trailer_read()?;
+        Ok(0)
+    }
+}
+
+/// Error from a decoder, with the position where it happened.
+///
+/// Decoders return this inside an `io::Error` when enabled with
+/// [`Decoder::set_error_context`]. It can be retrieved with
+/// `io::Error::get_ref` and `downcast_ref`:
+///
+/// ```rust
+/// use std::io::Read;
+/// use zstd::stream::raw::DecodeError;
+///
+/// let input = &b"\x28\xb5\x2f\xfd\x00\x00\xff\xff"[..];
+/// let mut decoder = zstd::Decoder::new(input).unwrap();
+/// decoder.set_error_context(true);
+/// let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+/// let context = err
+///     .get_ref()
+///     .and_then(|e| e.downcast_ref::<DecodeError>())
+///     .unwrap();
+/// assert_eq!(context.frame_index(), 0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    message: &'static str,
+    compressed_offset: u64,
+    decompressed_offset: u64,
+    frame_index: u64,
+    zstd_error: Option<zstd_safe::Error>,
+    // Required window size and window log limit, for window errors.
+    window: Option<(Option<u64>, u32)>,
+}
+
+impl DecodeError {
+    /// Returns the error message, without the position.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// Returns the number of compressed bytes read before the error.
+    ///
+    /// The corrupted data is at or shortly before this offset.
+    pub fn compressed_offset(&self) -> u64 {
+        self.compressed_offset
+    }
+
+    /// Returns the number of decompressed bytes produced before the error.
+    pub fn decompressed_offset(&self) -> u64 {
+        self.decompressed_offset
+    }
+
+    /// Returns the index of the frame being decoded, starting at `0`.
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// Returns the category of the error, if it was reported by zstd.
+    ///
+    /// This is `None` for errors detected by this crate, like missing
+    /// checksums or truncated input.
+    pub fn zstd_error_kind(&self) -> Option<zstd_safe::ErrorKind> {
+        self.zstd_error.map(zstd_safe::Error::kind)
+    }
+
+    /// Returns the error reported by zstd, with its exact code, if any.
+    pub fn zstd_error(&self) -> Option<zstd_safe::Error> {
+        self.zstd_error
+    }
+
+    /// Returns the window size required by the frame, for errors about the
+    /// window size.
+    ///
+    /// This is `None` for other errors, or if the frame header could not be
+    /// read.
+    pub fn required_window_size(&self) -> Option<u64> {
+        self.window.and_then(|(required, _)| required)
+    }
+
+    /// Returns the window log limit of the decoder, for errors about the
+    /// window size.
+    ///
+    /// A frame can be decoded if its window size is at most
+    /// `1 << window_log_max`.
+    pub fn window_log_max(&self) -> Option<u32> {
+        self.window.map(|(_, window_log_max)| window_log_max)
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (frame {}, compressed offset {}, decompressed offset {}",
+            self.message,
+            self.frame_index,
+            self.compressed_offset,
+            self.decompressed_offset
+        )?;
+        if let Some((required, window_log_max)) = self.window {
+            if let Some(required) = required {
+                write!(f, ", window size {}", required)?;
+            }
+            write!(f, ", window log max {}", window_log_max)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Error returned when a limit set on a decoder is exceeded.
+///
+/// Decoders return this inside an `io::Error` of kind `InvalidData`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeLimitExceeded {
+    /// More frames than allowed by [`Decoder::set_max_frames`].
+    Frames {
+        /// The maximum number of frames.
+        limit: u64,
+    },
+    /// More skippable data than allowed by
+    /// [`Decoder::set_max_skippable_bytes`].
+    SkippableBytes {
+        /// The maximum total size of skippable frames.
+        limit: u64,
+    },
+}
+
+impl std::fmt::Display for DecodeLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeLimitExceeded::Frames { limit } => {
+                write!(f, "input has more than {} frames", limit)
+            }
+            DecodeLimitExceeded::SkippableBytes { limit } => write!(
+                f,
+                "input has more than {} bytes of skippable frames",
+                limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeLimitExceeded {}
+
+/// Error returned when a frame doesn't match its declared content size.
+///
+/// See [`Decoder::enforce_content_size`]. Decoders return this inside an
+/// `io::Error` of kind `InvalidData`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ContentSizeMismatch {
+    /// The content size declared by the frame header.
+    pub expected: u64,
+    /// The number of bytes actually decoded.
+    pub actual: u64,
+}
+
+impl std::fmt::Display for ContentSizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frame declared {} bytes of content, but decoded to {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ContentSizeMismatch {}
+
+/// Counters of the work done on a zstd context by an encoder or decoder.
+///
+/// These help spot costly usage patterns, like a dictionary loaded again
+/// for each message, or a context reset after every few bytes. Work done
+/// when creating the encoder or decoder is included, but not the creation
+/// of the context itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ContextMetrics {
+    /// Number of times the session was reset, to start a new frame or to
+    /// change the dictionary.
+    pub resets: u64,
+
+    /// Number of dictionaries or prefixes loaded or referenced.
+    pub dictionary_loads: u64,
+}
+
+impl ContextMetrics {
+    /// Returns the metrics of a new context, after its setup.
+    fn setup(loads_dictionary: bool) -> Self {
+        ContextMetrics {
+            resets: 0,
+            dictionary_loads: u64::from(loads_dictionary),
         }
     }
 }
@@ -263,6 +1759,29 @@ impl Operation for Decoder<'_> {
 /// An in-memory encoder for streams of data.
 pub struct Encoder<'a> {
     context: MaybeOwnedCCtx<'a>,
+    // Dictionary referenced by the context, kept alive here.
+    dictionary: Option<Arc<EncoderDictionary<'static>>>,
+
+    // ID of the dictionary given when creating the encoder, `0` if none.
+    dict_id: u32,
+    // `true` if that dictionary has no ID (raw content or prefix).
+    raw_dictionary: bool,
+    dict_id_guard: bool,
+    // Skippable frame to write before the next frame, and how much of it
+    // was written already.
+    guard: Vec<u8>,
+    guard_pos: usize,
+    // `true` once the current frame was given to zstd.
+    frame_started: bool,
+    // Compression level last set, if known.
+    level: Option<i32>,
+    // Checksum of the content to write after each frame, if any.
+    content_checksum: Option<ContentChecksum>,
+    // Checksum of the current frame, until its trailer is prepared.
+    content_hasher: Option<ContentHasher>,
+    // `true` once the frame ended, and its trailer was put in `guard`.
+    in_trailer: bool,
+    metrics: ContextMetrics,
 }
 
 impl Encoder<'static> {
@@ -283,17 +1802,57 @@ impl Encoder<'static> {
             .load_dictionary(dictionary)
             .map_err(map_error_code)?;
 
-        Ok(Encoder {
-            context: MaybeOwnedCCtx::Owned(context),
-        })
+        let dict_id = zstd_safe::get_dict_id_from_dict(dictionary);
+        let mut encoder =
+            Encoder::from_context(MaybeOwnedCCtx::Owned(context), dict_id);
+        encoder.raw_dictionary = dict_id.is_none() && !dictionary.is_empty();
+        encoder.level = Some(level);
+        encoder.metrics = ContextMetrics::setup(!dictionary.is_empty());
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder, configured with the given options.
+    pub fn with_options(options: &Options) -> io::Result<Self> {
+        Self::with_options_and_prefix(options, &[])
+    }
+
+    /// Creates a new encoder, using a shared `EncoderDictionary`.
+    ///
+    /// Unlike [`Encoder::with_prepared_dictionary`], the encoder keeps the
+    /// dictionary alive, so it doesn't borrow anything.
+    pub fn with_shared_dictionary(
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let mut encoder = Encoder::with_prepared_dictionary(&dictionary)?;
+        encoder.dictionary = Some(dictionary);
+        Ok(encoder)
     }
 }
 
 impl<'a> Encoder<'a> {
     /// Creates a new encoder that uses the provided context for serialization.
     pub fn with_context(context: &'a mut zstd_safe::CCtx<'static>) -> Self {
-        Self {
-            context: MaybeOwnedCCtx::Borrowed(context),
+        Self::from_context(MaybeOwnedCCtx::Borrowed(context), None)
+    }
+
+    fn from_context(
+        context: MaybeOwnedCCtx<'a>,
+        dict_id: Option<NonZeroU32>,
+    ) -> Self {
+        Encoder {
+            context,
+            dictionary: None,
+            dict_id: dict_id.map_or(0, NonZeroU32::get),
+            raw_dictionary: false,
+            dict_id_guard: false,
+            guard: Vec::new(),
+            guard_pos: 0,
+            frame_started: false,
+            level: None,
+            content_checksum: None,
+            content_hasher: None,
+            in_trailer: false,
+            metrics: ContextMetrics::default(),
         }
     }
 
@@ -308,9 +1867,47 @@ impl<'a> Encoder<'a> {
         context
             .ref_cdict(dictionary.as_cdict())
             .map_err(map_error_code)?;
-        Ok(Encoder {
-            context: MaybeOwnedCCtx::Owned(context),
-        })
+        let dict_id = dictionary.as_cdict().get_dict_id();
+        let mut encoder =
+            Encoder::from_context(MaybeOwnedCCtx::Owned(context), dict_id);
+        encoder.raw_dictionary = dict_id.is_none();
+        encoder.metrics = ContextMetrics::setup(true);
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder configured with `options`, which also
+    /// references `ref_prefix` for the first frame, if not empty.
+    ///
+    /// Fails if both the options have a dictionary and a prefix is given.
+    pub(crate) fn with_options_and_prefix<'b>(
+        options: &Options,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        if !options.dictionary.is_empty() && !ref_prefix.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a dictionary and a prefix can't be used together",
+            ));
+        }
+
+        let mut context = zstd_safe::CCtx::create();
+        options.configure(&mut context)?;
+        if !ref_prefix.is_empty() {
+            context.ref_prefix(ref_prefix).map_err(map_error_code)?;
+        }
+
+        let dict_id = zstd_safe::get_dict_id_from_dict(&options.dictionary);
+        let has_dictionary =
+            !options.dictionary.is_empty() || !ref_prefix.is_empty();
+        let mut encoder =
+            Encoder::from_context(MaybeOwnedCCtx::Owned(context), dict_id);
+        encoder.raw_dictionary = dict_id.is_none() && has_dictionary;
+        encoder.level = Some(options.level);
+        encoder.metrics = ContextMetrics::setup(has_dictionary);
+        Ok(encoder)
     }
 
     /// Creates a new encoder initialized with the given ref prefix.
@@ -329,9 +1926,12 @@ impl<'a> Encoder<'a> {
 
         context.ref_prefix(ref_prefix).map_err(map_error_code)?;
 
-        Ok(Encoder {
-            context: MaybeOwnedCCtx::Owned(context),
-        })
+        let mut encoder =
+            Encoder::from_context(MaybeOwnedCCtx::Owned(context), None);
+        encoder.raw_dictionary = !ref_prefix.is_empty();
+        encoder.level = Some(level);
+        encoder.metrics = ContextMetrics::setup(true);
+        Ok(encoder)
     }
 
     /// Sets a compression parameter for this encoder.
@@ -341,6 +1941,9 @@ impl<'a> Encoder<'a> {
             MaybeOwnedCCtx::Borrowed(x) => x.set_parameter(parameter),
         }
         .map_err(map_error_code)?;
+        if let CParameter::CompressionLevel(level) = parameter {
+            self.level = Some(level);
+        }
         Ok(())
     }
 
@@ -367,6 +1970,166 @@ impl<'a> Encoder<'a> {
         .map_err(map_error_code)?;
         Ok(())
     }
+
+    /// Returns counters of the work done on the zstd context so far.
+    pub fn metrics(&self) -> ContextMetrics {
+        self.metrics
+    }
+
+    /// Sets whether to record the dictionary ID before each frame.
+    ///
+    /// When enabled, each frame is preceded by a skippable frame holding the
+    /// ID of the dictionary given when creating this encoder (`0` if none).
+    /// Decoders can check it with [`Decoder::set_dict_id_check`]. This makes
+    /// it safe to omit the ID from the frames themselves with
+    /// `include_dictid(false)`: decoding with the wrong dictionary then
+    /// fails instead of giving garbage.
+    ///
+    /// This applies from the current frame if nothing was written to it
+    /// yet, or from the next one (after [`Operation::reinit`]) otherwise.
+    ///
+    /// Raw content dictionaries and prefixes have no ID: enabling the guard
+    /// with one of them fails with an error of kind `InvalidInput`. With
+    /// [`Encoder::with_context`], any dictionary loaded in the context is
+    /// unknown to the encoder, which then records an ID of `0`.
+    pub fn set_dict_id_guard(&mut self, guard: bool) -> io::Result<()> {
+        if guard && self.raw_dictionary {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "dictionary has no ID to record",
+            ));
+        }
+        self.dict_id_guard = guard;
+        if !self.frame_started {
+            self.prepare_guard();
+        }
+        Ok(())
+    }
+
+    /// Sets a checksum of the content to write after each frame.
+    ///
+    /// When set, each frame is followed by a skippable frame holding the
+    /// checksum of its uncompressed content. Decoders can verify it with
+    /// [`Decoder::set_content_checksum`]. `None` (the default) disables it.
+    ///
+    /// Like [`Encoder::set_dict_id_guard`], this applies from the current
+    /// frame if nothing was written to it yet, or from the next one
+    /// otherwise.
+    pub fn set_content_checksum(&mut self, checksum: Option<ContentChecksum>) {
+        self.content_checksum = checksum;
+        if !self.frame_started {
+            self.content_hasher = checksum.map(ContentChecksum::hasher);
+        }
+    }
+
+    /// Adds the input consumed since `start` to the content checksum.
+    fn hash_input(&mut self, input: &InBuffer<'_>, start: usize) {
+        if let Some(hasher) = &mut self.content_hasher {
+            hasher.update(&input.src[start..input.pos()]);
+        }
+    }
+
+    /// Starts writing the content checksum trailer, once zstd ended the
+    /// frame.
+    ///
+    /// The trailer goes through the same buffer as the dictionary ID frame,
+    /// which is only used before the frame starts. Returns the number of
+    /// bytes still to write.
+    fn write_trailer<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+    ) -> usize {
+        let hasher = match self.content_hasher.take() {
+            Some(hasher) => hasher,
+            None => return 0,
+        };
+        self.guard.clear();
+        self.guard_pos = 0;
+        frame::write_skippable_frame(
+            &mut self.guard,
+            frame::CONTENT_CHECKSUM_MAGIC_VARIANT,
+            &hasher.trailer(),
+        )
+        .expect("checksum fits in a skippable frame");
+        self.in_trailer = true;
+        self.write_guard(output)
+    }
+
+    /// Prepares the skippable frame to write before the next frame.
+    fn prepare_guard(&mut self) {
+        self.guard.clear();
+        self.guard_pos = 0;
+        if self.dict_id_guard {
+            frame::write_skippable_frame(
+                &mut self.guard,
+                frame::DICT_ID_MAGIC_VARIANT,
+                &self.dict_id.to_le_bytes(),
+            )
+            .expect("dictionary ID fits in a skippable frame");
+        }
+    }
+
+    /// Writes what remains of the dictionary ID frame to `output`.
+    ///
+    /// Returns the number of bytes still to write. Until it is `0`, nothing
+    /// must be given to zstd.
+    fn write_guard<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+    ) -> usize {
+        if self.guard_pos < self.guard.len() {
+            let mut guard = InBuffer::around(&self.guard[self.guard_pos..]);
+            copy_input(&mut guard, output);
+            self.guard_pos += guard.pos();
+        }
+        let remaining = self.guard.len() - self.guard_pos;
+        if remaining == 0 {
+            self.frame_started = true;
+        }
+        remaining
+    }
+
+    /// Performs a single step of compression, with an explicit end directive.
+    ///
+    /// * `EndDirective::ZSTD_e_continue` behaves like `Operation::run`.
+    /// * `EndDirective::ZSTD_e_flush` also flushes all data given so far.
+    /// * `EndDirective::ZSTD_e_end` also ends the frame after the input.
+    ///
+    /// When flushing or ending, returns a lower bound for the amount of data
+    /// still to be written: keep calling this method (with the same
+    /// directive) until it returns `Ok(0)`.
+    pub fn run2<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+        end_directive: EndDirective,
+    ) -> io::Result<usize> {
+        let remaining = self.write_guard(output);
+        if remaining > 0 {
+            return Ok(remaining);
+        }
+
+        let ending = end_directive == EndDirective::ZSTD_e_end;
+        if ending && self.in_trailer {
+            return Ok(0);
+        }
+
+        let input_pos = input.pos();
+        let result = match &mut self.context {
+            MaybeOwnedCCtx::Owned(x) => {
+                x.compress_stream2(output, input, end_directive)
+            }
+            MaybeOwnedCCtx::Borrowed(x) => {
+                x.compress_stream2(output, input, end_directive)
+            }
+        };
+        self.hash_input(input, input_pos);
+
+        match result.map_err(map_error_code)? {
+            0 if ending => Ok(self.write_trailer(output)),
+            hint => Ok(hint),
+        }
+    }
 }
 
 impl<'a> Operation for Encoder<'a> {
@@ -375,17 +2138,29 @@ impl<'a> Operation for Encoder<'a> {
         input: &mut InBuffer<'_>,
         output: &mut OutBuffer<'_, C>,
     ) -> io::Result<usize> {
-        match &mut self.context {
+        let remaining = self.write_guard(output);
+        if remaining > 0 {
+            return Ok(remaining);
+        }
+
+        let input_pos = input.pos();
+        let result = match &mut self.context {
             MaybeOwnedCCtx::Owned(x) => x.compress_stream(output, input),
             MaybeOwnedCCtx::Borrowed(x) => x.compress_stream(output, input),
-        }
-        .map_err(map_error_code)
+        };
+        self.hash_input(input, input_pos);
+        result.map_err(map_error_code)
     }
 
     fn flush<C: WriteBuf + ?Sized>(
         &mut self,
         output: &mut OutBuffer<'_, C>,
     ) -> io::Result<usize> {
+        let remaining = self.write_guard(output);
+        if remaining > 0 {
+            return Ok(remaining);
+        }
+
         match &mut self.context {
             MaybeOwnedCCtx::Owned(x) => x.flush_stream(output),
             MaybeOwnedCCtx::Borrowed(x) => x.flush_stream(output),
@@ -398,14 +2173,37 @@ impl<'a> Operation for Encoder<'a> {
         output: &mut OutBuffer<'_, C>,
         _finished_frame: bool,
     ) -> io::Result<usize> {
+        let remaining = self.write_guard(output);
+        if remaining > 0 {
+            return Ok(remaining);
+        }
+
+        if self.in_trailer {
+            return Ok(0);
+        }
+
         match &mut self.context {
             MaybeOwnedCCtx::Owned(x) => x.end_stream(output),
             MaybeOwnedCCtx::Borrowed(x) => x.end_stream(output),
         }
         .map_err(map_error_code)
+        .map(|hint| match hint {
+            0 => self.write_trailer(output),
+            hint => hint,
+        })
+    }
+
+    fn finish_with_input<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+        _finished_frame: bool,
+    ) -> io::Result<usize> {
+        self.run2(input, output, EndDirective::ZSTD_e_end)
     }
 
     fn reinit(&mut self) -> io::Result<()> {
+        self.metrics.resets += 1;
         match &mut self.context {
             MaybeOwnedCCtx::Owned(x) => {
                 x.reset(zstd_safe::ResetDirective::SessionOnly)
@@ -415,10 +2213,27 @@ impl<'a> Operation for Encoder<'a> {
             }
         }
         .map_err(map_error_code)?;
+        self.frame_started = false;
+        self.prepare_guard();
+        self.content_hasher =
+            self.content_checksum.map(ContentChecksum::hasher);
+        self.in_trailer = false;
         Ok(())
     }
 }
 
+impl fmt::Debug for Encoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("level", &self.level)
+            .field("dict_id", &self.dict_id)
+            .field("dict_id_guard", &self.dict_id_guard)
+            .field("frame_started", &self.frame_started)
+            .field("content_checksum", &self.content_checksum)
+            .finish_non_exhaustive()
+    }
+}
+
 enum MaybeOwnedCCtx<'a> {
     Owned(zstd_safe::CCtx<'a>),
     Borrowed(&'a mut zstd_safe::CCtx<'static>),
@@ -473,4 +2288,158 @@ mod tests {
 
         assert_eq!(initial_data, output.as_slice());
     }
+
+    #[test]
+    fn test_run2() {
+        use super::{Encoder, EndDirective, InBuffer, OutBuffer};
+
+        let data = b"AbcdefAbcdefabcdef";
+        let mut encoder = Encoder::new(1).unwrap();
+
+        let mut output = Vec::with_capacity(128);
+        let mut output = OutBuffer::around(&mut output);
+
+        // Flushing makes everything so far readable.
+        let mut input = InBuffer::around(&data[..6]);
+        while encoder
+            .run2(&mut input, &mut output, EndDirective::ZSTD_e_flush)
+            .unwrap()
+            != 0
+        {}
+        assert_eq!(input.pos(), 6);
+
+        let mut input = InBuffer::around(&data[6..]);
+        while encoder
+            .run2(&mut input, &mut output, EndDirective::ZSTD_e_end)
+            .unwrap()
+            != 0
+        {}
+
+        let decoded = crate::decode_all(output.as_slice()).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_status() {
+        use super::{Decoder, Encoder, NoOp, Operation};
+
+        let data = b"AbcdefAbcdefabcdef";
+        let mut encoder = Encoder::new(1).unwrap();
+        let mut compressed = [0u8; 128];
+
+        let detailed = encoder
+            .run_on_buffers_detailed(data, &mut compressed)
+            .unwrap();
+        assert!(detailed.input_exhausted);
+        assert!(!detailed.frame_ended);
+        let mut written = detailed.status.bytes_written;
+
+        let status = encoder
+            .finish_on_buffers(&mut compressed[written..], false)
+            .unwrap();
+        assert_eq!(status.remaining, 0);
+        written += status.bytes_written;
+
+        let mut decoder = Decoder::new().unwrap();
+        let mut output = [0u8; 128];
+
+        let detailed = decoder
+            .run_on_buffers_detailed(&compressed[..written - 1], &mut output)
+            .unwrap();
+        assert!(detailed.input_exhausted);
+        assert!(!detailed.frame_ended);
+
+        let detailed = decoder
+            .run_on_buffers_detailed(
+                &compressed[written - 1..written],
+                &mut output,
+            )
+            .unwrap();
+        assert!(detailed.frame_ended);
+
+        let detailed =
+            NoOp.run_on_buffers_detailed(data, &mut output).unwrap();
+        assert_eq!(detailed.status.remaining, 0);
+        assert!(!detailed.frame_ended);
+    }
+
+    #[test]
+    fn test_metrics() {
+        use super::{ContextMetrics, Decoder, Encoder, Operation};
+
+        let dictionary = b"AbcdefAbcdefabcdef";
+        let mut encoder = Encoder::with_dictionary(1, dictionary).unwrap();
+        assert_eq!(
+            encoder.metrics(),
+            ContextMetrics {
+                resets: 0,
+                dictionary_loads: 1,
+            }
+        );
+
+        let mut compressed = Vec::new();
+        for _ in 0..3 {
+            let mut output = [0u8; 128];
+            let status = encoder
+                .run_on_buffers(&dictionary[..], &mut output)
+                .unwrap();
+            let mut written = status.bytes_written;
+            let status = encoder
+                .finish_on_buffers(&mut output[written..], false)
+                .unwrap();
+            written += status.bytes_written;
+            compressed.extend_from_slice(&output[..written]);
+            encoder.reinit().unwrap();
+        }
+        let metrics = encoder.metrics();
+        assert_eq!(metrics.resets, 3);
+        assert_eq!(metrics.dictionary_loads, 1);
+
+        let mut decoder = Decoder::with_dictionary(dictionary).unwrap();
+        let mut output = [0u8; 128];
+        let status = decoder.run_on_buffers(&compressed, &mut output).unwrap();
+        assert_eq!(&output[..status.bytes_written], dictionary);
+        let metrics = decoder.metrics();
+        assert_eq!(metrics.resets, 0);
+        assert_eq!(metrics.dictionary_loads, 1);
+        assert_eq!(Decoder::new().unwrap().metrics().dictionary_loads, 0);
+    }
+
+    #[test]
+    fn test_flush_status() {
+        use super::{Encoder, InBuffer, Operation, OutBuffer};
+
+        let text = include_bytes!("../../assets/example.txt");
+        let mut encoder = Encoder::new(1).unwrap();
+
+        // Without room for output, zstd keeps the data for later.
+        let mut input = InBuffer::around(&text[..]);
+        let mut empty = [0u8; 0];
+        encoder
+            .run(&mut input, &mut OutBuffer::around(&mut empty[..]))
+            .unwrap();
+
+        let mut compressed = Vec::new();
+        let mut chunk = [0u8; 16];
+        loop {
+            let mut output = OutBuffer::around(&mut chunk[..]);
+            let status = encoder.flush_status(&mut output).unwrap();
+            assert_eq!(status.bytes_written, output.pos());
+            compressed.extend_from_slice(output.as_slice());
+            if status.is_done() {
+                break;
+            }
+        }
+        assert_eq!(input.pos(), text.len());
+
+        loop {
+            let mut output = OutBuffer::around(&mut chunk[..]);
+            let status = encoder.finish_status(&mut output, false).unwrap();
+            compressed.extend_from_slice(output.as_slice());
+            if status.is_done() {
+                break;
+            }
+        }
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), &text[..]);
+    }
 }
@@ -0,0 +1,175 @@
+use std::io::{self, BufReader, Read};
+
+use zstd_safe::DParameter;
+
+use super::Decoder;
+use crate::stream::{raw, zio, ChecksumPolicy};
+
+/// Builder configuring a [`Decoder`] before creating it.
+///
+/// This is created by [`Decoder::builder`], and gathers the settings which
+/// otherwise need a specific constructor, or a setter called on the new
+/// decoder.
+///
+/// ```rust
+/// use std::io::Read;
+///
+/// let compressed = zstd::encode_all(&b"data"[..], 3)?;
+///
+/// let mut decoder = zstd::Decoder::builder(&compressed[..])
+///     .window_log_max(24)
+///     .max_frames(1)
+///     .build()?;
+/// let mut decompressed = Vec::new();
+/// decoder.read_to_end(&mut decompressed)?;
+/// assert_eq!(decompressed, b"data");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DecoderBuilder<'a, R> {
+    reader: R,
+    dictionary: Vec<u8>,
+    ref_prefix: Option<&'a [u8]>,
+    window_log_max: Option<u32>,
+    checksum_policy: ChecksumPolicy,
+    ignore_skippable: bool,
+    max_frames: Option<u64>,
+    max_skippable_bytes: Option<u64>,
+    enforce_content_size: bool,
+    single_frame: bool,
+}
+
+impl<'a, R: Read> DecoderBuilder<'a, R> {
+    /// Creates a new builder, with the default settings.
+    pub fn new(reader: R) -> Self {
+        DecoderBuilder {
+            reader,
+            dictionary: Vec::new(),
+            ref_prefix: None,
+            window_log_max: None,
+            checksum_policy: ChecksumPolicy::default(),
+            ignore_skippable: true,
+            max_frames: None,
+            max_skippable_bytes: None,
+            enforce_content_size: false,
+            single_frame: false,
+        }
+    }
+
+    /// Sets the dictionary to decompress with.
+    ///
+    /// The dictionary is copied. This can't be combined with
+    /// [`DecoderBuilder::ref_prefix`].
+    #[must_use]
+    pub fn dictionary(mut self, dictionary: &[u8]) -> Self {
+        self.dictionary = dictionary.to_vec();
+        self
+    }
+
+    /// Sets the prefix the first frame was compressed with.
+    ///
+    /// This can't be combined with [`DecoderBuilder::dictionary`].
+    #[must_use]
+    pub fn ref_prefix(mut self, ref_prefix: &'a [u8]) -> Self {
+        self.ref_prefix = Some(ref_prefix);
+        self
+    }
+
+    /// Sets the largest window the decoder accepts, as a power of 2.
+    ///
+    /// Frames requiring a larger window make decoding fail.
+    #[must_use]
+    pub fn window_log_max(mut self, window_log_max: u32) -> Self {
+        self.window_log_max = Some(window_log_max);
+        self
+    }
+
+    /// Sets how frame checksums are checked.
+    ///
+    /// See [`raw::Decoder::set_checksum_policy`].
+    #[must_use]
+    pub fn checksum_policy(mut self, policy: ChecksumPolicy) -> Self {
+        self.checksum_policy = policy;
+        self
+    }
+
+    /// Sets whether skippable frames are allowed in the input.
+    ///
+    /// See [`raw::Decoder::ignore_skippable_frames`].
+    #[must_use]
+    pub fn ignore_skippable_frames(mut self, ignore: bool) -> Self {
+        self.ignore_skippable = ignore;
+        self
+    }
+
+    /// Sets the maximum number of frames to decode.
+    ///
+    /// See [`raw::Decoder::set_max_frames`].
+    #[must_use]
+    pub fn max_frames(mut self, max: u64) -> Self {
+        self.max_frames = Some(max);
+        self
+    }
+
+    /// Sets the maximum total size of skippable frames.
+    ///
+    /// See [`raw::Decoder::set_max_skippable_bytes`].
+    #[must_use]
+    pub fn max_skippable_bytes(mut self, max: u64) -> Self {
+        self.max_skippable_bytes = Some(max);
+        self
+    }
+
+    /// Sets whether to check the content size declared by frames.
+    ///
+    /// See [`raw::Decoder::enforce_content_size`].
+    #[must_use]
+    pub fn enforce_content_size(mut self, enforce: bool) -> Self {
+        self.enforce_content_size = enforce;
+        self
+    }
+
+    /// Sets the decoder to stop after the first frame.
+    ///
+    /// See [`Decoder::single_frame`].
+    #[must_use]
+    pub fn single_frame(mut self) -> Self {
+        self.single_frame = true;
+        self
+    }
+
+    /// Creates the decoder.
+    ///
+    /// Fails if zstd rejects the settings, or if both a dictionary and a
+    /// prefix were given.
+    pub fn build(self) -> io::Result<Decoder<'a, BufReader<R>>> {
+        let mut decoder = match self.ref_prefix {
+            None => raw::Decoder::with_dictionary(&self.dictionary)?,
+            Some(_) if !self.dictionary.is_empty() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "a dictionary and a prefix can't be used together",
+                ));
+            }
+            Some(ref_prefix) => raw::Decoder::with_ref_prefix(ref_prefix)?,
+        };
+        if let Some(window_log_max) = self.window_log_max {
+            decoder.set_parameter(DParameter::WindowLogMax(window_log_max))?;
+        }
+        decoder.set_checksum_policy(self.checksum_policy);
+        decoder.ignore_skippable_frames(self.ignore_skippable);
+        decoder.set_max_frames(self.max_frames);
+        if let Some(max) = self.max_skippable_bytes {
+            decoder.set_max_skippable_bytes(Some(max));
+        }
+        decoder.enforce_content_size(self.enforce_content_size);
+
+        let buffer_size = zstd_safe::DCtx::in_size();
+        let reader = BufReader::with_capacity(buffer_size, self.reader);
+        let mut reader = zio::Reader::new(reader, decoder);
+        if self.single_frame {
+            reader.set_single_frame();
+        }
+        Ok(Decoder { reader })
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, BufRead, Read};
+
+use super::Decoder;
+use crate::frame::{self, FrameBoundary, FrameHeader};
+
+/// Reads a multi-frame stream one frame at a time.
+///
+/// Each call to [`Frames::next_frame`] gives a reader for the next frame,
+/// which ends with the frame. This lets different frames go to different
+/// handlers (for example a metadata frame followed by data frames), without
+/// decoding everything in memory.
+///
+/// This is not an `Iterator`, since each frame borrows the decoder:
+///
+/// ```rust
+/// use std::io::Read;
+///
+/// let mut input = zstd::encode_all(&b"metadata"[..], 1).unwrap();
+/// input.extend(zstd::encode_all(&b"data"[..], 1).unwrap());
+///
+/// let mut frames = zstd::stream::read::Decoder::new(&input[..])
+///     .unwrap()
+///     .frames();
+/// while let Some(mut frame) = frames.next_frame().unwrap() {
+///     let mut content = String::new();
+///     frame.read_to_string(&mut content).unwrap();
+///     println!("{}", content);
+/// }
+/// ```
+///
+/// Created by [`Decoder::frames`].
+pub struct Frames<'a, R> {
+    decoder: Decoder<'a, R>,
+    started: bool,
+}
+
+/// Reader for a single frame, returned by [`Frames::next_frame`].
+pub struct Frame<'f, 'a, R> {
+    decoder: &'f mut Decoder<'a, R>,
+}
+
+impl<'a, R: BufRead> Frames<'a, R> {
+    pub(super) fn new(decoder: Decoder<'a, R>) -> Self {
+        Frames {
+            decoder,
+            started: false,
+        }
+    }
+
+    /// Returns a reader for the next frame, or `None` at the end of input.
+    ///
+    /// If the previous frame was not read entirely, the rest of it is
+    /// decoded and discarded first.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame<'_, 'a, R>>> {
+        self.end_frame()?;
+
+        // This can fail (for example with `WouldBlock`): the call can then
+        // be retried, as nothing was started yet.
+        if self.decoder.reader.reader_mut().fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        self.started = true;
+        Ok(Some(Frame {
+            decoder: &mut self.decoder,
+        }))
+    }
+
+    /// Skips the next frame, without decompressing it.
+    ///
+    /// If the previous frame was not read entirely, the rest of it is
+    /// decoded and discarded first.
+    ///
+    /// Returns the header of the skipped frame (`None` for skippable frames)
+    /// and its compressed size, or `None` at the end of input. See
+    /// [`frame::skip_frame`].
+    pub fn skip_frame(
+        &mut self,
+    ) -> io::Result<Option<(Option<FrameHeader>, u64)>> {
+        self.end_frame()?;
+        frame::skip_frame(self.decoder.reader.reader_mut())
+    }
+
+    /// Finishes the current frame, if any.
+    fn end_frame(&mut self) -> io::Result<()> {
+        if self.started {
+            io::copy(&mut self.decoder, &mut io::sink())?;
+            self.decoder.reader.start_next_frame();
+            self.started = false;
+        }
+        Ok(())
+    }
+
+    /// Returns the inner decoder.
+    pub fn into_inner(self) -> Decoder<'a, R> {
+        self.decoder
+    }
+}
+
+impl<R: BufRead> Read for Frame<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+/// Iterator over the boundaries of the frames in a stream.
+///
+/// Frames are not decompressed: only their headers are parsed, so this is
+/// fast, and works on non-seekable inputs. Once an error is returned, the
+/// iteration stops.
+///
+/// Created by [`Decoder::frame_boundaries`].
+pub struct FrameBoundaries<R> {
+    reader: R,
+    offset: u64,
+    failed: bool,
+}
+
+impl<R: BufRead> FrameBoundaries<R> {
+    pub(super) fn new(reader: R) -> Self {
+        FrameBoundaries {
+            reader,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    /// Returns the inner reader.
+    ///
+    /// It is positioned right after the last frame returned.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for FrameBoundaries<R> {
+    type Item = io::Result<FrameBoundary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        match frame::skip_frame(&mut self.reader) {
+            Ok(Some((header, compressed_size))) => {
+                let boundary = FrameBoundary {
+                    offset: self.offset,
+                    compressed_size,
+                    header,
+                };
+                self.offset += compressed_size;
+                Some(Ok(boundary))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
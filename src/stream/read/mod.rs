@@ -1,22 +1,32 @@
 //! Implement pull-based [`Read`] trait for both compressing and decompressing.
 use std::io::{self, BufRead, BufReader, Read};
+use std::sync::Arc;
 
-use crate::dict::{DecoderDictionary, EncoderDictionary};
-use crate::stream::{raw, zio};
+use crate::dict::{DecoderDictionary, DictRegistry, EncoderDictionary};
+use crate::stream::pool::{BufferPool, PooledBufReader};
+use crate::stream::{raw, zio, SizeHint};
+use crate::Options;
 use zstd_safe;
 
+mod builder;
+mod frames;
 #[cfg(test)]
 mod tests;
 
+pub use self::builder::DecoderBuilder;
+pub use self::frames::{Frame, FrameBoundaries, Frames};
+
 /// A decoder that decompress input data from another `Read`.
 ///
 /// This allows to read a stream of compressed data
 /// (good for files or heavy network stream).
+#[derive(Debug)]
 pub struct Decoder<'a, R> {
     reader: zio::Reader<R, raw::Decoder<'a>>,
 }
 
 /// An encoder that compress input data from another `Read`.
+#[derive(Debug)]
 pub struct Encoder<'a, R> {
     reader: zio::Reader<R, raw::Encoder<'a>>,
 }
@@ -30,6 +40,27 @@ impl<R: Read> Decoder<'static, BufReader<R>> {
     }
 }
 
+impl<R: Read> Decoder<'static, PooledBufReader<R>> {
+    /// Creates a new decoder, using an input buffer from the given pool.
+    ///
+    /// The buffer goes back to the pool when the decoder is dropped.
+    pub fn new_with_pool(reader: R, pool: &BufferPool) -> io::Result<Self> {
+        let buffer_size = zstd_safe::DCtx::in_size();
+
+        Self::with_buffer(PooledBufReader::new(reader, buffer_size, pool))
+    }
+}
+
+impl Decoder<'static, Box<dyn BufRead + Send>> {
+    /// Creates a new decoder around a boxed `BufRead`.
+    ///
+    /// This gives a [`DynDecoder`](crate::stream::DynDecoder), which is the
+    /// same type whatever the source.
+    pub fn new_dyn(reader: Box<dyn BufRead + Send>) -> io::Result<Self> {
+        Self::with_buffer(reader)
+    }
+}
+
 impl<R: BufRead> Decoder<'static, R> {
     /// Creates a new decoder around a `BufRead`.
     pub fn with_buffer(reader: R) -> io::Result<Self> {
@@ -44,7 +75,52 @@ impl<R: BufRead> Decoder<'static, R> {
 
         Ok(Decoder { reader })
     }
+
+    /// Creates a new decoder, using a shared `DecoderDictionary`.
+    ///
+    /// The decoder keeps the dictionary alive, so it doesn't borrow
+    /// anything (unlike [`Decoder::with_prepared_dictionary`]).
+    pub fn with_shared_dictionary(
+        reader: R,
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let decoder = raw::Decoder::with_shared_dictionary(dictionary)?;
+        let reader = zio::Reader::new(reader, decoder);
+
+        Ok(Decoder { reader })
+    }
+
+    /// Creates a new decoder, selecting dictionaries from `registry`.
+    ///
+    /// Each frame uses the dictionary declared by its header. See
+    /// [`raw::Decoder::with_dictionaries`].
+    pub fn with_dictionaries(
+        reader: R,
+        registry: DictRegistry,
+    ) -> io::Result<Self> {
+        let decoder = raw::Decoder::with_dictionaries(registry)?;
+        let reader = zio::Reader::new(reader, decoder);
+
+        Ok(Decoder { reader })
+    }
+}
+
+impl<R: BufRead + Send + 'static> Decoder<'static, R> {
+    /// Returns this decoder as a boxed trait object.
+    ///
+    /// This is convenient to store decoders along with other readers.
+    pub fn into_boxed(self) -> Box<dyn Read + Send> {
+        Box::new(self)
+    }
 }
+
+impl<'a, R: Read> Decoder<'a, BufReader<R>> {
+    /// Returns a builder to configure a new decoder.
+    pub fn builder(reader: R) -> DecoderBuilder<'a, R> {
+        DecoderBuilder::new(reader)
+    }
+}
+
 impl<'a, R: BufRead> Decoder<'a, R> {
     /// Creates a new decoder which employs the provided context for deserialization.
     pub fn with_context(
@@ -59,6 +135,21 @@ impl<'a, R: BufRead> Decoder<'a, R> {
         }
     }
 
+    /// Creates a new decoder, finding dictionaries on demand.
+    ///
+    /// `resolver` is called with the dictionary ID of frames requiring a
+    /// dictionary the decoder doesn't have yet. See
+    /// [`raw::Decoder::set_dict_resolver`].
+    pub fn with_dict_resolver<F>(reader: R, resolver: F) -> io::Result<Self>
+    where
+        F: FnMut(u32) -> Option<Arc<DecoderDictionary<'static>>> + Send + 'a,
+    {
+        let mut decoder = raw::Decoder::new()?;
+        decoder.set_dict_resolver(resolver);
+        let reader = zio::Reader::new(reader, decoder);
+        Ok(Decoder { reader })
+    }
+
     /// Sets this `Decoder` to stop after the first frame.
     ///
     /// By default, it keeps concatenating frames until EOF is reached.
@@ -68,6 +159,48 @@ impl<'a, R: BufRead> Decoder<'a, R> {
         self
     }
 
+    /// Sets this `Decoder` to read at most `bytes` compressed bytes.
+    ///
+    /// Past this limit, the reader is treated as if it reached EOF, and is
+    /// never read further. This is useful to decode a stream embedded in a
+    /// container, when its compressed size is known: the reader is left
+    /// right after the stream.
+    ///
+    /// A frame cut short by the limit is reported as an error.
+    #[must_use]
+    pub fn with_input_limit(mut self, bytes: u64) -> Self {
+        self.reader.set_input_limit(Some(bytes));
+        self
+    }
+
+    /// Splits the input into frames, each read separately.
+    ///
+    /// See [`Frames`].
+    pub fn frames(mut self) -> Frames<'a, R> {
+        self.reader.set_single_frame();
+        Frames::new(self)
+    }
+
+    /// Lists the frames in the input, without decompressing them.
+    ///
+    /// Offsets start at the current position of the reader, so this should
+    /// be called before reading anything. See [`FrameBoundaries`].
+    pub fn frame_boundaries(self) -> FrameBoundaries<R> {
+        FrameBoundaries::new(self.reader.into_inner())
+    }
+
+    /// Sets the size of the read-ahead buffer. `0` disables it (the default).
+    ///
+    /// Reads smaller than this are served from an internal buffer of
+    /// decompressed data, filled `bytes` at a time. This costs an extra
+    /// copy, but is much faster when issuing many tiny reads (like
+    /// `read_exact` on small headers).
+    ///
+    /// Any buffered data is lost when using [`Decoder::finish`].
+    pub fn set_readahead(&mut self, bytes: usize) {
+        self.reader.set_readahead(bytes);
+    }
+
     /// Creates a new decoder, using an existing `DecoderDictionary`.
     ///
     /// The dictionary must be the same as the one used during compression.
@@ -133,6 +266,13 @@ impl<R: BufRead> Read for Decoder<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.reader.read(buf)
     }
+
+    fn read_vectored(
+        &mut self,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> io::Result<usize> {
+        self.reader.read_vectored(bufs)
+    }
 }
 
 impl<R: Read> Encoder<'static, BufReader<R>> {
@@ -144,6 +284,39 @@ impl<R: Read> Encoder<'static, BufReader<R>> {
     }
 }
 
+impl<R: Read + SizeHint> Encoder<'static, BufReader<R>> {
+    /// Creates a new encoder, pledging the size given by the reader.
+    ///
+    /// If the reader knows its size (like a regular file), it will be
+    /// written in the frame header. See [`Encoder::pledge_size_hint`].
+    ///
+    /// This can be disabled with [`Encoder::include_contentsize`], or by
+    /// calling `set_pledged_src_size(None)` before reading.
+    pub fn new_with_size_hint(reader: R, level: i32) -> io::Result<Self> {
+        let mut encoder = Self::new(reader, level)?;
+        encoder.pledge_size_hint()?;
+        Ok(encoder)
+    }
+}
+
+impl<R: Read> Encoder<'static, PooledBufReader<R>> {
+    /// Creates a new encoder, using an input buffer from the given pool.
+    ///
+    /// The buffer goes back to the pool when the encoder is dropped.
+    pub fn new_with_pool(
+        reader: R,
+        level: i32,
+        pool: &BufferPool,
+    ) -> io::Result<Self> {
+        let buffer_size = zstd_safe::CCtx::in_size();
+
+        Self::with_buffer(
+            PooledBufReader::new(reader, buffer_size, pool),
+            level,
+        )
+    }
+}
+
 impl<R: BufRead> Encoder<'static, R> {
     /// Creates a new encoder around a `BufRead`.
     pub fn with_buffer(reader: R, level: i32) -> io::Result<Self> {
@@ -163,6 +336,62 @@ impl<R: BufRead> Encoder<'static, R> {
 
         Ok(Encoder { reader })
     }
+
+    /// Creates a new encoder, configured with the given options.
+    pub fn with_options(reader: R, options: &Options) -> io::Result<Self> {
+        let encoder = raw::Encoder::with_options(options)?;
+        let reader = zio::Reader::new(reader, encoder);
+
+        Ok(Encoder { reader })
+    }
+
+    /// Creates a new encoder, using a shared `EncoderDictionary`.
+    ///
+    /// The encoder keeps the dictionary alive, so it doesn't borrow
+    /// anything (unlike [`Encoder::with_prepared_dictionary`]).
+    pub fn with_shared_dictionary(
+        reader: R,
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let encoder = raw::Encoder::with_shared_dictionary(dictionary)?;
+        let reader = zio::Reader::new(reader, encoder);
+
+        Ok(Encoder { reader })
+    }
+}
+
+impl<R: BufRead + Send + 'static> Encoder<'static, R> {
+    /// Returns this encoder as a boxed trait object.
+    ///
+    /// This is convenient to store encoders along with other readers.
+    pub fn into_boxed(self) -> Box<dyn Read + Send> {
+        Box::new(self)
+    }
+}
+
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+impl<'b> Encoder<'static, &'b [u8]> {
+    /// Creates a new encoder, compressing `input` without copying it.
+    ///
+    /// zstd usually copies its input into an internal buffer, since the
+    /// input may be gone by the next call. Here the whole input is already in
+    /// memory, so it is compressed in place (using
+    /// `CParameter::StableInBuffer`). This saves a copy of the input, and
+    /// the memory for zstd's input buffer.
+    ///
+    /// The size of the input is written in the frame header.
+    ///
+    /// [`Encoder::flush`] cannot be used on this encoder.
+    pub fn with_stable_input(input: &'b [u8], level: i32) -> io::Result<Self> {
+        let mut encoder = raw::Encoder::new(level)?;
+        encoder.set_parameter(zstd_safe::CParameter::StableInBuffer(true))?;
+
+        let mut reader = zio::Reader::new(input, encoder);
+        reader.set_stable_input();
+
+        Ok(Encoder { reader })
+    }
 }
 
 impl<'a, R: BufRead> Encoder<'a, R> {
@@ -213,6 +442,37 @@ impl<'a, R: BufRead> Encoder<'a, R> {
         self.reader.flush(out)
     }
 
+    /// Sets this `Encoder` to read at most `bytes` bytes from the reader.
+    ///
+    /// Past this limit, the reader is treated as if it reached EOF (which
+    /// ends the frame), and is never read further.
+    #[must_use]
+    pub fn with_input_limit(mut self, bytes: u64) -> Self {
+        self.reader.set_input_limit(Some(bytes));
+        self
+    }
+
+    /// Ends the current frame, without waiting for the end of the input.
+    ///
+    /// Following reads return the end of the frame, then `0`, even though
+    /// the reader may have more data. This is useful to cut a live stream
+    /// into self-contained chunks (for example to upload them separately).
+    ///
+    /// Once the frame was read entirely, [`Encoder::start_next_frame`]
+    /// resumes reading the input into a new frame.
+    pub fn finish_frame_now(&mut self) {
+        self.reader.finish_now();
+    }
+
+    /// Resumes compression into a new frame, after
+    /// [`Encoder::finish_frame_now`].
+    ///
+    /// Returns `false` (and does nothing) if the end of the current frame
+    /// was not read yet.
+    pub fn start_next_frame(&mut self) -> io::Result<bool> {
+        self.reader.restart()
+    }
+
     /// Return the inner `Read`.
     ///
     /// Calling `finish()` is not *required* after reading a stream -
@@ -224,17 +484,44 @@ impl<'a, R: BufRead> Encoder<'a, R> {
     crate::encoder_common!(reader);
 }
 
+impl<'a, R: BufRead + SizeHint> Encoder<'a, R> {
+    /// Pledges the size given by the reader, if it knows it.
+    ///
+    /// This must be called before reading anything from this encoder.
+    ///
+    /// Returns `true` if a size was pledged.
+    pub fn pledge_size_hint(&mut self) -> io::Result<bool> {
+        match self.reader.reader().size_hint() {
+            Some(size) => {
+                self.set_pledged_src_size(Some(size))?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
 impl<R: BufRead> Read for Encoder<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.reader.read(buf)
     }
+
+    fn read_vectored(
+        &mut self,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> io::Result<usize> {
+        self.reader.read_vectored(bufs)
+    }
 }
 
 fn _assert_traits() {
     use std::io::Cursor;
 
     fn _assert_send<T: Send>(_: T) {}
+    fn _assert_sync<T: Sync>(_: T) {}
 
     _assert_send(Decoder::new(Cursor::new(Vec::new())));
     _assert_send(Encoder::new(Cursor::new(Vec::new()), 1));
+    _assert_sync(Decoder::new(Cursor::new(Vec::new())));
+    _assert_sync(Encoder::new(Cursor::new(Vec::new()), 1));
 }
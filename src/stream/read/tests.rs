@@ -1,5 +1,5 @@
 use crate::stream::read::{Decoder, Encoder};
-use std::io::Read;
+use std::io::{self, Read};
 
 #[test]
 fn test_error_handling() {
@@ -8,7 +8,7 @@ fn test_error_handling() {
     let mut decoder = Decoder::new(&invalid_input[..]).unwrap();
     let output = decoder.read_to_end(&mut Vec::new());
 
-    assert_eq!(output.is_err(), true);
+    assert!(output.is_err());
 }
 
 #[test]
@@ -25,3 +25,752 @@ fn test_cycle() {
 
     assert_eq!(input, &buffer[..]);
 }
+
+#[test]
+fn test_frame_boundaries() {
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    compressed.extend(crate::encode_all(&b"barbaz"[..], 1).unwrap());
+
+    let boundaries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let sink = boundaries.clone();
+    decoder.on_frame_end(move |offset| sink.lock().unwrap().push(offset));
+
+    let mut buffer = Vec::new();
+    decoder.read_to_end(&mut buffer).unwrap();
+
+    assert_eq!(decoder.frames_decoded(), 2);
+    assert_eq!(decoder.total_out(), 9);
+    assert_eq!(*boundaries.lock().unwrap(), [3, 9]);
+}
+
+#[test]
+fn test_next_frame_end() {
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    crate::frame::write_skippable_frame(&mut compressed, 0, b"meta").unwrap();
+    compressed.extend(crate::encode_all(&b"barbaz"[..], 1).unwrap());
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.next_frame_end(), None);
+    decoder.track_frame_ends(true);
+
+    // A read can go past the end of a frame: the offsets tell where.
+    let mut output = Vec::new();
+    let mut ends = Vec::new();
+    let mut buffer = [0u8; 2];
+    loop {
+        let len = decoder.read(&mut buffer).unwrap();
+        output.extend_from_slice(&buffer[..len]);
+        while let Some(end) = decoder.next_frame_end() {
+            assert!(end <= output.len() as u64);
+            ends.push(end as usize);
+        }
+        if len == 0 {
+            break;
+        }
+    }
+    assert_eq!(ends, [3, 3, 9]);
+
+    let mut start = 0;
+    let entries: Vec<_> = ends
+        .iter()
+        .map(|&end| {
+            let entry = &output[start..end];
+            start = end;
+            entry
+        })
+        .collect();
+    assert_eq!(entries, [&b"foo"[..], b"", b"barbaz"]);
+}
+
+#[test]
+fn test_decode_limits() {
+    use crate::stream::raw::DecodeLimitExceeded;
+
+    let limit_error = |decoder: &mut Decoder<'_, _>| {
+        let err = io::copy(decoder, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        *err.get_ref()
+            .and_then(|e| e.downcast_ref::<DecodeLimitExceeded>())
+            .unwrap()
+    };
+
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    crate::frame::write_skippable_frame(&mut compressed, 0, b"meta").unwrap();
+    compressed.extend(crate::encode_all(&b"barbaz"[..], 1).unwrap());
+
+    // Exactly at the limits.
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.set_max_frames(Some(3));
+    decoder.set_max_skippable_bytes(Some(12));
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"foobarbaz");
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.set_max_frames(Some(2));
+    assert_eq!(
+        limit_error(&mut decoder),
+        DecodeLimitExceeded::Frames { limit: 2 }
+    );
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.set_max_skippable_bytes(Some(11));
+    assert_eq!(
+        limit_error(&mut decoder),
+        DecodeLimitExceeded::SkippableBytes { limit: 11 }
+    );
+
+    // Skippable frames given to a callback are limited by default.
+    let mut large = Vec::new();
+    crate::frame::write_skippable_frame(&mut large, 0, &vec![0; 16 << 20])
+        .unwrap();
+    let mut decoder = Decoder::new(&large[..]).unwrap();
+    decoder.on_skippable_frame(|_, _| ());
+    assert_eq!(
+        limit_error(&mut decoder),
+        DecodeLimitExceeded::SkippableBytes { limit: 16 << 20 }
+    );
+    let mut decoder = Decoder::new(&large[..]).unwrap();
+    decoder.on_skippable_frame(|_, _| ());
+    decoder.set_max_skippable_bytes(None);
+    io::copy(&mut decoder, &mut io::sink()).unwrap();
+
+    // Empty frames are frames too.
+    let empty = crate::encode_all(&b""[..], 1).unwrap();
+    let many = empty.repeat(100);
+    let mut decoder = Decoder::new(&many[..]).unwrap();
+    decoder.set_max_frames(Some(10));
+    assert_eq!(
+        limit_error(&mut decoder),
+        DecodeLimitExceeded::Frames { limit: 10 }
+    );
+}
+
+#[test]
+fn test_builder() {
+    use crate::stream::raw::DecodeLimitExceeded;
+    use crate::stream::ChecksumPolicy;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let dictionary = &text[..500];
+    let mut compressed =
+        crate::stream::encode_all_with_dictionary(&text[..], 3, dictionary)
+            .unwrap();
+    compressed.extend(compressed.clone());
+
+    let decode = |decoder: Decoder<'_, _>| {
+        let mut decoder = decoder;
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).map(|_| decompressed)
+    };
+
+    let decoder = Decoder::builder(&compressed[..])
+        .dictionary(dictionary)
+        .window_log_max(21)
+        .single_frame()
+        .build()
+        .unwrap();
+    assert_eq!(decode(decoder).unwrap(), &text[..]);
+
+    let decoder = Decoder::builder(&compressed[..])
+        .dictionary(dictionary)
+        .max_frames(1)
+        .build()
+        .unwrap();
+    let err = decode(decoder).unwrap_err();
+    assert_eq!(
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<DecodeLimitExceeded>()),
+        Some(&DecodeLimitExceeded::Frames { limit: 1 })
+    );
+
+    // `encode_all` doesn't write checksums.
+    let decoder = Decoder::builder(&compressed[..])
+        .dictionary(dictionary)
+        .checksum_policy(ChecksumPolicy::FramePlusVerifyOnDecode)
+        .build()
+        .unwrap();
+    assert!(decode(decoder).is_err());
+}
+
+#[test]
+fn test_frame_walk() {
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    let first_size = compressed.len() as u64;
+    crate::frame::write_skippable_frame(&mut compressed, 0, b"meta").unwrap();
+    compressed.extend(crate::encode_all(&b"barbaz"[..], 1).unwrap());
+
+    let boundaries: Vec<_> = Decoder::new(&compressed[..])
+        .unwrap()
+        .frame_boundaries()
+        .map(|boundary| {
+            let boundary = boundary.unwrap();
+            (boundary.offset, boundary.header.is_some())
+        })
+        .collect();
+    assert_eq!(
+        boundaries,
+        [(0, true), (first_size, false), (first_size + 12, true)]
+    );
+
+    // Skip frames on a non-seekable input, even after a partial read.
+    let mut frames = Decoder::new(&compressed[..]).unwrap().frames();
+    let mut byte = [0u8];
+    frames
+        .next_frame()
+        .unwrap()
+        .unwrap()
+        .read_exact(&mut byte)
+        .unwrap();
+    assert_eq!(&byte, b"f");
+    let (header, size) = frames.skip_frame().unwrap().unwrap();
+    assert_eq!((header, size), (None, 12));
+
+    let mut content = Vec::new();
+    let mut frame = frames.next_frame().unwrap().unwrap();
+    frame.read_to_end(&mut content).unwrap();
+    assert_eq!(content, b"barbaz");
+    assert!(frames.skip_frame().unwrap().is_none());
+}
+
+#[test]
+fn test_skippable_frames() {
+    use std::sync::{Arc, Mutex};
+
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    crate::frame::write_skippable_frame(&mut compressed, 3, b"meta").unwrap();
+    compressed.extend(crate::encode_all(&b"bar"[..], 1).unwrap());
+
+    // Read one byte at a time, to split the skippable frame.
+    let reader = std::io::BufReader::with_capacity(1, &compressed[..]);
+    let mut decoder = Decoder::with_buffer(reader).unwrap();
+    let payloads = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&payloads);
+    decoder.on_skippable_frame(move |magic_variant, payload| {
+        sink.lock().unwrap().push((magic_variant, payload.to_vec()))
+    });
+
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"foobar");
+    assert_eq!(*payloads.lock().unwrap(), [(3, b"meta".to_vec())]);
+    assert_eq!(decoder.frames_decoded(), 3);
+    assert_eq!(decoder.total_in(), compressed.len() as u64);
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.ignore_skippable_frames(false);
+    let mut output = Vec::new();
+    assert!(decoder.read_to_end(&mut output).is_err());
+    assert_eq!(output, b"foo");
+}
+
+#[test]
+fn test_checksum_policy() {
+    use crate::stream::{write, ChecksumPolicy};
+    use std::io::Write;
+
+    let mut encoder = write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder
+        .set_checksum_policy(ChecksumPolicy::FramePlusVerifyOnDecode)
+        .unwrap();
+    encoder.write_all(b"checked").unwrap();
+    let checked = encoder.finish().unwrap();
+    let unchecked = crate::encode_all(&b"unchecked"[..], 1).unwrap();
+
+    let decode = |input: &[u8], policy| {
+        let mut decoder = Decoder::new(input).unwrap();
+        decoder.set_checksum_policy(policy);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).map(|_| output)
+    };
+
+    let strict = ChecksumPolicy::FramePlusVerifyOnDecode;
+    assert_eq!(decode(&checked, strict).unwrap(), b"checked");
+    assert!(decode(&unchecked, strict).is_err());
+    assert_eq!(
+        decode(&unchecked, ChecksumPolicy::Frame).unwrap(),
+        b"unchecked"
+    );
+}
+
+#[test]
+fn test_current_frame_dict_id() {
+    use crate::frame::FrameHeader;
+
+    let mut input = crate::encode_all(&b"no dictionary"[..], 1).unwrap();
+    FrameHeader {
+        content_size: Some(10),
+        dict_id: Some(0x1234),
+        ..FrameHeader::default()
+    }
+    .write(&mut input)
+    .unwrap();
+    input.extend_from_slice(&[0; 8]);
+
+    // Whole input at once, and split headers.
+    for capacity in [input.len(), 1] {
+        let reader = std::io::BufReader::with_capacity(capacity, &input[..]);
+        let mut decoder = Decoder::with_buffer(reader).unwrap();
+        assert_eq!(decoder.current_frame_dict_id(), None);
+
+        let mut output = Vec::new();
+        assert!(decoder.read_to_end(&mut output).is_err());
+        assert_eq!(output, b"no dictionary");
+        assert_eq!(decoder.current_frame_dict_id(), Some(0x1234));
+    }
+}
+
+#[test]
+fn test_content_size() {
+    let text = include_bytes!("../../../assets/example.txt");
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder
+        .set_pledged_src_size(Some(text.len() as u64))
+        .unwrap();
+    std::io::Write::write_all(&mut encoder, text).unwrap();
+    let mut compressed = encoder.finish().unwrap();
+    // A second frame, without declared size.
+    compressed.extend(crate::encode_all(&b"unknown size"[..], 1).unwrap());
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.content_size(), None);
+
+    let mut buffer = [0; 16];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(decoder.content_size(), Some(text.len() as u64));
+
+    decoder.read_to_end(&mut Vec::new()).unwrap();
+    assert_eq!(decoder.content_size(), None);
+}
+
+#[test]
+fn test_enforce_content_size() {
+    use crate::stream::raw::ContentSizeMismatch;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder
+        .set_pledged_src_size(Some(text.len() as u64))
+        .unwrap();
+    std::io::Write::write_all(&mut encoder, text).unwrap();
+    let frame = encoder.finish().unwrap();
+
+    // Frames with and without declared size.
+    let mut input = frame.clone();
+    input.extend(crate::encode_all(&b"unknown size"[..], 1).unwrap());
+    input.extend_from_slice(&frame);
+
+    let mut decoder = Decoder::builder(&input[..])
+        .enforce_content_size(true)
+        .build()
+        .unwrap();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output.len(), 2 * text.len() + 12);
+
+    // Declare one byte more than a large frame holds.
+    let large: Vec<u8> = text.iter().copied().cycle().take(1 << 20).collect();
+    let frame = crate::encode_all(&large[..], 1).unwrap();
+    let (mut header, header_size) =
+        crate::frame::FrameHeader::parse(&frame).unwrap();
+    header.window_size = Some(1 << 20);
+    header.content_size = Some(large.len() as u64 + 1);
+    let mut tampered = Vec::new();
+    header.write(&mut tampered).unwrap();
+    tampered.extend_from_slice(&frame[header_size..]);
+
+    // zstd alone doesn't notice.
+    let mut output = Vec::new();
+    Decoder::new(&tampered[..])
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    assert_eq!(output, large);
+
+    let mut decoder = Decoder::new(&tampered[..]).unwrap();
+    decoder.enforce_content_size(true);
+    let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.get_ref().unwrap().downcast_ref(),
+        Some(&ContentSizeMismatch {
+            expected: large.len() as u64 + 1,
+            actual: large.len() as u64,
+        })
+    );
+}
+
+#[test]
+fn test_required_window_size() {
+    use crate::stream::raw::DecodeError;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder.window_log(24).unwrap();
+    std::io::Write::write_all(&mut encoder, text).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.window_log_max(20).unwrap();
+    decoder.set_error_context(true);
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(decoder.required_window_size(), Some(1 << 24));
+
+    let context = err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<DecodeError>())
+        .unwrap();
+    assert_eq!(context.required_window_size(), Some(1 << 24));
+    assert_eq!(context.window_log_max(), Some(20));
+
+    // Raising the limit is enough.
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.window_log_max(24).unwrap();
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, text);
+}
+
+#[test]
+fn test_error_context() {
+    use crate::stream::raw::DecodeError;
+
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    let valid_len = compressed.len() as u64;
+    compressed.extend_from_slice(b"Abcdefghabcdefgh");
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.set_error_context(true);
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    let context = err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<DecodeError>())
+        .unwrap();
+    assert_eq!(context.frame_index(), 1);
+    assert_eq!(context.decompressed_offset(), 3);
+    assert!(context.compressed_offset() >= valid_len);
+    assert_eq!(
+        context.zstd_error_kind(),
+        Some(zstd_safe::ErrorKind::PrefixUnknown)
+    );
+    assert!(err.to_string().contains("frame 1"));
+
+    // Disabled by default.
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert!(err
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<DecodeError>()
+        .is_none());
+    // Without context, the error is classified directly.
+    assert_eq!(
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<zstd_safe::Error>())
+            .map(|e| e.kind()),
+        Some(zstd_safe::ErrorKind::PrefixUnknown)
+    );
+}
+
+#[test]
+fn test_input_limit() {
+    let text = include_bytes!("../../../assets/example.txt");
+    let compressed = crate::encode_all(&text[..], 1).unwrap();
+
+    // The stream is embedded in a container, followed by more data.
+    let container = [&b"HEAD"[..], &compressed, b"TAIL"].concat();
+    let mut reader = &container[4..];
+
+    let mut decoder = Decoder::with_buffer(&mut reader)
+        .unwrap()
+        .with_input_limit(compressed.len() as u64);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, text);
+    assert_eq!(reader, b"TAIL");
+
+    // A truncated stream is an error.
+    let mut decoder = Decoder::with_buffer(&compressed[..])
+        .unwrap()
+        .with_input_limit(compressed.len() as u64 - 1);
+    assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+
+    let mut encoder = Encoder::with_buffer(&text[..], 1)
+        .unwrap()
+        .with_input_limit(100);
+    let mut compressed = Vec::new();
+    encoder.read_to_end(&mut compressed).unwrap();
+    assert_eq!(crate::decode_all(&compressed[..]).unwrap(), &text[..100]);
+}
+
+#[test]
+fn test_finish_frame_now() {
+    let text = include_bytes!("../../../assets/example.txt");
+    let input: Vec<u8> =
+        text.iter().cycle().take(1_000_000).copied().collect();
+
+    // A small buffer, so the input is not read all at once.
+    let reader = std::io::BufReader::with_capacity(1000, &input[..]);
+    let mut encoder = Encoder::with_buffer(reader, 1).unwrap();
+
+    let mut first = vec![0; 100];
+    let n = encoder.read(&mut first).unwrap();
+    first.truncate(n);
+    encoder.finish_frame_now();
+    assert!(!encoder.start_next_frame().unwrap());
+    encoder.read_to_end(&mut first).unwrap();
+
+    // The first frame is complete on its own.
+    let first_content = crate::decode_all(&first[..]).unwrap();
+    assert!(!first_content.is_empty());
+    assert!(first_content.len() < input.len());
+
+    assert!(encoder.start_next_frame().unwrap());
+    let mut second = Vec::new();
+    encoder.read_to_end(&mut second).unwrap();
+
+    first.extend(second);
+    assert_eq!(crate::decode_all(&first[..]).unwrap(), input);
+}
+
+#[test]
+fn test_size_hint() {
+    use std::io::{Seek, SeekFrom};
+
+    let mut file = std::fs::File::open("assets/example.txt").unwrap();
+    file.seek(SeekFrom::Start(100)).unwrap();
+    let len = file.metadata().unwrap().len() - 100;
+
+    let mut encoder = Encoder::new_with_size_hint(file, 1).unwrap();
+    let mut compressed = Vec::new();
+    encoder.read_to_end(&mut compressed).unwrap();
+    let content_size = zstd_safe::get_frame_content_size(&compressed);
+    assert_eq!(content_size.unwrap(), Some(len));
+
+    // Devices don't have a known size.
+    #[cfg(unix)]
+    {
+        let file = std::fs::File::open("/dev/null").unwrap();
+        let mut encoder = Encoder::new(file, 1).unwrap();
+        assert!(!encoder.pledge_size_hint().unwrap());
+        let mut compressed = Vec::new();
+        encoder.read_to_end(&mut compressed).unwrap();
+        let content_size = zstd_safe::get_frame_content_size(&compressed);
+        assert_eq!(content_size.unwrap(), None);
+    }
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn test_stable_input() {
+    // Several blocks, read through a small buffer.
+    let text = include_bytes!("../../../assets/example.txt");
+    let input: Vec<u8> = (0..1000u32)
+        .flat_map(|i| text.iter().map(move |&b| b ^ (i as u8 & 1)))
+        .collect();
+
+    let mut encoder = Encoder::with_stable_input(&input, 1).unwrap();
+    let mut compressed = Vec::new();
+    let mut buffer = [0u8; 100];
+    loop {
+        let n = encoder.read(&mut buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        compressed.extend_from_slice(&buffer[..n]);
+    }
+    assert!(encoder.finish().is_empty());
+
+    let content_size = zstd_safe::get_frame_content_size(&compressed);
+    assert_eq!(content_size.unwrap(), Some(input.len() as u64));
+    assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+}
+
+#[cfg(feature = "zdict_builder")]
+#[test]
+fn test_dict_resolver() {
+    use crate::dict::DecoderDictionary;
+    use std::sync::{Arc, Mutex};
+
+    // Trained dictionaries have an ID, unlike raw content.
+    let samples: Vec<Vec<u8>> = ["src/lib.rs", "src/stream/raw.rs"]
+        .iter()
+        .map(|path| std::fs::read(path).unwrap())
+        .collect();
+    let dictionaries: Vec<Vec<u8>> = samples
+        .iter()
+        .map(|sample| {
+            let chunks: Vec<&[u8]> = sample.chunks(256).collect();
+            crate::dict::from_samples(&chunks, 4000).unwrap()
+        })
+        .collect();
+    let ids: Vec<u32> = dictionaries
+        .iter()
+        .map(|dictionary| {
+            zstd_safe::get_dict_id_from_dict(dictionary).unwrap()
+        })
+        .map(u32::from)
+        .collect();
+
+    let mut compressed = Vec::new();
+    for (i, sample) in samples.iter().enumerate() {
+        let dictionary = &dictionaries[i % 2];
+        compressed.extend(
+            crate::bulk::Compressor::with_dictionary(1, dictionary)
+                .unwrap()
+                .compress(sample)
+                .unwrap(),
+        );
+    }
+    compressed.extend(crate::encode_all(&samples[0][..], 1).unwrap());
+    compressed.extend(
+        crate::bulk::Compressor::with_dictionary(1, &dictionaries[0])
+            .unwrap()
+            .compress(&samples[0])
+            .unwrap(),
+    );
+
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    let log = requested.clone();
+    let mut decoder =
+        Decoder::with_dict_resolver(&compressed[..], move |id| {
+            log.lock().unwrap().push(id);
+            let i = ids.iter().position(|&known| known == id)?;
+            Some(Arc::new(DecoderDictionary::copy(&dictionaries[i])))
+        })
+        .unwrap();
+
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    let expected = [&samples[0][..], &samples[1], &samples[0], &samples[0]];
+    assert_eq!(output, expected.concat());
+
+    // Each dictionary is only resolved once.
+    assert_eq!(requested.lock().unwrap().len(), 2);
+}
+
+#[cfg(feature = "zdict_builder")]
+#[test]
+fn test_with_dictionaries() {
+    use crate::dict::{DecoderDictionary, DictRegistry};
+    use std::sync::Arc;
+
+    let samples: Vec<Vec<u8>> = ["src/lib.rs", "src/stream/raw.rs"]
+        .iter()
+        .map(|path| std::fs::read(path).unwrap())
+        .collect();
+    let dictionaries: Vec<Vec<u8>> = samples
+        .iter()
+        .map(|sample| {
+            let chunks: Vec<&[u8]> = sample.chunks(256).collect();
+            crate::dict::from_samples(&chunks, 4000).unwrap()
+        })
+        .collect();
+
+    let mut registry = DictRegistry::new();
+    for dictionary in &dictionaries {
+        let dictionary = Arc::new(DecoderDictionary::copy(dictionary));
+        assert!(registry.insert(dictionary).unwrap().is_none());
+    }
+    assert_eq!(registry.len(), 2);
+    // Raw content has no ID.
+    let raw = Arc::new(DecoderDictionary::copy(b"raw content"));
+    assert!(registry.insert(raw).is_err());
+
+    let mut compressed = Vec::new();
+    for (sample, dictionary) in samples.iter().zip(dictionaries.iter().rev()) {
+        compressed.extend(
+            crate::bulk::Compressor::with_dictionary(1, dictionary)
+                .unwrap()
+                .compress(sample)
+                .unwrap(),
+        );
+    }
+    compressed.extend(crate::encode_all(&samples[0][..], 1).unwrap());
+
+    let mut decoder =
+        Decoder::with_dictionaries(&compressed[..], registry.clone()).unwrap();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    let expected = [&samples[0][..], &samples[1], &samples[0]];
+    assert_eq!(output, expected.concat());
+
+    // Without the right dictionary, decoding fails.
+    let id = zstd_safe::get_dict_id_from_dict(&dictionaries[1]).unwrap();
+    registry.remove(id.get()).unwrap();
+    let mut decoder =
+        Decoder::with_dictionaries(&compressed[..], registry).unwrap();
+    assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_frames() {
+    let contents: [&[u8]; 4] = [b"metadata", b"", b"first data", b"last"];
+    let mut compressed = Vec::new();
+    for content in &contents {
+        compressed.extend(crate::encode_all(*content, 1).unwrap());
+    }
+
+    let mut frames = Decoder::new(&compressed[..]).unwrap().frames();
+    for content in &contents {
+        let mut frame = frames.next_frame().unwrap().unwrap();
+        let mut buffer = Vec::new();
+        frame.read_to_end(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], *content);
+    }
+    assert!(frames.next_frame().unwrap().is_none());
+
+    // Frames not entirely read are skipped.
+    let mut frames = Decoder::new(&compressed[..]).unwrap().frames();
+    let mut byte = [0u8];
+    frames
+        .next_frame()
+        .unwrap()
+        .unwrap()
+        .read_exact(&mut byte)
+        .unwrap();
+    frames.next_frame().unwrap().unwrap();
+    let mut buffer = Vec::new();
+    let mut frame = frames.next_frame().unwrap().unwrap();
+    frame.read_to_end(&mut buffer).unwrap();
+    assert_eq!(&buffer[..], contents[2]);
+}
+
+#[test]
+fn test_read_vectored() {
+    use std::io::IoSliceMut;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let compressed = crate::encode_all(&text[..], 1).unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let mut header = [0u8; 10];
+    let mut body = vec![0u8; text.len()];
+    let n = decoder
+        .read_vectored(&mut [
+            IoSliceMut::new(&mut header),
+            IoSliceMut::new(&mut []),
+            IoSliceMut::new(&mut body),
+        ])
+        .unwrap();
+    assert!(n > header.len());
+    let mut output = header.to_vec();
+    output.extend_from_slice(&body[..n - header.len()]);
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, text);
+
+    // Errors after some output are reported by the next call.
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    compressed.extend_from_slice(b"Abcdefghabcdefgh");
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let mut first = [0u8; 3];
+    let mut second = [0u8; 10];
+    let mut bufs = [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)];
+    assert_eq!(decoder.read_vectored(&mut bufs).unwrap(), 3);
+    assert!(decoder.read_vectored(&mut bufs).is_err());
+    assert_eq!(&first, b"foo");
+}
@@ -0,0 +1,284 @@
+//! Compress and decompress sequences of records, one frame per record.
+//!
+//! This is a common way to frame messages: each record is compressed
+//! independently (usually with a dictionary, since records tend to be
+//! small), and the frames are simply concatenated. The end of each frame
+//! marks the end of a record, so no extra length prefix is needed.
+//!
+//! The compression and decompression contexts are re-used between records.
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::bulk::Compressor;
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+
+/// Writes each record as a complete zstd frame.
+pub struct Encoder<'a, W> {
+    writer: W,
+    compressor: Compressor<'a>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Encoder<'static, W> {
+    /// Creates a new record encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> io::Result<Self> {
+        Self::with_dictionary(writer, level, &[])
+    }
+
+    /// Creates a new record encoder, using an existing dictionary.
+    ///
+    /// The same dictionary will be required to decompress the records.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn with_dictionary(
+        writer: W,
+        level: i32,
+        dictionary: &[u8],
+    ) -> io::Result<Self> {
+        let compressor = Compressor::with_dictionary(level, dictionary)?;
+        Ok(Self::with_compressor(writer, compressor))
+    }
+}
+
+impl<'a, W: Write> Encoder<'a, W> {
+    /// Creates a new record encoder around the given compressor.
+    pub fn with_compressor(writer: W, compressor: Compressor<'a>) -> Self {
+        Encoder {
+            writer,
+            compressor,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Creates a new record encoder, using an existing `EncoderDictionary`.
+    ///
+    /// The same dictionary will be required to decompress the records.
+    pub fn with_prepared_dictionary<'b>(
+        writer: W,
+        dictionary: &'a EncoderDictionary<'b>,
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let compressor = Compressor::with_prepared_dictionary(dictionary)?;
+        Ok(Self::with_compressor(writer, compressor))
+    }
+
+    /// Compresses `record` and writes it as a single frame.
+    pub fn put(&mut self, record: &[u8]) -> io::Result<()> {
+        self.buffer.clear();
+        self.buffer.reserve(zstd_safe::compress_bound(record.len()));
+        self.compressor
+            .compress_to_buffer(record, &mut self.buffer)?;
+        self.writer.write_all(&self.buffer)
+    }
+
+    /// Flushes the underlying writer.
+    ///
+    /// Records are always written completely by [`Encoder::put`], so this
+    /// doesn't need to be called between records.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Sets the given zstd compression parameter.
+    pub fn set_parameter(
+        &mut self,
+        parameter: zstd_safe::CParameter,
+    ) -> io::Result<()> {
+        self.compressor.set_parameter(parameter)
+    }
+
+    crate::encoder_parameters!();
+}
+
+/// Reads records written by [`Encoder`], one frame at a time.
+///
+/// This is an iterator over the decompressed records. Once an error is
+/// returned, the iteration stops.
+///
+/// Skippable frames are returned as empty records.
+pub struct Decoder<'a, R> {
+    reader: R,
+    operation: raw::Decoder<'a>,
+    failed: bool,
+}
+
+impl<R: Read> Decoder<'static, BufReader<R>> {
+    /// Creates a new record decoder.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let buffer_size = zstd_safe::DCtx::in_size();
+
+        Self::with_buffer(BufReader::with_capacity(buffer_size, reader))
+    }
+}
+
+impl<R: BufRead> Decoder<'static, R> {
+    /// Creates a new record decoder around a `BufRead`.
+    pub fn with_buffer(reader: R) -> io::Result<Self> {
+        Self::with_dictionary(reader, &[])
+    }
+
+    /// Creates a new record decoder, using an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_dictionary(reader: R, dictionary: &[u8]) -> io::Result<Self> {
+        let operation = raw::Decoder::with_dictionary(dictionary)?;
+        Ok(Self::with_decoder(reader, operation))
+    }
+}
+
+impl<'a, R: BufRead> Decoder<'a, R> {
+    /// Creates a new record decoder around the given raw decoder.
+    pub fn with_decoder(reader: R, operation: raw::Decoder<'a>) -> Self {
+        Decoder {
+            reader,
+            operation,
+            failed: false,
+        }
+    }
+
+    /// Creates a new record decoder, using an existing `DecoderDictionary`.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    pub fn with_prepared_dictionary<'b>(
+        reader: R,
+        dictionary: &DecoderDictionary<'b>,
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let operation = raw::Decoder::with_prepared_dictionary(dictionary)?;
+        Ok(Self::with_decoder(reader, operation))
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    ///
+    /// It is positioned right after the last record returned.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Sets a decompression parameter on the decompression stream.
+    pub fn set_parameter(
+        &mut self,
+        parameter: zstd_safe::DParameter,
+    ) -> io::Result<()> {
+        self.operation.set_parameter(parameter)
+    }
+
+    crate::decoder_parameters!();
+
+    /// Reads and decompresses the next record.
+    ///
+    /// Returns `Ok(None)` at the end of the input.
+    fn read_record(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let mut record = Vec::new();
+        loop {
+            let input = self.reader.fill_buf()?;
+            if input.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "incomplete record",
+                ));
+            }
+
+            if record.capacity() - record.len() < zstd_safe::DCtx::out_size() {
+                record.reserve(zstd_safe::DCtx::out_size());
+            }
+            let pos = record.len();
+            let mut src = InBuffer::around(input);
+            let mut dst = OutBuffer::around_pos(&mut record, pos);
+            let hint = self.operation.run(&mut src, &mut dst)?;
+            let consumed = src.pos();
+            self.reader.consume(consumed);
+
+            if hint == 0 {
+                self.operation.reinit()?;
+                return Ok(Some(record));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Decoder<'_, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let result = self.read_record().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Encoder};
+
+    #[test]
+    fn test_records() {
+        let text = include_bytes!("../../assets/example.txt");
+        let dictionary = &text[..512];
+        let records: Vec<&[u8]> =
+            vec![b"", &text[..10], &text[512..], text, b"last"];
+
+        let mut encoder =
+            Encoder::with_dictionary(Vec::new(), 3, dictionary).unwrap();
+        for record in &records {
+            encoder.put(record).unwrap();
+        }
+        let compressed = encoder.into_inner();
+
+        let decoder =
+            Decoder::with_dictionary(&compressed[..], dictionary).unwrap();
+        let decoded: Vec<Vec<u8>> = decoder.collect::<Result<_, _>>().unwrap();
+        assert_eq!(decoded, records);
+
+        // A truncated record is an error.
+        let truncated = &compressed[..compressed.len() - 1];
+        let mut decoder =
+            Decoder::with_dictionary(truncated, dictionary).unwrap();
+        for _ in 0..records.len() - 1 {
+            decoder.next().unwrap().unwrap();
+        }
+        assert!(decoder.next().unwrap().is_err());
+        assert!(decoder.next().is_none());
+    }
+}
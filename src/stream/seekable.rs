@@ -0,0 +1,811 @@
+//! Compress and decompress in zstd's seekable format.
+//!
+//! A seekable stream is a regular zstd stream, made of independent frames of
+//! bounded decompressed size, followed by a seek table in a skippable frame.
+//! Regular decoders can read it as usual. [`Decoder`] uses the seek table to
+//! read any range of the decompressed data, only decompressing the frames
+//! involved.
+//!
+//! This is compatible with the format from zstd's `contrib/seekable_format`.
+//!
+//! ```rust
+//! use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+//! use zstd::stream::seekable::{Decoder, Encoder};
+//!
+//! let mut encoder = Encoder::new(Vec::new(), 1).unwrap().frame_size(1024);
+//! for i in 0..1000 {
+//!     writeln!(encoder, "line {}", i).unwrap();
+//! }
+//! let compressed = encoder.finish().unwrap();
+//!
+//! let mut decoder = Decoder::new(Cursor::new(compressed)).unwrap();
+//! decoder.seek(SeekFrom::Start(8881)).unwrap();
+//! let mut line = [0u8; 9];
+//! decoder.read_exact(&mut line).unwrap();
+//! assert_eq!(&line, b"line 999\n");
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::bulk::Decompressor;
+use crate::frame::{self, SkippableHeader};
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+use crate::xxhash::XxHash64;
+use crate::Options;
+
+pub use crate::frame::SEEK_TABLE_MAGIC_VARIANT;
+
+/// Magic number ending the seek table.
+pub const SEEKABLE_MAGIC_NUMBER: u32 = 0x8F92_EAB1;
+
+/// Largest decompressed size of a frame.
+pub const FRAME_SIZE_MAX: u32 = 1 << 30;
+
+/// Default decompressed size of each frame.
+const FRAME_SIZE_DEFAULT: u32 = 1 << 20;
+
+/// Size of the footer ending the seek table.
+const FOOTER_SIZE: u64 = 9;
+
+/// Bit of the seek table descriptor indicating checksums.
+const CHECKSUM_FLAG: u8 = 0x80;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Position and size of a frame in a seekable stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeekFrame {
+    /// Offset of the frame in the compressed stream.
+    pub compressed_offset: u64,
+    /// Size of the compressed frame.
+    pub compressed_size: u32,
+    /// Offset of the content of the frame in the decompressed data.
+    pub decompressed_offset: u64,
+    /// Size of the decompressed content of the frame.
+    pub decompressed_size: u32,
+    /// Lowest 32 bits of the XXH64 of the decompressed content, if the
+    /// seek table includes checksums.
+    pub checksum: Option<u32>,
+}
+
+/// The seek table of a seekable stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeekTable {
+    frames: Vec<SeekFrame>,
+    checksums: bool,
+}
+
+impl SeekTable {
+    /// Reads the seek table at the end of `reader`.
+    ///
+    /// The position of `reader` afterwards is unspecified.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < SkippableHeader::SIZE as u64 + FOOTER_SIZE {
+            return Err(invalid_data("input is too small for a seek table"));
+        }
+
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+        reader.read_exact(&mut footer)?;
+        if read_u32(&footer[5..]) != SEEKABLE_MAGIC_NUMBER {
+            return Err(invalid_data("seek table not found"));
+        }
+        let num_frames = u64::from(read_u32(&footer));
+        let descriptor = footer[4];
+        if descriptor & 0x7C != 0 {
+            return Err(invalid_data("seek table uses reserved bits"));
+        }
+        let checksums = descriptor & CHECKSUM_FLAG != 0;
+
+        let entry_size = if checksums { 12 } else { 8 };
+        let table_size = num_frames * entry_size + FOOTER_SIZE;
+        let table_start = end
+            .checked_sub(table_size + SkippableHeader::SIZE as u64)
+            .ok_or_else(|| invalid_data("seek table is truncated"))?;
+
+        let mut header = [0u8; SkippableHeader::SIZE];
+        reader.seek(SeekFrom::Start(table_start))?;
+        reader.read_exact(&mut header)?;
+        match SkippableHeader::parse(&header)? {
+            Some(header)
+                if header.magic_variant == SEEK_TABLE_MAGIC_VARIANT
+                    && u64::from(header.size) == table_size => {}
+            _ => return Err(invalid_data("seek table header is invalid")),
+        }
+
+        let mut entries = vec![0u8; (num_frames * entry_size) as usize];
+        reader.read_exact(&mut entries)?;
+
+        let mut table = SeekTable {
+            frames: Vec::with_capacity(num_frames as usize),
+            checksums,
+        };
+        for entry in entries.chunks(entry_size as usize) {
+            let checksum = if checksums {
+                Some(read_u32(&entry[8..]))
+            } else {
+                None
+            };
+            let decompressed_size = read_u32(&entry[4..]);
+            if decompressed_size > FRAME_SIZE_MAX {
+                return Err(invalid_data("seek table frame is too large"));
+            }
+            table.push(read_u32(entry), decompressed_size, checksum);
+        }
+
+        if table.compressed_size() > table_start {
+            return Err(invalid_data("seek table does not match the input"));
+        }
+        Ok(table)
+    }
+
+    /// Returns all the frames, in order.
+    pub fn frames(&self) -> &[SeekFrame] {
+        &self.frames
+    }
+
+    /// Returns `true` if the seek table includes checksums.
+    pub fn has_checksums(&self) -> bool {
+        self.checksums
+    }
+
+    /// Returns the index of the frame containing the given decompressed
+    /// offset, or `None` if it is past the end.
+    pub fn frame_index_at(&self, decompressed_offset: u64) -> Option<usize> {
+        let index = self.frames.partition_point(|frame| {
+            frame.decompressed_offset + u64::from(frame.decompressed_size)
+                <= decompressed_offset
+        });
+        if index < self.frames.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the total size of the frames, without the seek table.
+    pub fn compressed_size(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| {
+            frame.compressed_offset + u64::from(frame.compressed_size)
+        })
+    }
+
+    /// Returns the total size of the decompressed data.
+    pub fn decompressed_size(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| {
+            frame.decompressed_offset + u64::from(frame.decompressed_size)
+        })
+    }
+
+    /// Adds a frame after the current ones.
+    fn push(
+        &mut self,
+        compressed_size: u32,
+        decompressed_size: u32,
+        checksum: Option<u32>,
+    ) {
+        self.frames.push(SeekFrame {
+            compressed_offset: self.compressed_size(),
+            compressed_size,
+            decompressed_offset: self.decompressed_size(),
+            decompressed_size,
+            checksum,
+        });
+    }
+
+    /// Appends the seek table, as a skippable frame, to `output`.
+    fn write(&self, output: &mut Vec<u8>) -> io::Result<()> {
+        let mut table = Vec::new();
+        for frame in &self.frames {
+            table.extend_from_slice(&frame.compressed_size.to_le_bytes());
+            table.extend_from_slice(&frame.decompressed_size.to_le_bytes());
+            if let Some(checksum) = frame.checksum {
+                table.extend_from_slice(&checksum.to_le_bytes());
+            }
+        }
+        let num_frames = u32::try_from(self.frames.len())
+            .map_err(|_| invalid_data("too many frames for a seek table"))?;
+        table.extend_from_slice(&num_frames.to_le_bytes());
+        table.push(if self.checksums { CHECKSUM_FLAG } else { 0 });
+        table.extend_from_slice(&SEEKABLE_MAGIC_NUMBER.to_le_bytes());
+
+        frame::write_skippable_frame(output, SEEK_TABLE_MAGIC_VARIANT, &table)
+    }
+}
+
+/// An encoder writing a seekable stream.
+///
+/// The input is split into frames of [`Encoder::frame_size`] bytes, and the
+/// seek table is written by [`Encoder::finish`].
+///
+/// Don't forget to call [`finish()`] before dropping it!
+///
+/// [`finish()`]: Encoder::finish
+pub struct Encoder<'a, W> {
+    encoder: raw::Encoder<'a>,
+    writer: W,
+
+    frame_size: u32,
+    // Compressed and decompressed bytes in the current frame.
+    compressed: u64,
+    decompressed: u32,
+    hasher: Option<XxHash64>,
+    table: SeekTable,
+
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Encoder<'static, W> {
+    /// Creates a new seekable encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> io::Result<Self> {
+        Ok(Self::with_encoder(writer, raw::Encoder::new(level)?))
+    }
+
+    /// Creates a new seekable encoder, configured with the given options.
+    pub fn with_options(writer: W, options: &Options) -> io::Result<Self> {
+        Ok(Self::with_encoder(
+            writer,
+            raw::Encoder::with_options(options)?,
+        ))
+    }
+}
+
+impl<'a, W: Write> Encoder<'a, W> {
+    /// Creates a new seekable encoder around the given raw encoder.
+    pub fn with_encoder(writer: W, encoder: raw::Encoder<'a>) -> Self {
+        Encoder {
+            encoder,
+            writer,
+            frame_size: FRAME_SIZE_DEFAULT,
+            compressed: 0,
+            decompressed: 0,
+            hasher: None,
+            table: SeekTable::default(),
+            buffer: Vec::with_capacity(zstd_safe::CCtx::out_size()),
+        }
+    }
+
+    /// Sets the decompressed size of each frame (1 MiB by default).
+    ///
+    /// Smaller frames make random access cheaper, but compress less. The
+    /// size is clamped to `1..=FRAME_SIZE_MAX`.
+    #[must_use]
+    pub fn frame_size(mut self, bytes: u32) -> Self {
+        self.frame_size = bytes.clamp(1, FRAME_SIZE_MAX);
+        self
+    }
+
+    /// Sets whether the seek table includes a checksum of each frame.
+    ///
+    /// This must be set before writing any data.
+    #[must_use]
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.table.checksums = checksums;
+        self.hasher = if checksums {
+            Some(XxHash64::new(0))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Returns the seek table of the frames completed so far.
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// **Required**: Finishes the last frame, writes the seek table, and
+    /// returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.decompressed > 0 {
+            self.end_frame()?;
+        }
+        self.buffer.clear();
+        self.table.write(&mut self.buffer)?;
+        self.writer.write_all(&self.buffer)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the content of `self.buffer` to the writer.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.compressed += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Ends the current frame, and adds it to the seek table.
+    fn end_frame(&mut self) -> io::Result<()> {
+        loop {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            let hint = self.encoder.finish(&mut output, true)?;
+            self.write_buffer()?;
+            if hint == 0 {
+                break;
+            }
+        }
+
+        let compressed = u32::try_from(self.compressed)
+            .map_err(|_| invalid_data("compressed frame is too large"))?;
+        let checksum = self.hasher.as_mut().map(|hasher| {
+            let checksum = hasher.digest() as u32;
+            *hasher = XxHash64::new(0);
+            checksum
+        });
+        self.table.push(compressed, self.decompressed, checksum);
+        self.compressed = 0;
+        self.decompressed = 0;
+        self.encoder.reinit()
+    }
+}
+
+impl<'a, W: Write> Write for Encoder<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Never cross the end of a frame.
+        let room = (self.frame_size - self.decompressed) as usize;
+        let len = buf.len().min(room);
+
+        let mut input = InBuffer::around(&buf[..len]);
+        while input.pos() < len {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            self.encoder.run(&mut input, &mut output)?;
+            self.write_buffer()?;
+        }
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..len]);
+        }
+        self.decompressed += len as u32;
+
+        if self.decompressed == self.frame_size {
+            self.end_frame()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        loop {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            let hint = self.encoder.flush(&mut output)?;
+            self.write_buffer()?;
+            if hint == 0 {
+                break;
+            }
+        }
+        self.writer.flush()
+    }
+}
+
+/// A decoder reading any range of a seekable stream.
+///
+/// This implements [`Seek`] over the decompressed data. Reads only
+/// decompress the frame they need, which is kept in memory for the
+/// following reads.
+///
+/// With [`Decoder::cache_size`], more decompressed frames are kept, so that
+/// going back to a recent region doesn't decompress it again.
+pub struct Decoder<'a, R> {
+    reader: R,
+    decompressor: Decompressor<'a>,
+    table: SeekTable,
+
+    // Position in the decompressed data.
+    position: u64,
+    // Recently decompressed frames.
+    cache: FrameCache,
+    compressed: Vec<u8>,
+}
+
+/// Least recently used decompressed frames.
+struct FrameCache {
+    // Decompressed frames by index, with the time they were last used.
+    frames: HashMap<usize, (u64, Vec<u8>)>,
+    // Indices of the cached frames by time they were last used, oldest
+    // first.
+    order: BTreeMap<u64, usize>,
+    // Total size of the cached frames.
+    size: usize,
+    // The last used frame is always kept, even if larger than this.
+    max_bytes: usize,
+    // Increases with each access.
+    clock: u64,
+}
+
+impl FrameCache {
+    fn new() -> Self {
+        FrameCache {
+            frames: HashMap::new(),
+            order: BTreeMap::new(),
+            size: 0,
+            max_bytes: 0,
+            clock: 0,
+        }
+    }
+
+    /// Marks frame `index` as used, and returns `true` if it is cached.
+    fn touch(&mut self, index: usize) -> bool {
+        let (last_used, _) = match self.frames.get_mut(&index) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        self.order.remove(last_used);
+        self.clock += 1;
+        *last_used = self.clock;
+        self.order.insert(self.clock, index);
+        true
+    }
+
+    /// Returns the content of frame `index`, which must be cached.
+    fn content(&self, index: usize) -> &[u8] {
+        &self.frames[&index].1
+    }
+
+    fn insert(&mut self, index: usize, content: Vec<u8>) {
+        self.clock += 1;
+        self.size += content.len();
+        self.order.insert(self.clock, index);
+        self.frames.insert(index, (self.clock, content));
+        self.shrink();
+    }
+
+    /// Takes the buffer of an entry to evict, to reuse its allocation.
+    fn take_buffer(&mut self, size: usize) -> Vec<u8> {
+        if self.size + size <= self.max_bytes {
+            return Vec::new();
+        }
+        self.remove_oldest().unwrap_or_default()
+    }
+
+    fn remove_oldest(&mut self) -> Option<Vec<u8>> {
+        let (&last_used, &index) = self.order.iter().next()?;
+        self.order.remove(&last_used);
+        let (_, content) = self.frames.remove(&index)?;
+        self.size -= content.len();
+        Some(content)
+    }
+
+    /// Drops the least recently used entries until within bounds, keeping
+    /// the most recent one.
+    fn shrink(&mut self) {
+        while self.frames.len() > 1 && self.size > self.max_bytes {
+            self.remove_oldest();
+        }
+    }
+}
+
+impl<R: Read + Seek> Decoder<'static, R> {
+    /// Creates a new decoder, reading the seek table from `reader`.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Self::with_decompressor(reader, Decompressor::new()?)
+    }
+
+    /// Creates a new decoder, using the given dictionary.
+    pub fn with_dictionary(reader: R, dictionary: &[u8]) -> io::Result<Self> {
+        Self::with_decompressor(
+            reader,
+            Decompressor::with_dictionary(dictionary)?,
+        )
+    }
+}
+
+impl<'a, R: Read + Seek> Decoder<'a, R> {
+    /// Creates a new decoder, using the given decompressor for each frame.
+    pub fn with_decompressor(
+        mut reader: R,
+        decompressor: Decompressor<'a>,
+    ) -> io::Result<Self> {
+        let table = SeekTable::read_from(&mut reader)?;
+        Ok(Decoder {
+            reader,
+            decompressor,
+            table,
+            position: 0,
+            cache: FrameCache::new(),
+            compressed: Vec::new(),
+        })
+    }
+
+    /// Sets how many bytes of decompressed frames to keep in memory.
+    ///
+    /// When reading a frame again, it is only decompressed if it was dropped
+    /// from the cache. The least recently used frames are dropped first.
+    ///
+    /// By default, only the last frame read is kept. It is always kept,
+    /// even if it is larger than the cache.
+    #[must_use]
+    pub fn cache_size(mut self, bytes: usize) -> Self {
+        self.cache.max_bytes = bytes;
+        self.cache.shrink();
+        self
+    }
+
+    /// Returns the seek table of the stream.
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    /// Returns the total size of the decompressed data.
+    pub fn decompressed_size(&self) -> u64 {
+        self.table.decompressed_size()
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// The position of the reader doesn't matter to this decoder.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Decompresses the frame `index` into the cache, unless it is there.
+    fn load_frame(&mut self, index: usize) -> io::Result<()> {
+        if self.cache.touch(index) {
+            return Ok(());
+        }
+
+        let frame = self.table.frames[index];
+        self.compressed.resize(frame.compressed_size as usize, 0);
+        self.reader.seek(SeekFrom::Start(frame.compressed_offset))?;
+        self.reader.read_exact(&mut self.compressed)?;
+
+        let size = frame.decompressed_size as usize;
+        let mut content = self.cache.take_buffer(size);
+        content.clear();
+        content.reserve(size);
+        self.decompressor
+            .decompress_to_buffer(&self.compressed, &mut content)?;
+        if content.len() != size {
+            return Err(invalid_data("frame size does not match seek table"));
+        }
+        if let Some(checksum) = frame.checksum {
+            let mut hasher = XxHash64::new(0);
+            hasher.update(&content);
+            if hasher.digest() as u32 != checksum {
+                return Err(invalid_data("frame checksum does not match"));
+            }
+        }
+
+        self.cache.insert(index, content);
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> Read for Decoder<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let index = match self.table.frame_index_at(self.position) {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        self.load_frame(index)?;
+
+        let start = (self.position
+            - self.table.frames[index].decompressed_offset)
+            as usize;
+        let available = &self.cache.content(index)[start..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for Decoder<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.decompressed_size(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.position = position;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    use super::{Decoder, Encoder, SeekTable, FRAME_SIZE_MAX};
+
+    #[test]
+    fn test_seekable() {
+        let text = include_bytes!("../../assets/example.txt");
+
+        for checksums in [false, true] {
+            let mut encoder = Encoder::new(Vec::new(), 1)
+                .unwrap()
+                .frame_size(100)
+                .checksums(checksums);
+            for chunk in text.chunks(33) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let compressed = encoder.finish().unwrap();
+
+            // This is still a regular zstd stream.
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), text);
+
+            let mut decoder = Decoder::new(Cursor::new(&compressed)).unwrap();
+            let table = decoder.seek_table();
+            assert_eq!(table.has_checksums(), checksums);
+            assert_eq!(table.frames().len(), (text.len() + 99) / 100);
+            assert_eq!(decoder.decompressed_size(), text.len() as u64);
+
+            for start in [0, 99, 100, 250, 700, text.len() - 5] {
+                decoder.seek(SeekFrom::Start(start as u64)).unwrap();
+                let mut output = vec![0u8; 150.min(text.len() - start)];
+                decoder.read_exact(&mut output).unwrap();
+                assert_eq!(output, &text[start..start + output.len()]);
+            }
+
+            decoder.seek(SeekFrom::End(-10)).unwrap();
+            let mut output = Vec::new();
+            decoder.read_to_end(&mut output).unwrap();
+            assert_eq!(output, &text[text.len() - 10..]);
+            assert!(decoder.seek(SeekFrom::Current(-1000)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_seekable_empty() {
+        let compressed =
+            Encoder::new(Vec::new(), 1).unwrap().finish().unwrap();
+        let mut decoder = Decoder::new(Cursor::new(&compressed)).unwrap();
+        assert_eq!(decoder.seek_table(), &SeekTable::default());
+        assert_eq!(decoder.read(&mut [0u8; 10]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_seekable_invalid() {
+        let compressed = crate::encode_all(&b"not seekable"[..], 1).unwrap();
+        assert!(Decoder::new(Cursor::new(&compressed)).is_err());
+        assert!(Decoder::new(Cursor::new(&compressed[..3])).is_err());
+
+        // Frames can't be larger than `FRAME_SIZE_MAX`: the seek table is
+        // the size of the last frame, followed by a 9 bytes footer.
+        let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+        encoder.write_all(b"hello").unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        let end = compressed.len() - 9;
+        compressed[end - 4..end]
+            .copy_from_slice(&(FRAME_SIZE_MAX + 1).to_le_bytes());
+        assert!(Decoder::new(Cursor::new(&compressed)).is_err());
+    }
+
+    #[test]
+    fn test_seekable_cache() {
+        // Counts the bytes read from the compressed stream.
+        struct Counter {
+            inner: Cursor<Vec<u8>>,
+            count: usize,
+        }
+
+        impl Read for Counter {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.inner.read(buf)?;
+                self.count += n;
+                Ok(n)
+            }
+        }
+
+        impl Seek for Counter {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let text = include_bytes!("../../assets/example.txt");
+        let mut encoder = Encoder::new(Vec::new(), 1).unwrap().frame_size(100);
+        encoder.write_all(text).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // Reads a byte from each of the first 3 frames, twice.
+        let read_all = |cache_size| {
+            let counter = Counter {
+                inner: Cursor::new(compressed.clone()),
+                count: 0,
+            };
+            let mut decoder =
+                Decoder::new(counter).unwrap().cache_size(cache_size);
+            decoder.get_mut().count = 0;
+            for _ in 0..2 {
+                for offset in [0, 150, 250] {
+                    let mut byte = [0u8];
+                    decoder.seek(SeekFrom::Start(offset)).unwrap();
+                    decoder.read_exact(&mut byte).unwrap();
+                    assert_eq!(byte[0], text[offset as usize]);
+                }
+            }
+            decoder.get_ref().count
+        };
+
+        let table =
+            SeekTable::read_from(&mut Cursor::new(&compressed)).unwrap();
+        let once: usize = table.frames()[..3]
+            .iter()
+            .map(|frame| frame.compressed_size as usize)
+            .sum();
+
+        assert_eq!(read_all(0), 2 * once);
+        assert_eq!(read_all(300), once);
+        // Only two frames fit: each one is dropped before being read again.
+        assert_eq!(read_all(200), 2 * once);
+    }
+
+    #[test]
+    fn test_frame_cache() {
+        let mut cache = super::FrameCache::new();
+        cache.max_bytes = 30;
+        cache.insert(0, vec![0; 10]);
+        cache.insert(1, vec![1; 10]);
+        cache.insert(2, vec![2; 10]);
+        assert_eq!(cache.size, 30);
+
+        // Frame 1 is now the least recently used.
+        assert!(cache.touch(0));
+        cache.insert(3, vec![3; 10]);
+        assert!(!cache.touch(1));
+        assert_eq!(cache.content(0), [0; 10]);
+        assert_eq!(cache.size, 30);
+
+        // Buffers are taken from the least recently used frame.
+        assert_eq!(cache.take_buffer(10), [2; 10]);
+        assert_eq!(cache.size, 20);
+
+        // The last frame is kept, even if too large.
+        cache.insert(4, vec![4; 40]);
+        assert_eq!(cache.frames.len(), 1);
+        assert_eq!(cache.size, 40);
+        assert!(cache.touch(4));
+    }
+}
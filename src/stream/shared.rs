@@ -0,0 +1,210 @@
+//! Encoders and decoders which can be shared between threads.
+//!
+//! Stream encoders and decoders are `Send` and `Sync`, but they hold the
+//! state of a single stream, and need `&mut self` to do anything. This makes
+//! them a poor fit for a global `static` or a shared handle.
+//!
+//! [`SyncEncoder`] and [`SyncDecoder`] only hold the configuration (level
+//! and dictionary). Each call runs on a thread-local context, so any number
+//! of threads can use them at the same time through `&self`, without
+//! locking.
+use std::io;
+use std::sync::Arc;
+
+use super::{raw, read, write};
+use crate::context::{with_cctx, with_dctx};
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::map_error_code;
+
+/// A compression configuration, usable from any thread through `&self`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use zstd::stream::SyncEncoder;
+///
+/// let encoder = Arc::new(SyncEncoder::new(3));
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let encoder = Arc::clone(&encoder);
+///         std::thread::spawn(move || encoder.encode_all(&b"data"[..]))
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap().unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SyncEncoder {
+    level: i32,
+    dictionary: Option<Arc<EncoderDictionary<'static>>>,
+}
+
+impl SyncEncoder {
+    /// Creates a new shared encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> Self {
+        SyncEncoder {
+            level,
+            dictionary: None,
+        }
+    }
+
+    /// Creates a new shared encoder, using the given dictionary.
+    ///
+    /// The dictionary is prepared once, and shared by all threads.
+    pub fn with_dictionary(level: i32, dictionary: &[u8]) -> Self {
+        let dictionary = EncoderDictionary::copy(dictionary, level);
+        Self::with_prepared_dictionary(Arc::new(dictionary))
+    }
+
+    /// Creates a new shared encoder, using a prepared dictionary.
+    ///
+    /// The compression level is the one used to prepare the dictionary.
+    pub fn with_prepared_dictionary(
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> Self {
+        SyncEncoder {
+            level: 0,
+            dictionary: Some(dictionary),
+        }
+    }
+
+    /// Compresses all data from `source`, and appends it to `destination`.
+    pub fn copy_encode<R, W>(
+        &self,
+        mut source: R,
+        destination: W,
+    ) -> io::Result<W>
+    where
+        R: io::Read,
+        W: io::Write,
+    {
+        with_cctx(|context| {
+            match &self.dictionary {
+                Some(dictionary) => context.ref_cdict(dictionary.as_cdict()),
+                None => context.set_parameter(
+                    zstd_safe::CParameter::CompressionLevel(self.level),
+                ),
+            }
+            .map_err(map_error_code)?;
+
+            let mut encoder = write::Encoder::with_encoder(
+                destination,
+                raw::Encoder::with_context(context),
+            );
+            io::copy(&mut source, &mut encoder)?;
+            encoder.finish()
+        })
+    }
+
+    /// Compresses all data from `source`.
+    pub fn encode_all<R: io::Read>(&self, source: R) -> io::Result<Vec<u8>> {
+        self.copy_encode(source, Vec::new())
+    }
+}
+
+/// A decompression configuration, usable from any thread through `&self`.
+///
+/// See [`SyncEncoder`] for an example.
+#[derive(Clone, Default)]
+pub struct SyncDecoder {
+    dictionary: Option<Arc<DecoderDictionary<'static>>>,
+}
+
+impl SyncDecoder {
+    /// Creates a new shared decoder.
+    pub fn new() -> Self {
+        SyncDecoder { dictionary: None }
+    }
+
+    /// Creates a new shared decoder, using the given dictionary.
+    ///
+    /// The dictionary is prepared once, and shared by all threads.
+    pub fn with_dictionary(dictionary: &[u8]) -> Self {
+        let dictionary = DecoderDictionary::copy(dictionary);
+        Self::with_prepared_dictionary(Arc::new(dictionary))
+    }
+
+    /// Creates a new shared decoder, using a prepared dictionary.
+    pub fn with_prepared_dictionary(
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> Self {
+        SyncDecoder {
+            dictionary: Some(dictionary),
+        }
+    }
+
+    /// Decompresses all data from `source`, and appends it to `destination`.
+    pub fn copy_decode<R, W>(
+        &self,
+        source: R,
+        mut destination: W,
+    ) -> io::Result<W>
+    where
+        R: io::Read,
+        W: io::Write,
+    {
+        with_dctx(|context| {
+            if let Some(dictionary) = &self.dictionary {
+                context
+                    .ref_ddict(dictionary.as_ddict())
+                    .map_err(map_error_code)?;
+            }
+
+            let buffer_size = zstd_safe::DCtx::in_size();
+            let source = io::BufReader::with_capacity(buffer_size, source);
+            let mut decoder = read::Decoder::with_context(source, context);
+            io::copy(&mut decoder, &mut destination)?;
+            Ok(destination)
+        })
+    }
+
+    /// Decompresses all data from `source`.
+    pub fn decode_all<R: io::Read>(&self, source: R) -> io::Result<Vec<u8>> {
+        self.copy_decode(source, Vec::new())
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send_sync<T: Send + Sync>(_: T) {}
+
+    _assert_send_sync(SyncEncoder::new(1));
+    _assert_send_sync(SyncDecoder::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyncDecoder, SyncEncoder};
+
+    #[test]
+    fn test_shared() {
+        let text = include_bytes!("../../assets/example.txt");
+        let dictionary = &text[..512];
+
+        let encoder = SyncEncoder::with_dictionary(3, dictionary);
+        let decoder = SyncDecoder::with_dictionary(dictionary);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let compressed = encoder.encode_all(&text[..]).unwrap();
+                    let decompressed =
+                        decoder.decode_all(&compressed[..]).unwrap();
+                    assert_eq!(&decompressed[..], &text[..]);
+                });
+            }
+        });
+
+        // The dictionary is not left on the thread-local contexts.
+        encoder.encode_all(&text[..]).unwrap();
+        let compressed = SyncEncoder::new(1).encode_all(&text[..]).unwrap();
+        assert_eq!(
+            SyncDecoder::new().decode_all(&compressed[..]).unwrap(),
+            text
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Seek, Take};
+
+/// Readers which know how much data they have left.
+///
+/// Encoders can use this to pledge the size of their input, which then gets
+/// written in the frame header (like the `zstd` CLI does for files). This
+/// lets decoders allocate the right amount of memory, and may improve the
+/// compression ratio of small inputs.
+///
+/// The hint must be exact: if the reader ends up giving a different amount
+/// of data, compression will fail.
+pub trait SizeHint {
+    /// Returns the exact number of bytes left to read, if known.
+    fn size_hint(&self) -> Option<u64>;
+}
+
+impl SizeHint for File {
+    fn size_hint(&self) -> Option<u64> {
+        let metadata = self.metadata().ok()?;
+        // Pipes, sockets and devices don't have a meaningful length.
+        if !metadata.is_file() {
+            return None;
+        }
+        let position = (&mut &*self).stream_position().ok()?;
+        metadata.len().checked_sub(position)
+    }
+}
+
+impl SizeHint for &[u8] {
+    fn size_hint(&self) -> Option<u64> {
+        Some(self.len() as u64)
+    }
+}
+
+impl<T: AsRef<[u8]>> SizeHint for Cursor<T> {
+    fn size_hint(&self) -> Option<u64> {
+        let len = self.get_ref().as_ref().len() as u64;
+        Some(len.saturating_sub(self.position()))
+    }
+}
+
+impl<R: SizeHint> SizeHint for BufReader<R> {
+    fn size_hint(&self) -> Option<u64> {
+        let buffered = self.buffer().len() as u64;
+        Some(self.get_ref().size_hint()? + buffered)
+    }
+}
+
+impl<R: SizeHint> SizeHint for Take<R> {
+    fn size_hint(&self) -> Option<u64> {
+        Some(self.get_ref().size_hint()?.min(self.limit()))
+    }
+}
+
+impl<R: SizeHint + ?Sized> SizeHint for &mut R {
+    fn size_hint(&self) -> Option<u64> {
+        (**self).size_hint()
+    }
+}
+
+impl<R: SizeHint + ?Sized> SizeHint for Box<R> {
+    fn size_hint(&self) -> Option<u64> {
+        (**self).size_hint()
+    }
+}
@@ -276,3 +276,768 @@ fn test_finish_empty_encoder() {
     enc.write_all(b"this should not work").unwrap_err();
     enc.finish().unwrap();
 }
+
+#[test]
+fn test_nested_thread_local_contexts() {
+    // A source which itself uses the thread-local contexts.
+    struct Nested<'a>(&'a [u8]);
+
+    impl io::Read for Nested<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let round_trip = decode_all(&encode_all(self.0, 1)?[..])?;
+            assert_eq!(round_trip, self.0);
+            io::Read::read(&mut self.0, buf)
+        }
+    }
+
+    let input = b"AbcdefghAbcdefgh.";
+    let compressed = encode_all(Nested(input), 1).unwrap();
+    assert_eq!(decode_all(Nested(&compressed)).unwrap(), input);
+}
+
+#[test]
+fn test_shared_dictionary() {
+    use crate::dict::{DecoderDictionary, EncoderDictionary};
+    use crate::stream::{read, write};
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    // A writer we can still look at once the encoder is boxed.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let text = include_bytes!("../../assets/example.txt");
+    let dictionary = &text[..500];
+
+    let output = Shared::default();
+    let mut writers: Vec<Box<dyn Write + Send>> = Vec::new();
+    {
+        let dictionary = Arc::new(EncoderDictionary::copy(dictionary, 3));
+        writers.push(
+            write::Encoder::with_shared_dictionary(output.clone(), dictionary)
+                .unwrap()
+                .into_boxed(),
+        );
+    }
+    writers[0].write_all(text).unwrap();
+    drop(writers);
+    let compressed = output.0.lock().unwrap().clone();
+
+    let dictionary = Arc::new(DecoderDictionary::copy(dictionary));
+    let mut readers: Vec<Box<dyn Read + Send>> =
+        vec![read::Decoder::with_shared_dictionary(
+            io::Cursor::new(compressed.clone()),
+            Arc::clone(&dictionary),
+        )
+        .unwrap()
+        .into_boxed()];
+    drop(dictionary);
+
+    let mut decompressed = Vec::new();
+    readers[0].read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, text);
+
+    // Boxed decoders write everything out when dropped.
+    let output = Shared::default();
+    let mut writer =
+        write::Decoder::with_dictionary(output.clone(), &text[..500])
+            .unwrap()
+            .into_boxed();
+    writer.write_all(&compressed).unwrap();
+    drop(writer);
+    assert_eq!(&output.0.lock().unwrap()[..], &text[..]);
+}
+
+/// Multithreaded compression, where zstd's hints behave differently.
+#[cfg(feature = "zstdmt")]
+mod multithread {
+    use crate::stream::{chunk, read, write};
+    use std::io::{Read, Write};
+
+    /// Returns `len` bytes of somewhat compressible data.
+    fn data(len: usize) -> Vec<u8> {
+        let text = include_bytes!("../../assets/example.txt");
+        let mut state = 1u32;
+        (0..len)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if state >> 30 == 0 {
+                    (state >> 16) as u8
+                } else {
+                    text[i % text.len()]
+                }
+            })
+            .collect()
+    }
+
+    fn encoder(
+        workers: u32,
+        job_size: u32,
+    ) -> write::Encoder<'static, Vec<u8>> {
+        let mut encoder = write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.multithread(workers).unwrap();
+        encoder
+            .set_parameter(zstd_safe::CParameter::JobSize(job_size))
+            .unwrap();
+        encoder
+    }
+
+    #[test]
+    fn test_auto() {
+        let input = data(3 << 20);
+
+        let mut encoder = write::Encoder::new(Vec::new(), 1).unwrap();
+        assert!(encoder.multithread_auto().unwrap() >= 1);
+        encoder.write_all(&input).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+
+        let mut encoder = write::Encoder::new(Vec::new(), 1).unwrap();
+        let workers = encoder
+            .multithread_auto_for_size(input.len() as u64)
+            .unwrap();
+        assert!((1..=3).contains(&workers));
+        encoder.write_all(&input).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+
+        // Tiny inputs don't need several workers.
+        assert_eq!(crate::stream::auto_workers_for_size(100), (1, 1 << 20));
+    }
+
+    #[test]
+    fn test_write_cycle() {
+        let input = data(3 << 20);
+
+        for &workers in &[1, 2, 4] {
+            for &job_size in &[0, 512 << 10, 1 << 20] {
+                for &write_size in &[1000, 100_000, 1 << 20] {
+                    let mut encoder = encoder(workers, job_size);
+                    for chunk in input.chunks(write_size) {
+                        encoder.write_all(chunk).unwrap();
+                    }
+                    let compressed = encoder.finish().unwrap();
+                    assert_eq!(
+                        crate::decode_all(&compressed[..]).unwrap(),
+                        input
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let input = data(2 << 20);
+        let (first, second) = input.split_at(input.len() / 3);
+
+        let mut encoder = encoder(2, 512 << 10);
+        encoder.write_all(first).unwrap();
+        encoder.flush().unwrap();
+
+        // Everything written so far can be decoded.
+        let mut decoder = chunk::Decoder::new().unwrap();
+        let mut decoded = Vec::new();
+        decoder.decode(encoder.get_ref(), &mut decoded).unwrap();
+        assert_eq!(decoded, first);
+
+        encoder.write_all(second).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+    }
+
+    #[test]
+    fn test_concatenated_frames() {
+        let input = data(1 << 20);
+
+        let mut compressed = Vec::new();
+        for _ in 0..3 {
+            let mut encoder = encoder(2, 0);
+            encoder.write_all(&input).unwrap();
+            compressed.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(
+            crate::decode_all(&compressed[..]).unwrap(),
+            input.repeat(3)
+        );
+    }
+
+    #[test]
+    fn test_read_cycle() {
+        let input = data(3 << 20);
+
+        for &workers in &[1, 3] {
+            for &read_size in &[7, 4096, 1 << 20] {
+                let mut encoder = read::Encoder::new(&input[..], 1).unwrap();
+                encoder.multithread(workers).unwrap();
+
+                // Small reads must not stop early.
+                let mut compressed = Vec::new();
+                let mut buffer = vec![0; read_size];
+                loop {
+                    let n = encoder.read(&mut buffer).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    compressed.extend_from_slice(&buffer[..n]);
+                }
+                assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_encoder() {
+        let input = data(2 << 20);
+
+        let mut encoder = chunk::Encoder::new(1).unwrap();
+        encoder.multithread(2).unwrap();
+
+        let mut compressed = Vec::new();
+        for chunk in input.chunks(300_000) {
+            encoder.encode(chunk, &mut compressed).unwrap();
+            encoder.flush(&mut compressed).unwrap();
+        }
+        encoder.finish(&mut compressed).unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+    }
+}
+
+#[cfg(feature = "zdict_builder")]
+#[test]
+fn test_dict_id_guard() {
+    use crate::stream::{read, write};
+    use std::io::{Read, Write};
+
+    let samples: Vec<Vec<u8>> = ["src/lib.rs", "src/stream/raw.rs"]
+        .iter()
+        .map(|path| std::fs::read(path).unwrap())
+        .collect();
+    let dictionaries: Vec<Vec<u8>> = samples
+        .iter()
+        .map(|sample| {
+            let chunks: Vec<&[u8]> = sample.chunks(256).collect();
+            crate::dict::from_samples(&chunks, 4000).unwrap()
+        })
+        .collect();
+
+    let mut encoder =
+        write::Encoder::with_dictionary(Vec::new(), 1, &dictionaries[0])
+            .unwrap();
+    encoder.include_dictid(false).unwrap();
+    encoder.set_dict_id_guard(true).unwrap();
+    encoder.write_all(&samples[0]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(crate::frame::is_skippable(&compressed));
+
+    let decode = |dictionary: &[u8], check: bool| {
+        let mut decoder =
+            read::Decoder::with_dictionary(&compressed[..], dictionary)
+                .unwrap();
+        decoder.set_dict_id_check(check).unwrap();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).map(|_| output)
+    };
+
+    assert_eq!(decode(&dictionaries[0], true).unwrap(), samples[0]);
+    // Without the check, the skippable frame is just ignored.
+    assert_eq!(decode(&dictionaries[0], false).unwrap(), samples[0]);
+    let error = decode(&dictionaries[1], true).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("another dictionary"));
+    assert!(decode(&[], true).is_err());
+
+    // Raw content dictionaries have no ID to record.
+    let raw = &samples[0][..1000];
+    let mut encoder =
+        write::Encoder::with_dictionary(Vec::new(), 1, raw).unwrap();
+    let error = encoder.set_dict_id_guard(true).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    let mut decoder = read::Decoder::with_dictionary(&[][..], raw).unwrap();
+    let error = decoder.set_dict_id_check(true).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    let prepared = crate::dict::DecoderDictionary::copy(raw);
+    let mut decoder =
+        read::Decoder::with_prepared_dictionary(&[][..], &prepared).unwrap();
+    assert!(decoder.set_dict_id_check(true).is_err());
+}
+
+#[test]
+fn test_dyn() {
+    use crate::stream::{DynDecoder, DynEncoder};
+    use std::io::{Read, Write};
+
+    // A single struct, whatever the source or destination.
+    struct Pipeline {
+        decoders: Vec<DynDecoder>,
+    }
+
+    let text = include_bytes!("../../assets/example.txt");
+    let mut encoder: DynEncoder =
+        Encoder::new_dyn(Box::new(Vec::new()), 1).unwrap();
+    encoder.write_all(text).unwrap();
+    drop(encoder.finish().unwrap());
+
+    let compressed = encode_all(&text[..], 1).unwrap();
+    let mut pipeline = Pipeline {
+        decoders: vec![
+            Decoder::new_dyn(Box::new(io::Cursor::new(compressed.clone())))
+                .unwrap(),
+            Decoder::new_dyn(Box::new(io::BufReader::new(io::Cursor::new(
+                compressed,
+            ))))
+            .unwrap(),
+        ],
+    };
+    for decoder in &mut pipeline.decoders {
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, text);
+    }
+}
+
+#[test]
+fn test_target_block_size() {
+    use crate::frame::{
+        parse_block_header, FrameHeader, TARGET_BLOCK_SIZE_MAX,
+        TARGET_BLOCK_SIZE_MIN,
+    };
+
+    let mut encoder = Encoder::new(Vec::new(), 3).unwrap();
+    assert!(encoder
+        .set_target_block_size(TARGET_BLOCK_SIZE_MIN - 1)
+        .is_err());
+    assert!(encoder
+        .set_target_block_size(TARGET_BLOCK_SIZE_MAX + 1)
+        .is_err());
+    assert!(encoder
+        .set_target_cblock_size(Some(TARGET_BLOCK_SIZE_MIN - 1))
+        .is_err());
+
+    // Older system libraries only know the parameter as experimental.
+    if zstd_safe::version_number() < 10506 {
+        return;
+    }
+
+    // Text-like data, spanning several full blocks.
+    let words: Vec<&[u8]> = include_bytes!("../../assets/example.txt")
+        .split(|&b| b == b' ')
+        .collect();
+    let mut state = 1u32;
+    let mut input = Vec::new();
+    while input.len() < 512 * 1024 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        input.extend_from_slice(words[(state >> 16) as usize % words.len()]);
+        input.push(b' ');
+    }
+
+    encoder.set_target_cblock_size(None).unwrap();
+    let target = 4096;
+    encoder.set_target_block_size(target).unwrap();
+    io::Write::write_all(&mut encoder, &input).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), input);
+
+    let (_, mut pos) = FrameHeader::parse(&compressed).unwrap();
+    let mut blocks = 0;
+    loop {
+        let (_, size, last) = parse_block_header(&compressed[pos..]).unwrap();
+        // The target is not a hard limit, but blocks stay close to it.
+        assert!(size <= target + target / 4, "block of {} bytes", size);
+        pos += 3 + size as usize;
+        blocks += 1;
+        if last {
+            break;
+        }
+    }
+    assert!(blocks > input.len() / (128 * 1024));
+}
+
+/// Retries `f` until it doesn't fail with `WouldBlock` or `Interrupted`.
+fn retry<T>(mut f: impl FnMut() -> io::Result<T>) -> T {
+    loop {
+        match f() {
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::Interrupted =>
+            {
+                continue
+            }
+            result => return result.unwrap(),
+        }
+    }
+}
+
+/// Inner IO failing with `WouldBlock` every other call.
+fn would_block_ops() -> impl Iterator<Item = PartialOp> + Send {
+    vec![
+        PartialOp::Err(io::ErrorKind::WouldBlock),
+        PartialOp::Limited(7),
+    ]
+    .into_iter()
+    .cycle()
+}
+
+#[test]
+fn test_would_block_write() {
+    use crate::stream::write;
+    use std::io::Write;
+
+    let text = include_bytes!("../../assets/example.txt");
+
+    // Every call can be retried after `WouldBlock`.
+    let mut encoder = write::Encoder::new(
+        PartialWrite::new(Vec::new(), would_block_ops()),
+        1,
+    )
+    .unwrap();
+    for mut chunk in text.chunks(100) {
+        while !chunk.is_empty() {
+            chunk = &chunk[retry(|| encoder.write(chunk))..];
+        }
+        retry(|| encoder.flush());
+    }
+    retry(|| encoder.do_finish());
+    let compressed = encoder.finish().unwrap().into_inner();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+
+    let mut decoder =
+        write::Decoder::new(PartialWrite::new(Vec::new(), would_block_ops()))
+            .unwrap();
+    for mut chunk in compressed.chunks(10) {
+        while !chunk.is_empty() {
+            chunk = &chunk[retry(|| decoder.write(chunk))..];
+        }
+    }
+    retry(|| decoder.flush());
+    assert_eq!(decoder.into_inner().into_inner(), text);
+}
+
+#[test]
+fn test_would_block_read() {
+    use crate::stream::read;
+    use partial_io::PartialRead;
+    use std::io::Read;
+
+    let text = include_bytes!("../../assets/example.txt");
+
+    let read_all = |reader: &mut dyn Read| {
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 3];
+        loop {
+            match retry(|| reader.read(&mut buffer)) {
+                0 => return output,
+                n => output.extend_from_slice(&buffer[..n]),
+            }
+        }
+    };
+
+    let mut encoder =
+        read::Encoder::new(PartialRead::new(&text[..], would_block_ops()), 1)
+            .unwrap();
+    let compressed = read_all(&mut encoder);
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+
+    for readahead in [0, 100] {
+        let mut decoder = read::Decoder::new(PartialRead::new(
+            &compressed[..],
+            would_block_ops(),
+        ))
+        .unwrap();
+        decoder.set_readahead(readahead);
+        assert_eq!(read_all(&mut decoder), text);
+    }
+}
+
+// Conformance tests: every wrapper must give the same result whatever the
+// inner IO does, as long as the caller retries `Interrupted` and
+// `WouldBlock` errors.
+
+/// Inner IO behaviours: short operations, with errors in between.
+pub(super) fn partial_op_patterns() -> Vec<Vec<PartialOp>> {
+    use io::ErrorKind::{Interrupted, WouldBlock};
+
+    vec![
+        vec![PartialOp::Limited(1)],
+        vec![PartialOp::Err(Interrupted), PartialOp::Limited(3)],
+        vec![PartialOp::Err(WouldBlock), PartialOp::Limited(7)],
+        vec![
+            PartialOp::Err(WouldBlock),
+            PartialOp::Err(Interrupted),
+            PartialOp::Unlimited,
+            PartialOp::Limited(2),
+        ],
+    ]
+}
+
+/// Repeats `ops` forever.
+pub(super) fn cycle_ops(
+    ops: &[PartialOp],
+) -> impl Iterator<Item = PartialOp> + Send {
+    Vec::from(ops).into_iter().cycle()
+}
+
+/// Data large enough to fill the internal buffers a few times.
+pub(super) fn conformance_text() -> Vec<u8> {
+    include_bytes!("../../assets/example.txt").repeat(50)
+}
+
+/// Compressed frames (with a skippable and an empty one in between), and
+/// their content.
+pub(super) fn multi_frame_input() -> (Vec<u8>, Vec<u8>) {
+    let text = conformance_text();
+    let mut compressed = encode_all(&text[..], 1).unwrap();
+    crate::frame::write_skippable_frame(&mut compressed, 0, b"meta").unwrap();
+    compressed.extend(encode_all(&b""[..], 1).unwrap());
+    compressed.extend(encode_all(&text[..1000], 19).unwrap());
+    let content = [&text[..], &text[..1000]].concat();
+    (compressed, content)
+}
+
+/// Reads until EOF, `size` bytes at a time, retrying failed reads.
+fn read_all_partial(reader: &mut dyn io::Read, size: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut buffer = vec![0u8; size];
+    loop {
+        match retry(|| reader.read(&mut buffer)) {
+            0 => return output,
+            n => output.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+/// Writes `data` in chunks of `size` bytes, retrying failed writes, and
+/// flushing now and then.
+fn write_all_partial(writer: &mut dyn io::Write, data: &[u8], size: usize) {
+    for (i, mut chunk) in data.chunks(size).enumerate() {
+        while !chunk.is_empty() {
+            chunk = &chunk[retry(|| writer.write(chunk))..];
+        }
+        if i % 100 == 0 {
+            retry(|| writer.flush());
+        }
+    }
+}
+
+#[test]
+fn test_partial_io_read_encoder() {
+    use crate::stream::read;
+    use partial_io::PartialRead;
+
+    let text = conformance_text();
+    for ops in partial_op_patterns() {
+        for &size in &[1, 100, 100_000] {
+            let reader = PartialRead::new(&text[..], cycle_ops(&ops));
+            let mut encoder = read::Encoder::new(reader, 1).unwrap();
+            let compressed = read_all_partial(&mut encoder, size);
+            assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+        }
+    }
+}
+
+#[test]
+fn test_partial_io_read_decoder() {
+    use crate::stream::read;
+    use partial_io::PartialRead;
+
+    let (compressed, content) = multi_frame_input();
+    for ops in partial_op_patterns() {
+        for &size in &[1, 100, 100_000] {
+            for &readahead in &[0, 64] {
+                let reader =
+                    PartialRead::new(&compressed[..], cycle_ops(&ops));
+                let mut decoder = read::Decoder::new(reader).unwrap();
+                decoder.set_readahead(readahead);
+                assert_eq!(read_all_partial(&mut decoder, size), content);
+            }
+        }
+
+        // Frame by frame.
+        let reader = PartialRead::new(&compressed[..], cycle_ops(&ops));
+        let mut frames = read::Decoder::new(reader).unwrap().frames();
+        let mut output = Vec::new();
+        let mut count = 0;
+        loop {
+            match frames.next_frame() {
+                Ok(Some(mut frame)) => {
+                    output.extend(read_all_partial(&mut frame, 100));
+                    count += 1;
+                }
+                Ok(None) => break,
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(output, content);
+        assert_eq!(count, 4);
+    }
+}
+
+#[test]
+fn test_partial_io_write_encoder() {
+    use crate::stream::write;
+
+    let text = conformance_text();
+    for ops in partial_op_patterns() {
+        for &size in &[1, 100, 100_000] {
+            let writer = PartialWrite::new(Vec::new(), cycle_ops(&ops));
+            let mut encoder = write::Encoder::new(writer, 1).unwrap();
+            write_all_partial(&mut encoder, &text, size);
+
+            // Finishing can be retried as well.
+            let writer = loop {
+                match encoder.try_finish() {
+                    Ok(writer) => break writer,
+                    Err((e, error))
+                        if error.kind() == io::ErrorKind::WouldBlock
+                            || error.kind() == io::ErrorKind::Interrupted =>
+                    {
+                        encoder = e
+                    }
+                    Err((_, error)) => panic!("{}", error),
+                }
+            };
+            let compressed = writer.into_inner();
+            assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+        }
+    }
+}
+
+#[test]
+fn test_partial_io_write_decoder() {
+    use crate::stream::write;
+    use std::io::Write;
+
+    let (compressed, content) = multi_frame_input();
+    for ops in partial_op_patterns() {
+        for &size in &[1, 100, 100_000] {
+            let writer = PartialWrite::new(Vec::new(), cycle_ops(&ops));
+            let mut decoder = write::Decoder::new(writer).unwrap();
+            write_all_partial(&mut decoder, &compressed, size);
+            retry(|| decoder.flush());
+            assert_eq!(decoder.frames_decoded(), 4);
+            assert_eq!(decoder.into_inner().into_inner(), content);
+        }
+    }
+}
+
+#[test]
+fn test_debug() {
+    use std::io::{Read, Write};
+
+    let mut encoder = Encoder::new(Vec::new(), 5).unwrap();
+    encoder.write_all(b"foo").unwrap();
+    let debug = format!("{:?}", encoder);
+    assert!(debug.contains("level: Some(5)"), "{}", debug);
+    assert!(debug.contains("finished: false"), "{}", debug);
+    let compressed = encoder.finish().unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    let debug = format!("{:?}", decoder);
+    assert!(debug.contains("frames_decoded: 1"), "{}", debug);
+    assert!(debug.contains("state: Finished"), "{}", debug);
+
+    let compressor = crate::bulk::Compressor::new(7).unwrap();
+    assert_eq!(
+        format!("{:?}", compressor),
+        "Compressor { level: Some(7), .. }"
+    );
+}
+
+#[test]
+fn test_content_checksum() {
+    use crate::stream::{read, write, ContentChecksum};
+    use std::io::{Read, Write};
+
+    let text = include_bytes!("../../assets/example.txt");
+    let checksum = Some(ContentChecksum::Crc32c);
+
+    let mut encoder = write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder.set_content_checksum(checksum);
+    encoder.write_all(text).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // The reading side writes the same trailer.
+    let mut encoder = read::Encoder::new(&text[..], 1).unwrap();
+    encoder.set_content_checksum(checksum);
+    let mut read_compressed = Vec::new();
+    encoder.read_to_end(&mut read_compressed).unwrap();
+    assert_eq!(read_compressed, compressed);
+
+    // The trailer is a skippable frame with the CRC-32C of the content.
+    let mut crc = crate::crc32c::Crc32c::new();
+    crc.update(text);
+    let mut expected = Vec::new();
+    crate::frame::write_skippable_frame(
+        &mut expected,
+        crate::frame::CONTENT_CHECKSUM_MAGIC_VARIANT,
+        &[&[1][..], &crc.digest().to_le_bytes()].concat(),
+    )
+    .unwrap();
+    assert!(compressed.ends_with(&expected));
+
+    // Decoders ignore it by default.
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+
+    let decode = |compressed: &[u8]| {
+        let mut decoder = read::Decoder::new(compressed).unwrap();
+        decoder.set_content_checksum(checksum);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).map(|_| output)
+    };
+    // Several frames are checked independently.
+    let mut concatenated = compressed.clone();
+    concatenated.extend_from_slice(&compressed);
+    assert_eq!(decode(&concatenated).unwrap(), [&text[..], text].concat());
+
+    let mut corrupted = compressed.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert_eq!(decode_all(&corrupted[..]).unwrap(), text);
+    let error = decode(&corrupted).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("content checksum mismatch"));
+
+    // Trailers can't be stripped, at the end or between frames.
+    let plain = encode_all(&text[..], 1).unwrap();
+    for stripped in [plain.clone(), [&plain[..], &compressed].concat()] {
+        let error = decode(&stripped).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("no content checksum"));
+    }
+}
+
+#[test]
+fn test_encode_all_with_reuse() {
+    let text = include_bytes!("../../assets/example.txt");
+    let options = crate::Options::new()
+        .level(5)
+        .dictionary(&text[..500])
+        .checksum(true);
+
+    // The thread-local context doesn't keep the options.
+    let expected = encode_all(&text[..], 1).unwrap();
+    for _ in 0..2 {
+        crate::stream::encode_all_with(&text[..], &options).unwrap();
+        let compressed = encode_all(&text[..], 1).unwrap();
+        assert_eq!(compressed, expected);
+        assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+    }
+
+    // Invalid options are still rejected.
+    let options = crate::Options::new().level(1000);
+    assert!(crate::stream::encode_all_with(&text[..], &options).is_err());
+}
@@ -0,0 +1,160 @@
+use std::io::{self, Write};
+
+use super::Encoder;
+use crate::preset::Preset;
+use crate::stream::raw;
+use crate::Options;
+
+/// Builder configuring an [`Encoder`] before creating it.
+///
+/// This is created by [`Encoder::builder`], and gathers the settings which
+/// otherwise need a specific constructor, or a setter called before any
+/// data is written.
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// let mut encoder = zstd::Encoder::builder(Vec::new())
+///     .level(19)
+///     .checksum(true)
+///     .window_log(20)
+///     .build()?;
+/// encoder.write_all(b"data")?;
+/// let compressed = encoder.finish()?;
+/// # assert_eq!(zstd::decode_all(&compressed[..])?, b"data");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct EncoderBuilder<'a, W> {
+    writer: W,
+    options: Options,
+    ref_prefix: Option<&'a [u8]>,
+}
+
+impl<'a, W: Write> EncoderBuilder<'a, W> {
+    /// Creates a new builder, with the default options.
+    pub fn new(writer: W) -> Self {
+        EncoderBuilder {
+            writer,
+            options: Options::new(),
+            ref_prefix: None,
+        }
+    }
+
+    /// Replaces all the options set so far.
+    ///
+    /// The prefix, if any, is kept.
+    #[must_use]
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Applies a preset, replacing the level, window, long-distance
+    /// matching, checksum and threads set so far.
+    ///
+    /// See [`Options::preset`].
+    #[must_use]
+    pub fn preset(mut self, preset: &Preset) -> Self {
+        self.options = self.options.preset(preset);
+        self
+    }
+
+    /// Sets the compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    #[must_use]
+    pub fn level(mut self, level: i32) -> Self {
+        self.options = self.options.level(level);
+        self
+    }
+
+    /// Sets the dictionary to compress with.
+    ///
+    /// This can't be combined with [`EncoderBuilder::ref_prefix`].
+    #[must_use]
+    pub fn dictionary(mut self, dictionary: &[u8]) -> Self {
+        self.options = self.options.dictionary(dictionary);
+        self
+    }
+
+    /// Sets a prefix to reference, for the first frame only.
+    ///
+    /// This can't be combined with [`EncoderBuilder::dictionary`].
+    #[must_use]
+    pub fn ref_prefix(mut self, ref_prefix: &'a [u8]) -> Self {
+        self.ref_prefix = Some(ref_prefix);
+        self
+    }
+
+    /// Sets whether to include a checksum at the end of each frame.
+    #[must_use]
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.options = self.options.checksum(checksum);
+        self
+    }
+
+    /// Sets the maximum back-reference distance, as a power of 2.
+    ///
+    /// See [`Options::window_log`].
+    #[must_use]
+    pub fn window_log(mut self, window_log: u32) -> Self {
+        self.options = self.options.window_log(window_log);
+        self
+    }
+
+    /// Sets whether to enable long-distance matching.
+    #[must_use]
+    pub fn long_distance_matching(mut self, enabled: bool) -> Self {
+        self.options = self.options.long_distance_matching(enabled);
+        self
+    }
+
+    /// Sets the size of the data to compress, if known in advance.
+    ///
+    /// See [`Options::pledged_size`].
+    #[must_use]
+    pub fn pledged_size(mut self, size: u64) -> Self {
+        self.options = self.options.pledged_size(size);
+        self
+    }
+
+    /// Sets the number of worker threads.
+    ///
+    /// `0` (the default) disables multithreading.
+    ///
+    /// Note: This is only available if the `zstdmt` cargo feature is activated.
+    #[cfg(feature = "zstdmt")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zstdmt")))]
+    #[must_use]
+    pub fn workers(mut self, workers: u32) -> Self {
+        self.options = self.options.workers(workers);
+        self
+    }
+
+    /// Sets how prepared dictionaries are used by the context.
+    ///
+    /// See [`Options::dict_attach_pref`].
+    #[cfg(feature = "experimental")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+    #[must_use]
+    pub fn dict_attach_pref(
+        mut self,
+        pref: zstd_safe::DictAttachPref,
+    ) -> Self {
+        self.options = self.options.dict_attach_pref(pref);
+        self
+    }
+
+    /// Creates the encoder.
+    ///
+    /// Fails if zstd rejects the options, or if both a dictionary and a
+    /// prefix were given.
+    pub fn build(self) -> io::Result<Encoder<'a, W>> {
+        let encoder = raw::Encoder::with_options_and_prefix(
+            &self.options,
+            self.ref_prefix.unwrap_or(&[]),
+        )?;
+        Ok(Encoder::with_encoder(self.writer, encoder))
+    }
+}
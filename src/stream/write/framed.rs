@@ -0,0 +1,83 @@
+use std::io::{self, Write};
+
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+
+/// Compresses data to a writer, for encoders deciding where frames end.
+///
+/// This holds what [`RotatingEncoder`] and [`SplittingEncoder`] share: they
+/// only decide when to call [`FrameWriter::end_frame`].
+///
+/// [`RotatingEncoder`]: super::RotatingEncoder
+/// [`SplittingEncoder`]: super::SplittingEncoder
+pub(super) struct FrameWriter<'a, W> {
+    encoder: raw::Encoder<'a>,
+    pub(super) writer: W,
+
+    // Compressed bytes written so far.
+    pub(super) written: u64,
+    // Whether the current frame has received any data.
+    pub(super) started: bool,
+
+    buffer: Vec<u8>,
+}
+
+impl<'a, W: Write> FrameWriter<'a, W> {
+    pub(super) fn new(encoder: raw::Encoder<'a>, writer: W) -> Self {
+        FrameWriter {
+            encoder,
+            writer,
+            written: 0,
+            started: false,
+            buffer: Vec::with_capacity(zstd_safe::CCtx::out_size()),
+        }
+    }
+
+    /// Compresses all of `data` in the current frame.
+    pub(super) fn compress(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut input = InBuffer::around(data);
+        while input.pos() < data.len() {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            self.encoder.run(&mut input, &mut output)?;
+            self.write_buffer()?;
+        }
+        self.started = true;
+        Ok(())
+    }
+
+    /// Ends the current frame, and gets ready for the next one.
+    ///
+    /// The writer isn't flushed.
+    pub(super) fn end_frame(&mut self) -> io::Result<()> {
+        loop {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            let hint = self.encoder.finish(&mut output, true)?;
+            self.write_buffer()?;
+            if hint == 0 {
+                break;
+            }
+        }
+        self.started = false;
+        self.encoder.reinit()
+    }
+
+    /// Flushes the data given to the current frame, and the writer.
+    pub(super) fn flush(&mut self) -> io::Result<()> {
+        loop {
+            let mut output = OutBuffer::around(&mut self.buffer);
+            let hint = self.encoder.flush(&mut output)?;
+            self.write_buffer()?;
+            if hint == 0 {
+                break;
+            }
+        }
+        self.writer.flush()
+    }
+
+    /// Writes the content of `self.buffer` to the writer.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.written += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+}
@@ -1,14 +1,31 @@
 //! Implement push-based [`Write`] trait for both compressing and decompressing.
+use std::fmt;
 use std::io::{self, Write};
+use std::sync::Arc;
 
 use zstd_safe;
 
-use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::dict::{DecoderDictionary, DictRegistry, EncoderDictionary};
+use crate::stream::pool::BufferPool;
 use crate::stream::{raw, zio};
+use crate::Options;
+
+mod builder;
+mod framed;
+mod passthrough;
+mod rotate;
+mod split;
+mod text;
 
 #[cfg(test)]
 mod tests;
 
+pub use self::builder::EncoderBuilder;
+pub use self::passthrough::PassthroughEncoder;
+pub use self::rotate::RotatingEncoder;
+pub use self::split::SplittingEncoder;
+pub use self::text::TextWriter;
+
 /// An encoder that compress and forward data to another writer.
 ///
 /// This allows to compress a stream of data
@@ -24,6 +41,7 @@ mod tests;
 /// [`finish()`]: #method.finish
 /// [`auto_finish()`]: #method.auto_finish
 /// [`AutoFinishEncoder`]: AutoFinishEncoder
+#[derive(Debug)]
 pub struct Encoder<'a, W: Write> {
     // output writer (compressed data)
     writer: zio::Writer<W, raw::Encoder<'a>>,
@@ -35,6 +53,7 @@ pub struct Encoder<'a, W: Write> {
 /// You can use [`auto_flush()`] to automatically flush the writer on drop.
 ///
 /// [`auto_flush()`]: Decoder::auto_flush
+#[derive(Debug)]
 pub struct Decoder<'a, W: Write> {
     // output writer (decompressed data)
     writer: zio::Writer<W, raw::Decoder<'a>>,
@@ -98,6 +117,18 @@ impl<'a, W: Write, F: FnMut(io::Result<()>)> AutoFlushDecoder<'a, W, F> {
     }
 }
 
+impl<W, F> fmt::Debug for AutoFlushDecoder<'_, W, F>
+where
+    W: Write + fmt::Debug,
+    F: FnMut(io::Result<()>),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoFlushDecoder")
+            .field("decoder", &self.decoder)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<W, F> Drop for AutoFlushDecoder<'_, W, F>
 where
     W: Write,
@@ -117,6 +148,13 @@ impl<W: Write, F: FnMut(io::Result<()>)> Write for AutoFlushDecoder<'_, W, F> {
         self.decoder.as_mut().unwrap().write(buf)
     }
 
+    fn write_vectored(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<usize> {
+        self.decoder.as_mut().unwrap().write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.decoder.as_mut().unwrap().flush()
     }
@@ -146,6 +184,18 @@ impl<'a, W: Write, F: FnMut(io::Result<W>)> AutoFinishEncoder<'a, W, F> {
     }
 }
 
+impl<W, F> fmt::Debug for AutoFinishEncoder<'_, W, F>
+where
+    W: Write + fmt::Debug,
+    F: FnMut(io::Result<W>),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoFinishEncoder")
+            .field("encoder", &self.encoder)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<W: Write, F: FnMut(io::Result<W>)> Drop for AutoFinishEncoder<'_, W, F> {
     fn drop(&mut self) {
         let result = self.encoder.take().unwrap().finish();
@@ -160,6 +210,13 @@ impl<W: Write, F: FnMut(io::Result<W>)> Write for AutoFinishEncoder<'_, W, F> {
         self.encoder.as_mut().unwrap().write(buf)
     }
 
+    fn write_vectored(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<usize> {
+        self.encoder.as_mut().unwrap().write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.encoder.as_mut().unwrap().flush()
     }
@@ -175,6 +232,19 @@ impl<W: Write> Encoder<'static, W> {
         Self::with_dictionary(writer, level, &[])
     }
 
+    /// Creates a new encoder, using an output buffer from the given pool.
+    ///
+    /// The buffer goes back to the pool when the encoder is dropped.
+    pub fn new_with_pool(
+        writer: W,
+        level: i32,
+        pool: &BufferPool,
+    ) -> io::Result<Self> {
+        let encoder = raw::Encoder::new(level)?;
+        let writer = zio::Writer::with_buffer_pool(pool, writer, encoder);
+        Ok(Self::with_writer(writer))
+    }
+
     /// Creates a new encoder, using an existing dictionary.
     ///
     /// (Provides better compression ratio for small files,
@@ -189,9 +259,58 @@ impl<W: Write> Encoder<'static, W> {
         let encoder = raw::Encoder::with_dictionary(level, dictionary)?;
         Ok(Self::with_encoder(writer, encoder))
     }
+
+    /// Creates a new encoder, configured with the given options.
+    pub fn with_options(writer: W, options: &Options) -> io::Result<Self> {
+        let encoder = raw::Encoder::with_options(options)?;
+        Ok(Self::with_encoder(writer, encoder))
+    }
+
+    /// Creates a new encoder, using a shared `EncoderDictionary`.
+    ///
+    /// The encoder keeps the dictionary alive, so it doesn't borrow
+    /// anything (unlike [`Encoder::with_prepared_dictionary`]).
+    pub fn with_shared_dictionary(
+        writer: W,
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let encoder = raw::Encoder::with_shared_dictionary(dictionary)?;
+        Ok(Self::with_encoder(writer, encoder))
+    }
+}
+
+impl Encoder<'static, Box<dyn Write + Send>> {
+    /// Creates a new encoder around a boxed `Write`.
+    ///
+    /// This gives a [`DynEncoder`](crate::stream::DynEncoder), which is the
+    /// same type whatever the destination.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new_dyn(
+        writer: Box<dyn Write + Send>,
+        level: i32,
+    ) -> io::Result<Self> {
+        Self::new(writer, level)
+    }
+}
+
+impl<W: Write + Send + 'static> Encoder<'static, W> {
+    /// Returns this encoder as a boxed trait object.
+    ///
+    /// The stream is finished when the box is dropped (see
+    /// [`Encoder::auto_finish`]). Errors at that point are ignored: flush
+    /// the box before dropping it to catch most of them.
+    pub fn into_boxed(self) -> Box<dyn Write + Send> {
+        Box::new(self.auto_finish())
+    }
 }
 
 impl<'a, W: Write> Encoder<'a, W> {
+    /// Returns a builder to configure a new encoder.
+    pub fn builder(writer: W) -> EncoderBuilder<'a, W> {
+        EncoderBuilder::new(writer)
+    }
+
     /// Creates a new encoder from a prepared zio writer.
     pub fn with_writer(writer: zio::Writer<W, raw::Encoder<'a>>) -> Self {
         Self { writer }
@@ -259,6 +378,57 @@ impl<'a, W: Write> Encoder<'a, W> {
         AutoFinishEncoder::new(self, f)
     }
 
+    /// Returns an encoder splitting its output into parts of at most
+    /// `bytes` compressed bytes.
+    ///
+    /// The current writer receives the first part. Whenever the next write
+    /// could make the current part exceed the budget, its frame is ended and
+    /// `new_writer` is called to get the writer for the next part. Each part
+    /// is a standalone frame, which can be decompressed on its own.
+    ///
+    /// Data already written to this encoder is flushed to the current writer
+    /// first, and is not counted towards the budget.
+    ///
+    /// Returns an error if `bytes` is smaller than
+    /// [`RotatingEncoder::min_budget()`]. Note that this does not support
+    /// multithreaded compression, nor a pledged source size.
+    pub fn rotate_at_compressed_size<F>(
+        mut self,
+        bytes: u64,
+        new_writer: F,
+    ) -> io::Result<RotatingEncoder<'a, W, F>>
+    where
+        F: FnMut() -> W,
+    {
+        self.writer.flush()?;
+        let (writer, encoder) = self.writer.into_inner();
+        RotatingEncoder::new(encoder, writer, bytes, new_writer)
+    }
+
+    /// Returns an encoder ending frames exactly at the given offsets of the
+    /// input.
+    ///
+    /// `offsets` count the uncompressed bytes written to the returned
+    /// encoder, and must be increasing. Each frame can be decompressed on
+    /// its own, and gives exactly the input between two offsets. Frames are
+    /// never empty: an offset equal to the previous one is ignored.
+    ///
+    /// Data already written to this encoder is flushed first, and is part of
+    /// the first frame.
+    ///
+    /// A decreasing offset makes the next write fail.
+    pub fn split_at_offsets<I>(
+        mut self,
+        offsets: I,
+    ) -> io::Result<SplittingEncoder<'a, W, I::IntoIter>>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        self.writer.flush()?;
+        let (writer, encoder) = self.writer.into_inner();
+        Ok(SplittingEncoder::new(encoder, writer, offsets.into_iter()))
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.writer.writer()
@@ -279,7 +449,13 @@ impl<'a, W: Write> Encoder<'a, W> {
     ///
     /// This returns the inner writer in case you need it.
     ///
-    /// To get back `self` in case an error happened, use `try_finish`.
+    /// To get back `self` in case an error happened, use `try_finish`. This
+    /// is needed with non-blocking writers: after `WouldBlock`, call
+    /// `try_finish` (or `do_finish`) again to continue.
+    ///
+    /// This does not flush the inner writer, unless enabled with
+    /// [`flush_on_finish`](#method.flush_on_finish): use
+    /// [`finish_flush()`](#method.finish_flush) to do it once.
     ///
     /// **Note**: If you don't want (or can't) call `finish()` manually after
     ///           writing your data, consider using `auto_finish()` to get an
@@ -288,6 +464,26 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.try_finish().map_err(|(_, err)| err)
     }
 
+    /// **Required**: Finishes the stream, then flushes the inner writer.
+    ///
+    /// This is like [`finish()`](#method.finish), for callers that need the
+    /// compressed data to leave any buffer in the inner writer.
+    pub fn finish_flush(self) -> io::Result<W> {
+        let mut writer = self.finish()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    /// Sets whether finishing the stream also flushes the inner writer.
+    ///
+    /// This applies to every way of finishing the stream, including
+    /// [`AutoFinishEncoder`]. Disabled by default.
+    #[must_use]
+    pub fn flush_on_finish(mut self, flush: bool) -> Self {
+        self.writer.set_flush_on_finish(flush);
+        self
+    }
+
     /// **Required**: Attempts to finish the stream.
     ///
     /// You *need* to finish the stream when you're done writing, either with
@@ -298,6 +494,8 @@ impl<'a, W: Write> Encoder<'a, W> {
     ///
     /// `write` on this object will panic after `try_finish` has been called,
     /// even if it fails.
+    // The error gives back the whole encoder, however large it is.
+    #[allow(clippy::result_large_err)]
     pub fn try_finish(mut self) -> Result<W, (Self, io::Error)> {
         match self.writer.finish() {
             // Return the writer, because why not
@@ -314,6 +512,46 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.writer.finish()
     }
 
+    /// Makes one step towards flushing the stream, without waiting for it
+    /// to complete.
+    ///
+    /// Returns an estimate of how many compressed bytes are still buffered
+    /// by zstd. `0` means everything written so far reached the inner
+    /// writer, which was then flushed, like with [`flush()`]. Otherwise,
+    /// more calls are needed: the caller can do something else in between,
+    /// and keep writing data if it wants.
+    ///
+    /// This matters with multithreaded compression, where [`flush()`] waits
+    /// until every worker is done. Each `try_flush` call still waits for
+    /// some output to be ready, but returns as soon as it has written it.
+    ///
+    /// [`flush()`]: Write::flush
+    pub fn try_flush(&mut self) -> io::Result<usize> {
+        self.writer.try_flush()
+    }
+
+    /// Writes all the given slices, as if they were concatenated.
+    ///
+    /// Each slice is given to zstd in turn, without first copying them into
+    /// a single buffer. This suits messages assembled from several
+    /// fragments, like a header and a body.
+    pub fn write_vectored_all(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<()> {
+        for buf in bufs {
+            self.writer.write_all(buf)?;
+        }
+        Ok(())
+    }
+
+    /// Returns an adapter to write formatted text with `write!`.
+    ///
+    /// See [`TextWriter`].
+    pub fn text_writer(&mut self) -> TextWriter<'_, Self> {
+        TextWriter::new(self)
+    }
+
     /// Return a recommendation for the size of data to write at once.
     pub fn recommended_input_size() -> usize {
         zstd_safe::CCtx::in_size()
@@ -327,17 +565,46 @@ impl<'a, W: Write> Write for Encoder<'a, W> {
         self.writer.write(buf)
     }
 
+    fn write_vectored(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<usize> {
+        self.writer.write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.writer.flush()
     }
 }
 
+impl<'a> Encoder<'a, std::fs::File> {
+    /// **Required**: Finishes the stream, then syncs the file to disk.
+    ///
+    /// This is like [`finish()`](#method.finish), followed by
+    /// [`File::sync_data`](std::fs::File::sync_data): once it returns, the
+    /// compressed data is durably stored.
+    pub fn finish_sync_data(self) -> io::Result<std::fs::File> {
+        let file = self.finish_flush()?;
+        file.sync_data()?;
+        Ok(file)
+    }
+}
+
 impl<W: Write> Decoder<'static, W> {
     /// Creates a new decoder.
     pub fn new(writer: W) -> io::Result<Self> {
         Self::with_dictionary(writer, &[])
     }
 
+    /// Creates a new decoder, using an output buffer from the given pool.
+    ///
+    /// The buffer goes back to the pool when the decoder is dropped.
+    pub fn new_with_pool(writer: W, pool: &BufferPool) -> io::Result<Self> {
+        let decoder = raw::Decoder::new()?;
+        let writer = zio::Writer::with_buffer_pool(pool, writer, decoder);
+        Ok(Self::with_writer(writer))
+    }
+
     /// Creates a new decoder, using an existing dictionary.
     ///
     /// (Provides better compression ratio for small files,
@@ -346,6 +613,43 @@ impl<W: Write> Decoder<'static, W> {
         let decoder = raw::Decoder::with_dictionary(dictionary)?;
         Ok(Self::with_decoder(writer, decoder))
     }
+
+    /// Creates a new decoder, using a shared `DecoderDictionary`.
+    ///
+    /// The decoder keeps the dictionary alive, so it doesn't borrow
+    /// anything (unlike [`Decoder::with_prepared_dictionary`]).
+    pub fn with_shared_dictionary(
+        writer: W,
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> io::Result<Self> {
+        let decoder = raw::Decoder::with_shared_dictionary(dictionary)?;
+        Ok(Self::with_decoder(writer, decoder))
+    }
+
+    /// Creates a new decoder, selecting dictionaries from `registry`.
+    ///
+    /// Each frame uses the dictionary declared by its header. See
+    /// [`raw::Decoder::with_dictionaries`].
+    pub fn with_dictionaries(
+        writer: W,
+        registry: DictRegistry,
+    ) -> io::Result<Self> {
+        let decoder = raw::Decoder::with_dictionaries(registry)?;
+        Ok(Self::with_decoder(writer, decoder))
+    }
+}
+
+impl<W: Write + Send + 'static> Decoder<'static, W> {
+    /// Returns this decoder as a boxed trait object.
+    ///
+    /// This is convenient to store decoders along with other writers.
+    ///
+    /// The decoder is flushed when the box is dropped (see
+    /// [`Decoder::auto_flush`]). Errors at that point are ignored: flush the
+    /// box before dropping it to catch them.
+    pub fn into_boxed(self) -> Box<dyn Write + Send> {
+        Box::new(self.auto_flush())
+    }
 }
 
 impl<'a, W: Write> Decoder<'a, W> {
@@ -370,6 +674,20 @@ impl<'a, W: Write> Decoder<'a, W> {
         Decoder { writer }
     }
 
+    /// Creates a new decoder, finding dictionaries on demand.
+    ///
+    /// `resolver` is called with the dictionary ID of frames requiring a
+    /// dictionary the decoder doesn't have yet. See
+    /// [`raw::Decoder::set_dict_resolver`].
+    pub fn with_dict_resolver<F>(writer: W, resolver: F) -> io::Result<Self>
+    where
+        F: FnMut(u32) -> Option<Arc<DecoderDictionary<'static>>> + Send + 'a,
+    {
+        let mut decoder = raw::Decoder::new()?;
+        decoder.set_dict_resolver(resolver);
+        Ok(Self::with_decoder(writer, decoder))
+    }
+
     /// Creates a new decoder, using an existing prepared `DecoderDictionary`.
     ///
     /// (Provides better compression ratio for small files,
@@ -435,6 +753,13 @@ impl<W: Write> Write for Decoder<'_, W> {
         self.writer.write(buf)
     }
 
+    fn write_vectored(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<usize> {
+        self.writer.write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.writer.flush()
     }
@@ -442,9 +767,12 @@ impl<W: Write> Write for Decoder<'_, W> {
 
 fn _assert_traits() {
     fn _assert_send<T: Send>(_: T) {}
+    fn _assert_sync<T: Sync>(_: T) {}
 
     _assert_send(Decoder::new(Vec::new()));
     _assert_send(Encoder::new(Vec::new(), 1));
     _assert_send(Decoder::new(Vec::new()).unwrap().auto_flush());
     _assert_send(Encoder::new(Vec::new(), 1).unwrap().auto_finish());
+    _assert_sync(Decoder::new(Vec::new()));
+    _assert_sync(Encoder::new(Vec::new(), 1));
 }
@@ -0,0 +1,228 @@
+use std::io::{self, Write};
+
+use crate::bulk::{self, Compressor};
+use crate::frame;
+
+/// Default size of the chunks compressed (or stored) independently.
+const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+
+/// An encoder that only compresses data when it is worth it.
+///
+/// Input is cut into chunks, each one written as an independent zstd frame.
+/// Before compressing a chunk, a small sample of it is compressed with a fast
+/// level. If this doesn't save enough space, the chunk is stored as-is, in a
+/// frame made of raw blocks.
+///
+/// This is much faster on already-compressed inputs (images, videos,
+/// archives, ...), and the output can still be read by any zstd decoder.
+///
+/// Don't forget to call [`finish()`] before dropping it!
+///
+/// [`finish()`]: PassthroughEncoder::finish
+pub struct PassthroughEncoder<'a, W: Write> {
+    writer: W,
+
+    compressor: Compressor<'a>,
+    probe: Compressor<'static>,
+
+    // Chunks compressing to more than this ratio are stored.
+    threshold: f32,
+    chunk_size: usize,
+
+    // Input for the current chunk.
+    input: Vec<u8>,
+    // Output for the current chunk.
+    output: Vec<u8>,
+
+    stored_chunks: u64,
+    compressed_chunks: u64,
+}
+
+impl<W: Write> PassthroughEncoder<'static, W> {
+    /// Creates a new passthrough encoder.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> io::Result<Self> {
+        Self::with_dictionary(writer, level, &[])
+    }
+
+    /// Creates a new passthrough encoder, using an existing dictionary.
+    ///
+    /// Stored chunks do not use the dictionary, but it will still be
+    /// required to decompress the other ones.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn with_dictionary(
+        writer: W,
+        level: i32,
+        dictionary: &[u8],
+    ) -> io::Result<Self> {
+        let compressor = Compressor::with_dictionary(level, dictionary)?;
+        Self::with_compressor(writer, compressor)
+    }
+}
+
+impl<'a, W: Write> PassthroughEncoder<'a, W> {
+    /// Creates a new passthrough encoder using the given compressor.
+    pub fn with_compressor(
+        writer: W,
+        compressor: Compressor<'a>,
+    ) -> io::Result<Self> {
+        Ok(PassthroughEncoder {
+            writer,
+            compressor,
+            probe: Compressor::new(1)?,
+            threshold: 0.9,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            input: Vec::new(),
+            output: Vec::new(),
+            stored_chunks: 0,
+            compressed_chunks: 0,
+        })
+    }
+
+    /// Sets the compression ratio above which chunks are stored.
+    ///
+    /// The ratio is the compressed size divided by the original size. For
+    /// example, with the default of `0.9`, chunks are only compressed if
+    /// this saves at least 10% of their size.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// Sets the size of the chunks compressed (or stored) independently.
+    ///
+    /// Smaller chunks adapt faster to changes in the input, but compress
+    /// less. Defaults to 1MB.
+    ///
+    /// Only affects the next chunk.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+
+    /// Returns the number of chunks written without compression so far.
+    pub fn stored_chunks(&self) -> u64 {
+        self.stored_chunks
+    }
+
+    /// Returns the number of chunks written compressed so far.
+    pub fn compressed_chunks(&self) -> u64 {
+        self.compressed_chunks
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// **Required**: Finishes the stream.
+    ///
+    /// This writes the last chunk, and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_chunk()?;
+        Ok(self.writer)
+    }
+
+    /// Compresses or stores the current chunk, and sends it to the writer.
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.input.is_empty() {
+            return Ok(());
+        }
+
+        self.output.clear();
+        if self.is_worth_compressing()? {
+            self.output
+                .reserve(zstd_safe::compress_bound(self.input.len()));
+            self.compressor
+                .compress_to_buffer(&self.input, &mut self.output)?;
+        }
+
+        // The probe may have been too optimistic.
+        if self.output.is_empty() || self.output.len() >= self.input.len() {
+            self.output.clear();
+            frame::write_raw_frame(&self.input, &mut self.output)?;
+            self.stored_chunks += 1;
+        } else {
+            self.compressed_chunks += 1;
+        }
+
+        self.writer.write_all(&self.output)?;
+        self.input.clear();
+        Ok(())
+    }
+
+    /// Compresses a sample of the current chunk, and checks its ratio.
+    fn is_worth_compressing(&mut self) -> io::Result<bool> {
+        let ratio = bulk::sample_ratio(
+            &mut self.probe,
+            &self.input,
+            &mut self.output,
+        )?;
+        Ok(ratio < self.threshold)
+    }
+}
+
+impl<W: Write> Write for PassthroughEncoder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.input.len() >= self.chunk_size {
+            self.write_chunk()?;
+        }
+
+        let len = buf.len().min(self.chunk_size - self.input.len());
+        self.input.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Writes the current chunk (as a complete frame), and flushes the writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.writer.flush()
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(PassthroughEncoder::new(Vec::new(), 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PassthroughEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn test_passthrough() {
+        let text = include_bytes!("../../../assets/example.txt").repeat(64);
+        // Pseudo-random data is not compressible.
+        let mut state = 1u32;
+        let noise: Vec<u8> = (0..text.len())
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 24) as u8
+            })
+            .collect();
+
+        let mut encoder = PassthroughEncoder::new(Vec::new(), 1).unwrap();
+        encoder.write_all(&noise).unwrap();
+        encoder.flush().unwrap();
+        encoder.write_all(&text).unwrap();
+        encoder.flush().unwrap();
+
+        assert_eq!(encoder.stored_chunks(), 1);
+        assert_eq!(encoder.compressed_chunks(), 1);
+        let encoded = encoder.finish().unwrap();
+        assert!(encoded.len() < noise.len() + text.len() / 2);
+
+        let decoded = crate::decode_all(&encoded[..]).unwrap();
+        assert_eq!(decoded, [&noise[..], &text[..]].concat());
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::{self, Write};
+
+use super::framed::FrameWriter;
+use crate::frame;
+use crate::stream::raw;
+
+/// An encoder splitting its output into parts of bounded compressed size.
+///
+/// Each part is a complete zstd frame, written to its own writer, and can be
+/// decompressed on its own. Concatenating all the parts gives a valid
+/// multi-frame stream for the entire input.
+///
+/// Before compressing more data, the encoder checks whether the worst-case
+/// size of the frame could exceed the budget. If so, it ends the frame,
+/// flushes the current writer, and moves on to a new one. Parts never exceed
+/// the budget, and are usually a bit smaller (by up to a few hundred KB).
+///
+/// The output only depends on the data written and on the size of each call
+/// to `write`.
+///
+/// This is created by [`Encoder::rotate_at_compressed_size`].
+///
+/// Don't forget to call [`finish()`] before dropping it!
+///
+/// [`Encoder::rotate_at_compressed_size`]: super::Encoder::rotate_at_compressed_size
+/// [`finish()`]: RotatingEncoder::finish
+pub struct RotatingEncoder<'a, W, F> {
+    // Counts the compressed bytes written to the current part.
+    inner: FrameWriter<'a, W>,
+    new_writer: F,
+
+    budget: u64,
+    parts: u64,
+}
+
+impl<'a, W: Write, F: FnMut() -> W> RotatingEncoder<'a, W, F> {
+    pub(super) fn new(
+        encoder: raw::Encoder<'a>,
+        writer: W,
+        budget: u64,
+        new_writer: F,
+    ) -> io::Result<Self> {
+        if budget < Self::margin() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "compressed size budget is too small",
+            ));
+        }
+
+        Ok(RotatingEncoder {
+            inner: FrameWriter::new(encoder, writer),
+            new_writer,
+            budget,
+            parts: 1,
+        })
+    }
+
+    /// Returns the worst-case size needed to end a frame after a write.
+    ///
+    /// zstd may hold up to a block of input not compressed yet, and each
+    /// write adds at most another block.
+    fn margin() -> u64 {
+        let block = frame::BLOCK_SIZE_MAX as usize;
+        (zstd_safe::compress_bound(2 * block)
+            + frame::FrameHeader::MAX_SIZE
+            + frame::Checksum::SIZE) as u64
+    }
+
+    /// Returns the smallest budget accepted.
+    pub fn min_budget() -> u64 {
+        Self::margin()
+    }
+
+    /// Returns the number of parts started so far, including the current one.
+    pub fn parts(&self) -> u64 {
+        self.parts
+    }
+
+    /// Returns the number of compressed bytes written to the current part.
+    pub fn part_size(&self) -> u64 {
+        self.inner.written
+    }
+
+    /// Acquires a reference to the current writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner.writer
+    }
+
+    /// Acquires a mutable reference to the current writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner.writer
+    }
+
+    /// **Required**: Finishes the last part, and returns its writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.end_frame()?;
+        self.inner.writer.flush()?;
+        Ok(self.inner.writer)
+    }
+
+    /// Moves on to a new part.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.inner.end_frame()?;
+        self.inner.writer.flush()?;
+
+        self.inner.writer = (self.new_writer)();
+        self.inner.written = 0;
+        self.parts += 1;
+        Ok(())
+    }
+}
+
+impl<'a, W: Write, F: FnMut() -> W> Write for RotatingEncoder<'a, W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.inner.started
+            && self.inner.written + Self::margin() > self.budget
+        {
+            self.rotate()?;
+        }
+
+        let len = buf.len().min(frame::BLOCK_SIZE_MAX as usize);
+        self.inner.compress(&buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use super::RotatingEncoder;
+    use crate::stream::write::Encoder;
+
+    #[derive(Clone, Default)]
+    struct Part(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Part {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rotate() {
+        // Random-ish data, which doesn't compress well.
+        let mut state = 1u64;
+        let input: Vec<u8> = (0..2_000_000)
+            .map(|_| {
+                state =
+                    state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect();
+
+        let budget =
+            RotatingEncoder::<Part, fn() -> Part>::min_budget() + 1000;
+        let parts = Rc::new(RefCell::new(vec![Part::default()]));
+        let first = parts.borrow()[0].clone();
+
+        let mut encoder = Encoder::new(first, 1)
+            .unwrap()
+            .rotate_at_compressed_size(budget, || {
+                let part = Part::default();
+                parts.borrow_mut().push(part.clone());
+                part
+            })
+            .unwrap();
+        for chunk in input.chunks(100_000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        assert!(encoder.parts() > 2);
+        encoder.finish().unwrap();
+
+        let mut decompressed = Vec::new();
+        for part in parts.borrow().iter() {
+            let part = part.0.borrow();
+            assert!(part.len() as u64 <= budget);
+            // Each part is a standalone frame.
+            decompressed.extend(crate::decode_all(&part[..]).unwrap());
+        }
+        assert_eq!(decompressed, input);
+    }
+}
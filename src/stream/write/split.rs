@@ -0,0 +1,216 @@
+use std::io::{self, Write};
+
+use super::framed::FrameWriter;
+use crate::stream::raw;
+
+/// An encoder ending frames at given offsets of the input.
+///
+/// Each frame starts and ends exactly at the requested uncompressed offsets,
+/// so it can be decompressed on its own and always gives the same slice of
+/// the input. This is useful to align compressed chunks with the blocks of a
+/// content-addressed store.
+///
+/// A single call to `write` never crosses an offset: it stops there and ends
+/// the frame. After such a call, [`SplittingEncoder::compressed_offset`]
+/// gives the end of the frame in the output.
+///
+/// This is created by [`Encoder::split_at_offsets`].
+///
+/// Don't forget to call [`finish()`] before dropping it!
+///
+/// [`Encoder::split_at_offsets`]: super::Encoder::split_at_offsets
+/// [`finish()`]: SplittingEncoder::finish
+pub struct SplittingEncoder<'a, W, I> {
+    inner: FrameWriter<'a, W>,
+
+    offsets: I,
+    // Where to end the current frame, if anywhere.
+    next: Option<u64>,
+    // `true` if `next` needs to be taken from `offsets`.
+    needs_next: bool,
+
+    // Uncompressed bytes written so far.
+    position: u64,
+    frames: u64,
+}
+
+impl<'a, W: Write, I: Iterator<Item = u64>> SplittingEncoder<'a, W, I> {
+    pub(super) fn new(
+        encoder: raw::Encoder<'a>,
+        writer: W,
+        offsets: I,
+    ) -> Self {
+        SplittingEncoder {
+            inner: FrameWriter::new(encoder, writer),
+            offsets,
+            next: None,
+            needs_next: true,
+            position: 0,
+            frames: 0,
+        }
+    }
+
+    /// Returns the number of frames completed so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of uncompressed bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the number of compressed bytes written to the writer so far.
+    pub fn compressed_offset(&self) -> u64 {
+        self.inner.written
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner.writer
+    }
+
+    /// **Required**: Finishes the last frame, and returns the writer.
+    ///
+    /// If the input ended exactly at an offset, no empty frame is added.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.inner.started || self.frames == 0 {
+            self.end_frame()?;
+        }
+        self.inner.writer.flush()?;
+        Ok(self.inner.writer)
+    }
+
+    /// Takes the next offset after the current position.
+    fn next_offset(&mut self) -> io::Result<()> {
+        self.next = loop {
+            match self.offsets.next() {
+                None => break None,
+                Some(offset) if offset > self.position => break Some(offset),
+                // Frames are never empty.
+                Some(offset) if offset == self.position => continue,
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "split offsets must be increasing",
+                    ))
+                }
+            }
+        };
+        self.needs_next = false;
+        Ok(())
+    }
+
+    /// Ends the current frame.
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.inner.end_frame()?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+impl<'a, W: Write, I: Iterator<Item = u64>> Write
+    for SplittingEncoder<'a, W, I>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Done here rather than after the previous write, so an invalid
+        // offset fails before any data is consumed.
+        if self.needs_next {
+            self.next_offset()?;
+        }
+
+        let len = match self.next {
+            Some(next) if next - self.position < buf.len() as u64 => {
+                (next - self.position) as usize
+            }
+            _ => buf.len(),
+        };
+
+        self.inner.compress(&buf[..len])?;
+        self.position += len as u64;
+
+        if self.next == Some(self.position) {
+            self.end_frame()?;
+            self.needs_next = true;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::stream::write::Encoder;
+
+    #[test]
+    fn test_split_at_offsets() {
+        let text = include_bytes!("../../../assets/example.txt");
+        let offsets = [0, 100, 100, 500, 501, text.len() as u64];
+
+        let mut encoder = Encoder::new(Vec::new(), 1)
+            .unwrap()
+            .split_at_offsets(offsets.iter().copied())
+            .unwrap();
+
+        // Compressed and uncompressed ends of each frame.
+        let mut ends = Vec::new();
+        for mut chunk in text.chunks(77) {
+            while !chunk.is_empty() {
+                let frames = encoder.frames();
+                let written = encoder.write(chunk).unwrap();
+                chunk = &chunk[written..];
+                if encoder.frames() > frames {
+                    ends.push((
+                        encoder.compressed_offset() as usize,
+                        encoder.position() as usize,
+                    ));
+                }
+            }
+        }
+        let compressed = encoder.finish().unwrap();
+
+        let positions: Vec<usize> = ends.iter().map(|end| end.1).collect();
+        assert_eq!(positions, [100, 500, 501, text.len()]);
+        // No empty frame at the end.
+        assert_eq!(ends[3].0, compressed.len());
+
+        let mut start = (0, 0);
+        for end in ends {
+            let frame = &compressed[start.0..end.0];
+            let content = crate::decode_all(frame).unwrap();
+            assert_eq!(content, &text[start.1..end.1]);
+            start = end;
+        }
+    }
+
+    #[test]
+    fn test_split_at_decreasing_offsets() {
+        let mut encoder = Encoder::new(Vec::new(), 1)
+            .unwrap()
+            .split_at_offsets(vec![10, 5])
+            .unwrap();
+        encoder.write_all(&[0; 10]).unwrap();
+        assert_eq!(
+            encoder.write(&[0; 10]).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -54,7 +54,11 @@ fn test_partial_write_finish() {
     assert_eq!(&decode_all(&buf[..]).unwrap(), &input);
 }
 
-fn setup_partial_write(input_data: &[u8]) -> Encoder<PartialWrite<Vec<u8>>> {
+// A single partial write is enough here.
+#[allow(clippy::unused_io_amount)]
+fn setup_partial_write(
+    input_data: &[u8],
+) -> Encoder<'static, PartialWrite<Vec<u8>>> {
     let buf =
         PartialWrite::new(Vec::new(), iter::repeat(PartialOp::Limited(1)));
     let mut z = Encoder::new(buf, 1).unwrap();
@@ -70,3 +74,296 @@ fn setup_partial_write(input_data: &[u8]) -> Encoder<PartialWrite<Vec<u8>>> {
 
     z
 }
+
+#[test]
+fn test_frame_boundaries() {
+    let mut compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    compressed.extend(crate::encode_all(&b"barbaz"[..], 1).unwrap());
+
+    let mut decoder = Decoder::new(Vec::new()).unwrap();
+    // Feed the input one byte at a time.
+    for byte in &compressed {
+        decoder.write_all(&[*byte]).unwrap();
+    }
+    decoder.flush().unwrap();
+
+    assert_eq!(decoder.frames_decoded(), 2);
+    assert_eq!(decoder.total_out(), 9);
+    assert_eq!(decoder.into_inner(), b"foobarbaz");
+}
+
+#[test]
+fn test_skippable_frames() {
+    use std::sync::{Arc, Mutex};
+
+    let mut compressed = Vec::new();
+    crate::frame::write_skippable_frame(&mut compressed, 0, b"header")
+        .unwrap();
+    compressed.extend(crate::encode_all(&b"foo"[..], 1).unwrap());
+    crate::frame::write_skippable_frame(&mut compressed, 15, b"").unwrap();
+    crate::frame::write_skippable_frame(&mut compressed, 1, b"trailer")
+        .unwrap();
+
+    for chunk_size in [1, 5, compressed.len()] {
+        let payloads = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&payloads);
+
+        let mut decoder = Decoder::new(Vec::new()).unwrap();
+        decoder.on_skippable_frame(move |magic_variant, payload| {
+            sink.lock().unwrap().push((magic_variant, payload.to_vec()))
+        });
+        for chunk in compressed.chunks(chunk_size) {
+            decoder.write_all(chunk).unwrap();
+        }
+        decoder.flush().unwrap();
+
+        assert_eq!(decoder.frames_decoded(), 4);
+        assert_eq!(decoder.into_inner(), b"foo");
+        assert_eq!(
+            *payloads.lock().unwrap(),
+            [
+                (0, b"header".to_vec()),
+                (15, Vec::new()),
+                (1, b"trailer".to_vec())
+            ]
+        );
+    }
+}
+
+#[test]
+fn test_flush_on_finish() {
+    let text = include_bytes!("../../../assets/example.txt");
+    let buffered = || std::io::BufWriter::with_capacity(1 << 20, Vec::new());
+
+    // By default, the data stays in the inner writer's buffer.
+    let mut encoder = Encoder::new(buffered(), 1).unwrap();
+    encoder.write_all(text).unwrap();
+    assert!(encoder.finish().unwrap().get_ref().is_empty());
+
+    let mut encoder = Encoder::new(buffered(), 1).unwrap();
+    encoder.write_all(text).unwrap();
+    let writer = encoder.finish_flush().unwrap();
+    assert_eq!(decode_all(&writer.get_ref()[..]).unwrap(), text);
+
+    let mut encoder =
+        Encoder::new(buffered(), 1).unwrap().flush_on_finish(true);
+    encoder.write_all(text).unwrap();
+    let writer = encoder.finish().unwrap();
+    assert_eq!(decode_all(&writer.get_ref()[..]).unwrap(), text);
+}
+
+#[test]
+fn test_write_vectored_all() {
+    use std::io::IoSlice;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let (header, body) = text.split_at(10);
+
+    let mut encoder = Encoder::new(
+        PartialWrite::new(Vec::new(), iter::repeat(PartialOp::Limited(5))),
+        1,
+    )
+    .unwrap();
+    encoder
+        .write_vectored_all(&[
+            IoSlice::new(header),
+            IoSlice::new(b""),
+            IoSlice::new(body),
+        ])
+        .unwrap();
+    let compressed = encoder.finish().unwrap().into_inner();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+}
+
+#[test]
+fn test_try_flush() {
+    let text = include_bytes!("../../../assets/example.txt");
+
+    let mut workers = vec![0];
+    if cfg!(feature = "zstdmt") {
+        workers.push(2);
+    }
+
+    for n_workers in workers {
+        let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+        #[cfg(feature = "zstdmt")]
+        encoder.multithread(n_workers).unwrap();
+        #[cfg(not(feature = "zstdmt"))]
+        let _ = n_workers;
+
+        encoder.write_all(text).unwrap();
+        while encoder.try_flush().unwrap() > 0 {}
+        assert_eq!(encoder.try_flush().unwrap(), 0);
+
+        // Everything written so far can be decompressed already.
+        let mut decoder =
+            crate::stream::read::Decoder::new(&encoder.get_ref()[..]).unwrap();
+        let mut output = vec![0u8; text.len()];
+        std::io::Read::read_exact(&mut decoder, &mut output).unwrap();
+        assert_eq!(&output[..], &text[..]);
+
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+    }
+}
+
+#[test]
+fn test_write_vectored() {
+    use std::io::IoSlice;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let chunks: Vec<&[u8]> = text.chunks(100).collect();
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    let mut slices: Vec<IoSlice<'_>> =
+        chunks.iter().map(|chunk| IoSlice::new(chunk)).collect();
+    // All slices are taken in a single call.
+    assert_eq!(encoder.write_vectored(&slices).unwrap(), text.len());
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+
+    // Decoding stops at the end of each frame.
+    let mut concatenated = crate::encode_all(&b"foo"[..], 1).unwrap();
+    let frame_len = concatenated.len();
+    concatenated.extend(crate::encode_all(&b"bar"[..], 1).unwrap());
+    let (first, second) = concatenated.split_at(5);
+    slices = vec![IoSlice::new(first), IoSlice::new(second)];
+    let mut decoder = Decoder::new(Vec::new()).unwrap();
+    assert_eq!(decoder.write_vectored(&slices).unwrap(), frame_len);
+    decoder.write_all(&concatenated[frame_len..]).unwrap();
+    decoder.flush().unwrap();
+    assert_eq!(decoder.frames_decoded(), 2);
+    assert_eq!(decoder.into_inner(), b"foobar");
+
+    // Slices taken before an error are still reported.
+    let compressed = crate::encode_all(&b"foo"[..], 1).unwrap();
+    let corrupt = [0xff; 8];
+    slices = vec![IoSlice::new(&compressed[..6]), IoSlice::new(&corrupt)];
+    let mut decoder = Decoder::new(Vec::new()).unwrap();
+    assert_eq!(decoder.write_vectored(&slices).unwrap(), 6);
+    assert!(decoder.write(&corrupt).is_err());
+}
+
+#[test]
+fn test_text_writer() {
+    use std::fmt::Write as _;
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    let mut text = encoder.text_writer();
+    write!(text, "é-{}", 42).unwrap();
+    writeln!(text, "!").unwrap();
+    assert!(text.take_error().is_none());
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), "é-42!\n".as_bytes());
+
+    // I/O errors are kept aside.
+    let writer = PartialWrite::new(
+        Vec::new(),
+        iter::repeat(PartialOp::Err(std::io::ErrorKind::Other)),
+    );
+    let mut encoder = Encoder::new(writer, 1).unwrap();
+    let mut text = encoder.text_writer();
+    // Output only reaches the writer once zstd produced enough of it.
+    assert!((0..1_000_000).any(|i| write!(text, "{} ", i).is_err()));
+    let error = text.take_error().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::Other);
+    assert!(text.take_error().is_none());
+}
+
+#[test]
+fn test_builder() {
+    let text = include_bytes!("../../../assets/example.txt");
+    let prefix = &text[..500];
+
+    let mut encoder = Encoder::builder(Vec::new())
+        .level(19)
+        .checksum(true)
+        .pledged_size(text.len() as u64)
+        .ref_prefix(prefix)
+        .build()
+        .unwrap();
+    encoder.write_all(text).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let (header, _) = crate::frame::FrameHeader::parse(&compressed).unwrap();
+    assert!(header.checksum);
+    assert_eq!(header.content_size, Some(text.len() as u64));
+
+    // The prefix is needed to decompress.
+    assert!(decode_all(&compressed[..]).is_err());
+    let mut decoder = crate::stream::read::Decoder::builder(&compressed[..])
+        .ref_prefix(prefix)
+        .build()
+        .unwrap();
+    let mut decompressed = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+    assert_eq!(&decompressed[..], &text[..]);
+
+    let err = Encoder::builder(Vec::new())
+        .dictionary(prefix)
+        .ref_prefix(prefix)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(Encoder::builder(Vec::new()).level(1000).build().is_err());
+
+    // Settings after a preset override it.
+    let mut encoder = Encoder::builder(Vec::new())
+        .preset(&crate::preset::Preset::archival())
+        .checksum(false)
+        .build()
+        .unwrap();
+    encoder.write_all(text).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let (header, _) = crate::frame::FrameHeader::parse(&compressed).unwrap();
+    assert!(!header.checksum);
+    assert_eq!(header.window_size, Some(1 << 27));
+    assert_eq!(decode_all(&compressed[..]).unwrap(), text);
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn test_dict_attach_pref() {
+    use crate::dict::EncoderDictionary;
+    use zstd_safe::DictAttachPref;
+
+    let text = include_bytes!("../../../assets/example.txt");
+    let dictionary = &text[..500];
+    let prepared = EncoderDictionary::copy(dictionary, 3);
+
+    for &pref in &[
+        DictAttachPref::DefaultAttach,
+        DictAttachPref::ForceAttach,
+        DictAttachPref::ForceCopy,
+        DictAttachPref::ForceLoad,
+    ] {
+        let mut encoder =
+            Encoder::with_prepared_dictionary(Vec::new(), &prepared).unwrap();
+        encoder.dict_attach_pref(pref).unwrap();
+        encoder.write_all(text).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder =
+            crate::stream::read::Decoder::builder(&compressed[..])
+                .dictionary(dictionary)
+                .build()
+                .unwrap();
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+        assert_eq!(&decompressed[..], &text[..]);
+    }
+
+    let options = crate::Options::new()
+        .dictionary(dictionary)
+        .dict_attach_pref(DictAttachPref::ForceCopy);
+    let compressed =
+        crate::stream::encode_all_with(&text[..], &options).unwrap();
+    let mut decompressed = Vec::new();
+    crate::stream::copy_decode_with_dictionary(
+        &compressed[..],
+        &mut decompressed,
+        dictionary,
+    )
+    .unwrap();
+    assert_eq!(&decompressed[..], &text[..]);
+}
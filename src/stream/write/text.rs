@@ -0,0 +1,69 @@
+use std::fmt;
+use std::io::{self, Write};
+
+/// Adapter writing formatted text to an encoder, with [`std::fmt::Write`].
+///
+/// This can be created by [`Encoder::text_writer`], and lets `write!` stream
+/// text into the encoder without first formatting it into a `String`.
+///
+/// Each string is written entirely before the next one, so the compressed
+/// text stays valid UTF-8 unless writing fails. `fmt::Error` doesn't carry
+/// any information: the underlying I/O error is kept, and can be retrieved
+/// with [`TextWriter::take_error`].
+///
+/// ```rust
+/// use std::fmt::Write;
+///
+/// let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 3)?;
+/// let mut text = encoder.text_writer();
+/// for i in 0..3 {
+///     if writeln!(text, "line {}", i).is_err() {
+///         return Err(text.take_error().unwrap());
+///     }
+/// }
+/// let compressed = encoder.finish()?;
+///
+/// let decompressed = zstd::decode_all(&compressed[..])?;
+/// assert_eq!(decompressed, b"line 0\nline 1\nline 2\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`Encoder::text_writer`]: super::Encoder::text_writer
+pub struct TextWriter<'e, W: ?Sized> {
+    writer: &'e mut W,
+    error: Option<io::Error>,
+}
+
+impl<'e, W: Write + ?Sized> TextWriter<'e, W> {
+    /// Creates a new adapter around the given writer.
+    pub fn new(writer: &'e mut W) -> Self {
+        TextWriter {
+            writer,
+            error: None,
+        }
+    }
+
+    /// Returns the error which made the last write fail, if any.
+    ///
+    /// The error is cleared, so later failures can be told apart.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl<W: Write + ?Sized> fmt::Write for TextWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+impl<W: ?Sized> fmt::Debug for TextWriter<'_, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextWriter")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,4 +1,16 @@
 //! Wrappers around raw operations implementing `std::io::{Read, Write}`.
+//!
+//! # Errors from the inner IO
+//!
+//! Errors from the inner reader or writer, like `WouldBlock` from a
+//! non-blocking socket, are returned as-is, and never lose or repeat data:
+//!
+//! * A `read` or `write` returning an error did not consume or produce
+//!   anything. Input consumed and output produced by earlier calls is kept.
+//! * `flush` and `finish` can be called again after an error, and continue
+//!   where they stopped.
+//!
+//! So after such an error, simply retry the same call once the IO is ready.
 
 mod reader;
 mod writer;
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::io::{self, BufRead, Read};
 
 use crate::stream::raw::{InBuffer, Operation, OutBuffer};
@@ -17,8 +18,23 @@ pub struct Reader<R, D> {
 
     single_frame: bool,
     finished_frame: bool,
+
+    // Whether the input stays in place until the end (see `set_stable_input`).
+    stable_input: bool,
+    // Position in the input, which is only consumed at the end.
+    stable_pos: usize,
+
+    // Input bytes left to consume, if limited.
+    input_limit: Option<u64>,
+
+    // Output produced ahead of time, for small reads.
+    readahead: Vec<u8>,
+    readahead_pos: usize,
+    readahead_end: usize,
+    readahead_size: usize,
 }
 
+#[derive(Debug)]
 enum State {
     // Still actively reading from the inner `Read`
     Reading,
@@ -39,6 +55,13 @@ impl<R, D> Reader<R, D> {
             state: State::Reading,
             single_frame: false,
             finished_frame: false,
+            stable_input: false,
+            stable_pos: 0,
+            input_limit: None,
+            readahead: Vec::new(),
+            readahead_pos: 0,
+            readahead_end: 0,
+            readahead_size: 0,
         }
     }
 
@@ -47,6 +70,108 @@ impl<R, D> Reader<R, D> {
         self.single_frame = true;
     }
 
+    /// Resumes reading after a frame, when stopped by single-frame mode.
+    ///
+    /// The next frame will also stop reading once decoded. Returns `false`
+    /// (and does nothing) if `self` was not stopped at the end of a frame.
+    pub fn start_next_frame(&mut self) -> bool {
+        match self.state {
+            State::Finished if self.single_frame && self.finished_frame => {
+                self.state = State::Reading;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stops reading input, and finishes the operation now.
+    ///
+    /// Following reads return the rest of the output (for encoders, the end
+    /// of the current frame), then `0`, even if the reader has more data.
+    /// Use [`Reader::restart`] to keep going afterwards.
+    pub fn finish_now(&mut self) {
+        if let State::Reading = self.state {
+            self.state = State::PastEof;
+        }
+    }
+
+    /// Starts reading input again, once the operation is finished.
+    ///
+    /// This re-initializes the operation (for encoders, the next read starts
+    /// a new frame). Returns `false` (and does nothing) if the operation is
+    /// not finished yet: the rest of the output needs to be read first.
+    pub fn restart(&mut self) -> io::Result<bool>
+    where
+        D: Operation,
+    {
+        match self.state {
+            State::Finished => {
+                self.operation.reinit()?;
+                self.state = State::Reading;
+                self.finished_frame = false;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Gives the whole input to the operation at once, and keeps it there.
+    ///
+    /// Normally, input is consumed from the reader as soon as the operation
+    /// takes it, so the operation needs to copy it (zstd keeps the last
+    /// window of input in its own buffer). In this mode, the input is only
+    /// consumed at the end, and the operation is finished right away with
+    /// [`Operation::finish_with_input`]. Combined with
+    /// `CParameter::StableInBuffer`, this lets zstd compress directly from
+    /// the reader's buffer, without copying it.
+    ///
+    /// This is only correct if `fill_buf()` on the reader returns _all_ the
+    /// remaining input, at the same address each time, until it is consumed
+    /// (like `&[u8]` does). This mode produces a single frame.
+    pub fn set_stable_input(&mut self) {
+        self.stable_input = true;
+    }
+
+    /// Limits the input to the next `bytes` bytes of the reader.
+    ///
+    /// Past this limit, the reader is treated as if it reached EOF. This
+    /// lets an operation read a region of known length, leaving the rest of
+    /// the reader untouched. `None` removes the limit.
+    pub fn set_input_limit(&mut self, bytes: Option<u64>) {
+        self.input_limit = bytes;
+    }
+
+    /// Sets the size of the read-ahead buffer. `0` disables it (the default).
+    ///
+    /// Reads smaller than this are served from an internal buffer, filled
+    /// `bytes` at a time. This costs an extra copy, but saves a call to the
+    /// operation for each small read (like parsers reading 4-byte headers).
+    ///
+    /// Data already buffered is kept. Any buffered data is lost when using
+    /// [`Reader::into_inner`].
+    pub fn set_readahead(&mut self, bytes: usize) {
+        self.readahead.truncate(self.readahead_end);
+        self.readahead.drain(..self.readahead_pos);
+        self.readahead_pos = 0;
+        self.readahead_end = self.readahead.len();
+        self.readahead_size = bytes;
+    }
+
+    /// Copies buffered data to `buf`.
+    fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
+        let available =
+            &self.readahead[self.readahead_pos..self.readahead_end];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.readahead_pos += len;
+        len
+    }
+
+    /// Returns a reference to the underlying operation.
+    pub fn operation(&self) -> &D {
+        &self.operation
+    }
+
     /// Returns a mutable reference to the underlying operation.
     pub fn operation_mut(&mut self) -> &mut D {
         &mut self.operation
@@ -74,11 +199,15 @@ impl<R, D> Reader<R, D> {
     where
         D: Operation,
     {
+        // Data in the read-ahead buffer comes first.
+        if self.readahead_pos < self.readahead_end {
+            return Ok(self.read_buffered(output));
+        }
         self.operation.flush(&mut OutBuffer::around(output))
     }
 }
-// Read and retry on Interrupted errors.
-fn fill_buf<R>(reader: &mut R) -> io::Result<&[u8]>
+// Read and retry on Interrupted errors, and apply the input limit.
+fn fill_buf<R>(reader: &mut R, limit: Option<u64>) -> io::Result<&[u8]>
 where
     R: BufRead,
 {
@@ -99,7 +228,22 @@ where
 
     // eprintln!("Filled buffer: {:?}", res);
 
-    Ok(res)
+    match limit {
+        Some(limit) if limit < res.len() as u64 => Ok(&res[..limit as usize]),
+        _ => Ok(res),
+    }
+}
+
+impl<R: fmt::Debug, D: fmt::Debug> fmt::Debug for Reader<R, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("reader", &self.reader)
+            .field("operation", &self.operation)
+            .field("state", &self.state)
+            .field("single_frame", &self.single_frame)
+            .field("finished_frame", &self.finished_frame)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<R, D> Read for Reader<R, D>
@@ -108,6 +252,102 @@ where
     D: Operation,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.readahead_pos < self.readahead_end {
+            return Ok(self.read_buffered(buf));
+        }
+        if buf.len() >= self.readahead_size {
+            return self.read_direct(buf);
+        }
+
+        // Small read: fill the read-ahead buffer first.
+        let mut readahead = std::mem::take(&mut self.readahead);
+        readahead.resize(self.readahead_size, 0);
+        let result = self.read_direct(&mut readahead);
+        self.readahead = readahead;
+        self.readahead_pos = 0;
+        self.readahead_end = *result.as_ref().unwrap_or(&0);
+        result?;
+
+        Ok(self.read_buffered(buf))
+    }
+
+    fn read_vectored(
+        &mut self,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            if buf.is_empty() {
+                continue;
+            }
+            let bytes_read = match self.read(buf) {
+                Ok(bytes_read) => bytes_read,
+                // The input causing the error was not consumed: it will be
+                // reported again by the next call.
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            };
+            total += bytes_read;
+            // Only move on to the next slice if this one was filled.
+            if bytes_read < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+impl<R, D> Reader<R, D>
+where
+    R: BufRead,
+    D: Operation,
+{
+    /// Counts `bytes` consumed against the input limit.
+    fn consume_limit(&mut self, bytes: usize) {
+        if let Some(limit) = &mut self.input_limit {
+            *limit -= bytes as u64;
+        }
+    }
+
+    /// Reads from a stable input, see [`Reader::set_stable_input`].
+    fn read_stable(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let State::Finished = self.state {
+                return Ok(0);
+            }
+
+            let input = fill_buf(&mut self.reader, self.input_limit)?;
+            let mut src = InBuffer::around(input);
+            src.set_pos(self.stable_pos);
+            let mut dst = OutBuffer::around(buf);
+
+            let hint = self.operation.finish_with_input(
+                &mut src,
+                &mut dst,
+                self.finished_frame,
+            )?;
+            self.stable_pos = src.pos();
+            let bytes_written = dst.pos();
+
+            if hint == 0 {
+                self.reader.consume(self.stable_pos);
+                self.consume_limit(self.stable_pos);
+                self.stable_pos = 0;
+                self.state = State::Finished;
+            }
+
+            if bytes_written > 0 || buf.is_empty() {
+                return Ok(bytes_written);
+            }
+        }
+    }
+
+    /// Reads directly into `buf`, without using the read-ahead buffer.
+    fn read_direct(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stable_input {
+            return self.read_stable(buf);
+        }
+
         // Keep trying until _something_ has been written.
         let mut first = true;
         loop {
@@ -120,7 +360,7 @@ where
                             // eprintln!("First run, no input coming.");
                             b""
                         } else {
-                            fill_buf(&mut self.reader)?
+                            fill_buf(&mut self.reader, self.input_limit)?
                         };
 
                         // eprintln!("Input = {:?}", input);
@@ -166,6 +406,7 @@ where
                     };
 
                     self.reader.consume(bytes_read);
+                    self.consume_limit(bytes_read);
 
                     if bytes_written > 0 {
                         return Ok(bytes_written);
@@ -189,6 +430,11 @@ where
                         // This indicates that the footer is complete.
                         // This is the only way to terminate the stream cleanly.
                         self.state = State::Finished;
+                    } else if dst.pos() == 0 && dst.capacity() > 0 {
+                        // Returning 0 here would look like EOF, but there is
+                        // more to come (multithreaded compression may not
+                        // have any output ready yet).
+                        continue;
                     }
 
                     return Ok(dst.pos());
@@ -238,4 +484,39 @@ mod tests {
         let decoded = crate::decode_all(&output[..]).unwrap();
         assert_eq!(&decoded, input);
     }
+
+    #[test]
+    fn test_readahead() {
+        use crate::stream::raw::Decoder;
+
+        let input = include_bytes!("../../../assets/example.txt");
+        let mut compressed = crate::encode_all(&input[..], 1).unwrap();
+        compressed.extend_from_slice(b"trailing");
+
+        let mut reader =
+            Reader::new(Cursor::new(&compressed), Decoder::new().unwrap());
+        reader.set_single_frame();
+        reader.set_readahead(64);
+
+        let mut output = Vec::new();
+        let mut header = [0u8; 4];
+        for _ in 0..10 {
+            reader.read_exact(&mut header).unwrap();
+            output.extend_from_slice(&header);
+        }
+
+        // Buffered data is kept when changing the size.
+        reader.set_readahead(0);
+        reader.read_exact(&mut header).unwrap();
+        output.extend_from_slice(&header);
+
+        reader.set_readahead(1000);
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(&output, input);
+
+        // Only the frame was consumed.
+        let mut rest = Vec::new();
+        reader.into_inner().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailing");
+    }
 }
@@ -1,5 +1,7 @@
+use std::fmt;
 use std::io::{self, Write};
 
+use crate::stream::pool::{BufferPool, PooledBuffer};
 use crate::stream::raw::{InBuffer, Operation, OutBuffer};
 
 // input -> [ zstd -> buffer -> writer ]
@@ -25,7 +27,7 @@ pub struct Writer<W, D> {
     /// Output buffer
     ///
     /// Where the operation writes, before it gets flushed to the writer
-    buffer: Vec<u8>,
+    buffer: PooledBuffer,
 
     // When `true`, indicates that nothing should be added to the buffer.
     // All that's left if to empty the buffer.
@@ -36,6 +38,9 @@ pub struct Writer<W, D> {
     /// Only happens when decompressing.
     /// The context needs to be re-initialized to process the next frame.
     finished_frame: bool,
+
+    /// When `true`, `finish` also flushes the writer.
+    flush_on_finish: bool,
 }
 
 impl<W, D> Writer<W, D>
@@ -64,17 +69,37 @@ where
         output_buffer: Vec<u8>,
         writer: W,
         operation: D,
+    ) -> Self {
+        Self::with_pooled_buffer(output_buffer.into(), writer, operation)
+    }
+
+    /// Creates a new `Writer`, using an output buffer from the given pool.
+    ///
+    /// The buffer goes back to the pool when the `Writer` is dropped.
+    pub fn with_buffer_pool(
+        pool: &BufferPool,
+        writer: W,
+        operation: D,
+    ) -> Self {
+        // 32KB buffer? That's what flate2 uses
+        Self::with_pooled_buffer(pool.get(32 * 1024), writer, operation)
+    }
+
+    fn with_pooled_buffer(
+        buffer: PooledBuffer,
+        writer: W,
+        operation: D,
     ) -> Self {
         Writer {
             writer,
             operation,
 
             offset: 0,
-            // 32KB buffer? That's what flate2 uses
-            buffer: output_buffer,
+            buffer,
 
             finished: false,
             finished_frame: false,
+            flush_on_finish: false,
         }
     }
 
@@ -95,6 +120,9 @@ where
             // At this point the buffer has been fully written out.
 
             if self.finished {
+                if self.flush_on_finish {
+                    self.writer.flush()?;
+                }
                 return Ok(());
             }
 
@@ -110,20 +138,47 @@ where
             // Could happen with invalid data, ...
             let hint = hint?;
 
-            if hint != 0 && self.buffer.is_empty() {
-                // This happens if we are decoding an incomplete frame.
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "incomplete frame",
-                ));
-            }
-
             // println!("Finishing {}, {}", bytes_written, hint);
 
+            // A non-zero hint with no output is not an error: with
+            // multithreaded compression, workers may still be busy. Decoders
+            // report incomplete frames as errors from `finish` directly.
             self.finished = hint == 0;
         }
     }
 
+    /// Makes one step towards flushing the stream.
+    ///
+    /// Returns an estimate of how many bytes are still held by the
+    /// operation. When it returns `0`, everything given so far has reached
+    /// the writer, which is then flushed. Otherwise, call it again to make
+    /// more progress.
+    pub fn try_flush(&mut self) -> io::Result<usize> {
+        self.write_from_offset()?;
+
+        let hint = if self.finished {
+            0
+        } else {
+            let hint = self.with_buffer(|dst, op| op.flush(dst));
+            self.offset = 0;
+            let hint = hint?;
+            self.write_from_offset()?;
+            hint
+        };
+
+        if hint == 0 {
+            self.writer.flush()?;
+        }
+        Ok(hint)
+    }
+
+    /// Sets whether `finish` also flushes the writer.
+    ///
+    /// By default, `finish` only writes the end of the stream to the writer.
+    pub fn set_flush_on_finish(&mut self, flush: bool) {
+        self.flush_on_finish = flush;
+    }
+
     /// Run the given closure on `self.buffer`.
     ///
     /// The buffer will be cleared, and made available wrapped in an `OutBuffer`.
@@ -132,7 +187,7 @@ where
         F: FnOnce(&mut OutBuffer<'_, Vec<u8>>, &mut D) -> T,
     {
         self.buffer.clear();
-        let mut output = OutBuffer::around(&mut self.buffer);
+        let mut output = OutBuffer::around(&mut *self.buffer);
         // eprintln!("Output: {:?}", output);
         f(&mut output, &mut self.operation)
     }
@@ -200,6 +255,18 @@ where
     }
 }
 
+impl<W: fmt::Debug, D: fmt::Debug> fmt::Debug for Writer<W, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("writer", &self.writer)
+            .field("operation", &self.operation)
+            .field("pending", &(self.buffer.len() - self.offset))
+            .field("finished", &self.finished)
+            .field("finished_frame", &self.finished_frame)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<W, D> Write for Writer<W, D>
 where
     W: Write,
@@ -252,6 +319,63 @@ where
         }
     }
 
+    fn write_vectored(
+        &mut self,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "encoder is finished",
+            ));
+        }
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        // Like `write`, but gives as many slices as possible to the
+        // operation before sending its output to the writer.
+        loop {
+            self.write_from_offset()?;
+
+            if self.finished_frame {
+                self.operation.reinit()?;
+                self.finished_frame = false;
+            }
+
+            let mut bytes_read = 0;
+            let hint: io::Result<usize> = self.with_buffer(|dst, op| {
+                let mut hint = 1;
+                for buf in bufs {
+                    let mut src = InBuffer::around(buf);
+                    let result = op.run(&mut src, dst);
+                    bytes_read += src.pos;
+                    hint = match result {
+                        Ok(hint) => hint,
+                        // Some slices were already taken: report them,
+                        // the error will come up again on the next call.
+                        Err(_) if bytes_read > 0 => break,
+                        Err(e) => return Err(e),
+                    };
+                    // Stop at the end of a frame, or once the output is
+                    // full.
+                    if hint == 0 || src.pos < buf.len() {
+                        break;
+                    }
+                }
+                Ok(hint)
+            });
+
+            self.offset = 0;
+            let hint = hint?;
+
+            if hint == 0 {
+                self.finished_frame = true;
+            }
+
+            if bytes_read > 0 || total == 0 {
+                return Ok(bytes_read);
+            }
+        }
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         let mut finished = self.finished;
         loop {
@@ -295,6 +419,24 @@ mod tests {
         assert_eq!(&output, input);
     }
 
+    #[test]
+    fn test_passthrough() {
+        use crate::stream::raw::Passthrough;
+
+        let input = b"AbcdefghAbcdefgh.";
+
+        let mut writer = Writer::new(Vec::new(), Passthrough::new());
+        writer.write_all(&input[..8]).unwrap();
+        writer.write_all(&input[8..]).unwrap();
+        writer.finish().unwrap();
+        writer.finish().unwrap();
+
+        let (output, operation) = writer.into_inner();
+        assert_eq!(&output, input);
+        assert_eq!(operation.frames(), 1);
+        assert_eq!(operation.bytes(), input.len() as u64);
+    }
+
     #[test]
     fn test_compress() {
         use crate::stream::raw::Encoder;
@@ -331,4 +473,26 @@ mod tests {
         // println!("Output: {:?}", output);
         assert_eq!(&output, input);
     }
+
+    #[test]
+    fn test_dyn_operation() {
+        use crate::stream::raw::{Decoder, DynOperation, Encoder, NoOp};
+
+        let input = b"AbcdefghAbcdefgh.";
+
+        let run = |operation: Box<dyn DynOperation>, data: &[u8]| {
+            let mut output = Vec::new();
+            {
+                let mut writer = Writer::new(&mut output, operation);
+                writer.write_all(data).unwrap();
+                writer.finish().unwrap();
+            }
+            output
+        };
+
+        let compressed = run(Box::new(Encoder::new(1).unwrap()), input);
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input);
+        assert_eq!(run(Box::new(Decoder::new().unwrap()), &compressed), input);
+        assert_eq!(run(Box::new(NoOp), input), input);
+    }
 }
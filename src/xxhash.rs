@@ -0,0 +1,192 @@
+//! XXH64 implementation, as used for zstd frame checksums.
+//!
+//! The zstd library only uses xxhash internally, so this re-implements it.
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(buf)
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn merge_round(acc: u64, val: u64) -> u64 {
+    (acc ^ round(0, val))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+/// Streaming XXH64 hasher.
+#[derive(Clone, Debug)]
+pub(crate) struct XxHash64 {
+    seed: u64,
+    total_len: u64,
+    acc: [u64; 4],
+    buffer: [u8; 32],
+    buffered: usize,
+}
+
+impl XxHash64 {
+    /// Creates a new hasher using the given seed.
+    pub(crate) fn new(seed: u64) -> Self {
+        XxHash64 {
+            seed,
+            total_len: 0,
+            acc: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            buffer: [0; 32],
+            buffered: 0,
+        }
+    }
+
+    /// Adds data to the hash.
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        if self.buffered > 0 {
+            let missing = (32 - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + missing]
+                .copy_from_slice(&data[..missing]);
+            self.buffered += missing;
+            data = &data[missing..];
+
+            if self.buffered < 32 {
+                return;
+            }
+            let buffer = self.buffer;
+            self.consume_stripe(&buffer);
+            self.buffered = 0;
+        }
+
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.consume_stripe(stripe);
+        }
+
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    fn consume_stripe(&mut self, stripe: &[u8]) {
+        for (i, acc) in self.acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u64(&stripe[i * 8..]));
+        }
+    }
+
+    /// Returns the hash of all the data given so far.
+    pub(crate) fn digest(&self) -> u64 {
+        let mut hash = if self.total_len >= 32 {
+            let [v1, v2, v3, v4] = self.acc;
+            let mut hash = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            for v in self.acc {
+                hash = merge_round(hash, v);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+
+        hash = hash.wrapping_add(self.total_len);
+
+        let mut rest = &self.buffer[..self.buffered];
+        while rest.len() >= 8 {
+            hash ^= round(0, read_u64(rest));
+            hash = hash
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            hash ^= u64::from(read_u32(rest)).wrapping_mul(PRIME64_1);
+            hash = hash
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash ^= u64::from(byte).wrapping_mul(PRIME64_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^= hash >> 32;
+        hash
+    }
+}
+
+/// Computes the XXH64 hash of `data`, using the given seed.
+///
+/// This is the same implementation as used for frame checksums: the
+/// checksum of a frame is the lower 32 bits of the hash of its content,
+/// with a seed of `0`.
+///
+/// ```rust
+/// let hash = zstd::xxhash64(b"abc", 0);
+/// assert_eq!(hash, 0x44BC_2CF5_AD77_0999);
+/// ```
+#[cfg(any(test, feature = "xxhash"))]
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    let mut hasher = XxHash64::new(seed);
+    hasher.update(data);
+    hasher.digest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{xxhash64, XxHash64};
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(xxhash64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxhash64(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxhash64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(
+            xxhash64(b"Nobody inspects the spammish repetition", 0),
+            0xFBCE_A83C_8A37_8BF1
+        );
+    }
+
+    #[test]
+    fn test_streaming() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let expected = xxhash64(&data, 42);
+
+        for &step in &[1, 3, 31, 32, 33, 100] {
+            let mut hasher = XxHash64::new(42);
+            for chunk in data.chunks(step) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.digest(), expected);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Golden-file tests for `Preset::stable`.
+#![cfg(feature = "stability")]
+
+use std::io::Write;
+
+use zstd::preset::{Preset, STABLE_ZSTD_VERSION};
+
+const TEXT: &[u8] = include_bytes!("../assets/example.txt");
+// Not named `.zst`, so it is included in the package.
+const GOLDEN: &[u8] = include_bytes!("../assets/example.txt.stable");
+
+fn compress(chunk_size: usize) -> Vec<u8> {
+    let mut encoder = zstd::Encoder::new(Vec::new(), 0).unwrap();
+    encoder.apply_preset(&Preset::stable()).unwrap();
+    for chunk in TEXT.chunks(chunk_size) {
+        encoder.write_all(chunk).unwrap();
+    }
+    encoder.finish().unwrap()
+}
+
+// Only the bundled library is pinned: a system library may differ.
+#[cfg_attr(feature = "pkg-config", ignore = "uses the system library")]
+#[test]
+fn test_stable_output() {
+    assert_eq!(zstd::zstd_safe::version_number(), STABLE_ZSTD_VERSION);
+
+    for chunk_size in [1, 100, TEXT.len()] {
+        assert_eq!(compress(chunk_size), GOLDEN);
+    }
+
+    let mut encoder = zstd::Encoder::builder(Vec::new())
+        .preset(&Preset::stable())
+        .build()
+        .unwrap();
+    encoder.write_all(TEXT).unwrap();
+    assert_eq!(encoder.finish().unwrap(), GOLDEN);
+    assert_eq!(zstd::decode_all(GOLDEN).unwrap(), TEXT);
+}
@@ -0,0 +1,260 @@
+//! Error categories, from `zstd_errors.h`.
+use crate::ErrorCode;
+
+/// The category of a zstd error code.
+///
+/// This mirrors `ZSTD_ErrorCode`. Each variant has the value zstd gives it,
+/// which stays the same across zstd versions.
+///
+/// Use [`get_error_code`] to get the category of an error code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[repr(u32)]
+pub enum ErrorKind {
+    /// Not an error.
+    NoError = 0,
+    /// Any error not covered by another category.
+    Generic = 1,
+    PrefixUnknown = 10,
+    VersionUnsupported = 12,
+    FrameParameterUnsupported = 14,
+    /// The frame needs a larger window than the decoder allows.
+    FrameParameterWindowTooLarge = 16,
+    CorruptionDetected = 20,
+    ChecksumWrong = 22,
+    LiteralsHeaderWrong = 24,
+    DictionaryCorrupted = 30,
+    DictionaryWrong = 32,
+    DictionaryCreationFailed = 34,
+    ParameterUnsupported = 40,
+    ParameterCombinationUnsupported = 41,
+    ParameterOutOfBound = 42,
+    TableLogTooLarge = 44,
+    MaxSymbolValueTooLarge = 46,
+    MaxSymbolValueTooSmall = 48,
+    CannotProduceUncompressedBlock = 49,
+    StabilityConditionNotRespected = 50,
+    StageWrong = 60,
+    InitMissing = 62,
+    MemoryAllocation = 64,
+    WorkSpaceTooSmall = 66,
+    DstSizeTooSmall = 70,
+    SrcSizeWrong = 72,
+    DstBufferNull = 74,
+    NoForwardProgressDestFull = 80,
+    NoForwardProgressInputEmpty = 82,
+    FrameIndexTooLarge = 100,
+    SeekableIo = 102,
+    DstBufferWrong = 104,
+    SrcBufferWrong = 105,
+    SequenceProducerFailed = 106,
+    ExternalSequencesInvalid = 107,
+}
+
+impl ErrorKind {
+    /// Returns the category with the given `ZSTD_ErrorCode` value.
+    ///
+    /// Values unknown to this version are [`ErrorKind::Generic`].
+    pub fn from_raw(value: usize) -> Self {
+        use ErrorKind::*;
+
+        match value {
+            0 => NoError,
+            10 => PrefixUnknown,
+            12 => VersionUnsupported,
+            14 => FrameParameterUnsupported,
+            16 => FrameParameterWindowTooLarge,
+            20 => CorruptionDetected,
+            22 => ChecksumWrong,
+            24 => LiteralsHeaderWrong,
+            30 => DictionaryCorrupted,
+            32 => DictionaryWrong,
+            34 => DictionaryCreationFailed,
+            40 => ParameterUnsupported,
+            41 => ParameterCombinationUnsupported,
+            42 => ParameterOutOfBound,
+            44 => TableLogTooLarge,
+            46 => MaxSymbolValueTooLarge,
+            48 => MaxSymbolValueTooSmall,
+            49 => CannotProduceUncompressedBlock,
+            50 => StabilityConditionNotRespected,
+            60 => StageWrong,
+            62 => InitMissing,
+            64 => MemoryAllocation,
+            66 => WorkSpaceTooSmall,
+            70 => DstSizeTooSmall,
+            72 => SrcSizeWrong,
+            74 => DstBufferNull,
+            80 => NoForwardProgressDestFull,
+            82 => NoForwardProgressInputEmpty,
+            100 => FrameIndexTooLarge,
+            102 => SeekableIo,
+            104 => DstBufferWrong,
+            105 => SrcBufferWrong,
+            106 => SequenceProducerFailed,
+            107 => ExternalSequencesInvalid,
+            _ => Generic,
+        }
+    }
+
+    /// Returns the error code zstd uses for this category.
+    ///
+    /// This is the inverse of [`get_error_code`].
+    pub const fn to_error_code(self) -> ErrorCode {
+        0usize.wrapping_sub(self as usize)
+    }
+}
+
+impl core::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(get_error_string(*self))
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "std")))]
+impl std::error::Error for ErrorKind {}
+
+/// Returns the category of an error code.
+///
+/// This is the equivalent of `ZSTD_getErrorCode`. Codes that are not errors
+/// give [`ErrorKind::NoError`].
+pub fn get_error_code(code: ErrorCode) -> ErrorKind {
+    if crate::is_error(code) {
+        ErrorKind::from_raw(0usize.wrapping_sub(code))
+    } else {
+        ErrorKind::NoError
+    }
+}
+
+/// An error code returned by zstd.
+///
+/// Unlike [`ErrorKind`], this keeps the exact code, so codes unknown to
+/// this version keep their own message instead of the generic one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Error(ErrorCode);
+
+impl Error {
+    /// Wraps an error code returned by zstd.
+    pub fn from_code(code: ErrorCode) -> Self {
+        Error(code)
+    }
+
+    /// Returns the raw error code.
+    pub fn code(self) -> ErrorCode {
+        self.0
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(self) -> ErrorKind {
+        get_error_code(self.0)
+    }
+}
+
+impl core::fmt::Debug for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Error")
+            .field("code", &self.0)
+            .field("kind", &self.kind())
+            .field("name", &crate::get_error_name(self.0))
+            .finish()
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(crate::get_error_name(self.0))
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "std")))]
+impl std::error::Error for Error {}
+
+/// Returns the message of an error category.
+///
+/// This is the equivalent of `ZSTD_getErrorString`, and gives the same
+/// message as [`get_error_name`](crate::get_error_name) for the codes of
+/// this category.
+#[cfg(feature = "experimental")]
+pub fn get_error_string(kind: ErrorKind) -> &'static str {
+    use zstd_sys::ZSTD_ErrorCode::*;
+
+    let code = match kind {
+        ErrorKind::NoError => ZSTD_error_no_error,
+        ErrorKind::Generic => ZSTD_error_GENERIC,
+        ErrorKind::PrefixUnknown => ZSTD_error_prefix_unknown,
+        ErrorKind::VersionUnsupported => ZSTD_error_version_unsupported,
+        ErrorKind::FrameParameterUnsupported => {
+            ZSTD_error_frameParameter_unsupported
+        }
+        ErrorKind::FrameParameterWindowTooLarge => {
+            ZSTD_error_frameParameter_windowTooLarge
+        }
+        ErrorKind::CorruptionDetected => ZSTD_error_corruption_detected,
+        ErrorKind::ChecksumWrong => ZSTD_error_checksum_wrong,
+        ErrorKind::LiteralsHeaderWrong => ZSTD_error_literals_headerWrong,
+        ErrorKind::DictionaryCorrupted => ZSTD_error_dictionary_corrupted,
+        ErrorKind::DictionaryWrong => ZSTD_error_dictionary_wrong,
+        ErrorKind::DictionaryCreationFailed => {
+            ZSTD_error_dictionaryCreation_failed
+        }
+        ErrorKind::ParameterUnsupported => ZSTD_error_parameter_unsupported,
+        ErrorKind::ParameterCombinationUnsupported => {
+            ZSTD_error_parameter_combination_unsupported
+        }
+        ErrorKind::ParameterOutOfBound => ZSTD_error_parameter_outOfBound,
+        ErrorKind::TableLogTooLarge => ZSTD_error_tableLog_tooLarge,
+        ErrorKind::MaxSymbolValueTooLarge => {
+            ZSTD_error_maxSymbolValue_tooLarge
+        }
+        ErrorKind::MaxSymbolValueTooSmall => {
+            ZSTD_error_maxSymbolValue_tooSmall
+        }
+        ErrorKind::StabilityConditionNotRespected => {
+            ZSTD_error_stabilityCondition_notRespected
+        }
+        ErrorKind::StageWrong => ZSTD_error_stage_wrong,
+        ErrorKind::InitMissing => ZSTD_error_init_missing,
+        ErrorKind::MemoryAllocation => ZSTD_error_memory_allocation,
+        ErrorKind::WorkSpaceTooSmall => ZSTD_error_workSpace_tooSmall,
+        ErrorKind::DstSizeTooSmall => ZSTD_error_dstSize_tooSmall,
+        ErrorKind::SrcSizeWrong => ZSTD_error_srcSize_wrong,
+        ErrorKind::DstBufferNull => ZSTD_error_dstBuffer_null,
+        ErrorKind::NoForwardProgressDestFull => {
+            ZSTD_error_noForwardProgress_destFull
+        }
+        ErrorKind::NoForwardProgressInputEmpty => {
+            ZSTD_error_noForwardProgress_inputEmpty
+        }
+        ErrorKind::FrameIndexTooLarge => ZSTD_error_frameIndex_tooLarge,
+        ErrorKind::SeekableIo => ZSTD_error_seekableIO,
+        ErrorKind::DstBufferWrong => ZSTD_error_dstBuffer_wrong,
+        ErrorKind::SrcBufferWrong => ZSTD_error_srcBuffer_wrong,
+        ErrorKind::SequenceProducerFailed => {
+            ZSTD_error_sequenceProducer_failed
+        }
+        ErrorKind::ExternalSequencesInvalid => {
+            ZSTD_error_externalSequences_invalid
+        }
+        // Not in these bindings yet.
+        ErrorKind::CannotProduceUncompressedBlock => {
+            return crate::get_error_name(kind.to_error_code());
+        }
+    };
+    // Safety: Just FFI
+    unsafe { crate::c_char_to_str(zstd_sys::ZSTD_getErrorString(code)) }
+}
+
+/// Returns the message of an error category.
+///
+/// This is the equivalent of `ZSTD_getErrorString`, and gives the same
+/// message as [`get_error_name`](crate::get_error_name) for the codes of
+/// this category.
+///
+/// `ZSTD_getErrorString` itself is only bound with the `experimental`
+/// feature: without it, this goes through the category's error code.
+#[cfg(not(feature = "experimental"))]
+pub fn get_error_string(kind: ErrorKind) -> &'static str {
+    crate::get_error_name(kind.to_error_code())
+}
@@ -18,21 +18,45 @@
 //! Features denoted as experimental in the C library are hidden behind an
 //! `experimental` feature.
 #![cfg_attr(feature = "doc-cfg", feature(doc_cfg))]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-// TODO: Use alloc feature instead to implement stuff for Vec
-// TODO: What about Cursor?
 #[cfg(feature = "std")]
 extern crate std;
 
 #[cfg(test)]
 mod tests;
 
+mod errors;
+pub use errors::{get_error_code, get_error_string, Error, ErrorKind};
+
+mod strategy;
+pub use strategy::{parse_strategy, strategy_name, ParseStrategyError};
+
 // Re-export zstd-sys
 pub use zstd_sys;
 
 /// How to compress data.
+///
+/// See [`parse_strategy`] and [`strategy_name`] to use names like
+/// `btultra2`.
 pub use zstd_sys::ZSTD_strategy as Strategy;
 
+/// What to do at the end of a streaming compression step.
+pub use zstd_sys::ZSTD_EndDirective as EndDirective;
+
+/// Frame header, as decoded by [`get_frame_header`].
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+pub use zstd_sys::ZSTD_frameHeader as FrameHeader;
+
+/// Type of a frame: regular or skippable.
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+pub use zstd_sys::ZSTD_frameType_e as FrameType;
+
 /// Reset directive.
 // pub use zstd_sys::ZSTD_ResetDirective as ResetDirective;
 use core::ffi::{c_char, c_int, c_ulonglong, c_void};
@@ -71,6 +95,17 @@ impl core::fmt::Display for ContentSizeError {
     }
 }
 
+/// zstd's `srcSize_wrong` error ("Src size is incorrect").
+///
+/// Error code values are stable, so errors detected on this side can be
+/// reported with zstd's own codes.
+const ERROR_SRC_SIZE_WRONG: ErrorCode =
+    ErrorKind::SrcSizeWrong.to_error_code();
+
+/// zstd's `dstSize_tooSmall` error ("Destination buffer is too small").
+const ERROR_DST_SIZE_TOO_SMALL: ErrorCode =
+    ErrorKind::DstSizeTooSmall.to_error_code();
+
 /// Returns true if code represents error.
 fn is_error(code: usize) -> bool {
     // Safety: Just FFI
@@ -192,6 +227,19 @@ pub fn decompress<C: WriteBuf + ?Sized>(
     }
 }
 
+/// Decompresses all the frames in `src` into `dst`.
+///
+/// `src` can be a concatenation of several frames, which are decompressed
+/// one after the other. See [`DCtx::decompress_all_into`].
+///
+/// Returns the number of bytes written to `dst`.
+pub fn decompress_all_into<C: WriteBuf + ?Sized>(
+    dst: &mut C,
+    src: &[u8],
+) -> SafeResult {
+    DCtx::create().decompress_all_into(dst, src)
+}
+
 /// Wraps the `ZSTD_getDecompressedSize` function.
 ///
 /// Returns `None` if the size could not be found, or if the content is actually empty.
@@ -518,7 +566,7 @@ impl<'a> CCtx<'a> {
         &mut self,
         output: &mut OutBuffer<'_, C>,
         input: &mut InBuffer<'_>,
-        end_op: zstd_sys::ZSTD_EndDirective,
+        end_op: EndDirective,
     ) -> SafeResult {
         let mut output = output.wrap();
         let mut input = input.wrap();
@@ -533,6 +581,40 @@ impl<'a> CCtx<'a> {
         })
     }
 
+    /// Compresses all of `input`, appending the result to `output`.
+    ///
+    /// This calls [`CCtx::compress_stream2`] until `input` is entirely
+    /// consumed and, for `ZSTD_e_flush` and `ZSTD_e_end`, until the directive
+    /// is complete. `output` grows as needed.
+    ///
+    /// Returns the number of bytes appended to `output`.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "alloc")))]
+    pub fn compress_stream2_simple(
+        &mut self,
+        output: &mut alloc::vec::Vec<u8>,
+        input: &[u8],
+        end_op: EndDirective,
+    ) -> SafeResult {
+        let start = output.len();
+        let mut input = InBuffer::around(input);
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(Self::out_size());
+            }
+            let pos = output.len();
+            let mut out = OutBuffer::around_pos(output, pos);
+            let remaining =
+                self.compress_stream2(&mut out, &mut input, end_op)?;
+
+            if input.pos() == input.src.len()
+                && (end_op == EndDirective::ZSTD_e_continue || remaining == 0)
+            {
+                return Ok(output.len() - start);
+            }
+        }
+    }
+
     /// Flush any intermediate buffer.
     ///
     /// To fully flush, you should keep calling this function until it returns `Ok(0)`.
@@ -1170,6 +1252,44 @@ impl<'a> DCtx<'a> {
         parse_code(code)
     }
 
+    /// Decompresses all the frames in `src` into `dst`.
+    ///
+    /// Unlike [`DCtx::decompress`], `src` can be a concatenation of several
+    /// frames (including skippable frames), like the output of multiple
+    /// compression calls appended together. It is decompressed with
+    /// [`DCtx::decompress_stream`], so the decompressed size doesn't need to
+    /// be recorded in the frame headers.
+    ///
+    /// The session is reset first, but parameters and dictionaries are kept.
+    ///
+    /// Returns the number of bytes written to `dst`. If `dst` is too small, or
+    /// if `src` ends in the middle of a frame, an error is returned.
+    pub fn decompress_all_into<C: WriteBuf + ?Sized>(
+        &mut self,
+        dst: &mut C,
+        src: &[u8],
+    ) -> SafeResult {
+        self.reset(ResetDirective::SessionOnly)?;
+
+        let mut output = OutBuffer::around(dst);
+        let mut input = InBuffer::around(src);
+        let mut hint = 0;
+        while input.pos() < src.len() || hint != 0 {
+            let (in_pos, out_pos) = (input.pos(), output.pos());
+            hint = self.decompress_stream(&mut output, &mut input)?;
+
+            if input.pos() == in_pos && output.pos() == out_pos {
+                return Err(if output.pos() == output.capacity() {
+                    ERROR_DST_SIZE_TOO_SMALL
+                } else {
+                    ERROR_SRC_SIZE_WRONG
+                });
+            }
+        }
+
+        Ok(output.pos())
+    }
+
     /// Wraps the `ZSTD_DStreamInSize()` function.
     ///
     /// Returns a hint for the recommended size of the input buffer for decompression.
@@ -1540,17 +1660,24 @@ pub struct InBuffer<'a> {
 /// initializing it. No re-allocation typically occur after the initial creation.
 ///
 /// The main implementors are:
-/// * `Vec<u8>` and similar structures. These hold both a length (initialized data) and a capacity
-///   (allocated memory).
+/// * `Vec<u8>` and similar structures, with the `alloc` feature. These hold both a length
+///   (initialized data) and a capacity (allocated memory).
 ///
 ///   Use `Vec::with_capacity` to create an empty `Vec` with non-zero capacity, and the length
 ///   field will be updated to cover the data written to it (as long as it fits in the given
 ///   capacity).
+///
+///   With the `allocator_api` feature (nightly only), this includes `Vec<u8, A>` with any
+///   allocator.
+/// * `bumpalo::collections::Vec<u8>`, with the `bumpalo` feature. This works like `Vec<u8>`, with
+///   the memory allocated in a bump arena.
 /// * `[u8]` and `[u8; N]`. These must start already-initialized, and will not be resized. It will
 ///   be up to the caller to only use the part that was written (as returned by the various writing
 ///   operations).
 /// * `std::io::Cursor<T: WriteBuf>`. This will ignore data before the cursor's position, and
 ///   append data after that.
+/// * `std::io::Cursor<&mut [u8]>`. This uses the whole slice, and the cursor's position as the
+///   length of the data written, like a `Vec<u8>`.
 pub unsafe trait WriteBuf {
     /// Returns the valid data part of this container. Should only cover initialized data.
     fn as_slice(&self) -> &[u8];
@@ -1649,29 +1776,134 @@ where
     }
 }
 
+/// A cursor over a slice is used like a `Vec<u8>`, with the position as the length.
+///
+/// The whole slice is available. As with a `Vec<u8>`, functions like [`compress`] write from the
+/// start of the slice, whatever the current position, and then set the position to the end of the
+/// data written. This differs from `std::io::Write`, which writes at the current position: use
+/// `OutBuffer::around_pos(cursor, cursor.position())` to do that.
 #[cfg(feature = "std")]
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "std")))]
-unsafe impl<'a> WriteBuf for &'a mut std::vec::Vec<u8> {
+unsafe impl WriteBuf for std::io::Cursor<&mut [u8]> {
     fn as_slice(&self) -> &[u8] {
-        std::vec::Vec::as_slice(self)
+        let end = (self.position() as usize).min(self.get_ref().len());
+        &self.get_ref()[..end]
     }
 
     fn capacity(&self) -> usize {
-        std::vec::Vec::capacity(self)
+        self.get_ref().len()
     }
 
     fn as_mut_ptr(&mut self) -> *mut u8 {
-        std::vec::Vec::as_mut_ptr(self)
+        self.get_mut().as_mut_ptr()
     }
 
     unsafe fn filled_until(&mut self, n: usize) {
-        std::vec::Vec::set_len(self, n)
+        self.set_position(n as u64);
     }
 }
 
-#[cfg(feature = "std")]
-#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "std")))]
-unsafe impl WriteBuf for std::vec::Vec<u8> {
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "alloc")))]
+unsafe impl<'a> WriteBuf for &'a mut alloc::vec::Vec<u8> {
+    fn as_slice(&self) -> &[u8] {
+        alloc::vec::Vec::as_slice(self)
+    }
+
+    fn capacity(&self) -> usize {
+        alloc::vec::Vec::capacity(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        alloc::vec::Vec::as_mut_ptr(self)
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        alloc::vec::Vec::set_len(self, n)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "alloc")))]
+unsafe impl WriteBuf for alloc::vec::Vec<u8> {
+    fn as_slice(&self) -> &[u8] {
+        &self[..]
+    }
+    fn capacity(&self) -> usize {
+        self.capacity()
+    }
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+    unsafe fn filled_until(&mut self, n: usize) {
+        self.set_len(n);
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "allocator_api"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "alloc")))]
+unsafe impl<'a, A: alloc::alloc::Allocator> WriteBuf
+    for &'a mut alloc::vec::Vec<u8, A>
+{
+    fn as_slice(&self) -> &[u8] {
+        alloc::vec::Vec::as_slice(self)
+    }
+
+    fn capacity(&self) -> usize {
+        alloc::vec::Vec::capacity(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        alloc::vec::Vec::as_mut_ptr(self)
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        alloc::vec::Vec::set_len(self, n)
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "allocator_api"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "alloc")))]
+unsafe impl<A: alloc::alloc::Allocator> WriteBuf for alloc::vec::Vec<u8, A> {
+    fn as_slice(&self) -> &[u8] {
+        &self[..]
+    }
+    fn capacity(&self) -> usize {
+        self.capacity()
+    }
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+    unsafe fn filled_until(&mut self, n: usize) {
+        self.set_len(n);
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "bumpalo")))]
+unsafe impl<'a, 'bump> WriteBuf
+    for &'a mut bumpalo::collections::Vec<'bump, u8>
+{
+    fn as_slice(&self) -> &[u8] {
+        bumpalo::collections::Vec::as_slice(self)
+    }
+
+    fn capacity(&self) -> usize {
+        bumpalo::collections::Vec::capacity(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        bumpalo::collections::Vec::as_mut_ptr(self)
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        bumpalo::collections::Vec::set_len(self, n)
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "bumpalo")))]
+unsafe impl<'bump> WriteBuf for bumpalo::collections::Vec<'bump, u8> {
     fn as_slice(&self) -> &[u8] {
         &self[..]
     }
@@ -1722,24 +1954,25 @@ unsafe impl WriteBuf for [u8] {
     }
 }
 
-/*
-// This is possible, but... why?
-unsafe impl<'a> WriteBuf for OutBuffer<'a, [u8]> {
+/// Writing to an `OutBuffer` fills its underlying buffer, and moves its
+/// position accordingly.
+///
+/// This lets an `OutBuffer` be used through another `OutBuffer`, for example
+/// to erase the type of the underlying buffer.
+unsafe impl<'a, C: WriteBuf + ?Sized> WriteBuf for OutBuffer<'a, C> {
     fn as_slice(&self) -> &[u8] {
-        self.dst
+        self.dst.as_slice()
     }
     fn capacity(&self) -> usize {
-        self.dst.len()
+        self.dst.capacity()
     }
     fn as_mut_ptr(&mut self) -> *mut u8 {
         self.dst.as_mut_ptr()
     }
     unsafe fn filled_until(&mut self, n: usize) {
-        self.pos = n;
+        self.set_pos(n);
     }
 }
-*/
-
 #[derive(Debug)]
 /// Wrapper around an output buffer.
 ///
@@ -1992,6 +2225,28 @@ pub fn is_frame(buffer: &[u8]) -> bool {
     unsafe { zstd_sys::ZSTD_isFrame(ptr_void(buffer), buffer.len()) > 0 }
 }
 
+/// Wraps the `ZSTD_getFrameHeader()` function.
+///
+/// Returns `Ok(None)` if `src` is too short to contain the entire header.
+#[cfg(feature = "experimental")]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
+pub fn get_frame_header(src: &[u8]) -> Result<Option<FrameHeader>, ErrorCode> {
+    let mut header = core::mem::MaybeUninit::<FrameHeader>::uninit();
+    // Safety: Just FFI
+    let code = unsafe {
+        zstd_sys::ZSTD_getFrameHeader(
+            header.as_mut_ptr(),
+            ptr_void(src),
+            src.len(),
+        )
+    };
+    match parse_code(code)? {
+        // Safety: zstd fills the header when it returns 0.
+        0 => Ok(Some(unsafe { header.assume_init() })),
+        _ => Ok(None),
+    }
+}
+
 /// Wraps the `ZSTD_getDictID_fromDict()` function.
 ///
 /// Returns `None` if the dictionary is not a valid zstd dictionary.
@@ -2075,11 +2330,18 @@ pub enum DictAttachPref {
 
 #[cfg(feature = "experimental")]
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Setting for features which zstd can enable automatically.
+///
+/// Wraps the `ZSTD_paramSwitch_e` enum.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ParamSwitch {
+    /// Let zstd decide, usually based on the compression parameters.
+    #[default]
     Auto = zstd_sys::ZSTD_paramSwitch_e::ZSTD_ps_auto as u32,
+    /// Always use the feature.
     Enable = zstd_sys::ZSTD_paramSwitch_e::ZSTD_ps_enable as u32,
+    /// Never use the feature.
     Disable = zstd_sys::ZSTD_paramSwitch_e::ZSTD_ps_disable as u32,
 }
 
@@ -2135,6 +2397,11 @@ pub enum CParameter {
     #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
     UseBlockSplitter(ParamSwitch),
 
+    /// Whether to use the row-based match finder.
+    ///
+    /// It speeds up the greedy and lazy strategies (roughly levels 5 to 12),
+    /// at the cost of some memory. With `Auto` (the default), it is used
+    /// for these strategies when the window is large enough.
     #[cfg(feature = "experimental")]
     #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "experimental")))]
     UseRowMatchFinder(ParamSwitch),
@@ -2263,6 +2530,13 @@ pub enum DParameter {
 }
 
 /// Wraps the `ZDICT_trainFromBuffer()` function.
+///
+/// `samples_buffer` must be the concatenation of all samples, and
+/// `samples_sizes` their sizes. If the sizes don't add up to the length of
+/// `samples_buffer`, an error is returned (`get_error_name` gives
+/// `"Src size is incorrect"`).
+///
+/// See also [`train_from_samples`], which builds the buffer itself.
 #[cfg(feature = "zdict_builder")]
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zdict_builder")))]
 pub fn train_from_buffer<C: WriteBuf + ?Sized>(
@@ -2270,7 +2544,14 @@ pub fn train_from_buffer<C: WriteBuf + ?Sized>(
     samples_buffer: &[u8],
     samples_sizes: &[usize],
 ) -> SafeResult {
-    assert_eq!(samples_buffer.len(), samples_sizes.iter().sum());
+    let total = samples_sizes
+        .iter()
+        .try_fold(0usize, |total, &size| total.checked_add(size));
+    if total != Some(samples_buffer.len())
+        || samples_sizes.len() > u32::MAX as usize
+    {
+        return Err(ERROR_SRC_SIZE_WRONG);
+    }
 
     unsafe {
         dict_buffer.write_from(|buffer, capacity| {
@@ -2285,6 +2566,24 @@ pub fn train_from_buffer<C: WriteBuf + ?Sized>(
     }
 }
 
+/// Trains a dictionary from a list of samples.
+///
+/// This concatenates the samples and calls [`train_from_buffer`].
+#[cfg(all(feature = "zdict_builder", feature = "alloc"))]
+#[cfg_attr(
+    feature = "doc-cfg",
+    doc(cfg(all(feature = "zdict_builder", feature = "alloc")))
+)]
+pub fn train_from_samples<C: WriteBuf + ?Sized>(
+    dict_buffer: &mut C,
+    samples: &[&[u8]],
+) -> SafeResult {
+    let samples_buffer: alloc::vec::Vec<u8> = samples.concat();
+    let samples_sizes: alloc::vec::Vec<usize> =
+        samples.iter().map(|sample| sample.len()).collect();
+    train_from_buffer(dict_buffer, &samples_buffer, &samples_sizes)
+}
+
 /// Wraps the `ZDICT_getDictID()` function.
 #[cfg(feature = "zdict_builder")]
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "zdict_builder")))]
@@ -0,0 +1,67 @@
+//! Names for compression strategies.
+use core::fmt;
+
+use crate::Strategy;
+
+/// Every strategy with its short name, from fastest to strongest.
+const STRATEGIES: [(Strategy, &str); 9] = [
+    (Strategy::ZSTD_fast, "fast"),
+    (Strategy::ZSTD_dfast, "dfast"),
+    (Strategy::ZSTD_greedy, "greedy"),
+    (Strategy::ZSTD_lazy, "lazy"),
+    (Strategy::ZSTD_lazy2, "lazy2"),
+    (Strategy::ZSTD_btlazy2, "btlazy2"),
+    (Strategy::ZSTD_btopt, "btopt"),
+    (Strategy::ZSTD_btultra, "btultra"),
+    (Strategy::ZSTD_btultra2, "btultra2"),
+];
+
+/// Returns the short name of `strategy`, like `btultra2`.
+///
+/// Returns `None` for strategies unknown to this version of `zstd-safe`.
+pub fn strategy_name(strategy: Strategy) -> Option<&'static str> {
+    STRATEGIES
+        .iter()
+        .find(|&&(known, _)| known == strategy)
+        .map(|&(_, name)| name)
+}
+
+/// Parses a strategy from its short name (`btultra2`), its C name
+/// (`ZSTD_btultra2`), or its numeric value (`9`).
+///
+/// Names are case-insensitive.
+pub fn parse_strategy(s: &str) -> Result<Strategy, ParseStrategyError> {
+    let s = s.trim();
+    let found = if let Ok(value) = s.parse::<u32>() {
+        STRATEGIES
+            .iter()
+            .find(|&&(strategy, _)| strategy as u32 == value)
+    } else {
+        let name = match s.get(..5) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("zstd_") => &s[5..],
+            _ => s,
+        };
+        STRATEGIES
+            .iter()
+            .find(|&&(_, known)| known.eq_ignore_ascii_case(name))
+    };
+    found
+        .map(|&(strategy, _)| strategy)
+        .ok_or(ParseStrategyError)
+}
+
+/// Error returned by [`parse_strategy`] for an unknown strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseStrategyError;
+
+impl fmt::Display for ParseStrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "unknown strategy (expected one of fast, dfast, greedy, lazy, \
+             lazy2, btlazy2, btopt, btultra, btultra2, or 1-9)",
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseStrategyError {}
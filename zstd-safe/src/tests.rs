@@ -1,9 +1,12 @@
 extern crate std;
 use crate as zstd_safe;
 
+// Some tests need optional features: without them, these are unused.
+#[allow(unused_imports)]
 use self::std::vec::Vec;
 
 const INPUT: &[u8] = b"Rust is a multi-paradigm system programming language focused on safety, especially safe concurrency. Rust is syntactically similar to C++, but is designed to provide better memory safety while maintaining high performance.";
+#[allow(dead_code)]
 const LONG_CONTENT: &str = include_str!("lib.rs");
 
 #[cfg(feature = "std")]
@@ -46,6 +49,67 @@ fn test_writebuf() {
     assert_eq!(data.as_slice(), &[0, 1, 2, 3, 0, 0, 4, 5, 6, 7]);
 }
 
+#[cfg(feature = "std")]
+#[test]
+fn test_writebuf_slice_cursor() {
+    use zstd_safe::{OutBuffer, WriteBuf};
+
+    let mut buffer = [0u8; 1024];
+    let mut cursor = std::io::Cursor::new(&mut buffer[..]);
+    assert_eq!(cursor.capacity(), 1024);
+    assert!(cursor.as_slice().is_empty());
+
+    // The position tracks the data written.
+    let written = zstd_safe::compress(&mut cursor, INPUT, 3).unwrap();
+    assert_eq!(cursor.position(), written as u64);
+    assert_eq!(cursor.as_slice().len(), written);
+
+    let mut decompressed = std::vec![0u8; INPUT.len()];
+    let mut output = std::io::Cursor::new(&mut decompressed[..]);
+    zstd_safe::decompress(&mut output, cursor.as_slice()).unwrap();
+    assert_eq!(output.position(), INPUT.len() as u64);
+    assert_eq!(output.as_slice(), INPUT);
+
+    // Writing after the current position keeps the earlier data.
+    let mut cctx = zstd_safe::CCtx::create();
+    let start = cursor.position() as usize;
+    let mut out = OutBuffer::around_pos(&mut cursor, start);
+    cctx.compress_stream2(
+        &mut out,
+        &mut zstd_safe::InBuffer::around(INPUT),
+        zstd_safe::zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
+    )
+    .unwrap();
+    assert_eq!(cursor.position(), 2 * written as u64);
+    assert_eq!(
+        cursor.get_ref()[..written],
+        cursor.get_ref()[written..2 * written]
+    );
+
+    // Like with a `Vec<u8>`, functions start writing at the beginning.
+    let rewritten = zstd_safe::compress(&mut cursor, INPUT, 3).unwrap();
+    assert_eq!(cursor.position(), rewritten as u64);
+}
+
+#[cfg(feature = "bumpalo")]
+#[test]
+fn test_writebuf_bumpalo() {
+    let arena = bumpalo::Bump::new();
+
+    let mut compressed = bumpalo::collections::Vec::with_capacity_in(
+        zstd_safe::compress_bound(INPUT.len()),
+        &arena,
+    );
+    zstd_safe::compress(&mut compressed, INPUT, 3).unwrap();
+
+    let mut decompressed =
+        bumpalo::collections::Vec::with_capacity_in(INPUT.len(), &arena);
+    let written =
+        zstd_safe::decompress(&mut decompressed, &compressed).unwrap();
+    assert_eq!(written, INPUT.len());
+    assert_eq!(&decompressed[..], INPUT);
+}
+
 #[cfg(feature = "std")]
 #[test]
 fn test_simple_cycle() {
@@ -60,6 +124,69 @@ fn test_simple_cycle() {
     assert_eq!(INPUT, decompressed);
 }
 
+#[cfg(feature = "alloc")]
+#[test]
+fn test_compress_stream2_simple() {
+    use zstd_safe::EndDirective;
+
+    let mut cctx = zstd_safe::CCtx::create();
+    let mut compressed = Vec::new();
+
+    // Nothing needs to come out yet.
+    cctx.compress_stream2_simple(
+        &mut compressed,
+        INPUT,
+        EndDirective::ZSTD_e_continue,
+    )
+    .unwrap();
+    let written = cctx
+        .compress_stream2_simple(
+            &mut compressed,
+            LONG_CONTENT.as_bytes(),
+            EndDirective::ZSTD_e_end,
+        )
+        .unwrap();
+    assert!(written > 0);
+
+    let mut decompressed =
+        Vec::with_capacity(INPUT.len() + LONG_CONTENT.len());
+    zstd_safe::decompress(&mut decompressed, &compressed).unwrap();
+    assert_eq!(decompressed, [INPUT, LONG_CONTENT.as_bytes()].concat());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_decompress_all_into() {
+    let mut first = Vec::with_capacity(256);
+    zstd_safe::compress(&mut first, INPUT, 3).unwrap();
+    let mut second = Vec::with_capacity(256);
+    zstd_safe::compress(&mut second, b"second", 1).unwrap();
+    let compressed = &[first, second].concat()[..];
+
+    let mut buffer = std::vec![0u8; 512];
+    let written =
+        zstd_safe::decompress_all_into(&mut buffer, compressed).unwrap();
+    assert_eq!(buffer[..written], [INPUT, &b"second"[..]].concat()[..]);
+
+    let mut buffer = std::vec![0u8; 512];
+    let error = zstd_safe::decompress_all_into(
+        &mut buffer,
+        &compressed[..compressed.len() - 1],
+    )
+    .unwrap_err();
+    assert_eq!(zstd_safe::get_error_name(error), "Src size is incorrect");
+
+    let mut buffer = std::vec![0u8; INPUT.len()];
+    let error =
+        zstd_safe::decompress_all_into(&mut buffer, compressed).unwrap_err();
+    assert_eq!(
+        zstd_safe::get_error_name(error),
+        "Destination buffer is too small"
+    );
+
+    assert_eq!(zstd_safe::decompress_all_into(&mut buffer, &[]), Ok(0));
+}
+
 #[test]
 fn test_cctx_cycle() {
     let mut buffer = std::vec![0u8; 256];
@@ -75,6 +202,7 @@ fn test_cctx_cycle() {
     assert_eq!(INPUT, decompressed);
 }
 
+#[cfg(feature = "zdict_builder")]
 #[test]
 fn test_dictionary() {
     // Prepare some content to train the dictionary.
@@ -89,6 +217,36 @@ fn test_dictionary() {
             .unwrap();
     let dict_buffer = &dict_buffer[..written];
 
+    // The same dictionary can be trained from separate samples.
+    #[cfg(feature = "alloc")]
+    {
+        let samples: Vec<&[u8]> = LONG_CONTENT
+            .split_inclusive('\n')
+            .map(str::as_bytes)
+            .collect();
+        let mut from_samples = std::vec![0u8; 100_000];
+        let written =
+            zstd_safe::train_from_samples(&mut from_samples[..], &samples)
+                .unwrap();
+        assert_eq!(&from_samples[..written], dict_buffer);
+    }
+
+    // Sizes not matching the buffer are an error, not a panic.
+    let error = zstd_safe::train_from_buffer(
+        &mut std::vec![0u8; 1000][..],
+        bytes,
+        &[1, 2, 3],
+    )
+    .unwrap_err();
+    assert_eq!(zstd_safe::get_error_name(error), "Src size is incorrect");
+    let error = zstd_safe::train_from_buffer(
+        &mut std::vec![0u8; 1000][..],
+        bytes,
+        &[usize::MAX, bytes.len() + 1],
+    )
+    .unwrap_err();
+    assert_eq!(zstd_safe::get_error_name(error), "Src size is incorrect");
+
     // Create pre-hashed dictionaries for (de)compression
     let cdict = zstd_safe::create_cdict(dict_buffer, 3);
     let ddict = zstd_safe::create_ddict(dict_buffer);
@@ -124,6 +282,29 @@ fn test_dictionary() {
     assert_eq!(bytes, decompressed);
 }
 
+#[cfg(feature = "experimental")]
+#[test]
+fn test_row_match_finder() {
+    use zstd_safe::{CParameter, ParamSwitch};
+
+    for mode in [ParamSwitch::Auto, ParamSwitch::Enable, ParamSwitch::Disable]
+    {
+        let mut cctx = zstd_safe::CCtx::default();
+        cctx.set_parameter(CParameter::CompressionLevel(7)).unwrap();
+        cctx.set_parameter(CParameter::UseRowMatchFinder(mode))
+            .unwrap();
+
+        let mut buffer = std::vec![0u8; 256];
+        let written = cctx.compress2(&mut buffer[..], INPUT).unwrap();
+
+        let mut decompressed = std::vec![0u8; 256];
+        let size =
+            zstd_safe::decompress(&mut decompressed[..], &buffer[..written])
+                .unwrap();
+        assert_eq!(&decompressed[..size], INPUT);
+    }
+}
+
 #[test]
 fn test_checksum() {
     let mut buffer = std::vec![0u8; 256];
@@ -173,3 +354,69 @@ fn test_upper_bound() {
         Ok(INPUT.len() as u64)
     );
 }
+
+#[test]
+fn test_strategy_names() {
+    use zstd_safe::{parse_strategy, strategy_name, Strategy};
+
+    assert_eq!(parse_strategy("btultra2"), Ok(Strategy::ZSTD_btultra2));
+    assert_eq!(parse_strategy("ZSTD_lazy2"), Ok(Strategy::ZSTD_lazy2));
+    assert_eq!(parse_strategy("Fast"), Ok(Strategy::ZSTD_fast));
+    assert_eq!(parse_strategy("4"), Ok(Strategy::ZSTD_lazy));
+    assert!(parse_strategy("10").is_err());
+    assert!(parse_strategy("ultra").is_err());
+
+    let name = strategy_name(Strategy::ZSTD_btopt).unwrap();
+    assert_eq!(name, "btopt");
+    assert_eq!(parse_strategy(name), Ok(Strategy::ZSTD_btopt));
+}
+
+#[test]
+fn test_error_kind() {
+    use zstd_safe::ErrorKind;
+
+    let mut buffer = std::vec![0u8; 4];
+    let error = zstd_safe::compress(&mut buffer[..], INPUT, 3).unwrap_err();
+    assert_eq!(zstd_safe::get_error_code(error), ErrorKind::DstSizeTooSmall);
+    assert_eq!(ErrorKind::DstSizeTooSmall.to_error_code(), error);
+
+    let error = zstd_safe::decompress(&mut buffer[..], INPUT).unwrap_err();
+    assert_eq!(zstd_safe::get_error_code(error), ErrorKind::PrefixUnknown);
+
+    assert_eq!(zstd_safe::get_error_code(0), ErrorKind::NoError);
+    assert_eq!(ErrorKind::from_raw(1000), ErrorKind::Generic);
+
+    assert_eq!(
+        zstd_safe::get_error_string(ErrorKind::PrefixUnknown),
+        zstd_safe::get_error_name(error)
+    );
+    assert_eq!(
+        std::format!("{}", ErrorKind::DstSizeTooSmall),
+        "Destination buffer is too small"
+    );
+    assert_eq!(
+        zstd_safe::get_error_string(ErrorKind::NoError),
+        "No error detected"
+    );
+    for value in 0..=120 {
+        let kind = ErrorKind::from_raw(value);
+        assert_eq!(
+            zstd_safe::get_error_string(kind),
+            zstd_safe::get_error_name(kind.to_error_code())
+        );
+    }
+
+    // Unknown codes keep their message, and the code itself.
+    let unknown = 0usize.wrapping_sub(119);
+    let error = zstd_safe::Error::from_code(unknown);
+    assert_eq!(error.code(), unknown);
+    assert_eq!(error.kind(), ErrorKind::Generic);
+    assert_eq!(
+        std::format!("{}", error),
+        zstd_safe::get_error_name(unknown)
+    );
+    assert_ne!(
+        std::format!("{}", error),
+        zstd_safe::get_error_string(ErrorKind::Generic)
+    );
+}